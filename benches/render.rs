@@ -0,0 +1,77 @@
+//! Timings of the work done per frame and per refresh with a busy server, e.g. hundreds of
+//! sessions on a host reached over SSH. Run with `cargo bench`, which also reports
+//! the change since the previous run.
+
+use std::{hint::black_box, sync::Arc};
+use criterion::{criterion_group, criterion_main, Criterion};
+use ratatui::{backend::TestBackend, Terminal};
+use tmm::{app::{App, GroupBy}, tmux::{MockClient, TmuxClient}, ui};
+
+const SESSIONS: usize = 500;
+
+/// `list-sessions` and `list-panes` output for `count` sessions of three windows each, in a
+/// handful of session groups
+fn list_sessions_output(count: usize) -> String {
+    let mut out = String::new();
+    for idx in 0..count {
+        let group = if idx % 5 == 0 { format!("group-{}", idx % 7) } else { String::new() };
+        out.push_str(&format!("S\t${}\t3\t{}\t{}\t{}\t{}\tsession-{:03}\n",
+            idx, 1709301900 + idx, idx % 2, 1709309900 + idx, group, idx));
+    }
+    for idx in 0..count {
+        for pane in 0..3 {
+            out.push_str(&format!("P\t${}\t{}\n", idx, (idx + pane) % 50 == 0));
+        }
+    }
+    out
+}
+
+fn render(c: &mut Criterion) {
+    let mock = Arc::new(MockClient::new());
+    mock.reply("list-sessions", Ok(&list_sessions_output(SESSIONS)));
+    let mut app = App::with_client(Arc::clone(&mock) as Arc<dyn TmuxClient>);
+    let mut terminal = Terminal::new(TestBackend::new(160, 50)).expect("test backend");
+
+    c.bench_function("render", |b| b.iter(|| {
+        terminal.draw(|frame| ui::render(&mut app, frame)).expect("draw");
+    }));
+    c.bench_function("render while scrolling", |b| b.iter(|| {
+        app.select_next();
+        terminal.draw(|frame| ui::render(&mut app, frame)).expect("draw");
+    }));
+    app.group_by = GroupBy::SessionGroup;
+    c.bench_function("render grouped", |b| b.iter(|| {
+        terminal.draw(|frame| ui::render(&mut app, frame)).expect("draw");
+    }));
+    app.group_by = GroupBy::None;
+    app.search();
+    if let Some(textarea) = app.search_session_ta.as_mut() {
+        textarea.insert_str("sion-1");
+    }
+    c.bench_function("render searching", |b| b.iter(|| {
+        terminal.draw(|frame| ui::render(&mut app, frame)).expect("draw");
+    }));
+}
+
+fn refresh(c: &mut Criterion) {
+    let mock = Arc::new(MockClient::new());
+    mock.reply("list-sessions", Ok(&list_sessions_output(SESSIONS)));
+    let mut app = App::with_client(Arc::clone(&mock) as Arc<dyn TmuxClient>);
+
+    c.bench_function("refresh", |b| b.iter(|| {
+        app.refresh();
+        black_box(&app.sessions);
+    }));
+    // Every refresh finds the sessions changed, e.g. because one of them is in use
+    let listings = [list_sessions_output(SESSIONS), list_sessions_output(SESSIONS - 1)];
+    let mut next = 0;
+    c.bench_function("refresh with changes", |b| b.iter(|| {
+        mock.reply("list-sessions", Ok(&listings[next]));
+        next = 1 - next;
+        app.refresh();
+        black_box(&app.sessions);
+    }));
+}
+
+criterion_group!(benches, render, refresh);
+criterion_main!(benches);
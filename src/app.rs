@@ -1,10 +1,27 @@
 use std::{
-    collections::{HashMap, HashSet}, env, error, process::Command, str::from_utf8
+    collections::{HashMap, HashSet, VecDeque}, env, error, fs, io, mem, ops::RangeInclusive, path::PathBuf, process::{Command, ExitStatus, Output}, str::from_utf8, sync::{atomic::{AtomicBool, Ordering}, mpsc, Arc},
+    os::unix::process::ExitStatusExt, thread,
+    time::{Duration, Instant}
 };
-use tui_textarea::TextArea;
-use ratatui::style::{Color, Style};
+use crossterm::event::KeyCode;
+use tui_textarea::{CursorMove, TextArea};
+use ratatui::{layout::Rect, style::{Color, Style, Stylize}};
 use indexmap::IndexMap;
 
+use crate::archive::{self, Archived};
+use crate::batch::Batch;
+use crate::config::{self, Colors, Config, Theme};
+use crate::event::Event;
+use crate::history::{self, HistoryEntry, TimeZone};
+use crate::{clipboard, log, meta};
+use crate::pattern::{self, Pattern};
+use crate::keymap::{Action, KeyBinding, Keymap};
+use crate::scheduler::{Job, JobResult, Scheduler};
+use crate::source::{self, Source, SourceEntry};
+use crate::template::Template;
+use crate::tmux::{self, ProcessClient, Server, TmuxClient};
+use crate::tui;
+
 /// Application result type.
 pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
 
@@ -16,15 +33,940 @@ pub enum AppState {
     Renaming,
     WarnNested,
     NewSession,
+    BatchRenaming,
+    BatchConfirm,
+    BatchRunning,
+    BatchResults,
+    History,
+    Windows,
+    Error,
+    RenameConflict,
+    AttachSizing,
+    AttachCommand,
+    Coloring,
+    Hinting,
+    Archive,
+    DetachingClients,
+    KillingServer,
+    ServerInfo,
+    Help,
+    SplitPane,
+    KillingPane,
+    CapturingPane,
+    DeepSearching,
+    DeepSearchRunning,
+    DeepResults,
+    Copying,
+    Servers,
+    Clients,
+    KillingClient,
+    Templates,
 }
 
-#[derive(Debug)]
+/// How the windows of a session are sized when it is attached while other clients are attached
+/// to it too
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachSize {
+    /// Detach the other clients, so the windows take the size of this terminal (`attach -d`)
+    Detach,
+    /// Keep the other clients, and size the windows to the smallest of them (`window-size smallest`)
+    Smallest,
+    /// Keep the other clients, and size the windows to the one most recently used (`window-size
+    /// latest`)
+    Latest,
+}
+
+impl AttachSize {
+    /// Every sizing, in the order they are offered
+    pub const ALL: [AttachSize; 3] = [AttachSize::Detach, AttachSize::Smallest, AttachSize::Latest];
+
+    /// Name used in the configuration file, and the value of tmux's `window-size` option for the
+    /// sizings that keep the other clients
+    pub fn name(self) -> &'static str {
+        match self {
+            AttachSize::Detach => "detach",
+            AttachSize::Smallest => "smallest",
+            AttachSize::Latest => "latest",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum ExitAction {
-    AttachSession(String, bool),
-    NewSession,
+    /// Attach (host, session name) sized as given
+    AttachSession(Option<String>, String, AttachSize),
+    /// Attach (host, session name) and select the window with the given index, and optionally the
+    /// pane with the given index in that window, sized as given
+    AttachWindow(Option<String>, String, usize, Option<usize>, AttachSize),
+    /// Create and attach an unnamed session, starting with the given command instead of the
+    /// default one
+    NewSession(Option<String>),
+    /// Quit without attaching anything. If nested, also detach the tmux client.
+    Detach,
     None
 }
 
+/// A program the interface is suspended for, so that it can use the terminal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Foreground {
+    /// Edit the configuration file at the path in `$VISUAL` or `$EDITOR`, then reload it
+    EditConfig(PathBuf),
+    /// Stop tmm itself, leaving the terminal to the shell until it is continued
+    Stop,
+}
+
+impl Foreground {
+    /// Run the program, returning once it exits
+    pub fn run(&self) -> io::Result<ExitStatus> {
+        match self {
+            Foreground::EditConfig(path) => {
+                let editor = ["VISUAL", "EDITOR"].into_iter()
+                    .filter_map(|name| env::var(name).ok())
+                    .find(|editor| !editor.trim().is_empty())
+                    .unwrap_or_else(|| "vi".to_owned());
+                // Through the shell, for editors given with arguments such as `code -w`
+                Command::new("sh").arg("-c").arg(format!("{} \"$1\"", editor)).arg("sh").arg(path).status()
+            }
+            Foreground::Stop => {
+                tui::stop_process();
+                Ok(ExitStatus::from_raw(0))
+            }
+        }
+    }
+}
+
+/// A tmux session on the local server or a remote host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    /// Remote host the session lives on, `None` for the local server
+    pub host: Option<String>,
+    /// Session name
+    pub name: String,
+    /// tmux's session id, e.g. `$3`, which unlike the name and list position stays the same for the
+    /// lifetime of the session
+    pub id: String,
+    /// Number of windows
+    pub windows: usize,
+    /// When the session was created, in seconds since the unix epoch
+    pub created: u64,
+    /// Number of attached clients, not counting tmm's own control mode client
+    pub attached: usize,
+    /// When the session was last active, in seconds since the unix epoch
+    pub activity: u64,
+    /// Number of panes whose process has exited (only possible with `remain-on-exit`)
+    pub dead_panes: usize,
+    /// tmux session group (sessions sharing windows, see `new-session -t`), if any
+    pub group: Option<String>,
+    /// The last attach through tmm, on any machine, from the session's metadata
+    pub last_attach: Option<meta::Attach>,
+    /// Accent color, from the session's [`meta::COLOR`] option
+    pub color: Option<Color>,
+}
+
+impl Session {
+    /// Name shown in the list and used to identify the session across hosts, e.g. `dev` or
+    /// `build-box/dev`
+    pub fn label(&self) -> String {
+        match &self.host {
+            Some(host) => format!("{}/{}", host, self.name),
+            None => self.name.to_owned(),
+        }
+    }
+
+    /// Description shown next to the name, like `tmux ls` prints it, e.g.
+    /// `2 windows (created 2024-03-01 14:05) (attached)`, with times in `zone`
+    pub fn desc(&self, zone: TimeZone) -> String {
+        let mut desc = format!("{} windows (created {})", self.windows, history::format_timestamp(self.created, zone));
+        if let Some(group) = &self.group {
+            desc.push_str(&format!(" (group {})", group));
+        }
+        if self.attached > 0 {
+            desc.push_str(" (attached)");
+        }
+        desc
+    }
+
+    /// Target for tmux's `-t` that refers to exactly this session: its id if known, otherwise its
+    /// name
+    pub fn target(&self) -> &str {
+        if self.id.is_empty() { &self.name } else { &self.id }
+    }
+}
+
+/// An action on a single session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionAction {
+    /// Kill the session
+    Kill,
+    /// Interrupt the processes of every pane (Ctrl-C, then SIGTERM), and kill the session once
+    /// they have had [`KILL_GRACE`] to exit
+    KillGracefully,
+    /// Rename the session to the contained name
+    Rename(String),
+    /// Detach every client attached to the session
+    DetachClients,
+    /// Set the session's accent color to the contained one, or unset it
+    SetColor(Option<String>),
+}
+
+impl SessionAction {
+    /// What the status line says once the action is done to the session labelled `label`, if
+    /// anything: a graceful kill says so when it starts, and a new color shows by itself
+    fn done_message(&self, label: &str) -> Option<String> {
+        match self {
+            SessionAction::Kill => Some(format!("Killed {}", label)),
+            SessionAction::Rename(rename) => Some(format!("Renamed {} to {}", label, rename)),
+            SessionAction::DetachClients => Some(format!("Detached the clients of {}", label)),
+            SessionAction::KillGracefully | SessionAction::SetColor(_) => None,
+        }
+    }
+}
+
+/// An action requested while its host's session list was being refreshed. It is applied once the
+/// refresh lands, against the session's id rather than its row, which may have moved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingAction {
+    /// Index into [`App::hosts`]
+    pub host_idx: usize,
+    /// Id of the targeted session
+    pub session_id: String,
+    /// What to do to the session
+    pub action: SessionAction,
+}
+
+/// A field of the new session form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormField {
+    /// The session name
+    Name,
+    /// The directory the session starts in
+    Directory,
+    /// The command the session starts with
+    Command,
+    /// The template the session is created from
+    Template,
+}
+
+/// The new session form: a name, and optionally the directory the session starts in, a command
+/// for it to start with instead of the server's default, or a template to create it from
+#[derive(Debug, Clone)]
+pub struct NewSessionForm<'a> {
+    pub name: TextArea<'a>,
+    pub directory: TextArea<'a>,
+    pub command: TextArea<'a>,
+    /// Only offered when the configuration file has templates
+    pub template: Option<TextArea<'a>>,
+    /// The field being typed in
+    pub focus: FormField,
+    /// Directories the directory field could be completed to, shown after an ambiguous `Tab`
+    pub candidates: Vec<String>,
+}
+
+impl<'a> NewSessionForm<'a> {
+    /// An empty form, where the directory field shows `default_directory`, the command field
+    /// `default_command` and the template field the names of `templates` until something is typed
+    fn new(default_directory: &str, default_command: &str, templates: &[Template]) -> Self {
+        let field = |placeholder: &str| {
+            let mut textarea = TextArea::default();
+            textarea.set_cursor_line_style(Style::default());
+            textarea.set_placeholder_text(placeholder);
+            textarea
+        };
+        let names: Vec<&str> = templates.iter().map(|template| template.name.as_str()).collect();
+        let template = (!templates.is_empty()).then(|| field(&format!("none ({})", names.join(", "))));
+        let mut form = Self {
+            name: field(""),
+            directory: field(default_directory),
+            command: field(default_command),
+            template,
+            focus: FormField::Name,
+            candidates: vec![],
+        };
+        form.show_cursor();
+        form
+    }
+
+    /// The field being typed in
+    pub fn focused_mut(&mut self) -> &mut TextArea<'a> {
+        match (self.focus, &mut self.template) {
+            (FormField::Template, Some(template)) => template,
+            (FormField::Directory, _) => &mut self.directory,
+            (FormField::Command, _) => &mut self.command,
+            _ => &mut self.name,
+        }
+    }
+
+    /// Move to the next field, and from the last one back to the first
+    pub fn next_field(&mut self) {
+        self.focus = match self.focus {
+            FormField::Name => FormField::Directory,
+            FormField::Directory => FormField::Command,
+            FormField::Command if self.template.is_some() => FormField::Template,
+            FormField::Command | FormField::Template => FormField::Name,
+        };
+        self.candidates.clear();
+        self.show_cursor();
+    }
+
+    /// Complete the directory field as far as the directories it could stand for agree, or list
+    /// them if they do not agree on more. Returns false if there was nothing to complete.
+    pub fn complete_directory(&mut self) -> bool {
+        let text = self.directory.lines()[0].clone();
+        let completions = source::complete_directory(&text);
+        self.candidates.clear();
+        let Some(first) = completions.first() else { return false };
+        let common = completions.iter().fold(first.as_str(), |common, completion| {
+            let len = common.char_indices().zip(completion.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(common.len().min(completion.len()), |((idx, _), _)| idx);
+            &common[..len]
+        });
+        if common.len() > text.len() {
+            self.directory.move_cursor(CursorMove::End);
+            self.directory.delete_line_by_head();
+            self.directory.insert_str(common);
+            return true;
+        }
+        if completions.len() > 1 {
+            // By their last component, e.g. `web/`
+            self.candidates = completions.iter().map(|completion| {
+                let name = completion.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+                format!("{}/", name)
+            }).collect();
+            return true;
+        }
+        false
+    }
+
+    /// Show the cursor in the focused field only
+    fn show_cursor(&mut self) {
+        let focus = self.focus;
+        let fields = [(FormField::Name, Some(&mut self.name)), (FormField::Directory, Some(&mut self.directory)),
+            (FormField::Command, Some(&mut self.command)), (FormField::Template, self.template.as_mut())];
+        for (field, textarea) in fields {
+            if let Some(textarea) = textarea {
+                textarea.set_cursor_style(if field == focus { Style::default().reversed() } else { Style::default() });
+            }
+        }
+    }
+
+    /// The directory typed in, if any, with `~` expanded
+    pub fn directory(&self) -> Option<String> {
+        Some(self.directory.lines()[0].trim()).filter(|dir| !dir.is_empty()).map(source::expand_home)
+    }
+
+    /// The command typed in, if any
+    pub fn command(&self) -> Option<&str> {
+        Some(self.command.lines()[0].trim()).filter(|command| !command.is_empty())
+    }
+
+    /// The template typed in, if any
+    pub fn template(&self) -> Option<&str> {
+        self.template.as_ref().map(|template| template.lines()[0].trim()).filter(|template| !template.is_empty())
+    }
+}
+
+/// A window of a session, as listed by `tmux list-windows`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+    /// Window index
+    pub index: usize,
+    /// Window name
+    pub name: String,
+    /// Number of panes
+    pub panes: usize,
+    /// Whether this is the session's current window
+    pub active: bool,
+    /// Whether the window's active pane is zoomed to take up the whole window
+    pub zoomed: bool,
+    /// Size as `WIDTHxHEIGHT`
+    pub size: String,
+    /// Command running in the window's active pane
+    pub command: String,
+    /// The window's panes
+    pub pane_list: Vec<Pane>,
+}
+
+/// A pane of a window, as listed by `tmux list-panes`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pane {
+    /// Pane index within its window
+    pub index: usize,
+    /// Whether this is the window's active pane
+    pub active: bool,
+    /// Whether this is the server's marked pane (`select-pane -m`), which `{marked}` targets
+    pub marked: bool,
+    /// Size as `WIDTHxHEIGHT`
+    pub size: String,
+    /// Command running in the pane
+    pub command: String,
+    /// Current working directory of the pane
+    pub path: String,
+}
+
+/// How a remote host answered the last attempts to list its sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HostState {
+    /// When its sessions were last listed
+    pub last_success: Option<Instant>,
+    /// Whether the latest attempt failed, e.g. timed out or could not connect
+    pub failing: bool,
+}
+
+/// Connection status of a host, shown in the header of its section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostStatus {
+    /// Not heard from yet
+    Connecting,
+    /// The latest listing succeeded
+    Connected,
+    /// The latest listing failed, and the sessions shown are from the last one that succeeded,
+    /// this long ago
+    Stale(Duration),
+    /// Never reached
+    Disconnected,
+}
+
+/// A client attached to a session, as listed by `tmux list-clients`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Client {
+    /// Name, which targets the client
+    pub name: String,
+    /// Terminal the client runs in
+    pub tty: String,
+    /// Size as `WIDTHxHEIGHT`
+    pub size: String,
+    /// Type of the terminal, e.g. `xterm-256color`
+    pub termname: String,
+    /// When the client was last used, in seconds since the epoch
+    pub activity: u64,
+}
+
+/// A pane whose scrollback mentions the term of a deep search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneMatch {
+    /// Remote host of the pane's server, `None` for the local one
+    pub host: Option<String>,
+    /// Name of the pane's session
+    pub session: String,
+    /// Index of the pane's window
+    pub window: usize,
+    /// Pane index within its window
+    pub pane: usize,
+    /// Command running in the pane
+    pub command: String,
+    /// The most recent line mentioning the term, trimmed
+    pub line: String,
+    /// How many lines mention the term
+    pub count: usize,
+}
+
+/// A deep search running in the background
+#[derive(Debug)]
+pub struct PaneSearch {
+    /// Tells the events of this search from those of an earlier one
+    generation: u64,
+    /// Set to stop the search before its next pane
+    cancel: Arc<AtomicBool>,
+    /// Whether its thread was started, which happens at the next tick
+    started: bool,
+    /// Panes searched so far
+    pub done: usize,
+    /// Panes to search, once every server listed them
+    pub total: Option<usize>,
+}
+
+/// Progress of a deep search, sent from its thread
+#[derive(Debug, Clone)]
+pub enum PaneSearchUpdate {
+    /// Panes searched so far, out of how many
+    Progress(usize, usize),
+    /// Every pane was searched, and these mention the term
+    Finished(Vec<PaneMatch>),
+}
+
+/// The split pane form of the windows view: the directory the new pane starts in and the command
+/// it runs, both optional
+#[derive(Debug, Clone)]
+pub struct SplitForm<'a> {
+    /// Pane being split, as a tmux target
+    pub target: String,
+    /// Whether the new pane goes below the pane (`split-window -v`) rather than to its right
+    pub below: bool,
+    pub directory: TextArea<'a>,
+    pub command: TextArea<'a>,
+    /// The field being typed in, [`FormField::Directory`] or [`FormField::Command`]
+    pub focus: FormField,
+}
+
+impl<'a> SplitForm<'a> {
+    /// An empty form, where the directory field shows `default_directory` and the command field
+    /// `default_command` until something is typed
+    fn new(target: String, below: bool, default_directory: &str, default_command: &str) -> Self {
+        let field = |placeholder: &str| {
+            let mut textarea = TextArea::default();
+            textarea.set_cursor_line_style(Style::default());
+            textarea.set_placeholder_text(placeholder);
+            textarea
+        };
+        let mut form = Self { target, below, directory: field(default_directory), command: field(default_command), focus: FormField::Directory };
+        form.command.set_cursor_style(Style::default());
+        form
+    }
+
+    /// The field being typed in
+    pub fn focused_mut(&mut self) -> &mut TextArea<'a> {
+        match self.focus {
+            FormField::Command => &mut self.command,
+            _ => &mut self.directory,
+        }
+    }
+
+    /// Move to the other field
+    pub fn next_field(&mut self) {
+        self.focus = if self.focus == FormField::Directory { FormField::Command } else { FormField::Directory };
+        let (focused, other) = match self.focus {
+            FormField::Command => (&mut self.command, &mut self.directory),
+            _ => (&mut self.directory, &mut self.command),
+        };
+        focused.set_cursor_style(Style::default().reversed());
+        other.set_cursor_style(Style::default());
+    }
+
+    /// The directory typed in, if any, with `~` expanded
+    pub fn directory(&self) -> Option<String> {
+        Some(self.directory.lines()[0].trim()).filter(|dir| !dir.is_empty()).map(source::expand_home)
+    }
+
+    /// The command typed in, if any
+    pub fn command(&self) -> Option<&str> {
+        Some(self.command.lines()[0].trim()).filter(|command| !command.is_empty())
+    }
+}
+
+/// A short message in the bottom border of the current view, which goes away by itself, unlike
+/// the error popup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub message: String,
+    /// Whether it reports a failure, shown for longer and in the color of dead sessions
+    pub failure: bool,
+    /// When it was shown
+    pub shown: Instant,
+}
+
+/// A tmux server, as `tmux display-message` describes it, to tell which one tmm talks to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// Remote host the server runs on, `None` for the local one
+    pub host: Option<String>,
+    /// Process id of the server
+    pub pid: u32,
+    /// Socket the server listens on, e.g. `/tmp/tmux-1000/default`
+    pub socket_path: String,
+    /// When the server started, in seconds since the epoch
+    pub start_time: u64,
+    /// tmux version the server runs
+    pub version: String,
+}
+
+impl ServerInfo {
+    /// Labelled lines describing the server, for the server info popup and `tmm doctor`, with
+    /// times in `zone`
+    pub fn describe(&self, zone: TimeZone) -> Vec<(&'static str, String)> {
+        let uptime = history::now().saturating_sub(self.start_time);
+        vec![
+            ("Host", self.host.clone().unwrap_or_else(|| "local".to_owned())),
+            ("Version", self.version.clone()),
+            ("PID", self.pid.to_string()),
+            ("Socket", self.socket_path.clone()),
+            ("Started", format!("{} (up {})", history::format_timestamp(self.start_time, zone), history::format_duration(uptime))),
+        ]
+    }
+}
+
+/// How the sessions list is divided into sections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroupBy {
+    /// A flat list
+    #[default]
+    None,
+    /// One section per host
+    Host,
+    /// One section per tmux session group, plus one for the ungrouped sessions
+    SessionGroup,
+}
+
+impl GroupBy {
+    /// Every grouping, in the order the group key cycles through them
+    pub const ALL: [GroupBy; 3] = [GroupBy::None, GroupBy::Host, GroupBy::SessionGroup];
+
+    /// Name used in the configuration file
+    pub fn name(self) -> &'static str {
+        match self {
+            GroupBy::None => "none",
+            GroupBy::Host => "host",
+            GroupBy::SessionGroup => "session_group",
+        }
+    }
+}
+
+/// Which sessions the list shows, by whether a client is attached to them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AttachedFilter {
+    /// Every session
+    #[default]
+    All,
+    /// Only sessions no client is attached to
+    Detached,
+    /// Only sessions some client is attached to
+    Attached,
+}
+
+impl AttachedFilter {
+    /// Every filter, in the order the filter key cycles through them
+    pub const ALL: [AttachedFilter; 3] = [AttachedFilter::All, AttachedFilter::Detached, AttachedFilter::Attached];
+
+    /// Name used in the configuration file
+    pub fn name(self) -> &'static str {
+        match self {
+            AttachedFilter::All => "all",
+            AttachedFilter::Detached => "detached",
+            AttachedFilter::Attached => "attached",
+        }
+    }
+}
+
+/// What attaching the session tmm is nested in does, instead of switching the client to itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CurrentSession {
+    /// Stay in tmm and say so in the status bar
+    Notice,
+    /// Quit tmm, back to the session
+    #[default]
+    Quit,
+    /// Open the session's windows view
+    Windows,
+}
+
+impl CurrentSession {
+    /// Every behavior, as named in the configuration file
+    pub const ALL: [CurrentSession; 3] = [CurrentSession::Notice, CurrentSession::Quit, CurrentSession::Windows];
+
+    /// Name used in the configuration file
+    pub fn name(self) -> &'static str {
+        match self {
+            CurrentSession::Notice => "notice",
+            CurrentSession::Quit => "quit",
+            CurrentSession::Windows => "windows",
+        }
+    }
+}
+
+/// The view tmm starts in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StartupView {
+    /// The list, with the configured sources
+    #[default]
+    Sessions,
+    /// The list with only the projects source, like `tmm projects`
+    Projects,
+    /// The session templates
+    Templates,
+    /// The attach history
+    History,
+}
+
+impl StartupView {
+    /// Every view tmm can start in
+    pub const ALL: [StartupView; 4] = [StartupView::Sessions, StartupView::Projects, StartupView::Templates, StartupView::History];
+
+    /// Name used in the configuration file
+    pub fn name(self) -> &'static str {
+        match self {
+            StartupView::Sessions => "sessions",
+            StartupView::Projects => "projects",
+            StartupView::Templates => "templates",
+            StartupView::History => "history",
+        }
+    }
+}
+
+/// Text of a session's row in the sessions list. Formatted whenever the sessions change rather
+/// than on every frame, which matters with hundreds of sessions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionRow {
+    /// [`Session::label`]
+    pub label: String,
+    /// Window count badge, e.g. `[4w]`, padded to the widest badge
+    pub badge: String,
+    /// Label right aligned to the widest one, followed by [`Session::desc`]
+    pub text: String,
+}
+
+/// Order of the sessions of each host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// As listed by tmux
+    #[default]
+    None,
+    /// Alphabetically by name
+    Name,
+    /// Oldest first
+    Created,
+    /// Most recently used first
+    Activity,
+    /// Attached sessions first, otherwise as listed by tmux
+    Attached,
+}
+
+impl SortOrder {
+    /// Every order, in the order the sort key cycles through them
+    pub const ALL: [SortOrder; 5] = [SortOrder::None, SortOrder::Name, SortOrder::Created, SortOrder::Activity, SortOrder::Attached];
+
+    /// Name used in the configuration file and the list's header
+    pub fn name(self) -> &'static str {
+        match self {
+            SortOrder::None => "none",
+            SortOrder::Name => "name",
+            SortOrder::Created => "created",
+            SortOrder::Activity => "activity",
+            SortOrder::Attached => "attached",
+        }
+    }
+
+    /// Look up an order by [`SortOrder::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sort| sort.name() == name)
+    }
+}
+
+/// File remembering the sort order last chosen with the sort key
+fn sort_file() -> Option<PathBuf> {
+    Some(history::state_dir()?.join("sort"))
+}
+
+/// A row of the sessions list
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ListRow {
+    /// Section header with the group's title, its number of sessions and whether it is collapsed.
+    /// Only collapsed headers can be selected, standing in for their hidden sessions.
+    Header(String, usize, bool),
+    /// Session, by index into [`App::sessions`]
+    Session(usize),
+    /// Entry of another source, by index into [`App::entries`]
+    Entry(usize),
+}
+
+/// A row the selection can stop at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    /// A session, or the collapsed section starting with it
+    Session(usize),
+    /// An entry of another source
+    Entry(usize),
+}
+
+/// A row of the windows view, which is a tree of windows and, for expanded windows, their panes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeRow {
+    /// Window, by index into [`App::windows`]
+    Window(usize),
+    /// Pane, by index into [`App::windows`] and that window's `pane_list`
+    Pane(usize, usize),
+}
+
+/// Arguments listing the sessions of a server: one tab separated line per session, prefixed with
+/// `S`, followed by one line per pane, prefixed with `P`, so that dead panes are known. The session
+/// name comes last since it is the only field that may itself contain tabs.
+const LIST_SESSIONS_ARGS: [&str; 16] = [
+    "list-sessions", "-F",
+    "S\t#{session_id}\t#{session_windows}\t#{session_created}\t#{session_attached}\t#{session_activity}\t#{session_group}\t#{session_name}",
+    ";", "list-panes", "-a", "-F", "P\t#{session_id}\t#{pane_dead}",
+    ";", "list-sessions", "-F", "M\t#{session_id}\t#{@tmm_attached}",
+    ";", "list-sessions", "-F", "C\t#{session_id}\t#{@tmm_color}",
+];
+
+/// Format of the window lines of [`list_windows_args`]. The name is last since it is the only field
+/// that may itself contain tabs.
+const WINDOW_FORMAT: &str = "W\t#{session_id}\t#{window_index}\t#{window_panes}\t#{window_active}\t#{window_zoomed_flag}\t#{window_width}x#{window_height}\t#{pane_current_command}\t#{window_name}";
+
+/// Format of the pane lines of [`list_windows_args`]
+const PANE_FORMAT: &str = "P\t#{session_id}\t#{window_index}\t#{pane_index}\t#{pane_active}\t#{pane_marked}\t#{pane_width}x#{pane_height}\t#{pane_current_command}\t#{pane_current_path}";
+
+/// Arguments listing windows and their panes in one go, of the session `target` or, with `None`,
+/// of every session of the server: one line per window, prefixed with `W` and its session's id,
+/// then one per pane, prefixed with `P`, its session's id and its window's index
+fn list_windows_args(target: Option<&str>) -> Vec<String> {
+    let (windows, panes) = match target {
+        Some(target) => (vec!["-t", target], vec!["-s", "-t", target]),
+        None => (vec!["-a"], vec!["-a"]),
+    };
+    ["list-windows"].into_iter().chain(windows).chain(["-F", WINDOW_FORMAT, ";", "list-panes"])
+        .chain(panes).chain(["-F", PANE_FORMAT])
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Parse [`list_windows_args`] output into the windows of each session, by session id
+fn parse_windows(stdout: &str) -> HashMap<String, Vec<Window>> {
+    let mut sessions: HashMap<String, Vec<Window>> = HashMap::new();
+    for line in stdout.lines().filter_map(|line| line.strip_prefix("W\t")) {
+        let mut parts = line.splitn(8, '\t');
+        let Some(id) = parts.next() else { continue };
+        let window = (|| Some(Window {
+            index: parts.next()?.parse().ok()?,
+            panes: parts.next()?.parse().ok()?,
+            active: parts.next()? == "1",
+            zoomed: parts.next()? == "1",
+            size: parts.next()?.to_owned(),
+            command: parts.next()?.to_owned(),
+            name: parts.next()?.to_owned(),
+            pane_list: vec![],
+        }))();
+        sessions.entry(id.to_owned()).or_default().extend(window);
+    }
+    // Panes are assigned to their windows
+    for line in stdout.lines().filter_map(|line| line.strip_prefix("P\t")) {
+        let mut parts = line.splitn(8, '\t');
+        let (Some(id), Some(window_index)) = (parts.next(), parts.next().and_then(|w| w.parse::<usize>().ok())) else { continue };
+        let Some(window) = sessions.get_mut(id).and_then(|windows| windows.iter_mut().find(|w| w.index == window_index)) else { continue };
+        let pane = (|| Some(Pane {
+            index: parts.next()?.parse().ok()?,
+            active: parts.next()? == "1",
+            marked: parts.next()? == "1",
+            size: parts.next()?.to_owned(),
+            command: parts.next()?.to_owned(),
+            path: parts.next()?.to_owned(),
+        }))();
+        window.pane_list.extend(pane);
+    }
+    sessions
+}
+
+/// `list-panes -F` format of the panes a deep search captures: id, window and pane index, command
+/// and session name
+const DEEP_SEARCH_FORMAT: &str = "#{pane_id}\t#{window_index}\t#{pane_index}\t#{pane_current_command}\t#{session_name}";
+
+/// The panes of the servers of `hosts` whose whole scrollback mentions `term`, ignoring case
+/// unless it has an uppercase letter. Every tmux command gives up after `timeout`, and a host whose
+/// pane could not be captured in time is not asked for its other panes. Reports the panes searched so far
+/// out of the total after each one, and stops early once `cancel` is set.
+fn search_panes(client: &dyn TmuxClient, hosts: &[Option<String>], term: &str, timeout: Duration, cancel: &AtomicBool,
+    mut progress: impl FnMut(usize, usize)) -> Vec<PaneMatch>
+{
+    let ignore_case = !term.chars().any(char::is_uppercase);
+    let needle = if ignore_case { term.to_lowercase() } else { term.to_owned() };
+    let mut panes = vec![];
+    for host in hosts {
+        let Some(output) = client.output_with_timeout(host.as_deref(), &["list-panes", "-a", "-F", DEEP_SEARCH_FORMAT], timeout) else { continue };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let mut parts = line.splitn(5, '\t');
+            let (Some(id), Some(window), Some(pane), Some(command), Some(session)) =
+                (parts.next(), parts.next(), parts.next(), parts.next(), parts.next()) else { continue };
+            let (Ok(window), Ok(pane)) = (window.parse(), pane.parse()) else { continue };
+            panes.push((host, id.to_owned(), window, pane, command.to_owned(), session.to_owned()));
+        }
+    }
+    let mut matches = vec![];
+    let mut unreachable = vec![];
+    for (done, (host, id, window, pane, command, session)) in panes.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        progress(done, panes.len());
+        if unreachable.contains(host) {
+            continue;
+        }
+        // -J joins lines tmux wrapped, so that a term is found across the wrap
+        let Some(capture) = client.output_with_timeout(host.as_deref(), &["capture-pane", "-p", "-J", "-S", "-", "-t", id], timeout) else {
+            unreachable.push(*host);
+            continue;
+        };
+        let text = String::from_utf8_lossy(&capture.stdout);
+        let found: Vec<&str> = text.lines().filter(|line| match ignore_case {
+            true => line.to_lowercase().contains(&needle),
+            false => line.contains(&needle),
+        }).collect();
+        let Some(last) = found.last() else { continue };
+        matches.push(PaneMatch { host: (*host).clone(), session: session.clone(), window: *window, pane: *pane, command: command.clone(),
+            line: last.trim().to_owned(), count: found.len() });
+    }
+    matches
+}
+
+/// How long a graceful kill gives the processes of a session's panes to exit
+pub const KILL_GRACE: Duration = Duration::from_millis(1500);
+
+/// How long a status message such as "Refreshed" stays on screen
+const STATUS_DURATION: Duration = Duration::from_secs(2);
+
+/// How long a status message about a failure stays on screen, long enough to be read
+const FAILURE_DURATION: Duration = Duration::from_secs(5);
+
+/// Prefix of a search term making the rest a regular expression instead of a fuzzy search
+const REGEX_SEARCH: &str = "re:";
+
+/// Letters of jump hints, home row first
+const HINT_LETTERS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+/// Why an action is refused in read-only mode
+const READ_ONLY: &str = "Not available in read-only mode";
+
+/// Entries of the sessions view's hotkey bar, labelled with their keys from [`App::keymap`]
+const SESSIONS_HOTKEYS: [(Action, &str); 36] = [
+    (Action::Quit, "Quit"),
+    (Action::Detach, "Detach"),
+    (Action::Attach, "Attach Session"),
+    (Action::Windows, "Windows"),
+    (Action::Rename, "Rename"),
+    (Action::New, "New"),
+    (Action::Delete, "Delete"),
+    (Action::Mark, "Mark"),
+    (Action::Group, "Group"),
+    (Action::Sort, "Sort"),
+    (Action::Filter, "Filter"),
+    (Action::Sources, "Sources"),
+    (Action::Fold, "Fold"),
+    (Action::History, "History"),
+    (Action::Preview, "Preview"),
+    (Action::Search, "Search"),
+    (Action::Refresh, "Refresh"),
+    (Action::Theme, "Theme"),
+    (Action::AttachSized, "Attach Sized"),
+    (Action::AttachRun, "Attach & Run"),
+    (Action::DetachClients, "Detach Clients"),
+    (Action::KillServer, "Kill Server"),
+    (Action::Clients, "Clients"),
+    (Action::RefreshHost, "Refresh Host"),
+    (Action::EditConfig, "Edit Config"),
+    (Action::Templates, "Templates"),
+    (Action::RenameCurrent, "Rename Current"),
+    (Action::Color, "Color"),
+    (Action::Jump, "Jump"),
+    (Action::Archive, "Archive"),
+    (Action::Archived, "Archived"),
+    (Action::ServerInfo, "Server Info"),
+    (Action::Help, "Help"),
+    (Action::DeepSearch, "Deep Search"),
+    (Action::CopyMode, "Copy"),
+    (Action::Servers, "Servers"),
+];
+
+/// Views whose keys the help lists after the sessions view's, with their headings
+const HELP_VIEWS: [(AppState, &str); 11] = [
+    (AppState::SessionsSearch, "Search"),
+    (AppState::Hinting, "Jump"),
+    (AppState::Windows, "Windows"),
+    (AppState::Clients, "Clients"),
+    (AppState::History, "History"),
+    (AppState::Templates, "Templates"),
+    (AppState::Archive, "Archive"),
+    (AppState::DeepResults, "Deep Search"),
+    (AppState::Copying, "Copy Mode"),
+    (AppState::Servers, "Servers"),
+    (AppState::BatchConfirm, "Batch"),
+];
 
 /// Application.
 #[derive(Debug)]
@@ -35,263 +977,4197 @@ pub struct App<'a> {
     pub counter: u8,
     /// session name to attach
     pub on_exit: ExitAction,
-    /// Existing Tmux sessions (name, desc)
-    pub sessions: Vec<(String, String)>,
+    /// Program to suspend the interface for, taken by the main loop
+    pub foreground: Option<Foreground>,
+    /// Configuration file, edited with [`Foreground::EditConfig`]
+    pub config_path: Option<PathBuf>,
+    /// Existing Tmux sessions, grouped by host in the order of `hosts`
+    pub sessions: Vec<Session>,
+    /// Row text of each session in [`App::sessions`], kept up to date by [`App::sessions_changed`]
+    pub session_rows: Vec<SessionRow>,
+    /// Hosts whose sessions are listed. `None` is the local server and always comes first.
+    pub hosts: Vec<Option<String>>,
+    /// How long to wait for a host to list its sessions before giving up until the next refresh
+    pub host_timeout: Duration,
     /// Selected session index
     pub selected_session: usize,
     /// The application state
     pub state: AppState,
     /// Rename prompt
     pub rename_session_ta: Option<TextArea<'a>>,
-    /// New session name prompt
-    pub new_session_ta: Option<TextArea<'a>>,
+    /// New session form
+    pub new_session_form: Option<NewSessionForm<'a>>,
+    /// Split pane form of the windows view
+    pub split_form: Option<SplitForm<'a>>,
+    /// The pane the kill pane confirmation is about, as a tmux target
+    pub killing_pane: Option<String>,
+    /// The pane whose output the capture prompt saves, as a tmux target
+    pub capturing_pane: Option<String>,
+    /// Capture prompt, for the file to save a pane's output to
+    pub capture_ta: Option<TextArea<'a>>,
     /// Search prompt
     pub search_session_ta: Option<TextArea<'a>>,
     /// The row selected by a search operation
     pub search_session_selected: Option<usize>,
     /// All row indexes that match current search terms
     pub matching_rows: Vec<usize>,
+    /// Labels of sessions marked for a batch operation
+    pub marked: HashSet<String>,
+    /// Session names by alias, which searching for an alias finds
+    pub aliases: IndexMap<String, String>,
+    /// Session templates the new session form and the templates view offer
+    pub templates: Vec<Template>,
+    /// Highlighted row of the templates view
+    pub selected_template: usize,
+    /// Directory archived sessions are kept in, see [`archive::archive_dir`]
+    pub archive_dir: Option<PathBuf>,
+    /// Archived sessions, most recent first, as loaded when the archive view opened
+    pub archived: Vec<Archived>,
+    /// Highlighted row of the archive view
+    pub selected_archived: usize,
+    /// Lines of the help scrolled past
+    pub help_scroll: usize,
+    /// Batch rename pattern prompt
+    pub batch_rename_ta: Option<TextArea<'a>>,
+    /// Prompt for the command to run in a session once it is attached
+    pub attach_command_ta: Option<TextArea<'a>>,
+    /// Textarea of the prompt for the selected session's accent color
+    pub color_ta: Option<TextArea<'a>>,
+    /// The session an open confirmation or prompt is about, as (host, session id, label), so
+    /// that answering it acts on that session even if a refresh moved it to another row
+    pub confirming: Option<(Option<String>, String, String)>,
+    /// Batch operation awaiting confirmation or showing its results
+    pub batch: Option<Batch>,
+    /// Background job coordinator
+    pub scheduler: Scheduler,
+    /// Per host, incremented every time its session list is refreshed synchronously, so that
+    /// background refreshes launched before then can be recognized as stale
+    pub sessions_generations: Vec<u64>,
+    /// Per host, the last listing applied and the control mode session it was parsed with. An
+    /// identical listing is not parsed again.
+    pub listings: Vec<Option<(Option<String>, String)>>,
+    /// Per host, how it answered its recent listings
+    pub host_states: Vec<HostState>,
+    /// Recent attach events, most recent first
+    pub history: Vec<HistoryEntry>,
+    /// Selected history index
+    pub selected_history: usize,
+    /// Name of the local machine, shown as the host of local history entries
+    pub local_hostname: String,
+    /// The time zone times are shown in, see [`App::set_time_zone`]
+    pub time_zone: TimeZone,
+    /// Whether the preview pane is shown next to the sessions list
+    pub preview_visible: bool,
+    /// How often the preview is captured again, if at all
+    pub preview_interval: Option<Duration>,
+    /// How often the windows of every session are listed again, if at all, besides when the
+    /// selection moves
+    pub windows_interval: Option<Duration>,
+    /// Label of the session the preview was last requested for
+    pub preview_label: Option<String>,
+    /// Captured contents of the previewed session's active pane
+    pub preview: Option<String>,
+    /// Incremented whenever the previewed session changes, so captures of the previous selection
+    /// that are still in flight are dropped
+    pub preview_generation: u64,
+    /// The session whose windows are listed in the windows view
+    pub windows_session: Option<Session>,
+    /// Windows of `windows_session`
+    pub windows: Vec<Window>,
+    /// Windows of the sessions of each host as last prefetched, by host and session id
+    pub prefetched_windows: HashMap<(Option<String>, String), Vec<Window>>,
+    /// Label of the session the windows were last prefetched for
+    prefetch_label: Option<String>,
+    /// Incremented whenever tmm changes windows, so prefetches from before that are dropped
+    windows_generation: u64,
+    /// Selected row of the windows view tree (see [`App::tree_rows`])
+    pub selected_window: usize,
+    /// tmux indexes of the windows whose panes are shown in the windows view
+    pub expanded_windows: HashSet<usize>,
+    /// The session whose clients are listed in the clients view
+    pub clients_session: Option<Session>,
+    /// Clients of `clients_session`, apart from tmm's control mode client
+    pub clients: Vec<Client>,
+    /// Selected row of the clients view
+    pub selected_client: usize,
+    /// Deep search prompt, for the term to look for in the scrollback of every pane
+    pub deep_search_ta: Option<TextArea<'a>>,
+    /// Term of the last deep search
+    pub deep_search_term: String,
+    /// The deep search running in the background, if any
+    pub pane_search: Option<PaneSearch>,
+    /// Generation of the last deep search started
+    pane_search_generation: u64,
+    /// Panes whose scrollback mentions the deep search term
+    pub pane_matches: Vec<PaneMatch>,
+    /// Selected row of the deep search results
+    pub selected_pane_match: usize,
+    /// Text of the list in copy mode, a line per row
+    pub copy_lines: Vec<String>,
+    /// Line of the copy mode cursor
+    pub copy_cursor: usize,
+    /// Where the visual selection of copy mode starts, while one is made
+    pub copy_anchor: Option<usize>,
+    /// View copy mode returns to
+    pub copy_from: AppState,
+    /// Name of the local tmux server when it is not the default one, to tell whose sessions are
+    /// listed
+    pub server: Option<String>,
+    /// Sockets of the local tmux servers listed in the servers view
+    pub servers: Vec<PathBuf>,
+    /// Selected row of the servers view
+    pub selected_server: usize,
+    /// Server shown in the server info popup
+    pub server_info: Option<ServerInfo>,
+    /// Actions waiting for an in-flight refresh to land
+    pub pending_actions: VecDeque<PendingAction>,
+    /// Kills waiting for the processes of gracefully killed sessions to exit, and when they are due
+    pub delayed_kills: Vec<(Instant, PendingAction)>,
+    /// Keys of the sessions view. The detach key, pressed twice, quits with [`ExitAction::Detach`].
+    pub keymap: Keymap,
+    /// Whether the detach key was just pressed once
+    pub pending_detach: bool,
+    /// Keys typed so far of a longer key sequence of [`App::keymap`], and when the last one was
+    pub pending_keys: Option<(Vec<KeyBinding>, Instant)>,
+    /// How the sessions list is divided into sections
+    pub group_by: GroupBy,
+    /// Whether the configuration sets `group_by`, which adding remote hosts then leaves alone
+    pub group_by_configured: bool,
+    /// Which sessions the list shows
+    pub attached_filter: AttachedFilter,
+    /// Titles of the collapsed sections
+    pub collapsed_groups: HashSet<String>,
+    /// Name a rename was requested to that another session already has, while asking how to resolve
+    /// the collision
+    pub rename_conflict: Option<String>,
+    /// Failures waiting to be shown in the error popup, oldest first
+    pub errors: VecDeque<String>,
+    /// Short message shown in the bottom border of the current view
+    pub status: Option<Status>,
+    /// State to return to once every error has been dismissed
+    pub state_before_error: AppState,
+    /// Sources shown in the list. Sessions always come first.
+    pub sources: Vec<Source>,
+    /// Directories searched for git repositories by [`Source::Projects`]
+    pub project_roots: Vec<String>,
+    /// Entries of the shown sources other than [`Source::Sessions`], in display order
+    pub entries: Vec<SourceEntry>,
+    /// Shown sources that could not be listed the last time, whose failure was reported already
+    pub failing_sources: HashSet<Source>,
+    /// Selected entry. While set, it is selected instead of [`App::selected_session`].
+    pub selected_entry: Option<usize>,
+    /// Order of the sessions of each host
+    pub sort: SortOrder,
+    /// Whether killing a single session asks for confirmation first
+    pub confirm_delete: bool,
+    /// Whether a batch operation is summarized for confirmation before it runs
+    pub confirm_batch: bool,
+    /// Percentage of the width taken by the preview pane
+    pub preview_width: u16,
+    /// Whether the hotkey bar is shown
+    pub hotkey_bar: bool,
+    /// Whether reports are shown in a tmux popup when nested
+    pub tmux_popup: bool,
+    /// Whether moving the selection past either end of the list wraps around to the other
+    pub wrap_around: bool,
+    /// Whether the rename prompt edits the name in the session's row rather than in a popup
+    pub inline_rename: bool,
+    /// Whether non-essential output such as status messages is left out
+    pub quiet: bool,
+    /// Whether the actions changing sessions or the server are disabled, see [`Action::mutates`]
+    pub read_only: bool,
+    /// How attached sessions are sized, unless chosen for a single attach
+    pub attach_size: AttachSize,
+    /// Command the attach and run prompt runs when nothing is typed
+    pub attach_command: Option<String>,
+    /// Whether Enter in the search attaches the highlighted match rather than only selecting it
+    pub search_attach: bool,
+    /// What attaching the session tmm runs in does
+    pub current_session: CurrentSession,
+    /// Command new sessions start with unless the new session form says otherwise
+    pub new_command: Option<String>,
+    /// Color theme
+    pub theme: Theme,
+    /// Colors the configuration file changed from the theme's
+    pub color_overrides: Vec<(String, Color)>,
+    /// Interface colors: the theme's with the overrides applied
+    pub colors: Colors,
+    /// Inner area of the sessions list and its scroll offset, as last rendered, if it was
+    pub list_hit_area: Option<(Rect, usize)>,
+    /// First row of the sessions list shown, kept from frame to frame so that moving the
+    /// selection scrolls the list only once it reaches an edge
+    pub list_offset: usize,
+    /// Buttons of the popup as last rendered, with the key each stands for
+    pub click_targets: Vec<(Rect, KeyCode)>,
+    /// When and on which row of the sessions list the last click was, to recognize double clicks
+    pub last_click: Option<(Instant, usize)>,
+    /// Letters of a jump hint typed so far, see [`App::hints`]
+    pub hint_typed: String,
+    /// Runs every tmux command
+    pub tmux: Arc<dyn TmuxClient>,
     /// hotkey bar
-    pub hotkeys: HashMap<AppState, IndexMap<&'a str, &'a str>>,
+    pub hotkeys: HashMap<AppState, IndexMap<String, &'a str>>,
+    /// Actions of the keys in the sessions view's hotkey bar, by key, to tell which are disabled
+    pub hotkey_actions: HashMap<String, Action>,
 }
 
 impl<'a> Default for App<'a> {
     fn default() -> Self {
+        Self::with_client(Arc::new(ProcessClient::default()))
+    }
+}
+
+impl<'a> App<'a> {
+    /// Constructs a new instance of [`App`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs a new instance of [`App`] that runs tmux commands with `tmux`.
+    pub fn with_client(tmux: Arc<dyn TmuxClient>) -> Self {
         let mut def = Self {
             running: true,
             counter: 0,
             sessions: vec![],
+            session_rows: vec![],
+            hosts: vec![None],
+            host_timeout: Duration::from_secs(5),
             selected_session: 0,
             on_exit: ExitAction::None,
+            foreground: None,
+            config_path: None,
             state: AppState::Sessions,
-            new_session_ta: None,
+            new_session_form: None,
+            split_form: None,
+            killing_pane: None,
+            capturing_pane: None,
+            capture_ta: None,
             rename_session_ta: None,
             search_session_ta: None,
             search_session_selected: None,
             matching_rows: vec![],
+            marked: HashSet::new(),
+            aliases: IndexMap::new(),
+            templates: vec![],
+            selected_template: 0,
+            archive_dir: archive::archive_dir(),
+            archived: vec![],
+            selected_archived: 0,
+            batch_rename_ta: None,
+            attach_command_ta: None,
+            color_ta: None,
+            confirming: None,
+            batch: None,
+            scheduler: Scheduler::new(),
+            sessions_generations: vec![0],
+            listings: vec![None],
+            host_states: vec![HostState::default()],
+            history: vec![],
+            selected_history: 0,
+            local_hostname: String::new(),
+            time_zone: TimeZone::Local,
+            preview_visible: false,
+            preview_interval: Some(Duration::from_secs(1)),
+            windows_interval: None,
+            preview_label: None,
+            preview: None,
+            preview_generation: 0,
+            windows_session: None,
+            windows: vec![],
+            prefetched_windows: HashMap::new(),
+            prefetch_label: None,
+            windows_generation: 0,
+            selected_window: 0,
+            expanded_windows: HashSet::new(),
+            clients_session: None,
+            clients: vec![],
+            selected_client: 0,
+            deep_search_ta: None,
+            deep_search_term: String::new(),
+            pane_search: None,
+            pane_search_generation: 0,
+            pane_matches: vec![],
+            selected_pane_match: 0,
+            copy_lines: vec![],
+            copy_cursor: 0,
+            copy_anchor: None,
+            copy_from: AppState::Sessions,
+            server: None,
+            servers: vec![],
+            selected_server: 0,
+            server_info: None,
+            help_scroll: 0,
+            pending_actions: VecDeque::new(),
+            delayed_kills: vec![],
+            keymap: Keymap::default(),
+            pending_detach: false,
+            pending_keys: None,
+            group_by: GroupBy::None,
+            group_by_configured: false,
+            attached_filter: AttachedFilter::All,
+            collapsed_groups: HashSet::new(),
+            rename_conflict: None,
+            errors: VecDeque::new(),
+            status: None,
+            state_before_error: AppState::Sessions,
+            sources: vec![Source::Sessions],
+            failing_sources: HashSet::new(),
+            entries: vec![],
+            selected_entry: None,
+            project_roots: vec![],
+            sort: SortOrder::None,
+            confirm_delete: true,
+            confirm_batch: true,
+            preview_width: 60,
+            hotkey_bar: true,
+            tmux_popup: false,
+            inline_rename: false,
+            wrap_around: false,
+            quiet: false,
+            read_only: false,
+            attach_size: AttachSize::Detach,
+            attach_command: None,
+            search_attach: false,
+            current_session: CurrentSession::Quit,
+            new_command: None,
+            theme: Theme::Dark,
+            color_overrides: vec![],
+            colors: Colors::default(),
+            list_hit_area: None,
+            list_offset: 0,
+            click_targets: vec![],
+            last_click: None,
+            hint_typed: String::new(),
+            tmux,
+            hotkey_actions: HashMap::new(),
             hotkeys: [
-                (AppState::Sessions, [
+                (AppState::Windows, [
+                    ("q", "Quit"),
+                    ("Esc", "Back"),
+                    ("Enter", "Attach"),
+                    ("0-9", "Attach Window"),
+                    ("l", "Expand"),
+                    ("h", "Collapse"),
+                    ("m", "Mark Pane"),
+                    ("'", "Attach Marked"),
+                    ("<", "Move Up"),
+                    (">", "Move Down"),
+                    ("%", "Split Right"),
+                    ("\"", "Split Below"),
+                    ("z", "Zoom"),
+                    ("x", "Kill Pane"),
+                    ("s", "Save Output"),
+                    ("y", "Copy"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::SplitPane, [
+                    ("Esc", "Back"),
+                    ("Tab", "Next Field"),
+                    ("Enter", "Split"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::History, [
+                    ("q", "Quit"),
+                    ("Esc", "Back"),
+                    ("Enter", "Attach"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::Clients, [
+                    ("q", "Quit"),
+                    ("Esc", "Back"),
+                    ("d", "Detach"),
+                    ("x", "Kill"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::KillingClient, [
+                    ("q", "Quit"),
+                    ("Esc", "Back"),
+                    ("y", "Kill Client"),
+                    ("n", "Cancel"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::Templates, [
                     ("q", "Quit"),
-                    ("a", "Attach Session"),
-                    ("r", "Rename"),
-                    ("n", "New"),
-                    ("x", "Delete"),
-                    ("/", "Search"),
-                ].iter().cloned().collect()),
+                    ("Esc", "Back"),
+                    ("Enter", "Create Session"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::Archive, [
+                    ("q", "Quit"),
+                    ("Esc", "Back"),
+                    ("Enter", "Restore"),
+                    ("x", "Purge"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
                 (AppState::Deleting, [
                     ("q", "Quit"),
                     ("Esc", "Back"),
                     ("y", "Delete"),
+                    ("g", "Delete Gracefully"),
+                    ("n", "Cancel"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::DetachingClients, [
+                    ("q", "Quit"),
+                    ("Esc", "Back"),
+                    ("y", "Detach"),
+                    ("n", "Cancel"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::KillingPane, [
+                    ("q", "Quit"),
+                    ("Esc", "Back"),
+                    ("y", "Kill Pane"),
+                    ("n", "Cancel"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::KillingServer, [
+                    ("q", "Quit"),
+                    ("Esc", "Back"),
+                    ("y", "Kill Server"),
                     ("n", "Cancel"),
-                ].iter().cloned().collect()),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
                 (AppState::Renaming, [
                     ("Esc", "Back"),
                     ("Enter", "Rename"),
-                ].iter().cloned().collect()),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::DeepSearching, [
+                    ("Esc", "Back"),
+                    ("Enter", "Search"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::DeepSearchRunning, [
+                    ("Esc", "Cancel"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::DeepResults, [
+                    ("q", "Quit"),
+                    ("Esc", "Back"),
+                    ("Enter", "Attach Pane"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::Servers, [
+                    ("q", "Quit"),
+                    ("Esc", "Back"),
+                    ("Enter", "Switch"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::Copying, [
+                    ("Esc", "Back"),
+                    ("v", "Select"),
+                    ("y", "Copy"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::CapturingPane, [
+                    ("Esc", "Back"),
+                    ("Enter", "Save"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::AttachCommand, [
+                    ("Esc", "Back"),
+                    ("Enter", "Attach & Run"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::Hinting, [
+                    ("Esc", "Cancel"),
+                    ("a-z", "Jump"),
+                    ("A-Z", "Jump & Attach"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::Coloring, [
+                    ("Esc", "Back"),
+                    ("Enter", "Set Color"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::NewSession, [
+                    ("Esc", "Back"),
+                    ("Tab", "Next Field"),
+                    ("Enter", "Create"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
                 (AppState::WarnNested, [
                     ("q", "Quit"),
                     ("Any", "Dismiss"),
-                ].iter().cloned().collect()),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::ServerInfo, [
+                    ("q", "Quit"),
+                    ("Any", "Dismiss"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::Help, [
+                    ("q", "Quit"),
+                    ("j/k", "Scroll"),
+                    ("Esc", "Back"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::RenameConflict, [
+                    ("s", "Add Suffix"),
+                    ("m", "Merge"),
+                    ("Esc", "Back"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::AttachSizing, [
+                    ("q", "Quit"),
+                    ("d", "Detach Others"),
+                    ("s", "Smallest"),
+                    ("l", "Latest"),
+                    ("Esc", "Back"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::Error, [
+                    ("q", "Quit"),
+                    ("Any", "Dismiss"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::BatchRenaming, [
+                    ("Esc", "Back"),
+                    ("Enter", "Review"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::BatchConfirm, [
+                    ("j/k", "Scroll"),
+                    ("y", "Apply"),
+                    ("n", "Cancel"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::BatchRunning, [
+                    ("j/k", "Scroll"),
+                    ("Esc", "Abort"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
+                (AppState::BatchResults, [
+                    ("j/k", "Scroll"),
+                    ("Any", "Dismiss"),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
                 (AppState::SessionsSearch, [
                     ("Esc", "Cancel"),
                     ("Enter", "Confirm"),
+                    ("M-Enter", "Attach"),
                     ("C-n", "Select next match"),
                     ("C-p", "Select previous match"),
-                ].iter().cloned().collect()),
+                ].map(|(key, action)| (key.to_owned(), action)).into_iter().collect()),
             ].iter().cloned().collect(),
         };
+        def.update_hotkeys();
         def.refresh();
         def
     }
-}
-
-impl<'a> App<'a> {
-    /// Constructs a new instance of [`App`].
-    pub fn new() -> Self {
-        Self::default()
-    }
 
     /// Set running to false to quit the application.
     pub fn quit(&mut self) {
         self.running = false;
     }
 
-    pub fn attach(&mut self, name: String, detach_others: bool) {
+    /// Quit without attaching anything, even if a session is selected, and detach the tmux client
+    /// if nested
+    pub fn detach(&mut self) {
         self.running = false;
-        self.on_exit = ExitAction::AttachSession(name.clone(), detach_others);
+        self.on_exit = ExitAction::Detach;
     }
 
-    pub fn increment_counter(&mut self) {
-        if let Some(res) = self.counter.checked_add(1) {
-            self.counter = res;
+    /// Quit and attach the selected session
+    pub fn attach_selected(&mut self, size: AttachSize) {
+        if !self.reselect_pinned() {
+            return;
         }
+        let Some(session) = self.sessions.get(self.selected_session) else { return };
+        if session.host.is_none() && self.current_session_name().is_some_and(|name| name == session.name) {
+            self.attach_current_session();
+            return;
+        }
+        self.running = false;
+        self.on_exit = ExitAction::AttachSession(session.host.clone(), session.name.clone(), size);
     }
 
-    pub fn decrement_counter(&mut self) {
-        if let Some(res) = self.counter.checked_sub(1) {
-            self.counter = res;
+    /// Attach the selected session, which tmm is nested in, as configured by `current_session`
+    fn attach_current_session(&mut self) {
+        match self.current_session {
+            CurrentSession::Notice => self.show_status("Already in this session"),
+            CurrentSession::Quit => self.quit(),
+            CurrentSession::Windows => self.show_windows(),
         }
     }
 
-    /// Refresh list of tmux sessions
-    pub fn refresh(&mut self) {
-        let output = Command::new("tmux")
-            .args(["ls"])
-            .output()
-            .expect("failed to refresh tmux");
-        let Ok(stdout) = from_utf8(&output.stdout) else { return };
-        // Since the list can change between refreshes, need to get the name of the currently
-        // highlighted session and then re-select that row after the list is updated.
-        let selected_name = self.sessions.get(self.selected_session).map_or(None, |x| Some(x.0.to_owned()));
-        self.sessions = stdout.lines().filter_map(|line| {
-            let mut parts = line.split(":");
-            if let Some(name) = parts.next() {
-                // Get the name and remaining description
-                let remainder = parts.collect::<Vec<&str>>().join(" ");
-                Some((name.to_owned(), remainder))
-            } else {
-                None
-            }
-        }).collect();
-        // Find the selected_name in the new session list and select it. If it's not there, do not
-        // change the selected row (e.g., on a rename, the new session will not be present, but
+    /// Ask for a command to run in the selected session before attaching it, offering the
+    /// configured attach command
+    pub fn confirm_attach_run(&mut self) {
+        if self.sessions.get(self.selected_session).is_none() {
+            return;
+        }
+        let mut textarea = TextArea::default();
+        textarea.set_cursor_line_style(Style::default());
+        if let Some(command) = &self.attach_command {
+            textarea.set_placeholder_text(command);
+        }
+        self.attach_command_ta = Some(textarea);
+        self.pin_selected();
+        self.state = AppState::AttachCommand;
+    }
+
+    /// Type `command`, or the configured attach command if it is empty, into the active pane of
+    /// the selected session, then quit and attach it. Stays put if the command cannot be sent.
+    pub fn attach_selected_and_run(&mut self, command: &str) {
+        if !self.reselect_pinned() {
+            return;
+        }
+        let Some(session) = self.sessions.get(self.selected_session).cloned() else { return };
+        let command = Some(command.trim()).filter(|command| !command.is_empty())
+            .or(self.attach_command.as_deref())
+            .map(str::to_owned);
+        if let Some(command) = command {
+            // The active pane of the session's current window, typed literally and then run
+            let target = format!("={}:", session.name);
+            for args in [["send-keys", "-t", &target, "-l", &command].as_slice(), &["send-keys", "-t", &target, "Enter"]] {
+                if let Err(err) = command_result(self.tmux.output(session.host.as_deref(), args)) {
+                    self.report_error(format!("Failed to run {:?} in {}: {}", command, session.label(), err));
+                    return;
+                }
+            }
+        }
+        self.attach_command_ta = None;
+        // Even the session tmm runs in, to see what the command does
+        self.running = false;
+        self.on_exit = ExitAction::AttachSession(session.host, session.name, self.attach_size);
+    }
+
+    /// Ask for the selected session's accent color, filled with its current one to edit
+    pub fn confirm_color(&mut self) {
+        let Some(session) = self.sessions.get(self.selected_session) else { return };
+        let mut textarea = TextArea::default();
+        textarea.set_cursor_line_style(Style::default());
+        textarea.set_placeholder_text("e.g. red or #ff8800, empty for none");
+        if let Some(color) = session.color {
+            textarea.insert_str(color.to_string().to_lowercase());
+        }
+        self.color_ta = Some(textarea);
+        self.pin_selected();
+        self.state = AppState::Coloring;
+    }
+
+    /// Set the selected session's accent color, or unset it if `color` is empty. Stays in the
+    /// prompt if the color is not one tmm knows.
+    pub fn set_selected_color(&mut self, color: &str) {
+        let color = color.trim();
+        if !color.is_empty() && color.parse::<Color>().is_err() {
+            self.show_status(&format!("Unknown color {:?}", color));
+            return;
+        }
+        self.act_on_selected(SessionAction::SetColor(Some(color.to_owned()).filter(|color| !color.is_empty())));
+    }
+
+    /// Ask how to size the selected session before attaching it
+    pub fn confirm_attach_size(&mut self) {
+        if self.sessions.get(self.selected_session).is_some() {
+            self.pin_selected();
+            self.state = AppState::AttachSizing;
+        }
+    }
+
+    pub fn increment_counter(&mut self) {
+        if let Some(res) = self.counter.checked_add(1) {
+            self.counter = res;
+        }
+    }
+
+    pub fn decrement_counter(&mut self) {
+        if let Some(res) = self.counter.checked_sub(1) {
+            self.counter = res;
+        }
+    }
+
+    /// Enable or disable (`None`) the periodic background refresh of the session lists
+    pub fn set_refresh_interval(&mut self, interval: Option<Duration>) {
+        for idx in 0..self.hosts.len() {
+            self.scheduler.set_interval(Job::Sessions(idx), interval);
+        }
+    }
+
+    /// Refresh the session lists of the remote hosts on their own interval, or not at all
+    /// (`None`), to spare a slow connection or a busy server
+    pub fn set_host_refresh_interval(&mut self, interval: Option<Duration>) {
+        for idx in 1..self.hosts.len() {
+            self.scheduler.set_interval(Job::Sessions(idx), interval);
+        }
+    }
+
+    /// Add remote hosts whose sessions are listed alongside the local ones. Each host is fetched
+    /// independently in the background, so a slow host does not hold up the others.
+    pub fn add_remote_hosts(&mut self, hosts: Vec<String>) {
+        for host in hosts {
+            self.hosts.push(Some(host));
+            self.sessions_generations.push(0);
+            self.listings.push(None);
+            self.host_states.push(HostState::default());
+            self.scheduler.trigger(Job::Sessions(self.hosts.len() - 1));
+            self.scheduler.set_interval(Job::Windows(self.hosts.len() - 1), self.windows_interval);
+            // With sessions from several machines, each host gets a section showing its status,
+            // unless the configuration says how to group
+            if !self.group_by_configured {
+                self.group_by = GroupBy::Host;
+            }
+        }
+        // Hosts are fetched concurrently, so allow one job per host in flight
+        self.scheduler.max_in_flight = self.scheduler.max_in_flight.max(self.hosts.len());
+    }
+
+    /// Show or hide the preview pane
+    pub fn toggle_preview(&mut self) {
+        self.preview_visible = !self.preview_visible;
+        if self.preview_visible {
+            self.scheduler.set_interval(Job::Preview, self.preview_interval);
+            self.scheduler.trigger(Job::Preview);
+        } else {
+            self.scheduler.set_interval(Job::Preview, None);
+            self.preview_label = None;
+            self.preview = None;
+        }
+    }
+
+    /// If the selection moved since the preview was last requested, invalidate the current preview
+    /// and capture the newly selected session as soon as possible
+    fn sync_preview(&mut self) {
+        if !self.preview_visible {
+            return;
+        }
+        let label = self.selected_session().map(Session::label);
+        if label != self.preview_label {
+            self.preview_label = label;
+            self.preview = None;
+            self.preview_generation += 1;
+            self.scheduler.trigger(Job::Preview);
+        }
+    }
+
+    /// If the selection moved since the windows were last prefetched, prefetch those of the newly
+    /// selected session's host. A single listing of every session costs tmux about as much as one
+    /// of a session, and covers the sessions next to it as well.
+    fn sync_windows_prefetch(&mut self) {
+        let label = self.selected_session().map(Session::label);
+        if label == self.prefetch_label {
+            return;
+        }
+        self.prefetch_label = label;
+        if let Some(session) = self.selected_session() {
+            let host_idx = self.hosts.iter().position(|host| *host == session.host).unwrap_or(0);
+            self.scheduler.trigger(Job::Windows(host_idx));
+        }
+    }
+
+    /// Handles the tick event of the terminal by launching any background jobs that are due.
+    pub fn tick(&mut self, sender: &mpsc::Sender<Event>) {
+        self.sync_preview();
+        self.sync_windows_prefetch();
+        self.apply_delayed_kills();
+        // A running batch takes one step per tick, and asks for the next tick right away so that
+        // it runs at full speed while still drawing its progress and reacting to keys in between
+        if self.state == AppState::BatchRunning {
+            self.step_batch();
+            if self.state == AppState::BatchRunning {
+                let _ = sender.send(Event::Tick);
+            }
+        }
+        self.start_pane_search(sender);
+        while let Some(job) = self.scheduler.due(Instant::now()) {
+            let (host, args, generation) = match job {
+                Job::Entries(source) => {
+                    let project_roots = self.project_roots.clone();
+                    let sender = sender.clone();
+                    thread::spawn(move || {
+                        let _ = sender.send(Event::Entries(source, source::fetch(source, &project_roots)));
+                    });
+                    continue;
+                }
+                Job::Sessions(idx) => (self.hosts[idx].clone(), LIST_SESSIONS_ARGS.map(str::to_owned).to_vec(),
+                    self.sessions_generations[idx]),
+                Job::Preview => {
+                    let Some(session) = self.sessions.get(self.selected_session) else {
+                        // Nothing to preview, try again at the next interval
+                        self.scheduler.finished(job, Instant::now());
+                        continue;
+                    };
+                    // `=name:` targets the active pane of exactly this session, not a prefix match
+                    let target = format!("={}:", session.name);
+                    (session.host.clone(), vec!["capture-pane".to_owned(), "-p".to_owned(), "-t".to_owned(), target],
+                        self.preview_generation)
+                }
+                Job::Windows(idx) => (self.hosts[idx].clone(), list_windows_args(None), self.windows_generation),
+            };
+            let timeout = self.host_timeout;
+            let sender = sender.clone();
+            let client = Arc::clone(&self.tmux);
+            thread::spawn(move || {
+                let output = Self::run_background(&*client, host.as_deref(), &args, timeout);
+                // The receiver only goes away when the app is exiting
+                let _ = sender.send(Event::Job(JobResult { job, generation, output }));
+            });
+        }
+    }
+
+    /// Apply the result of a background job
+    pub fn job_finished(&mut self, result: JobResult) {
+        self.scheduler.finished(result.job, Instant::now());
+        match result.job {
+            Job::Sessions(idx) => {
+                let state = &mut self.host_states[idx];
+                let was_failing = state.failing;
+                state.failing = result.output.is_none();
+                if !state.failing {
+                    state.last_success = Some(Instant::now());
+                }
+                // Drop results from before the list was last refreshed synchronously. If the host
+                // could not be reached, keep showing its last known sessions.
+                if result.generation == self.sessions_generations[idx] {
+                    if let Some(stdout) = result.output {
+                        self.apply_sessions(idx, &stdout);
+                    }
+                }
+                // Remote hosts show their failures in their section
+                if idx == 0 && self.host_states[0].failing && !was_failing {
+                    self.show_failure("Refresh failed: tmux did not reply");
+                }
+                self.apply_pending_actions(idx);
+            }
+            Job::Preview => {
+                if result.generation == self.preview_generation {
+                    self.preview = result.output;
+                }
+            }
+            // Entries report back as an `Event::Entries`
+            Job::Entries(_) => {}
+            Job::Windows(idx) => {
+                let Some(stdout) = result.output.filter(|_| result.generation == self.windows_generation) else { return };
+                let host = self.hosts[idx].clone();
+                self.prefetched_windows.retain(|(other, _), _| *other != host);
+                for (id, windows) in parse_windows(&stdout) {
+                    self.prefetched_windows.insert((host.clone(), id), windows);
+                }
+                // A windows view opened from the previous prefetch catches up
+                let Some(session) = self.windows_session.as_ref().filter(|_| self.state == AppState::Windows) else { return };
+                if let Some(windows) = self.prefetched_windows.get(&(session.host.clone(), session.id.clone())) {
+                    self.windows = windows.clone();
+                    self.selected_window = self.selected_window.min(self.tree_rows().len().saturating_sub(1));
+                }
+            }
+        }
+    }
+
+    /// Handle a tmux control mode notification. Changes to the local sessions made anywhere
+    /// refresh the list right away instead of at the next interval.
+    pub fn notification(&mut self, line: &str) {
+        let kind = line.split(' ').next().unwrap_or("");
+        if matches!(kind, "%sessions-changed" | "%session-renamed" | "%window-add" | "%window-close"
+            | "%unlinked-window-add" | "%unlinked-window-close" | "%client-session-changed" | "%client-detached"
+            | "%subscription-changed" | "%exit")
+        {
+            self.scheduler.trigger(Job::Sessions(0));
+            self.scheduler.trigger(Job::Windows(0));
+        }
+    }
+
+    /// Run a tmux command on a host. Returns `None` if the host could not be reached in time.
+    fn run_background(client: &dyn TmuxClient, host: Option<&str>, args: &[String], timeout: Duration) -> Option<String> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = client.output_with_timeout(host, &args, timeout)?;
+        // ssh reports its own failures with exit code 255. Any other failure is tmux's, e.g. for
+        // `ls` most likely because there is no server running, which just means there are no
+        // sessions.
+        if host.is_some() && output.status.code() == Some(255) {
+            return None;
+        }
+        from_utf8(&output.stdout).ok().map(str::to_owned)
+    }
+
+    /// Show the keys of [`App::keymap`] in the hotkey bar: every entry of the sessions view, and
+    /// the quit key of the other views
+    fn update_hotkeys(&mut self) {
+        let mut sessions = IndexMap::new();
+        self.hotkey_actions.clear();
+        for (action, description) in SESSIONS_HOTKEYS {
+            // Read-only mode hides the actions it disables
+            if self.read_only && action.mutates() {
+                continue;
+            }
+            let label = self.keymap.label(action);
+            if !label.is_empty() {
+                sessions.insert(label.clone(), description);
+                self.hotkey_actions.insert(label, action);
+            }
+        }
+        self.hotkeys.insert(AppState::Sessions, sessions);
+        // Enter and Alt-Enter in the search trade places with `search_attach`
+        if let Some(hotkeys) = self.hotkeys.get_mut(&AppState::SessionsSearch) {
+            let (enter, alt_enter) = if self.search_attach { ("Attach", "Select") } else { ("Confirm", "Attach") };
+            hotkeys.insert("Enter".to_owned(), enter);
+            hotkeys.insert("M-Enter".to_owned(), alt_enter);
+        }
+        let quit = self.keymap.label(Action::Quit);
+        for (state, hotkeys) in self.hotkeys.iter_mut() {
+            if *state == AppState::Sessions {
+                continue;
+            }
+            if let Some(idx) = hotkeys.iter().position(|(_, description)| *description == "Quit") {
+                hotkeys.shift_remove_index(idx);
+                if !quit.is_empty() {
+                    hotkeys.shift_insert(idx, quit.clone(), "Quit");
+                }
+            }
+        }
+    }
+
+    /// Switch to the next built-in color theme, keeping the configured color overrides
+    pub fn cycle_theme(&mut self) {
+        self.theme = self.theme.next();
+        self.colors = Colors::themed(self.theme, &self.color_overrides);
+        if let Some(textarea) = self.search_session_ta.as_mut() {
+            textarea.set_style(Style::default().fg(self.colors.dim));
+        }
+    }
+
+    /// Apply the interface settings of a configuration file. Hosts and refresh intervals are left
+    /// to the caller, since the command line can override them.
+    pub fn configure(&mut self, config: &Config) {
+        self.sort = config.sort;
+        self.confirm_delete = config.confirm_delete;
+        self.confirm_batch = config.confirm_batch;
+        self.preview_width = config.preview_width;
+        self.hotkey_bar = config.hotkey_bar && !self.quiet;
+        self.tmux_popup = config.tmux_popup;
+        self.inline_rename = config.inline_rename;
+        self.wrap_around = config.wrap_around;
+        if let Some(group_by) = config.group_by {
+            self.group_by = group_by;
+        }
+        self.group_by_configured = config.group_by.is_some();
+        self.attach_size = config.attach_size;
+        self.attach_command = config.attach_command.clone();
+        self.search_attach = config.search_attach;
+        self.current_session = config.current_session;
+        self.new_command = config.new_command.clone();
+        self.theme = config.theme;
+        self.color_overrides = config.color_overrides.clone();
+        self.colors = Colors::themed(self.theme, &self.color_overrides);
+        self.project_roots = config.projects.clone();
+        self.set_sources(config.sources.clone());
+        self.keymap = config.keys.clone();
+        self.aliases = config.aliases.clone();
+        self.templates = config.templates.clone();
+        self.update_hotkeys();
+        self.preview_interval = config::interval(config.preview_refresh);
+        if self.preview_visible {
+            self.scheduler.set_interval(Job::Preview, self.preview_interval);
+        }
+        if config.preview != self.preview_visible {
+            self.toggle_preview();
+        }
+        self.windows_interval = config::interval(config.windows_refresh);
+        for idx in 0..self.hosts.len() {
+            self.scheduler.set_interval(Job::Windows(idx), self.windows_interval);
+        }
+        self.sessions_changed();
+    }
+
+    /// Refresh list of tmux sessions
+    ///
+    /// The local server is listed immediately. Remote hosts are fetched in the background and
+    /// their results are applied as they arrive.
+    pub fn refresh(&mut self) {
+        let stdout = Self::run_background(&*self.tmux, None, &LIST_SESSIONS_ARGS.map(str::to_owned), self.host_timeout);
+        self.sessions_generations[0] += 1;
+        self.scheduler.reset(Job::Sessions(0), Instant::now());
+        for idx in 1..self.hosts.len() {
+            self.scheduler.trigger(Job::Sessions(idx));
+        }
+        self.load_entries();
+        let Some(stdout) = stdout else {
+            self.show_failure("Refresh failed: tmux did not reply");
+            return;
+        };
+        self.apply_sessions(0, &stdout);
+    }
+
+    /// Show the given sources, listing the entries of those other than sessions. Selects the
+    /// first entry if sessions are not shown.
+    pub fn set_sources(&mut self, sources: Vec<Source>) {
+        self.sources = sources;
+        // Sources that fail are reported again once shown again
+        self.failing_sources.clear();
+        self.load_entries();
+    }
+
+    /// Switch to the next mix of sources: sessions, sessions and directories, directories, and
+    /// projects if there are project roots
+    pub fn cycle_sources(&mut self) {
+        let sources = match self.sources.as_slice() {
+            [Source::Sessions] => vec![Source::Sessions, Source::Directories],
+            [Source::Sessions, Source::Directories] => vec![Source::Directories],
+            [Source::Directories] if !self.project_roots.is_empty() => vec![Source::Projects],
+            _ => vec![Source::Sessions],
+        };
+        self.selected_entry = None;
+        self.set_sources(sources);
+    }
+
+    /// Re-list the entries of the shown sources other than sessions: templates and archived
+    /// sessions right away, and the sources that are [`Source::is_fetched`] in the background.
+    /// The entries of sources no longer shown are dropped.
+    fn load_entries(&mut self) {
+        for source in Source::ALL {
+            if !self.sources.contains(&source) {
+                self.replace_entries(source, vec![]);
+                continue;
+            }
+            match source {
+                Source::Sessions => {}
+                Source::Directories | Source::Projects => self.scheduler.trigger(Job::Entries(source)),
+                Source::Templates => {
+                    let entries = self.templates.iter().map(|template| {
+                        SourceEntry { source, label: template.name.clone(), path: template.name.clone() }
+                    }).collect();
+                    self.replace_entries(source, entries);
+                }
+                Source::Snapshots => {
+                    let archived = self.archive_dir.as_deref().map(archive::load).unwrap_or_default();
+                    let entries = archived.into_iter().map(|archived| SourceEntry {
+                        source,
+                        label: format!("{}  archived {}", archived.template.name, history::format_timestamp(archived.timestamp, self.time_zone)),
+                        path: archived.path.to_string_lossy().into_owned(),
+                    }).collect();
+                    self.replace_entries(source, entries);
+                }
+            }
+        }
+    }
+
+    /// Apply the entries of a source listed in the background. A source that fails keeps no
+    /// entries, and its failure is reported once rather than at every refresh.
+    pub fn entries_loaded(&mut self, source: Source, entries: Result<Vec<SourceEntry>, String>) {
+        self.scheduler.finished(Job::Entries(source), Instant::now());
+        // The source may have been hidden in the meantime
+        if !self.sources.contains(&source) {
+            return;
+        }
+        match entries {
+            Ok(entries) => {
+                self.failing_sources.remove(&source);
+                self.replace_entries(source, entries);
+            }
+            Err(err) => {
+                self.replace_entries(source, vec![]);
+                if self.failing_sources.insert(source) {
+                    self.report_error(err);
+                }
+            }
+        }
+    }
+
+    /// Replace the entries of one source, keeping the entries in the order of [`App::sources`]
+    /// and the same entry selected if it is still listed. Selects the first entry if sessions are
+    /// not shown.
+    fn replace_entries(&mut self, source: Source, entries: Vec<SourceEntry>) {
+        let selected = self.selected_entry.and_then(|idx| self.entries.get(idx)).cloned();
+        self.entries.retain(|entry| entry.source != source);
+        self.entries.extend(entries);
+        // Stable, so each source's entries stay in the order they were listed in
+        self.entries.sort_by_key(|entry| self.sources.iter().position(|shown| *shown == entry.source));
+        self.selected_entry = selected.map(|selected| {
+            self.entries.iter().position(|entry| *entry == selected).unwrap_or(0)
+        }).filter(|_| !self.entries.is_empty());
+        if !self.sources.contains(&Source::Sessions) && self.selected_entry.is_none() && !self.entries.is_empty() {
+            self.selected_entry = Some(0);
+        }
+    }
+
+    /// The selected session, unless an entry of another source is selected or sessions are not
+    /// shown
+    pub fn selected_session(&self) -> Option<&Session> {
+        if self.selected_entry.is_some() || !self.sources.contains(&Source::Sessions) {
+            return None;
+        }
+        self.sessions.get(self.selected_session)
+    }
+
+    /// Why `action` of the sessions view cannot run on the selected row, if it cannot. The hotkey
+    /// bar dims such actions, and pressing their key says why.
+    pub fn disabled_reason(&self, action: Action) -> Option<String> {
+        // Actions on a session that other rows have no stand-in for
+        let session_only = matches!(action, Action::AttachRun | Action::Rename | Action::Delete | Action::DetachClients | Action::Clients | Action::Mark | Action::Color | Action::Archive);
+        let on_session = session_only || matches!(action, Action::Attach | Action::AttachSized | Action::Windows);
+        if self.read_only && action.mutates() {
+            return Some(READ_ONLY.to_owned());
+        }
+        if action == Action::Archive && self.selected_session().is_some_and(|session| session.host.is_some()) {
+            return Some("Only local sessions can be archived".to_owned());
+        }
+        if action == Action::RenameCurrent && !self.is_nested() {
+            return Some("Not running inside tmux".to_owned());
+        }
+        if !on_session {
+            return None;
+        }
+        if session_only {
+            if let Some(entry) = self.selected_entry.and_then(|idx| self.entries.get(idx)) {
+                return Some(format!("Not available for {}", entry.source.name()));
+            }
+            if self.selection_collapsed() {
+                return Some("Not available for a collapsed section".to_owned());
+            }
+        }
+        if self.selected_entry.is_some() {
+            return None;
+        }
+        let Some(session) = self.selected_session() else { return Some("No session selected".to_owned()) };
+        let host_idx = self.hosts.iter().position(|host| host == &session.host).unwrap_or(0);
+        if matches!(self.host_status(host_idx), HostStatus::Stale(_) | HostStatus::Disconnected) {
+            return Some(format!("{} is unreachable", session.host.as_deref().unwrap_or("The server")));
+        }
+        if action == Action::DetachClients && session.attached == 0 {
+            return Some(format!("No clients attached to {}", session.label()));
+        }
+        None
+    }
+
+    /// Run the default action of the selected entry's source. A directory or project is attached as
+    /// a session named after it, created in that directory unless a local session has that name
+    /// already. A template creates a session, and an archived session is restored.
+    pub fn open_selected_entry(&mut self) {
+        let Some(entry) = self.selected_entry.and_then(|idx| self.entries.get(idx)).cloned() else { return };
+        match entry.source {
+            Source::Sessions => {}
+            Source::Templates => self.new_session_from_template("", &entry.path, None),
+            Source::Snapshots => {
+                let archived = self.archive_dir.as_deref().map(archive::load).unwrap_or_default();
+                let Some(archived) = archived.into_iter().find(|archived| archived.path.as_os_str() == entry.path.as_str()) else {
+                    self.report_error(format!("{} is no longer archived", entry.label));
+                    return;
+                };
+                self.restore_archived(archived);
+                self.load_entries();
+            }
+            Source::Directories | Source::Projects => {
+                let name = entry.session_name();
+                if !self.sessions.iter().any(|session| session.host.is_none() && session.name == name) {
+                    if self.refuse_read_only() {
+                        return;
+                    }
+                    let result = command_result(self.tmux.output(None, &["new-session", "-d", "-s", &name, "-c", &entry.path]));
+                    if let Err(err) = result {
+                        self.report_error(err);
+                        return;
+                    }
+                }
+                self.running = false;
+                self.on_exit = ExitAction::AttachSession(None, name, self.attach_size);
+            }
+        }
+    }
+
+    /// Replace the sessions of one host with parsed [`LIST_SESSIONS_ARGS`] output
+    fn apply_sessions(&mut self, host_idx: usize, stdout: &str) {
+        // Since the list can change between refreshes, need to get the name of the currently
+        // highlighted session and then re-select that row after the list is updated.
+        let selected_label = self.sessions.get(self.selected_session).map(Session::label);
+        let host = self.hosts[host_idx].clone();
+        // tmm's own control mode client does not make a session attached
+        let control_session = if host_idx == 0 { self.tmux.control_session_id() } else { None };
+        // Most refreshes find nothing changed
+        let listing = (control_session, stdout.to_owned());
+        if self.listings[host_idx].as_ref() == Some(&listing) {
+            return;
+        }
+        let (control_session, _) = self.listings[host_idx].insert(listing);
+        let fetched = Self::parse_sessions(host, stdout, control_session.as_deref());
+        // Splice the fetched sessions in place of the host's old ones, keeping the list grouped by
+        // host in the configured order
+        let mut sessions = Vec::with_capacity(self.sessions.len());
+        for other in &self.hosts[..host_idx] {
+            sessions.extend(self.sessions.iter().filter(|s| &s.host == other).cloned());
+        }
+        sessions.extend(fetched);
+        for other in &self.hosts[host_idx + 1..] {
+            sessions.extend(self.sessions.iter().filter(|s| &s.host == other).cloned());
+        }
+        self.sessions = sessions;
+        self.sessions_changed();
+        // Find the selected_label in the new session list and select it. If it's not there, do
+        // not change the selected row (e.g., on a rename, the new session will not be present, but
         // want to maintain the selection)
-        if let Some(selected_name) = selected_name {
-            if let Some(idx) = self.sessions.iter().position(|(name, _)| name == &selected_name) {
+        if let Some(selected_label) = selected_label {
+            if let Some(idx) = self.sessions.iter().position(|s| s.label() == selected_label) {
                 self.selected_session = idx
             }
         }
-        // Ensure the selected session is legal
-        self.selected_session = self.selected_session.max(0).min(self.sessions.len()-1);
+        // Forget marks on sessions that no longer exist
+        let labels: HashSet<String> = self.sessions.iter().map(Session::label).collect();
+        self.marked.retain(|label| labels.contains(label));
+        // Ensure the selected session is legal, and shown
+        self.selected_session = self.selected_session.min(self.sessions.len().saturating_sub(1));
+        self.keep_selection_shown();
+        // A confirmation about a session that just went away has nothing left to confirm
+        if matches!(self.state, AppState::Deleting | AppState::DetachingClients | AppState::AttachSizing) {
+            self.reselect_pinned();
+        }
+    }
+
+    /// Show times in `zone`, e.g. UTC so that the rendering does not depend on the machine
+    pub fn set_time_zone(&mut self, zone: TimeZone) {
+        self.time_zone = zone;
+        self.sessions_changed();
+    }
+
+    /// Sort the sessions and format their rows again. Must follow every change of
+    /// [`App::sessions`].
+    pub fn sessions_changed(&mut self) {
+        self.sort_sessions();
+        let width = self.max_session_name_width();
+        let badge_width = self.sessions.iter().map(|session| session.windows.to_string().len()).max().unwrap_or(0) + 4;
+        self.session_rows = self.sessions.iter().map(|session| {
+            let label = session.label();
+            SessionRow {
+                badge: format!("{:<1$}", format!("[{}w]", session.windows), badge_width + 1),
+                text: format!("{:>2$}: {}", label, session.desc(self.time_zone), width),
+                label,
+            }
+        }).collect();
+    }
+
+    /// Order the sessions of each host by [`App::sort`], keeping hosts in the configured order
+    fn sort_sessions(&mut self) {
+        let hosts = &self.hosts;
+        let host_idx = |session: &Session| hosts.iter().position(|host| host == &session.host);
+        match self.sort {
+            SortOrder::None => {}
+            SortOrder::Name => self.sessions.sort_by(|a, b| host_idx(a).cmp(&host_idx(b)).then_with(|| a.name.cmp(&b.name))),
+            SortOrder::Created => self.sessions.sort_by_key(|s| (host_idx(s), s.created)),
+            SortOrder::Activity => self.sessions.sort_by_key(|s| (host_idx(s), std::cmp::Reverse(s.activity))),
+            SortOrder::Attached => self.sessions.sort_by_key(|s| (host_idx(s), s.attached == 0)),
+        }
+    }
+
+    /// Switch to the next sort order, keeping the selected session selected, and remember it for
+    /// the next start
+    pub fn cycle_sort(&mut self) {
+        let idx = SortOrder::ALL.iter().position(|sort| *sort == self.sort).unwrap_or(0);
+        self.sort = SortOrder::ALL[(idx + 1) % SortOrder::ALL.len()];
+        if let Some(path) = sort_file() {
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            let _ = fs::write(path, self.sort.name());
+        }
+        self.keeping_selection(|app| if app.sort == SortOrder::None {
+            // Only tmux knows its own order
+            app.refresh();
+        } else {
+            app.sessions_changed();
+        });
+    }
+
+    /// Change how the list is shown, such as its order, filter or grouping, keeping the selected
+    /// session selected wherever it lands rather than whichever one lands on its index. Sessions
+    /// are told apart by host and id, since ids only tell the sessions of one server apart. The
+    /// list scrolls to the selection when it is drawn, and a session the change hides gives way
+    /// to the first row.
+    fn keeping_selection(&mut self, change: impl FnOnce(&mut Self)) {
+        let selected = self.sessions.get(self.selected_session).map(|s| (s.host.clone(), s.id.clone()));
+        change(self);
+        if let Some(idx) = selected.and_then(|(host, id)| self.sessions.iter().position(|s| s.host == host && s.id == id)) {
+            self.selected_session = idx;
+        }
+        self.keep_selection_shown();
+    }
+
+    /// Use the sort order last chosen with the sort key, if any, over the configured one
+    pub fn restore_sort(&mut self) {
+        let saved = sort_file().and_then(|path| fs::read_to_string(path).ok());
+        if let Some(sort) = saved.and_then(|name| SortOrder::from_name(name.trim())) {
+            self.sort = sort;
+            self.sessions_changed();
+        }
+    }
+
+    /// Sessions by section title in display order, or `None` if the list is not grouped
+    fn groups(&self) -> Option<IndexMap<String, Vec<usize>>> {
+        let mut groups: IndexMap<String, Vec<usize>> = IndexMap::new();
+        for (idx, session) in self.sessions.iter().enumerate() {
+            if !self.is_shown(session) {
+                continue;
+            }
+            let title = match self.group_by {
+                GroupBy::None => return None,
+                GroupBy::Host => session.host.clone().unwrap_or_else(|| "local".to_owned()),
+                // Groups only span sessions of one server
+                GroupBy::SessionGroup => match (&session.host, &session.group) {
+                    (_, None) => "(ungrouped)".to_owned(),
+                    (None, Some(group)) => group.to_owned(),
+                    (Some(host), Some(group)) => format!("{}/{}", host, group),
+                },
+            };
+            groups.entry(title).or_default().push(idx);
+        }
+        // A host without sessions, perhaps because it cannot be reached, still shows its status
+        if self.group_by == GroupBy::Host {
+            for host in self.hosts.iter().flatten() {
+                groups.entry(host.to_owned()).or_default();
+            }
+        }
+        Some(groups)
+    }
+
+    /// The host a section is for, when grouped by host
+    pub fn section_host(&self, title: &str) -> Option<usize> {
+        if self.group_by != GroupBy::Host {
+            return None;
+        }
+        match title {
+            "local" => Some(0),
+            _ => self.hosts.iter().position(|host| host.as_deref() == Some(title)),
+        }
+    }
+
+    /// Connection status of a host. The local server is always there.
+    pub fn host_status(&self, host_idx: usize) -> HostStatus {
+        let state = self.host_states[host_idx];
+        match (state.last_success, state.failing) {
+            _ if host_idx == 0 => HostStatus::Connected,
+            (None, false) => HostStatus::Connecting,
+            (None, true) => HostStatus::Disconnected,
+            (Some(last), true) => HostStatus::Stale(last.elapsed()),
+            (Some(_), false) => HostStatus::Connected,
+        }
+    }
+
+    /// Refresh only the host of the selected session, e.g. once a VPN is back
+    pub fn refresh_selected_host(&mut self) {
+        let host = self.sessions.get(self.selected_session).map(|session| session.host.clone()).unwrap_or(None);
+        let host_idx = self.hosts.iter().position(|h| *h == host).unwrap_or(0);
+        if host_idx == 0 {
+            self.refresh();
+        } else {
+            self.scheduler.trigger(Job::Sessions(host_idx));
+        }
+        let message = format!("Refreshing {}", host.as_deref().unwrap_or("local"));
+        self.show_status(&message);
+    }
+
+    /// Whether a section is collapsed. Searching shows every section so that all matches are
+    /// visible.
+    fn is_collapsed(&self, title: &str) -> bool {
+        self.state != AppState::SessionsSearch && self.collapsed_groups.contains(title)
+    }
+
+    /// Rows of the sessions list: the sessions, divided into sections with header rows when
+    /// grouped. The sessions of collapsed sections are left out.
+    /// Entries of other sources follow in a section per source.
+    pub fn list_rows(&self) -> Vec<ListRow> {
+        let mut rows = vec![];
+        if self.sources.contains(&Source::Sessions) {
+            match self.groups() {
+                // While searching, the matching sessions by score take the place of the sections
+                _ if self.search_needle().is_some() => rows.extend(self.search_matches().into_iter().map(|(idx, _)| ListRow::Session(idx))),
+                None => rows.extend((0..self.sessions.len()).filter(|idx| self.is_shown(&self.sessions[*idx])).map(ListRow::Session)),
+                Some(groups) => for (title, members) in groups {
+                    let collapsed = self.is_collapsed(&title);
+                    rows.push(ListRow::Header(title, members.len(), collapsed));
+                    if !collapsed {
+                        rows.extend(members.into_iter().map(ListRow::Session));
+                    }
+                },
+            }
+        }
+        for source in &self.sources {
+            let members: Vec<usize> = (0..self.entries.len()).filter(|idx| self.entries[*idx].source == *source).collect();
+            if members.is_empty() {
+                continue;
+            }
+            rows.push(ListRow::Header(source.name().to_owned(), members.len(), false));
+            rows.extend(members.into_iter().map(ListRow::Entry));
+        }
+        rows
+    }
+
+    /// Title and sessions of the section containing a session, if grouped
+    fn group_of(&self, session_idx: usize) -> Option<(String, Vec<usize>)> {
+        self.groups()?.into_iter().find(|(_, members)| members.contains(&session_idx))
+    }
+
+    /// Row of `rows` showing the selection
+    pub fn selected_row(&self, rows: &[ListRow]) -> Option<usize> {
+        match self.selected_entry {
+            Some(entry) => rows.iter().position(|row| *row == ListRow::Entry(entry)),
+            None => self.row_of(rows, self.selected_session),
+        }
+    }
+
+    /// Row of `rows` showing a session: its own row or, if its section is collapsed, the header
+    pub fn row_of(&self, rows: &[ListRow], session_idx: usize) -> Option<usize> {
+        rows.iter().position(|row| *row == ListRow::Session(session_idx)).or_else(|| {
+            let (title, _) = self.group_of(session_idx)?;
+            rows.iter().position(|row| matches!(row, ListRow::Header(t, _, true) if *t == title))
+        })
+    }
+
+    /// Rows the selection can stop at, in display order. A collapsed section is a single stop at
+    /// its first session.
+    fn selection_stops(&self) -> Vec<Stop> {
+        self.list_rows().into_iter().filter_map(|row| match row {
+            ListRow::Session(idx) => Some(Stop::Session(idx)),
+            ListRow::Entry(idx) => Some(Stop::Entry(idx)),
+            ListRow::Header(title, _, true) => self.groups()?.get(&title)?.first().map(|idx| Stop::Session(*idx)),
+            ListRow::Header(..) => None,
+        }).collect()
+    }
+
+    fn select_stop(&mut self, stop: Stop) {
+        match stop {
+            Stop::Session(idx) => {
+                self.selected_session = idx;
+                self.selected_entry = None;
+            }
+            Stop::Entry(idx) => self.selected_entry = Some(idx),
+        }
+    }
+
+    /// Move the selection by `offset` stops, stopping at either end of the list unless `wrap`
+    /// carries it around to the other end
+    fn move_selection(&mut self, offset: isize, wrap: bool) {
+        let stops = self.selection_stops();
+        if stops.is_empty() {
+            return;
+        }
+        // A selection hidden in a collapsed section is at that section's stop
+        let current = match self.selected_entry {
+            Some(entry) => stops.iter().position(|stop| *stop == Stop::Entry(entry)),
+            None => stops.iter().position(|stop| *stop == Stop::Session(self.selected_session))
+                .or_else(|| {
+                    let (_, members) = self.group_of(self.selected_session)?;
+                    stops.iter().position(|stop| matches!(stop, Stop::Session(idx) if members.contains(idx)))
+                }),
+        }.unwrap_or(0);
+        let next = match wrap {
+            true => (current as isize + offset).rem_euclid(stops.len() as isize) as usize,
+            false => current.saturating_add_signed(offset).min(stops.len() - 1),
+        };
+        self.select_stop(stops[next]);
+    }
+
+    /// Indexes of the first nine sessions listed, which the digits 1 to 9 attach
+    pub fn numbered_sessions(&self) -> Vec<usize> {
+        self.list_rows().into_iter()
+            .filter_map(|row| match row {
+                ListRow::Session(idx) => Some(idx),
+                _ => None,
+            })
+            .take(9)
+            .collect()
+    }
+
+    /// Attach the session listed with `number`, from 1 to 9
+    pub fn attach_session_numbered(&mut self, number: usize) {
+        let Some(idx) = number.checked_sub(1).and_then(|idx| self.numbered_sessions().get(idx).copied()) else {
+            self.show_status(&format!("No session {}", number));
+            return;
+        };
+        self.select_stop(Stop::Session(idx));
+        self.attach_selected(self.attach_size);
+    }
+
+    /// Move the selection up (`direction` -1) or down (1) by a `1/fraction` of the list's height
+    /// as last rendered, scrolling the list as far so the selection stays put on screen
+    pub fn select_page(&mut self, direction: isize, fraction: usize) {
+        let height = self.list_hit_area.map_or(10, |(area, _)| area.height as usize);
+        let page = (height / fraction).max(1);
+        self.move_selection(direction * page as isize, false);
+        let max_offset = self.list_rows().len().saturating_sub(height);
+        self.list_offset = self.list_offset.saturating_add_signed(direction * page as isize).min(max_offset);
+    }
+
+    /// Row of [`App::list_rows`] at a screen position, as last rendered
+    pub fn list_row_at(&self, column: u16, row: u16) -> Option<usize> {
+        let (area, offset) = self.list_hit_area?;
+        if !(area.x..area.x + area.width).contains(&column) || !(area.y..area.y + area.height).contains(&row) {
+            return None;
+        }
+        let idx = offset + (row - area.y) as usize;
+        (idx < self.list_rows().len()).then_some(idx)
+    }
+
+    /// Select a row of [`App::list_rows`], e.g. when it is clicked. Selecting a section header
+    /// collapses or expands its section.
+    pub fn select_row(&mut self, row: usize) {
+        match self.list_rows().get(row) {
+            Some(ListRow::Session(idx)) => self.select_stop(Stop::Session(*idx)),
+            Some(ListRow::Entry(idx)) => self.select_stop(Stop::Entry(*idx)),
+            // Only sections of sessions can be collapsed
+            Some(ListRow::Header(title, ..)) => {
+                let Some(first) = self.groups().and_then(|groups| groups.get(title)?.first().copied()) else { return };
+                self.select_stop(Stop::Session(first));
+                self.toggle_group_collapsed();
+            }
+            None => {}
+        }
+    }
+
+    /// Jump hints of the sessions and entries on screen, by row of [`App::list_rows`]: a letter
+    /// each, or two when there are more rows than letters
+    pub fn hints(&self) -> Vec<(usize, String)> {
+        let Some((area, offset)) = self.list_hit_area else { return vec![] };
+        let rows: Vec<usize> = self.list_rows().iter().enumerate()
+            .skip(offset)
+            .take(area.height as usize)
+            .filter(|(_, row)| matches!(row, ListRow::Session(_) | ListRow::Entry(_)))
+            .map(|(idx, _)| idx)
+            .collect();
+        let letters: Vec<char> = HINT_LETTERS.chars().collect();
+        let labels: Vec<String> = if rows.len() <= letters.len() {
+            letters.iter().map(char::to_string).collect()
+        } else {
+            letters.iter().flat_map(|first| letters.iter().map(move |second| format!("{}{}", first, second))).collect()
+        };
+        rows.into_iter().zip(labels).collect()
+    }
+
+    /// Show jump hints on the rows on screen
+    pub fn start_hints(&mut self) {
+        if self.hints().is_empty() {
+            return;
+        }
+        self.hint_typed.clear();
+        self.state = AppState::Hinting;
+    }
+
+    /// Type a letter of a jump hint. Completing a hint selects its row, and attaches or opens it
+    /// too if the letter was typed in uppercase. A letter no hint continues cancels the hints.
+    pub fn type_hint(&mut self, letter: char) {
+        self.hint_typed.push(letter.to_ascii_lowercase());
+        let hints = self.hints();
+        if hints.iter().any(|(_, hint)| hint.starts_with(&self.hint_typed) && *hint != self.hint_typed) {
+            return;
+        }
+        self.state = AppState::Sessions;
+        let Some((row, _)) = hints.into_iter().find(|(_, hint)| *hint == self.hint_typed) else {
+            self.show_status(&format!("No hint {}", self.hint_typed));
+            return;
+        };
+        self.select_row(row);
+        if letter.is_uppercase() {
+            match self.selected_session() {
+                Some(_) => self.attach_selected(self.attach_size),
+                None => self.open_selected_entry(),
+            }
+        }
+    }
+
+    /// Select the next session (or collapsed section)
+    pub fn select_next(&mut self) {
+        self.move_selection(1, self.wrap_around);
+    }
+
+    /// Select the previous session (or collapsed section)
+    pub fn select_previous(&mut self) {
+        self.move_selection(-1, self.wrap_around);
+    }
+
+    /// Select the first row of the list
+    pub fn select_first(&mut self) {
+        if let Some(first) = self.selection_stops().first() {
+            self.select_stop(*first);
+        }
+    }
+
+    /// Select the last row of the list
+    pub fn select_last(&mut self) {
+        if let Some(last) = self.selection_stops().last() {
+            self.select_stop(*last);
+        }
+    }
+
+    /// Whether [`App::attached_filter`] lets a session be listed
+    pub fn is_shown(&self, session: &Session) -> bool {
+        match self.attached_filter {
+            AttachedFilter::All => true,
+            AttachedFilter::Detached => session.attached == 0,
+            AttachedFilter::Attached => session.attached > 0,
+        }
+    }
+
+    /// Cycle between listing every session, only the detached ones and only the attached ones
+    pub fn cycle_attached_filter(&mut self) {
+        self.keeping_selection(|app| app.attached_filter = match app.attached_filter {
+            AttachedFilter::All => AttachedFilter::Detached,
+            AttachedFilter::Detached => AttachedFilter::Attached,
+            AttachedFilter::Attached => AttachedFilter::All,
+        });
+    }
+
+    /// Move a selected session the attached filter hides to the first row
+    fn keep_selection_shown(&mut self) {
+        let hidden = self.sessions.get(self.selected_session).is_some_and(|session| !self.is_shown(session));
+        if self.selected_entry.is_none() && hidden {
+            self.select_first();
+        }
+    }
+
+    /// Switch to the next way of grouping the list: none, by host, by tmux session group
+    pub fn cycle_group_by(&mut self) {
+        self.keeping_selection(|app| app.group_by = match app.group_by {
+            GroupBy::None => GroupBy::Host,
+            GroupBy::Host => GroupBy::SessionGroup,
+            GroupBy::SessionGroup => GroupBy::None,
+        });
+    }
+
+    /// Collapse or expand the section of the selected session
+    pub fn toggle_group_collapsed(&mut self) {
+        if self.selected_entry.is_some() {
+            return;
+        }
+        let Some((title, members)) = self.group_of(self.selected_session) else { return };
+        if !self.collapsed_groups.remove(&title) {
+            self.collapsed_groups.insert(title);
+            // Select the header, which stands in for the section's sessions
+            self.selected_session = members[0];
+        }
+    }
+
+    /// Whether the selection is a collapsed section's header rather than a session
+    pub fn selection_collapsed(&self) -> bool {
+        self.selected_entry.is_none() && self.group_of(self.selected_session).is_some_and(|(title, _)| self.is_collapsed(&title))
+    }
+
+    /// List the sessions of a server outside the interface, e.g. for the command line
+    /// subcommands. The error is tmux's, or why it could not be run.
+    pub fn list_sessions(client: &dyn TmuxClient, host: Option<&str>) -> Result<Vec<Session>, String> {
+        match client.output(host, &LIST_SESSIONS_ARGS) {
+            Ok(output) if output.status.success() => {
+                Ok(Self::parse_sessions(host.map(str::to_owned), &String::from_utf8_lossy(&output.stdout), None))
+            }
+            output => command_result(output).map(|_| vec![]),
+        }
+    }
+
+    /// Parse [`LIST_SESSIONS_ARGS`] output into the sessions of `host`. `control_session` is the
+    /// id of the session tmm's control mode client is attached to, which is not counted as a client.
+    fn parse_sessions(host: Option<String>, stdout: &str, control_session: Option<&str>) -> Vec<Session> {
+        let mut dead_panes: HashMap<&str, usize> = HashMap::new();
+        for line in stdout.lines().filter_map(|line| line.strip_prefix("P\t")) {
+            if let Some((id, "1")) = line.split_once('\t') {
+                *dead_panes.entry(id).or_default() += 1;
+            }
+        }
+        let last_attaches: HashMap<&str, meta::Attach> = stdout.lines()
+            .filter_map(|line| line.strip_prefix("M\t")?.split_once('\t'))
+            .filter_map(|(id, value)| Some((id, meta::Attach::parse(value)?)))
+            .collect();
+        let colors: HashMap<&str, Color> = stdout.lines()
+            .filter_map(|line| line.strip_prefix("C\t")?.split_once('\t'))
+            .filter_map(|(id, value)| Some((id, value.parse().ok()?)))
+            .collect();
+        stdout.lines().filter_map(|line| line.strip_prefix("S\t")).filter_map(|line| {
+            let mut parts = line.splitn(7, '\t');
+            let id = parts.next()?;
+            let windows = parts.next()?.parse().ok()?;
+            let created = parts.next()?.parse().ok()?;
+            let mut attached: usize = parts.next()?.parse().ok()?;
+            if control_session == Some(id) {
+                attached = attached.saturating_sub(1);
+            }
+            let activity = parts.next()?.parse().ok()?;
+            let group = parts.next()?;
+            let name = parts.next()?;
+            Some(Session {
+                host: host.clone(),
+                name: name.to_owned(),
+                id: id.to_owned(),
+                windows,
+                created,
+                attached,
+                activity,
+                dead_panes: dead_panes.get(id).copied().unwrap_or(0),
+                group: (!group.is_empty()).then(|| group.to_owned()),
+                last_attach: last_attaches.get(id).cloned(),
+                color: colors.get(id).copied(),
+            })
+        }).collect()
+    }
+
+    /// Get the maximum width of all session names
+    pub fn max_session_name_width(&self) -> usize {
+        self.sessions.iter().map(|session| {
+            session.label().len()
+        }).fold(0, |acc, x| acc.max(x))
+    }
+
+    /// Toggle the batch mark on the selected session and move down to the next one
+    pub fn toggle_mark(&mut self) {
+        let Some(session) = self.sessions.get(self.selected_session) else { return };
+        let label = session.label();
+        if !self.marked.remove(&label) {
+            self.marked.insert(label);
+        }
+        self.select_next();
+    }
+
+    /// Unmark every session
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+    }
+
+    /// The marked sessions in list order
+    pub fn marked_sessions(&self) -> Vec<Session> {
+        self.sessions.iter()
+            .filter(|session| self.marked.contains(&session.label()))
+            .cloned()
+            .collect()
+    }
+
+    /// Start a confirmed delete. With marked sessions, this summarizes a batch kill instead.
+    pub fn confirm_delete(&mut self) {
+        if !self.marked.is_empty() {
+            self.batch = Some(Batch::kill(&self.marked_sessions()));
+            self.state = AppState::BatchConfirm;
+            if !self.confirm_batch {
+                self.run_batch();
+            }
+            return;
+        }
+        if !self.confirm_delete {
+            self.delete();
+            return;
+        }
+        self.pin_selected();
+        self.state = AppState::Deleting;
+    }
+
+    /// Start a confirmed rename. With marked sessions, this prompts for a batch rename pattern
+    /// instead.
+    pub fn confirm_rename(&mut self) {
+        if !self.marked.is_empty() {
+            let mut textarea = TextArea::default();
+            textarea.set_cursor_line_style(Style::default());
+            self.batch_rename_ta = Some(textarea);
+            self.state = AppState::BatchRenaming;
+            return;
+        }
+        self.open_rename_prompt();
+    }
+
+    /// Select the session tmm is running in and start renaming it, regardless of any marks. Does
+    /// nothing if tmm is not nested or the session is not listed.
+    pub fn confirm_rename_current(&mut self) {
+        let Some(current) = self.current_session_name() else { return };
+        let Some(idx) = self.sessions.iter().position(|s| s.host.is_none() && s.name == current) else { return };
+        self.selected_session = idx;
+        self.open_rename_prompt();
+    }
+
+    /// Create the textarea, filled with the selected session's name to edit, and switch to
+    /// renaming state
+    fn open_rename_prompt(&mut self) {
+        let mut textarea = TextArea::default();
+        textarea.set_cursor_line_style(Style::default());
+        if let Some(session) = self.sessions.get(self.selected_session) {
+            textarea.insert_str(&session.name);
+        }
+        // Selected with the cursor at the end, so that typing replaces the name and moving the
+        // cursor edits it
+        textarea.select_all();
+        self.rename_session_ta = Some(textarea);
+        self.pin_selected();
+        self.state = AppState::Renaming;
+    }
+
+    /// The session a search term names through an alias
+    pub fn alias_target(&self, needle: &str) -> Option<usize> {
+        let name = self.aliases.get(needle)?;
+        self.sessions.iter().position(|session| &session.label() == name)
+    }
+
+    /// The search term, while one is typed
+    fn search_needle(&self) -> Option<&str> {
+        let textarea = self.search_session_ta.as_ref().filter(|_| self.state == AppState::SessionsSearch)?;
+        Some(textarea.lines()[0].as_str()).filter(|needle| !needle.is_empty())
+    }
+
+    /// Shown sessions whose names fuzzily match the search term, best first and otherwise in list
+    /// order, with the byte offsets of the matched characters in their rows. The session an alias
+    /// names comes first, whether or not its name matches. In regex mode the rows matching are
+    /// listed in order.
+    pub fn search_matches(&self) -> Vec<(usize, Vec<usize>)> {
+        let Some(needle) = self.search_needle() else {
+            return vec![];
+        };
+        let shown = self.session_rows.iter().enumerate().filter(|(idx, _)| self.is_shown(&self.sessions[*idx]));
+        // `re:` makes the rest a regular expression, matched anywhere in the rows as shown
+        if let Some(regex) = needle.strip_prefix(REGEX_SEARCH) {
+            let Ok(regex) = Pattern::regex(regex) else { return vec![] };
+            return shown.filter_map(|(idx, row)| {
+                let found = regex.find_all(&row.text);
+                let positions = found.iter().flat_map(|range| row.text[range.clone()].char_indices().map(|(pos, _)| range.start + pos));
+                (!found.is_empty()).then(|| (idx, positions.collect()))
+            }).collect();
+        }
+        let alias_target = self.alias_target(needle);
+        let mut matches: Vec<(i64, usize, Vec<usize>)> = shown
+            .filter_map(|(idx, row)| {
+                // Only the name is matched, which is right aligned at the start of the row
+                let offset = row.text.find(&row.label).unwrap_or(0);
+                match pattern::fuzzy(needle, &row.label) {
+                    Some((score, positions)) => Some((score, idx, positions.into_iter().map(|pos| offset + pos).collect())),
+                    None => (alias_target == Some(idx)).then(|| (0, idx, vec![])),
+                }
+            })
+            .collect();
+        matches.sort_by_key(|(score, idx, _)| (alias_target != Some(*idx), -score));
+        matches.into_iter().map(|(_, idx, positions)| (idx, positions)).collect()
+    }
+
+    /// Why the search term is not a valid regular expression, in regex mode
+    pub fn search_error(&self) -> Option<String> {
+        let regex = self.search_needle()?.strip_prefix(REGEX_SEARCH)?;
+        Pattern::regex(regex).err()
+    }
+
+    /// Start searching
+    pub fn search(&mut self) {
+        // Create the textarea and switch to renaming state
+        let mut textarea = TextArea::default();
+        textarea.set_cursor_line_style(Style::default());
+        textarea.set_style(Style::default().fg(self.colors.dim));
+        self.search_session_ta = Some(textarea);
+        self.state = AppState::SessionsSearch;
+    }
+
+    /// Start searching for `term`, as if it was typed, e.g. for `tmm find`
+    pub fn search_for(&mut self, term: &str) {
+        self.search();
+        if let Some(textarea) = self.search_session_ta.as_mut() {
+            textarea.insert_str(term);
+        }
+    }
+
+    /// Summarize a batch rename of the marked sessions using `pattern`
+    pub fn batch_rename(&mut self, pattern: &str) {
+        self.batch_rename_ta = None;
+        self.batch = Some(Batch::rename(&self.marked_sessions(), pattern));
+        self.state = AppState::BatchConfirm;
+        if !self.confirm_batch {
+            self.run_batch();
+        }
+    }
+
+    /// Apply the confirmed batch and show its per-item results
+    pub fn run_batch(&mut self) {
+        if self.batch.is_some() {
+            self.state = AppState::BatchRunning;
+        }
+    }
+
+    /// Run the next item of the running batch, showing the results after the last one
+    pub fn step_batch(&mut self) {
+        let Some(batch) = self.batch.as_mut() else { return };
+        if !batch.run_next(&*self.tmux) {
+            self.finish_batch();
+        }
+    }
+
+    /// Stop the running batch before its next item, leaving the rest undone
+    pub fn abort_batch(&mut self) {
+        let Some(batch) = self.batch.as_mut() else { return };
+        batch.aborted = true;
+        self.finish_batch();
+    }
+
+    fn finish_batch(&mut self) {
+        let Some(batch) = self.batch.as_mut() else { return };
+        batch.scroll = 0;
+        let report = batch.report_success || batch.aborted || batch.failures() > 0;
+        let (summary, lines) = (batch.summary(), batch.result_lines());
+        self.marked.clear();
+        self.refresh();
+        if report && !self.show_in_tmux_popup(&summary, &lines) {
+            self.state = AppState::BatchResults;
+        } else {
+            self.dismiss_all();
+        }
+    }
+
+    /// Show a report in a tmux popup over tmm's client instead of an overlay, if configured and
+    /// running inside tmux. Returns whether it did.
+    pub fn show_in_tmux_popup(&self, title: &str, lines: &[String]) -> bool {
+        let Some(pane) = env::var("TMUX_PANE").ok().filter(|_| self.tmux_popup && self.is_nested()) else {
+            return false;
+        };
+        // Without a trailing newline, which would scroll the first line out of a popup sized to fit
+        let command = format!("printf %s {}", tmux::shell_quote(&lines.join("\n")));
+        let title = format!(" {} ", title);
+        let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0).max(title.len()) + 4;
+        let height = lines.len() + 2;
+        // The popup blocks until it is closed (with Esc), so it is shown from a thread of its own
+        let tmux = Arc::clone(&self.tmux);
+        thread::spawn(move || {
+            // Left to itself tmux could pick the control mode client, which cannot show popups, so
+            // the popup goes to the most recently used client of tmm's session
+            let format = "#{client_control_mode}\t#{client_activity}\t#{client_width}\t#{client_height}\t#{client_name}";
+            let Ok(output) = tmux.client_output(&["list-clients", "-t", &pane, "-F", format]) else { return };
+            let clients = String::from_utf8_lossy(&output.stdout).into_owned();
+            let client = clients.lines()
+                .filter_map(|line| {
+                    let mut parts = line.splitn(5, '\t');
+                    let control = parts.next()? == "1";
+                    let activity: u64 = parts.next()?.parse().ok()?;
+                    let size: (usize, usize) = (parts.next()?.parse().ok()?, parts.next()?.parse().ok()?);
+                    (!control).then_some((activity, size, parts.next()?))
+                })
+                .max_by_key(|(activity, _, _)| *activity);
+            let Some((_, (columns, rows), name)) = client else { return };
+            // Sized to the report, within the client
+            let (width, height) = (width.min(columns).to_string(), height.min(rows).to_string());
+            let _ = tmux.client_output(&["display-popup", "-c", name, "-T", &title, "-w", &width, "-h", &height, &command]);
+        });
+        true
+    }
+
+    /// Drill into the windows of the selected session
+    pub fn show_windows(&mut self) {
+        let Some(session) = self.sessions.get(self.selected_session).cloned() else { return };
+        self.windows = match self.prefetched_windows.get(&(session.host.clone(), session.id.clone())) {
+            // Shown right away, and brought up to date in the background
+            Some(windows) => {
+                let host_idx = self.hosts.iter().position(|host| *host == session.host).unwrap_or(0);
+                self.scheduler.trigger(Job::Windows(host_idx));
+                windows.clone()
+            }
+            None => self.list_windows(&session),
+        };
+        self.expanded_windows.clear();
+        // Start on the session's current window
+        self.selected_window = self.windows.iter().position(|w| w.active).unwrap_or(0);
+        self.windows_session = Some(session);
+        self.state = AppState::Windows;
+    }
+
+    /// List the windows of a session, including their panes
+    fn list_windows(&self, session: &Session) -> Vec<Window> {
+        let args = list_windows_args(Some(&format!("={}:", session.name)));
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let Ok(output) = self.tmux.output(session.host.as_deref(), &args) else {
+            return vec![];
+        };
+        parse_windows(&String::from_utf8_lossy(&output.stdout)).into_values().next().unwrap_or_default()
+    }
+
+    /// Rows of the windows view: every window, each followed by its panes if it is expanded
+    pub fn tree_rows(&self) -> Vec<TreeRow> {
+        let mut rows = vec![];
+        for (widx, window) in self.windows.iter().enumerate() {
+            rows.push(TreeRow::Window(widx));
+            if self.expanded_windows.contains(&window.index) {
+                rows.extend((0..window.pane_list.len()).map(|pidx| TreeRow::Pane(widx, pidx)));
+            }
+        }
+        rows
+    }
+
+    /// Show the panes of the selected window in the windows view
+    pub fn expand_selected_window(&mut self) {
+        if let Some(TreeRow::Window(widx)) = self.tree_rows().get(self.selected_window) {
+            self.expanded_windows.insert(self.windows[*widx].index);
+        }
+    }
+
+    /// Hide the panes of the selected window, or of the window of the selected pane, selecting the
+    /// window. Returns false if there was nothing to collapse.
+    pub fn collapse_selected_window(&mut self) -> bool {
+        let widx = match self.tree_rows().get(self.selected_window) {
+            Some(TreeRow::Window(widx)) => *widx,
+            Some(TreeRow::Pane(widx, _)) => *widx,
+            None => return false,
+        };
+        if !self.expanded_windows.remove(&self.windows[widx].index) {
+            return false;
+        }
+        self.selected_window = self.tree_rows().iter().position(|row| *row == TreeRow::Window(widx)).unwrap_or(0);
+        true
+    }
+
+    /// Quit and attach the selected window or pane of the windows view
+    pub fn attach_selected_window(&mut self) {
+        let Some(session) = &self.windows_session else { return };
+        let (window, pane) = match self.tree_rows().get(self.selected_window) {
+            Some(TreeRow::Window(widx)) => (&self.windows[*widx], None),
+            Some(TreeRow::Pane(widx, pidx)) => (&self.windows[*widx], Some(self.windows[*widx].pane_list[*pidx].index)),
+            None => return,
+        };
+        self.running = false;
+        self.on_exit = ExitAction::AttachWindow(session.host.clone(), session.name.clone(), window.index, pane, self.attach_size);
+    }
+
+    /// Attach the window of the windows view with the given index, like tmux's prefix and number
+    pub fn attach_window_numbered(&mut self, index: usize) {
+        let Some(widx) = self.windows.iter().position(|window| window.index == index) else {
+            self.show_status(&format!("No window {}", index));
+            return;
+        };
+        if let Some(row) = self.tree_rows().iter().position(|row| *row == TreeRow::Window(widx)) {
+            self.selected_window = row;
+        }
+        self.attach_selected_window();
+    }
+
+    /// Mark the selected pane of the windows view (or the active pane of the selected window), or
+    /// clear the mark if it is marked already. tmux has one marked pane per server.
+    pub fn toggle_pane_mark(&mut self) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let Some(session) = self.windows_session.clone() else { return };
+        let Some((window, pane)) = self.selected_pane() else { return };
+        let target = format!("={}:{}.{}", session.name, window, pane.index);
+        let output = self.tmux.output(session.host.as_deref(), &["select-pane", "-m", "-t", &target]);
+        if let Err(err) = command_result(output) {
+            self.report_error(format!("Failed to mark pane: {}", err));
+        }
+        // The rows stay the same, only their marks change
+        self.windows_generation += 1;
+        self.windows = self.list_windows(&session);
+    }
+
+    /// The selected pane of the windows view, or the active pane of the selected window, with the
+    /// tmux index of its window
+    fn selected_pane(&self) -> Option<(usize, &Pane)> {
+        match self.tree_rows().get(self.selected_window)? {
+            TreeRow::Window(widx) => {
+                let window = &self.windows[*widx];
+                Some((window.index, window.pane_list.iter().find(|pane| pane.active)?))
+            }
+            TreeRow::Pane(widx, pidx) => Some((self.windows[*widx].index, &self.windows[*widx].pane_list[*pidx])),
+        }
+    }
+
+    /// Ask where the pane split off the selected pane of the windows view starts and what it runs,
+    /// to put it below the pane or to its right
+    pub fn confirm_split(&mut self, below: bool) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let Some(session) = &self.windows_session else { return };
+        let Some((window, pane)) = self.selected_pane() else { return };
+        let target = format!("={}:{}.{}", session.name, window, pane.index);
+        // The new pane starts where the pane is, unless told otherwise
+        let directory = source::home_label(&pane.path);
+        let command = if session.host.is_none() { self.default_command() } else { "the default shell".to_owned() };
+        self.split_form = Some(SplitForm::new(target, below, &directory, &command));
+        self.state = AppState::SplitPane;
+    }
+
+    /// Split the pane of the split form, then show the new pane in the windows view. The active
+    /// pane stays the same, so whoever is attached is not disturbed.
+    pub fn split_pane(&mut self) {
+        let Some(form) = self.split_form.take() else { return };
+        self.state = AppState::Windows;
+        let Some(session) = self.windows_session.clone() else { return };
+        let Some((window, pane)) = self.selected_pane() else { return };
+        let directory = form.directory().unwrap_or_else(|| pane.path.clone());
+        let mut args = vec!["split-window", "-d", if form.below { "-v" } else { "-h" }, "-P", "-F", "#{pane_index}",
+            "-t", &form.target, "-c", &directory];
+        args.extend(form.command());
+        let output = self.tmux.output(session.host.as_deref(), &args);
+        let new_pane = output.as_ref().ok().and_then(|output| String::from_utf8_lossy(&output.stdout).trim().parse::<usize>().ok());
+        if let Err(err) = command_result(output) {
+            self.report_error(format!("Failed to split pane: {}", err));
+            return;
+        }
+        self.expanded_windows.insert(window);
+        self.windows_generation += 1;
+        self.windows = self.list_windows(&session);
+        let widx = self.windows.iter().position(|w| w.index == window);
+        let pidx = widx.and_then(|widx| self.windows[widx].pane_list.iter().position(|pane| Some(pane.index) == new_pane));
+        if let (Some(widx), Some(pidx)) = (widx, pidx) {
+            self.selected_window = self.tree_rows().iter().position(|row| *row == TreeRow::Pane(widx, pidx)).unwrap_or(0);
+        }
+    }
+
+    /// Close the split pane form, back to the windows view
+    pub fn cancel_split(&mut self) {
+        self.split_form = None;
+        self.state = AppState::Windows;
+    }
+
+    /// Ask before killing the selected pane of the windows view, or the active pane of the selected
+    /// window
+    pub fn confirm_kill_pane(&mut self) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let Some(session) = &self.windows_session else { return };
+        let Some((window, pane)) = self.selected_pane() else { return };
+        self.killing_pane = Some(format!("={}:{}.{}", session.name, window, pane.index));
+        self.state = AppState::KillingPane;
+    }
+
+    /// Kill the pane of the kill pane confirmation. Its window goes with its last pane, and the
+    /// session with its last window, back to the sessions view.
+    pub fn kill_pane(&mut self) {
+        let Some(target) = self.killing_pane.take() else { return };
+        self.state = AppState::Windows;
+        let Some(session) = self.windows_session.clone() else { return };
+        let output = self.tmux.output(session.host.as_deref(), &["kill-pane", "-t", &target]);
+        if let Err(err) = command_result(output) {
+            self.report_error(format!("Failed to kill pane: {}", err));
+            return;
+        }
+        self.show_status(&format!("Killed pane {}", target.trim_start_matches('=')));
+        self.windows_generation += 1;
+        self.windows = self.list_windows(&session);
+        if self.windows.is_empty() {
+            self.dismiss_all();
+            self.refresh();
+            return;
+        }
+        self.selected_window = self.selected_window.min(self.tree_rows().len().saturating_sub(1));
+    }
+
+    /// Close the kill pane confirmation, back to the windows view
+    pub fn cancel_kill_pane(&mut self) {
+        self.killing_pane = None;
+        self.state = AppState::Windows;
+    }
+
+    /// Ask for the file to save the output of the selected pane of the windows view to, filled with
+    /// one named after the pane in the home directory
+    pub fn confirm_capture_pane(&mut self) {
+        let Some(session) = &self.windows_session else { return };
+        let Some((window, pane)) = self.selected_pane() else { return };
+        let target = format!("={}:{}.{}", session.name, window, pane.index);
+        let mut textarea = TextArea::default();
+        textarea.set_cursor_line_style(Style::default());
+        textarea.insert_str(format!("~/{}-{}.{}.log", session.name, window, pane.index));
+        textarea.select_all();
+        self.capture_ta = Some(textarea);
+        self.capturing_pane = Some(target);
+        self.state = AppState::CapturingPane;
+    }
+
+    /// Save the whole scrollback of the pane of the capture prompt to `path`, back in the windows
+    /// view. The output comes back over `capture-pane -p` rather than through a tmux buffer, so the
+    /// paste buffers are left alone and a remote pane is saved on this machine.
+    pub fn capture_pane(&mut self, path: &str) {
+        self.capture_ta = None;
+        self.state = AppState::Windows;
+        let Some(target) = self.capturing_pane.take() else { return };
+        let Some(session) = &self.windows_session else { return };
+        let path = source::expand_home(path.trim());
+        // -J joins lines tmux wrapped, as they were printed
+        let output = self.tmux.output(session.host.as_deref(), &["capture-pane", "-p", "-J", "-S", "-", "-t", &target]);
+        let stdout = output.as_ref().map(|output| output.stdout.clone()).unwrap_or_default();
+        if let Err(err) = command_result(output) {
+            self.report_error(format!("Failed to capture pane: {}", err));
+            return;
+        }
+        match fs::write(&path, &stdout) {
+            Ok(()) => {
+                let lines = stdout.iter().filter(|byte| **byte == b'\n').count();
+                self.show_status(&format!("Saved {} lines to {}", lines, source::home_label(&path)));
+            }
+            Err(err) => self.report_error(format!("Could not write {}: {}", path, err)),
+        }
+    }
+
+    /// Close the capture prompt, back to the windows view
+    pub fn cancel_capture_pane(&mut self) {
+        self.capture_ta = None;
+        self.capturing_pane = None;
+        self.state = AppState::Windows;
+    }
+
+    /// Zoom the selected pane of the windows view to take up its whole window, or unzoom it, like
+    /// tmux's prefix and `z`
+    pub fn toggle_pane_zoom(&mut self) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let Some(session) = self.windows_session.clone() else { return };
+        let Some((window, pane)) = self.selected_pane() else { return };
+        let target = format!("={}:{}.{}", session.name, window, pane.index);
+        let output = self.tmux.output(session.host.as_deref(), &["resize-pane", "-Z", "-t", &target]);
+        if let Err(err) = command_result(output) {
+            self.report_error(format!("Failed to zoom pane: {}", err));
+        }
+        // The rows stay the same, only the zoom flags and sizes change
+        self.windows_generation += 1;
+        self.windows = self.list_windows(&session);
+    }
+
+    /// Move the selected window of the windows view one place up (towards the first window) or
+    /// down, swapping indexes with its neighbour, and keep it selected
+    pub fn move_selected_window(&mut self, down: bool) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let Some(session) = self.windows_session.clone() else { return };
+        let Some(TreeRow::Window(widx)) = self.tree_rows().get(self.selected_window).copied() else { return };
+        let Some(other) = (if down { widx.checked_add(1) } else { widx.checked_sub(1) }) else { return };
+        let (Some(window), Some(neighbour)) = (self.windows.get(widx), self.windows.get(other)) else { return };
+        let (index, other_index) = (window.index, neighbour.index);
+        let source = format!("={}:{}", session.name, index);
+        let target = format!("={}:{}", session.name, other_index);
+        // -d leaves the session's current window as it was
+        let output = self.tmux.output(session.host.as_deref(), &["swap-window", "-d", "-s", &source, "-t", &target]);
+        if let Err(err) = command_result(output) {
+            self.report_error(format!("Failed to move window: {}", err));
+            return;
+        }
+        // Expanded windows stay expanded under their new index
+        let expanded = (self.expanded_windows.remove(&index), self.expanded_windows.remove(&other_index));
+        if expanded.0 {
+            self.expanded_windows.insert(other_index);
+        }
+        if expanded.1 {
+            self.expanded_windows.insert(index);
+        }
+        self.windows_generation += 1;
+        self.windows = self.list_windows(&session);
+        self.selected_window = self.tree_rows().iter().position(|row| *row == TreeRow::Window(other)).unwrap_or(0);
+    }
+
+    /// Quit and attach the marked pane, if it is in the session of the windows view
+    pub fn attach_marked_pane(&mut self) {
+        let Some(session) = &self.windows_session else { return };
+        let marked = self.windows.iter()
+            .find_map(|window| window.pane_list.iter().find(|pane| pane.marked).map(|pane| (window.index, pane.index)));
+        let Some((window, pane)) = marked else {
+            let message = format!("No marked pane in {}", session.label());
+            self.show_status(&message);
+            return;
+        };
+        self.running = false;
+        self.on_exit = ExitAction::AttachWindow(session.host.clone(), session.name.clone(), window, Some(pane), self.attach_size);
+    }
+
+    /// Ask for a term to look for in the scrollback of every pane
+    pub fn start_deep_search(&mut self) {
+        let mut textarea = TextArea::default();
+        textarea.set_cursor_line_style(Style::default());
+        textarea.insert_str(&self.deep_search_term);
+        textarea.select_all();
+        self.deep_search_ta = Some(textarea);
+        self.state = AppState::DeepSearching;
+    }
+
+    /// Look for `term` in the whole scrollback of every pane of every reachable server, and list
+    /// the panes that mention it. Case is ignored unless the term has an uppercase letter. Every
+    /// pane is captured, which takes a while with a lot of scrollback, so it is only done on demand,
+    /// in the background from the next tick on, showing its progress until it finishes or is
+    /// cancelled.
+    pub fn deep_search(&mut self, term: &str) {
+        self.deep_search_ta = None;
+        self.deep_search_term = term.to_owned();
+        self.pane_search_generation += 1;
+        self.pane_search = Some(PaneSearch {
+            generation: self.pane_search_generation,
+            cancel: Arc::new(AtomicBool::new(false)),
+            started: false,
+            done: 0,
+            total: None,
+        });
+        self.state = AppState::DeepSearchRunning;
+    }
+
+    /// Start the thread of a deep search that is waiting for it
+    fn start_pane_search(&mut self, sender: &mpsc::Sender<Event>) {
+        let Some(search) = self.pane_search.as_mut().filter(|search| !search.started) else { return };
+        search.started = true;
+        // An unreachable host would only add its timeout
+        let hosts: Vec<Option<String>> = self.hosts.iter().enumerate()
+            .filter(|(idx, _)| *idx == 0 || !self.host_states[*idx].failing)
+            .map(|(_, host)| host.clone())
+            .collect();
+        let (generation, cancel) = (search.generation, Arc::clone(&search.cancel));
+        let (client, term, timeout, sender) = (Arc::clone(&self.tmux), self.deep_search_term.clone(), self.host_timeout, sender.clone());
+        thread::spawn(move || {
+            let matches = search_panes(&*client, &hosts, &term, timeout, &cancel, |done, total| {
+                let _ = sender.send(Event::PaneSearch(generation, PaneSearchUpdate::Progress(done, total)));
+            });
+            if !cancel.load(Ordering::Relaxed) {
+                let _ = sender.send(Event::PaneSearch(generation, PaneSearchUpdate::Finished(matches)));
+            }
+        });
+    }
+
+    /// Apply the progress of a deep search, unless it was cancelled or replaced since
+    pub fn pane_search_update(&mut self, generation: u64, update: PaneSearchUpdate) {
+        let Some(search) = self.pane_search.as_mut().filter(|search| search.generation == generation) else { return };
+        match update {
+            PaneSearchUpdate::Progress(done, total) => {
+                search.done = done;
+                search.total = Some(total);
+            }
+            PaneSearchUpdate::Finished(matches) => {
+                self.pane_search = None;
+                if matches.is_empty() {
+                    self.dismiss_all();
+                    self.show_status(&format!("No pane mentions {}", self.deep_search_term));
+                    return;
+                }
+                self.pane_matches = matches;
+                self.selected_pane_match = 0;
+                self.state = AppState::DeepResults;
+            }
+        }
+    }
+
+    /// Stop the running deep search before its next pane
+    pub fn cancel_deep_search(&mut self) {
+        self.dismiss_all();
+        self.show_status("Deep search cancelled");
+    }
+
+    /// Quit and attach the selected pane of the deep search results
+    pub fn attach_pane_match(&mut self) {
+        let Some(found) = self.pane_matches.get(self.selected_pane_match) else { return };
+        self.running = false;
+        self.on_exit = ExitAction::AttachWindow(found.host.clone(), found.session.clone(), found.window, Some(found.pane), self.attach_size);
+    }
+
+    /// Turn the list of the sessions or windows view into text to move through by keyboard and
+    /// copy rows of, for when the mouse cannot select it, with the cursor on the selected row
+    pub fn start_copy_mode(&mut self) {
+        let (lines, cursor): (Vec<String>, usize) = if self.state == AppState::Windows {
+            (self.tree_rows().iter().map(|row| self.tree_row_text(*row)).collect(), self.selected_window)
+        } else {
+            let rows = self.list_rows();
+            (rows.iter().map(|row| self.list_row_text(row)).collect(), self.selected_row(&rows).unwrap_or(0))
+        };
+        if lines.is_empty() {
+            return;
+        }
+        self.copy_cursor = cursor.min(lines.len() - 1);
+        self.copy_lines = lines;
+        self.copy_anchor = None;
+        self.copy_from = self.state.clone();
+        self.state = AppState::Copying;
+    }
+
+    /// A row of the sessions list as plain text, sessions as `tmux ls` prints them
+    fn list_row_text(&self, row: &ListRow) -> String {
+        match row {
+            ListRow::Header(title, count, _) => format!("{} ({})", title, count),
+            ListRow::Session(idx) => format!("{}: {}", self.sessions[*idx].label(), self.sessions[*idx].desc(self.time_zone)),
+            ListRow::Entry(idx) => self.entries[*idx].label.clone(),
+        }
+    }
+
+    /// A row of the windows view as plain text, windows as `tmux lsw` prints them
+    fn tree_row_text(&self, row: TreeRow) -> String {
+        match row {
+            TreeRow::Window(widx) => {
+                let window = &self.windows[widx];
+                format!("{}: {}{}{} ({} panes) [{}] {}", window.index, window.name, if window.active { "*" } else { "" },
+                    if window.zoomed { "Z" } else { "" }, window.panes, window.size, window.command)
+            }
+            TreeRow::Pane(widx, pidx) => {
+                let pane = &self.windows[widx].pane_list[pidx];
+                format!("{}.{}: [{}] {} {}", self.windows[widx].index, pane.index, pane.size, pane.command, pane.path)
+            }
+        }
+    }
+
+    /// Move the copy mode cursor by `delta` lines, stopping at either end
+    pub fn move_copy_cursor(&mut self, delta: isize) {
+        self.copy_cursor = self.copy_cursor.saturating_add_signed(delta).min(self.copy_lines.len().saturating_sub(1));
+    }
+
+    /// Start a visual selection at the copy mode cursor, or drop the one being made
+    pub fn toggle_copy_selection(&mut self) {
+        self.copy_anchor = match self.copy_anchor {
+            Some(_) => None,
+            None => Some(self.copy_cursor),
+        };
+    }
+
+    /// Lines copy mode would copy: from the start of the selection to the cursor, or the cursor's
+    /// line alone
+    pub fn copy_selection(&self) -> RangeInclusive<usize> {
+        let anchor = self.copy_anchor.unwrap_or(self.copy_cursor);
+        anchor.min(self.copy_cursor)..=anchor.max(self.copy_cursor)
+    }
+
+    /// Copy the selected lines to the clipboard and leave copy mode
+    pub fn yank(&mut self) {
+        let selection = self.copy_selection();
+        let count = selection.end() - selection.start() + 1;
+        let text = self.copy_lines[selection].join("\n");
+        let result = clipboard::copy(self.tmux.as_ref(), self.is_nested(), &text);
+        self.leave_copy_mode();
+        match result {
+            Ok(()) if count == 1 => self.show_status("Copied 1 line"),
+            Ok(()) => self.show_status(&format!("Copied {} lines", count)),
+            Err(err) => self.report_error(format!("Failed to copy: {}", err)),
+        }
+    }
+
+    /// Go back to the view copy mode was started from
+    pub fn leave_copy_mode(&mut self) {
+        self.copy_lines.clear();
+        self.copy_anchor = None;
+        self.state = mem::replace(&mut self.copy_from, AppState::Sessions);
+    }
+
+    /// List the clients attached to the selected session
+    pub fn show_clients(&mut self) {
+        let Some(session) = self.sessions.get(self.selected_session).cloned() else { return };
+        self.clients = self.list_clients(&session);
+        self.selected_client = 0;
+        self.clients_session = Some(session);
+        self.state = AppState::Clients;
+    }
+
+    fn list_clients(&self, session: &Session) -> Vec<Client> {
+        let format = "#{client_control_mode}\t#{client_tty}\t#{client_width}x#{client_height}\t#{client_activity}\t#{client_termname}\t#{client_name}";
+        let Ok(output) = self.tmux.output(session.host.as_deref(), &["list-clients", "-t", session.target(), "-F", format]) else {
+            return vec![];
+        };
+        String::from_utf8_lossy(&output.stdout).lines().filter_map(|line| {
+            let mut parts = line.splitn(6, '\t');
+            // tmm's own control mode client is not one to detach
+            if parts.next()? == "1" {
+                return None;
+            }
+            Some(Client {
+                tty: parts.next()?.to_owned(),
+                size: parts.next()?.to_owned(),
+                activity: parts.next()?.parse().ok()?,
+                termname: parts.next()?.to_owned(),
+                name: parts.next()?.to_owned(),
+            })
+        }).collect()
+    }
+
+    /// Detach the selected client of the clients view or, with `kill`, also end the shell (or ssh
+    /// session) it was started from, which tmux sends `SIGHUP`
+    pub fn detach_selected_client(&mut self, kill: bool) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let Some(session) = self.clients_session.clone() else { return };
+        let Some(client) = self.clients.get(self.selected_client) else { return };
+        let args = if kill { vec!["detach-client", "-P", "-t", &client.name] } else { vec!["detach-client", "-t", &client.name] };
+        let output = self.tmux.output(session.host.as_deref(), &args);
+        if let Err(err) = command_result(output) {
+            self.report_error(format!("Failed to detach client: {}", err));
+        }
+        self.clients = self.list_clients(&session);
+        self.selected_client = self.selected_client.min(self.clients.len().saturating_sub(1));
+    }
+
+    /// Ask before killing the selected client of the clients view, which ends what it was started
+    /// from too
+    pub fn confirm_kill_client(&mut self) {
+        if self.refuse_read_only() || self.clients.get(self.selected_client).is_none() {
+            return;
+        }
+        self.state = AppState::KillingClient;
+    }
+
+    /// Kill the client of the kill client confirmation, back to the clients view
+    pub fn kill_client(&mut self) {
+        self.state = AppState::Clients;
+        self.detach_selected_client(true);
+    }
+
+    /// Back to the clients view without killing the client
+    pub fn cancel_kill_client(&mut self) {
+        self.state = AppState::Clients;
+    }
+
+    /// Show the session templates with their descriptions
+    pub fn show_templates(&mut self) {
+        self.selected_template = 0;
+        self.state = AppState::Templates;
+    }
+
+    /// Save the selected local session's windows and panes in the archive, then kill it
+    pub fn archive_selected(&mut self) {
+        let Some(session) = self.sessions.get(self.selected_session).cloned() else { return };
+        let Some(dir) = self.archive_dir.clone() else {
+            self.report_error("No archive directory: neither $XDG_STATE_HOME nor $HOME is set".to_owned());
+            return;
+        };
+        let saved = archive::snapshot(self.tmux.as_ref(), session.target(), &session.name)
+            .and_then(|template| archive::save(&dir, &template));
+        if let Err(err) = saved {
+            self.report_error(format!("Failed to archive {}: {}", session.label(), err));
+            return;
+        }
+        self.act_on_selected(SessionAction::Kill);
+        self.show_status(&format!("Archived {}", session.label()));
+    }
+
+    /// Show the keys of every view
+    pub fn show_help(&mut self) {
+        self.help_scroll = 0;
+        self.state = AppState::Help;
+    }
+
+    /// Every key of every view, under the view's heading: the sessions view's from
+    /// [`App::keymap`], with every key of each action, and the fixed keys of the other views from
+    /// their hotkey bars
+    pub fn help_sections(&self) -> Vec<(&'static str, Vec<(String, &str)>)> {
+        let mut sessions: Vec<(String, &str)> = Action::ALL.into_iter()
+            .filter(|action| !self.keymap.keys(*action).is_empty())
+            // Read-only mode leaves out the actions it disables, as in the hotkey bar
+            .filter(|action| !(self.read_only && action.mutates()))
+            .map(|action| {
+                let keys = match action {
+                    Action::Detach => self.keymap.label(action),
+                    _ => self.keymap.keys(action).iter().map(ToString::to_string).collect::<Vec<_>>().join(", "),
+                };
+                (keys, action.description())
+            })
+            .collect();
+        sessions.push(("1-9".to_owned(), "Attach the session with that number"));
+        sessions.push(("Esc".to_owned(), "Unmark every session"));
+        let mut sections = vec![("Sessions", sessions)];
+        for (state, heading) in HELP_VIEWS {
+            if let Some(hotkeys) = self.hotkeys.get(&state) {
+                sections.push((heading, hotkeys.iter().map(|(key, description)| (key.clone(), *description)).collect()));
+            }
+        }
+        sections
+    }
+
+    /// Scroll the help by `lines`, up when negative
+    pub fn scroll_help(&mut self, lines: isize) {
+        let total: usize = self.help_sections().iter().map(|(_, keys)| keys.len() + 2).sum();
+        self.help_scroll = self.help_scroll.saturating_add_signed(lines).min(total.saturating_sub(1));
+    }
+
+    /// Show the archived sessions
+    pub fn show_archive(&mut self) {
+        self.archived = self.archive_dir.as_deref().map(archive::load).unwrap_or_default();
+        self.selected_archived = 0;
+        self.state = AppState::Archive;
+    }
+
+    /// Recreate the highlighted archived session and take it out of the archive. Refused if a
+    /// local session has its name.
+    pub fn restore_selected_archived(&mut self) {
+        let Some(archived) = self.archived.get(self.selected_archived).cloned() else { return };
+        self.restore_archived(archived);
+    }
+
+    /// Recreate an archived session and take it out of the archive. Refused if a local session
+    /// has its name.
+    fn restore_archived(&mut self, archived: Archived) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let name = archived.template.name.clone();
+        if self.sessions.iter().any(|session| session.host.is_none() && session.name == name) {
+            self.report_error(format!("Cannot restore {}: a session has its name", name));
+            return;
+        }
+        let result = archived.template.create(self.tmux.as_ref(), &name);
+        if let Err(err) = result {
+            self.report_error(format!("Failed to restore {}: {}", name, err));
+            return;
+        }
+        let _ = fs::remove_file(&archived.path);
+        self.select_created_session(&name);
+        self.dismiss_all();
+        self.show_status(&format!("Restored {}", name));
+    }
+
+    /// Delete the highlighted archived session for good
+    pub fn purge_selected_archived(&mut self) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let Some(archived) = self.archived.get(self.selected_archived) else { return };
+        if let Err(err) = fs::remove_file(&archived.path) {
+            self.report_error(format!("Failed to purge {}: {}", archived.path.display(), err));
+            return;
+        }
+        let name = self.archived.remove(self.selected_archived).template.name;
+        self.selected_archived = self.selected_archived.min(self.archived.len().saturating_sub(1));
+        self.show_status(&format!("Purged {}", name));
+    }
+
+    /// Switch to the view tmm starts in
+    pub fn show_startup_view(&mut self, view: StartupView) {
+        match view {
+            StartupView::Sessions => {}
+            StartupView::Projects => self.set_sources(vec![Source::Projects]),
+            StartupView::Templates => self.show_templates(),
+            StartupView::History => self.show_history(),
+        }
+    }
+
+    /// Show the attach history
+    pub fn show_history(&mut self) {
+        self.history = history::load();
+        self.selected_history = 0;
+        if self.local_hostname.is_empty() {
+            self.local_hostname = history::local_hostname();
+        }
+        self.state = AppState::History;
+    }
+
+    /// Whether a history entry's session still exists. `None` if its host is not being listed, so
+    /// it cannot be known.
+    pub fn history_entry_exists(&self, entry: &HistoryEntry) -> Option<bool> {
+        if !self.hosts.contains(&entry.host) {
+            return None;
+        }
+        Some(self.sessions.iter().any(|s| s.host == entry.host && s.name == entry.session))
+    }
+
+    /// Quit and re-attach the session of the selected history entry, unless it is known to be gone
+    pub fn attach_history_selected(&mut self) {
+        let Some(entry) = self.history.get(self.selected_history) else { return };
+        if self.history_entry_exists(entry) == Some(false) {
+            return;
+        }
+        self.running = false;
+        self.on_exit = ExitAction::AttachSession(entry.host.clone(), entry.session.clone(), self.attach_size);
+    }
+
+    /// Return to the sessions view
+    pub fn dismiss_all(&mut self) {
+        self.rename_session_ta = None;
+        self.rename_conflict = None;
+        self.search_session_ta = None;
+        self.batch_rename_ta = None;
+        self.attach_command_ta = None;
+        self.color_ta = None;
+        self.confirming = None;
+        self.batch = None;
+        self.windows_session = None;
+        self.windows.clear();
+        self.clients_session = None;
+        self.clients.clear();
+        self.server_info = None;
+        self.killing_pane = None;
+        self.capturing_pane = None;
+        self.capture_ta = None;
+        self.deep_search_ta = None;
+        if let Some(search) = self.pane_search.take() {
+            search.cancel.store(true, Ordering::Relaxed);
+        }
+        self.pane_matches.clear();
+        self.copy_lines.clear();
+        self.copy_anchor = None;
+        self.servers.clear();
+        self.state = AppState::Sessions;
+    }
+
+    /// Whether tmm runs inside the local tmux server it works with, see [`Server::is_nested`]
+    pub fn is_nested(&self) -> bool {
+        self.tmux.server().is_nested()
+    }
+
+    /// List the local tmux servers, with the current one selected
+    pub fn show_servers(&mut self) {
+        let current = self.tmux.server().current_socket();
+        self.servers = tmux::servers(&current);
+        self.selected_server = self.servers.iter().position(|socket| *socket == current).unwrap_or(0);
+        self.state = AppState::Servers;
+    }
+
+    /// Work with the server selected in the servers view from now on: list its sessions in place
+    /// of the current server's, and attach them from there
+    pub fn switch_server(&mut self) {
+        let Some(socket) = self.servers.get(self.selected_server).cloned() else { return };
+        self.dismiss_all();
+        if socket == self.tmux.server().current_socket() {
+            return;
+        }
+        self.tmux.use_server(Server::at(&socket));
+        self.server = self.tmux.server().label();
+        // Nothing known about the local sessions and their windows holds for the other server, and
+        // listings of the previous one still running are dropped when they finish
+        self.sessions_generations[0] += 1;
+        self.windows_generation += 1;
+        self.prefetched_windows.retain(|(host, _), _| host.is_some());
+        self.marked.clear();
+        self.selected_session = 0;
+        self.selected_entry = None;
+        self.refresh();
+        self.show_status(&format!("Switched to server {}", tmux::server_name(&socket)));
+    }
+
+    /// The session to attach without showing the TUI with `auto_attach`: the only one there is.
+    /// None while remote hosts are listed, whose sessions only arrive later, and none when it is
+    /// the session tmm runs in.
+    pub fn only_session(&self) -> Option<&Session> {
+        match self.sessions.as_slice() {
+            [session] if self.hosts.len() == 1 && self.current_session_name().as_ref() != Some(&session.name) => Some(session),
+            _ => None,
+        }
+    }
+
+    /// Name of the session tmm is running in, if nested
+    pub fn current_session_name(&self) -> Option<String> {
+        if !self.is_nested() {
+            return None;
+        }
+        // As tmm's own client, since over control mode the control client would be the one asking
+        let output = self.tmux.client_output(&["display-message", "-p", "#{session_name}"]).ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let name = from_utf8(&output.stdout).ok()?.trim_end_matches('\n').to_owned();
+        (!name.is_empty()).then_some(name)
+    }
+
+    /// Rename selected session
+    ///
+    /// If another session on the same host already has the name, ask whether to add a suffix or
+    /// merge into that session instead.
+    pub fn rename(&mut self, rename: &str) {
+        if !self.reselect_pinned() {
+            return;
+        }
+        if self.session_named(rename).is_some() {
+            self.rename_conflict = Some(rename.to_owned());
+            self.state = AppState::RenameConflict;
+            return;
+        }
+        self.act_on_selected(SessionAction::Rename(rename.to_owned()));
+    }
+
+    /// Index of the session, other than the selected one, on the selected session's host that tmux
+    /// would consider to be named `name`
+    fn session_named(&self, name: &str) -> Option<usize> {
+        let selected = self.sessions.get(self.selected_session)?;
+        let name = tmux_session_name(name)?;
+        self.sessions.iter().enumerate().position(|(idx, s)| {
+            idx != self.selected_session && s.host == selected.host && s.name == name
+        })
+    }
+
+    /// The conflicting name with the lowest numeric suffix that is still free, e.g. `dev-2`
+    pub fn suffixed_rename(&self) -> Option<String> {
+        let name = self.rename_conflict.as_ref()?;
+        (2..).map(|n| format!("{}-{}", name, n)).find(|candidate| self.session_named(candidate).is_none())
+    }
+
+    /// Resolve a rename collision by renaming to [`App::suffixed_rename`]
+    pub fn rename_with_suffix(&mut self) {
+        let Some(rename) = self.suffixed_rename() else { return };
+        self.rename_conflict = None;
+        self.act_on_selected(SessionAction::Rename(rename));
+    }
+
+    /// Resolve a rename collision by moving the selected session's windows into the session that
+    /// has the name
+    pub fn merge_into_conflict(&mut self) {
+        if !self.reselect_pinned() {
+            return;
+        }
+        let Some(target) = self.rename_conflict.take().and_then(|name| self.session_named(&name)) else { return };
+        let (Some(session), Some(into)) = (self.sessions.get(self.selected_session).cloned(), self.sessions.get(target).cloned()) else { return };
+        let output = self.tmux.output(session.host.as_deref(), &["list-windows", "-t", session.target(), "-F", "#{window_id}\t#{window_name}"]);
+        let windows = match output {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout).into_owned(),
+            output => {
+                self.dismiss_all();
+                if let Err(err) = command_result(output) {
+                    self.report_error(format!("Failed to merge session: {}", err));
+                }
+                return;
+            }
+        };
+        let windows: Vec<(String, String)> = windows.lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(id, name)| (id.to_owned(), name.to_owned()))
+            .collect();
+        self.dismiss_all();
+        self.batch = Some(Batch::merge(&session, &windows, &into));
+        self.run_batch();
+    }
+
+    /// Go back to the rename prompt from the collision popup
+    pub fn cancel_rename_conflict(&mut self) {
+        self.rename_conflict = None;
+        self.state = AppState::Renaming;
+    }
+
+    /// Delete a session
+    pub fn delete(&mut self) {
+        self.act_on_selected(SessionAction::Kill);
+    }
+
+    /// Interrupt the processes of the selected session, and kill it once they had time to exit
+    pub fn delete_gracefully(&mut self) {
+        if !self.reselect_pinned() {
+            return;
+        }
+        if let Some(session) = self.sessions.get(self.selected_session) {
+            let message = format!("Killing {} shortly", session.label());
+            self.show_status(&message);
+        }
+        self.act_on_selected(SessionAction::KillGracefully);
+    }
+
+    /// Ask before detaching the clients of the selected session, or just say there are none
+    pub fn confirm_detach_clients(&mut self) {
+        let Some(session) = self.sessions.get(self.selected_session) else { return };
+        if session.attached == 0 {
+            let message = format!("No clients attached to {}", session.label());
+            self.show_status(&message);
+            return;
+        }
+        self.pin_selected();
+        self.state = AppState::DetachingClients;
+    }
+
+    /// Detach every client from the selected session
+    pub fn detach_clients(&mut self) {
+        self.act_on_selected(SessionAction::DetachClients);
+    }
+
+    /// Host of the server [`App::kill_server`] kills: the selected session's, or the local one
+    pub fn server_host(&self) -> Option<String> {
+        self.sessions.get(self.selected_session).and_then(|session| session.host.clone())
+    }
+
+    /// Describe a server outside the interface, e.g. for `tmm doctor`. The error is tmux's, such as
+    /// that no server is running on its socket, or why it could not be run.
+    pub fn server_info(client: &dyn TmuxClient, host: Option<&str>, timeout: Duration) -> Result<ServerInfo, String> {
+        let output = client.output_with_timeout(host, &["display-message", "-p", "#{pid}\t#{socket_path}\t#{start_time}\t#{version}"], timeout)
+            .ok_or_else(|| "timed out".to_owned())?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_owned());
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut parts = stdout.trim_end_matches('\n').splitn(4, '\t');
+        let info = (|| Some(ServerInfo {
+            host: host.map(str::to_owned),
+            pid: parts.next()?.parse().ok()?,
+            socket_path: parts.next()?.to_owned(),
+            start_time: parts.next()?.parse().ok()?,
+            version: parts.next()?.to_owned(),
+        }))();
+        info.ok_or_else(|| format!("unexpected reply: {}", stdout.trim()))
+    }
+
+    /// Show the server of the selected session in the server info popup
+    pub fn show_server_info(&mut self) {
+        let host = self.server_host();
+        match Self::server_info(self.tmux.as_ref(), host.as_deref(), self.host_timeout) {
+            Ok(info) => {
+                self.server_info = Some(info);
+                self.state = AppState::ServerInfo;
+            }
+            Err(err) => self.report_error(format!("Could not describe the tmux server: {}", err)),
+        }
+    }
+
+    /// Ask before killing the whole tmux server
+    pub fn confirm_kill_server(&mut self) {
+        self.state = AppState::KillingServer;
+    }
+
+    /// Kill the tmux server of the selected session, ending every session on it
+    pub fn kill_server(&mut self) {
+        let host = self.server_host();
+        self.dismiss_all();
+        // The local server takes the control mode connection down with it before it could reply
+        let output = match host.as_deref() {
+            None => self.tmux.client_output(&["kill-server"]),
+            Some(_) => self.tmux.output(host.as_deref(), &["kill-server"]),
+        };
+        if let Err(err) = command_result(output) {
+            self.report_error(format!("Failed to kill server: {}", err));
+        }
+        self.refresh();
     }
 
-    /// Get the maximum width of all session names
-    pub fn max_session_name_width(&self) -> usize {
-        self.sessions.iter().map(|(name, _)| {
-            name.len()
-        }).fold(0, |acc, x| acc.max(x))
+    /// Remember the selected session as the one the confirmation or prompt being opened is about
+    fn pin_selected(&mut self) {
+        self.confirming = self.sessions.get(self.selected_session)
+            .map(|session| (session.host.clone(), session.id.clone(), session.label()));
     }
 
-    /// Start a confirmed delete
-    pub fn confirm_delete(&mut self) {
-        self.state = AppState::Deleting;
+    /// The session the open confirmation or prompt is about, if it still exists
+    pub fn pinned_session(&self) -> Option<&Session> {
+        let (host, id, _) = self.confirming.as_ref()?;
+        self.sessions.iter().find(|session| &session.host == host && &session.id == id)
     }
 
-    /// Start a confirmed rename
-    pub fn confirm_rename(&mut self) {
-        // Create the textarea and switch to renaming state
-        let mut textarea = TextArea::default();
-        textarea.set_cursor_line_style(Style::default());
-        self.rename_session_ta = Some(textarea);
-        self.state = AppState::Renaming;
+    /// Select the session the open confirmation or prompt is about again, wherever a refresh moved
+    /// it. Returns false, back in the sessions view with a notice, if it no longer exists.
+    fn reselect_pinned(&mut self) -> bool {
+        let Some((host, id, label)) = &self.confirming else { return true };
+        match self.sessions.iter().position(|session| &session.host == host && &session.id == id) {
+            Some(idx) => {
+                self.selected_session = idx;
+                true
+            }
+            None => {
+                let message = format!("{} no longer exists, nothing was done", label);
+                self.dismiss_all();
+                self.show_status(&message);
+                false
+            }
+        }
     }
 
-    /// Start searching
-    pub fn search(&mut self) {
-        // Create the textarea and switch to renaming state
-        let mut textarea = TextArea::default();
-        textarea.set_cursor_line_style(Style::default());
-        textarea.set_style(Style::default().fg(Color::DarkGray));
-        self.search_session_ta = Some(textarea);
-        self.state = AppState::SessionsSearch;
+    /// Apply an action to the selected session and return to the sessions view. If the session's
+    /// host is being refreshed, the action is queued until the refresh lands.
+    fn act_on_selected(&mut self, action: SessionAction) {
+        if !self.reselect_pinned() {
+            return;
+        }
+        let Some(session) = self.sessions.get(self.selected_session) else {
+            self.dismiss_all();
+            self.report_error("Could not identify session to act on".to_owned());
+            return;
+        };
+        let host_idx = self.hosts.iter().position(|host| host == &session.host).unwrap_or(0);
+        let label = session.label();
+        let pending = PendingAction { host_idx, session_id: session.target().to_owned(), action };
+        self.dismiss_all();
+        if self.scheduler.in_flight(Job::Sessions(host_idx)) {
+            self.pending_actions.push_back(pending);
+            return;
+        }
+        match self.apply_action(&pending) {
+            Ok(()) => {
+                if let Some(message) = pending.action.done_message(&label) {
+                    self.show_status(&message);
+                }
+            }
+            Err(err) => self.report_error(err),
+        }
+        // Restore state with a refresh
+        self.refresh();
     }
 
-    /// Return to the sessions view
-    pub fn dismiss_all(&mut self) {
-        self.rename_session_ta = None;
-        self.search_session_ta = None;
-        self.state = AppState::Sessions;
+    /// Apply the queued actions for a host whose refresh just landed. Actions on sessions that no
+    /// longer exist are dropped.
+    fn apply_pending_actions(&mut self, host_idx: usize) {
+        let (ready, waiting): (VecDeque<PendingAction>, VecDeque<PendingAction>) = self.pending_actions.drain(..)
+            .partition(|pending| pending.host_idx == host_idx);
+        self.pending_actions = waiting;
+        if ready.is_empty() {
+            return;
+        }
+        for pending in ready {
+            let host = &self.hosts[host_idx];
+            if self.sessions.iter().any(|s| &s.host == host && s.target() == pending.session_id) {
+                if let Err(err) = self.apply_action(&pending) {
+                    self.report_error(err);
+                }
+            }
+        }
+        self.refresh();
     }
 
-    pub fn is_nested() -> bool {
-        let envs: HashMap<String, String> = env::vars().collect();
-        envs.get("TMUX").is_some()
+    /// Kill the gracefully killed sessions whose grace period is over, unless they ended by
+    /// themselves in the meantime
+    fn apply_delayed_kills(&mut self) {
+        let now = Instant::now();
+        let (due, waiting): (Vec<_>, Vec<_>) = self.delayed_kills.drain(..).partition(|(at, _)| *at <= now);
+        self.delayed_kills = waiting;
+        if due.is_empty() {
+            return;
+        }
+        for (_, pending) in due {
+            if self.scheduler.in_flight(Job::Sessions(pending.host_idx)) {
+                self.pending_actions.push_back(pending);
+                continue;
+            }
+            let host = &self.hosts[pending.host_idx];
+            if self.sessions.iter().any(|s| &s.host == host && s.target() == pending.session_id) {
+                if let Err(err) = self.apply_action(&pending) {
+                    self.report_error(err);
+                }
+            }
+        }
+        self.refresh();
     }
 
-    /// Rename selected session
-    pub fn rename(&mut self, rename: &str) {
-        let Some((name, _)) = self.sessions.get(self.selected_session) else {
-            panic!("Could not identify session to delete");
+    /// Run the tmux command for an action. The error describes the failure for the error popup.
+    fn apply_action(&mut self, pending: &PendingAction) -> Result<(), String> {
+        let host = self.hosts[pending.host_idx].as_deref();
+        let target = pending.session_id.as_str();
+        match &pending.action {
+            SessionAction::Rename(rename) => {
+                let output = self.tmux.output(host, &["rename-session", "-t", target, rename]);
+                command_result(output).map_err(|err| format!("Failed to rename session: {}", err))
+            }
+            SessionAction::Kill => {
+                let output = self.tmux.output(host, &["kill-session", "-t", target]);
+                command_result(output).map_err(|err| format!("Failed to kill session: {}", err))
+            }
+            SessionAction::KillGracefully => {
+                let output = self.tmux.output(host, &["list-panes", "-s", "-t", target, "-F", "#{pane_id}\t#{pane_pid}"]);
+                let panes = match output {
+                    Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout).into_owned(),
+                    output => return command_result(output).map_err(|err| format!("Failed to kill session: {}", err)),
+                };
+                // Ctrl-C reaches whatever runs in the foreground, and SIGTERM the pane's own
+                // process when it is not a shell (interactive shells ignore it). `run-shell` sends
+                // the signal from the host of the tmux server, remote or not.
+                for pane in panes.lines() {
+                    let Some((id, pid)) = pane.split_once('\t') else { continue };
+                    let _ = self.tmux.output(host, &["send-keys", "-t", id, "C-c"]);
+                    let _ = self.tmux.output(host, &["run-shell", "-b", &format!("kill -TERM {}", pid)]);
+                }
+                let kill = PendingAction { action: SessionAction::Kill, ..pending.clone() };
+                self.delayed_kills.push((Instant::now() + KILL_GRACE, kill));
+                Ok(())
+            }
+            SessionAction::SetColor(color) => {
+                // `set-option` takes a pane, so the session's current one
+                let target = format!("{}:", target);
+                let output = match color {
+                    Some(color) => self.tmux.output(host, &["set-option", "-t", &target, meta::COLOR, color]),
+                    None => self.tmux.output(host, &["set-option", "-u", "-t", &target, meta::COLOR]),
+                };
+                command_result(output).map_err(|err| format!("Failed to set color: {}", err))
+            }
+            SessionAction::DetachClients => {
+                // tmm's own control client would go too, so on its session the other clients are
+                // detached one by one
+                if pending.host_idx != 0 || self.tmux.control_session_id().as_deref() != Some(target) {
+                    let output = self.tmux.output(host, &["detach-client", "-s", target]);
+                    return command_result(output).map_err(|err| format!("Failed to detach clients: {}", err));
+                }
+                let output = self.tmux.output(host, &["list-clients", "-t", target, "-F", "#{client_name}\t#{client_control_mode}"]);
+                let clients = match output {
+                    Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout).into_owned(),
+                    output => return command_result(output).map_err(|err| format!("Failed to detach clients: {}", err)),
+                };
+                for (client, _) in clients.lines().filter_map(|line| line.split_once('\t')).filter(|(_, control)| *control != "1") {
+                    let output = self.tmux.output(host, &["detach-client", "-t", client]);
+                    command_result(output).map_err(|err| format!("Failed to detach {}: {}", client, err))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Suspend the interface to edit the configuration file
+    pub fn edit_config(&mut self) {
+        let Some(path) = self.config_path.clone() else {
+            self.report_error("No configuration file: neither $XDG_CONFIG_HOME nor $HOME is set".to_owned());
+            return;
         };
-        let proc = Command::new("tmux")
-            .args(["rename-session", "-t", name, rename])
-            .output()
-            .expect(format!("failed to rename tmux session: {}", name).as_str());
-        if !proc.status.success() {
-            panic!("This is the failure message: {}", std::str::from_utf8(&proc.stderr).unwrap());
-            // TODO: display popup with error
+        // The editor can create the file, but not its directory
+        if let Some(dir) = path.parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                self.report_error(format!("Failed to create {}: {}", dir.display(), err));
+                return;
+            }
         }
-        self.refresh();
-        self.dismiss_all();
+        self.foreground = Some(Foreground::EditConfig(path));
     }
 
-    /// Delete a session
-    pub fn delete(&mut self) {
-        let Some((name, _)) = self.sessions.get(self.selected_session) else {
-            panic!("Could not identify session to delete");
-        };
-        // Kill the session
-        Command::new("tmux")
-            .args(["kill-session", "-t", name])
-            .output()
-            .expect(format!("failed to kill tmux session {}", name).as_str());
-        // TODO: check output.status and present dialog or message to user
-        // instead of just expect panic?
-        // Restore state with a refresh
-        self.refresh();
-        self.dismiss_all();
+    /// Suspend the interface and stop tmm, as Ctrl-z does to other programs
+    pub fn stop(&mut self) {
+        self.foreground = Some(Foreground::Stop);
+    }
+
+    /// Take up again after running `foreground` in the terminal
+    pub fn foreground_finished(&mut self, foreground: Foreground, status: io::Result<ExitStatus>) {
+        match (foreground, status) {
+            (Foreground::Stop, _) => {}
+            (Foreground::EditConfig(path), Ok(status)) if status.success() => {
+                let (mut config, err) = Config::load(&path);
+                // The templates of other files stay as they were found on startup
+                config.add_templates(self.templates.iter().filter(|template| template.file.is_some()).cloned().collect());
+                match err {
+                    // The file is left alone, so that it can be fixed by editing it again
+                    Some(err) => self.report_error(err),
+                    None => {
+                        // As on startup, the sort order last chosen wins over the file's
+                        self.configure(&config);
+                        self.restore_sort();
+                        self.show_status(&format!("Reloaded {}", path.display()));
+                    }
+                }
+            }
+            (Foreground::EditConfig(_), Ok(status)) => self.report_error(format!("The editor failed ({})", status)),
+            (Foreground::EditConfig(_), Err(err)) => self.report_error(format!("Failed to run the editor: {}", err)),
+        }
+    }
+
+    /// Leave out the hotkey bar and status messages, for when tmm is embedded in another workflow
+    pub fn set_quiet(&mut self) {
+        self.quiet = true;
+        self.hotkey_bar = false;
+    }
+
+    /// Disable the actions that change sessions or the server, and hide their hotkeys
+    pub fn set_read_only(&mut self) {
+        self.read_only = true;
+        for (state, descriptions) in [
+            (AppState::Windows, ["Move Up", "Move Down", "Split Right", "Split Below", "Zoom", "Mark Pane", "Kill Pane"].as_slice()),
+            (AppState::Clients, &["Detach", "Kill"]),
+            (AppState::Templates, &["Create Session"]),
+            (AppState::Archive, &["Restore", "Purge"]),
+        ] {
+            if let Some(hotkeys) = self.hotkeys.get_mut(&state) {
+                hotkeys.retain(|_, description| !descriptions.contains(description));
+            }
+        }
+        self.update_hotkeys();
+    }
+
+    /// In read-only mode, say so and return true
+    fn refuse_read_only(&mut self) -> bool {
+        if self.read_only {
+            self.show_status(READ_ONLY);
+        }
+        self.read_only
+    }
+
+    /// Show a short message in the bottom border for a moment, unless quiet
+    pub fn show_status(&mut self, message: &str) {
+        if self.quiet {
+            return;
+        }
+        self.status = Some(Status { message: message.to_owned(), failure: false, shown: Instant::now() });
+    }
+
+    /// Show a short message about something that went wrong in the background, which does not
+    /// warrant interrupting with the error popup, in the bottom border for a little longer. Quiet
+    /// or not.
+    pub fn show_failure(&mut self, message: &str) {
+        self.status = Some(Status { message: message.to_owned(), failure: true, shown: Instant::now() });
+    }
+
+    /// The status message, unless it has been shown long enough
+    pub fn status(&self) -> Option<&Status> {
+        self.status.as_ref().filter(|status| {
+            status.shown.elapsed() < if status.failure { FAILURE_DURATION } else { STATUS_DURATION }
+        })
+    }
+
+    /// Queue a failure for the error popup, which is shown over the current state until every
+    /// queued error has been dismissed
+    pub fn report_error(&mut self, message: String) {
+        log::record("error", &[("message", &message)]);
+        if self.state != AppState::Error {
+            self.state_before_error = self.state.clone();
+            self.state = AppState::Error;
+        }
+        self.errors.push_back(message);
+    }
+
+    /// Dismiss the oldest error, returning to the previous state after the last one
+    pub fn dismiss_error(&mut self) {
+        self.errors.pop_front();
+        if self.errors.is_empty() {
+            self.state = self.state_before_error.clone();
+        }
     }
 
     pub fn confirm_new_session(&mut self) {
-        // Create the textarea and switch to renaming state
-        let mut textarea = TextArea::default();
-        textarea.set_cursor_line_style(Style::default());
-        self.new_session_ta = Some(textarea);
+        // New sessions start where tmm runs, unless told otherwise
+        let directory = env::current_dir().map(|dir| source::home_label(&dir.to_string_lossy())).unwrap_or_default();
+        let mut form = NewSessionForm::new(&directory, &self.default_command(), &self.templates);
+        // The configured command is filled in, so that clearing it falls back to the server's
+        if let Some(command) = &self.new_command {
+            form.command.insert_str(command);
+        }
+        self.new_session_form = Some(form);
         self.state = AppState::NewSession;
     }
 
-    /// Create a new session
-    pub fn new_session(&mut self, name: Option<&str>) {
+    /// What new sessions of the local server start with when no command is given: the
+    /// `default-command` server option, or else the `default-shell` one
+    fn default_command(&self) -> String {
+        let option = |name: &str| self.tmux.output(None, &["show-options", "-gv", name]).ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+            .filter(|value| !value.is_empty());
+        match (option("default-command"), option("default-shell")) {
+            (Some(command), _) => format!("{} (default-command)", command),
+            (None, Some(shell)) => format!("{} (default-shell)", shell),
+            (None, None) => "the default shell".to_owned(),
+        }
+    }
+
+    /// Create a new session, starting in `dir` and with `command` instead of the default one if
+    /// given
+    pub fn new_session(&mut self, name: Option<&str>, dir: Option<&str>, command: Option<&str>) {
         if let Some(name) = name {
             // Create the named session, and highlight it in the list
-            let proc = Command::new("tmux")
-                .args(["new-session", "-d", "-s", name])
-                .output()
-                .expect(format!("failed to create new tmux session: {}", name).as_str());
-            if !proc.status.success() {
-                panic!("This is the failure message: {}", std::str::from_utf8(&proc.stderr).unwrap());
-                // TODO: display popup with error
-            }
-            // TODO: one common failure mode might be that the name already exists, e.g,
-            // "duplicate session: <name>"
-
-            // Highlight the newly created session. Tmux may modify characters that are provided
-            // based on illegal tmux session names (e.g., 8.1 -> 8_1). It does not report this
-            // modification, so we should discover the new session name using the set difference of
-            // the new list of sessions and the old list of sessions.
-            //
-            // TODO: if the user creates new sessions once the new-session procedure has started in
-            // tmm, multiple new sessions will appear in this set difference. Use fuzzy-matching to
-            // find the best match for the session name among the new sessions to give the best
-            // changes of highlighting the correct new session.
-            //
-            // Before refreshing, build a set of the current names
-            let old_session_names: HashSet<String> = self.sessions.iter().map(|(name, _)| name.to_owned()).collect();
-            self.refresh();
-            let new_session_names: HashSet<String> = self.sessions.iter().map(|(name, _)| name.to_owned()).collect();
-            if let Some(new_session_name) = new_session_names.difference(&old_session_names).next() {
-                // We were able to find the new session name
-                if let Some(idx) = self.sessions.iter().position(|(name, _)| name == new_session_name) {
-                    self.selected_session = idx;
-                }
-            } else {
-                // New session name not found for some reason. Do not change the selection.
+            let mut args = vec!["new-session", "-d", "-s", name];
+            if let Some(dir) = dir {
+                args.extend(["-c", dir]);
+            }
+            args.extend(command);
+            let output = self.tmux.output(None, &args);
+            if let Err(err) = command_result(output) {
+                // e.g. "duplicate session: <name>"
+                self.dismiss_all();
+                self.report_error(format!("Failed to create session: {}", err));
+                return;
             }
+
+            self.select_created_session(name);
             self.dismiss_all();
         } else {
             // Exit and attach new session
             self.running = false;
-            self.on_exit = ExitAction::NewSession;
+            self.on_exit = ExitAction::NewSession(self.new_command.clone());
+        }
+    }
+
+    /// Create a local session from the template named `template`, named after the template
+    /// unless `name` is given, and starting in `dir` instead of the template's directory if given
+    pub fn new_session_from_template(&mut self, name: &str, template: &str, dir: Option<&str>) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let Some(mut template) = self.templates.iter().find(|t| t.name == template).cloned() else {
+            self.dismiss_all();
+            self.report_error(format!("No template named {:?}", template));
+            return;
+        };
+        if let Some(dir) = dir {
+            template.dir = Some(dir.to_owned());
+        }
+        let name = if name.is_empty() { &template.name } else { name };
+        // A template failing halfway leaves a partial session, which is still highlighted
+        let result = template.create(self.tmux.as_ref(), name);
+        self.select_created_session(name);
+        self.dismiss_all();
+        if let Err(err) = result {
+            self.report_error(format!("Failed to create session: {}", err));
+            return;
+        }
+        // Then type the template's commands into its windows, showing their progress like a batch
+        let created = tmux_session_name(name);
+        let session = self.sessions.iter().find(|s| s.host.is_none() && Some(&s.name) == created.as_ref());
+        if let (Some(session), false) = (session, template.after.is_empty()) {
+            self.batch = Some(Batch::send_keys(session, &template.after));
+            self.state = AppState::BatchRunning;
+        }
+    }
+
+    /// What to tell about a name being typed for a new local session, or for the selected session
+    /// when `renaming`: that there is none, that another session has it, or the name tmux would
+    /// actually use. `None` if tmux takes it as it is.
+    pub fn name_feedback(&self, name: &str, renaming: bool) -> Option<String> {
+        let Some(actual) = tmux_session_name(name) else { return Some("Name required".to_owned()) };
+        let taken = if renaming {
+            self.session_named(name).is_some()
+        } else {
+            self.sessions.iter().any(|s| s.host.is_none() && s.name == actual)
+        };
+        match (taken, renaming) {
+            (true, true) => Some(format!("{} already exists: Enter to suffix or merge", actual)),
+            (true, false) => Some(format!("{} already exists", actual)),
+            _ if actual != name => Some(format!("tmux will name it {}", actual)),
+            _ => None,
+        }
+    }
+
+    /// The name the new session form would create its session with, which without a name typed in
+    /// is the template's
+    pub fn new_session_name<'f>(&self, form: &'f NewSessionForm) -> &'f str {
+        match (form.name.lines()[0].as_str(), form.template()) {
+            ("", Some(template)) => template,
+            (name, _) => name,
         }
     }
+
+    /// Whether the new session form names a session tmux can create: a name it accepts and that
+    /// no local session has
+    pub fn new_session_form_ready(&self) -> bool {
+        let Some(form) = &self.new_session_form else { return false };
+        let name = self.new_session_name(form);
+        tmux_session_name(name).is_some_and(|name| !self.sessions.iter().any(|s| s.host.is_none() && s.name == name))
+    }
+
+    /// Refresh the list and highlight the local session tmux created when asked for `name`
+    fn select_created_session(&mut self, name: &str) {
+        self.refresh();
+        let Some(name) = tmux_session_name(name) else { return };
+        if let Some(idx) = self.sessions.iter().position(|s| s.host.is_none() && s.name == name) {
+            self.selected_session = idx;
+        }
+    }
+}
+
+/// The name tmux gives a session asked to be named `name`: it refuses an empty name, and
+/// replaces the `:` and `.` it reserves for targets with `_`
+pub fn tmux_session_name(name: &str) -> Option<String> {
+    (!name.is_empty()).then(|| name.replace([':', '.'], "_"))
 }
+
+/// The outcome of a tmux command, with its stderr (or why it could not be run) as the error
+fn command_result(output: io::Result<Output>) -> Result<(), String> {
+    match output {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_owned()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmux::MockClient;
+
+    /// [`LIST_SESSIONS_ARGS`] output for sessions `dev` (two windows, one dead pane, last attached by
+    /// alice) and `ops:1` (attached, colored red)
+    const LS: &str = "S\t$1\t2\t1709301900\t0\t1709301900\t\tdev\n\
+        S\t$2\t1\t1709301960\t1\t1709301990\t\tops:1\n\
+        P\t$1\t0\n\
+        P\t$1\t1\n\
+        P\t$2\t0\n\
+        M\t$1\t1709302000 alice@laptop\n\
+        M\t$2\t\n\
+        C\t$1\t\n\
+        C\t$2\tred\n";
+
+    fn app_with(mock: &Arc<MockClient>) -> App<'static> {
+        mock.reply("list-sessions", Ok(LS));
+        App::with_client(Arc::clone(mock) as Arc<dyn TmuxClient>)
+    }
+
+    #[test]
+    fn refresh_parses_sessions() {
+        let mock = Arc::new(MockClient::new());
+        let app = app_with(&mock);
+        let names: Vec<&str> = app.sessions.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["dev", "ops:1"]);
+        assert_eq!(app.sessions[0].id, "$1");
+        assert_eq!(app.sessions[0].windows, 2);
+        assert_eq!(app.sessions[0].dead_panes, 1);
+        assert_eq!(app.sessions[0].last_attach, Some(meta::Attach { timestamp: 1709302000, by: "alice@laptop".to_owned() }));
+        assert_eq!(app.sessions[1].last_attach, None);
+        assert_eq!((app.sessions[0].color, app.sessions[1].color), (None, Some(Color::Red)));
+        assert_eq!(app.sessions[1].windows, 1);
+        assert_eq!(app.sessions[1].attached, 1);
+        assert_eq!(app.sessions[1].activity, 1709301990);
+        assert!(app.sessions[1].desc(TimeZone::Utc).ends_with("(attached)"));
+    }
+
+    #[test]
+    fn attached_sort_puts_attached_sessions_first() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.sort = SortOrder::Attached;
+        app.sessions_changed();
+        let names: Vec<&str> = app.sessions.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["ops:1", "dev"]);
+    }
+
+    #[test]
+    fn attached_filter_hides_sessions_and_their_selection() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.cycle_attached_filter();
+        assert_eq!(app.list_rows(), [ListRow::Session(0)]);
+        app.cycle_attached_filter();
+        assert_eq!(app.list_rows(), [ListRow::Session(1)]);
+        assert_eq!(app.selected_session, 1);
+    }
+
+    #[test]
+    fn reordering_keeps_the_selected_session() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        // The remote session has the id of a local one
+        app.add_remote_hosts(vec!["build".to_owned()]);
+        app.job_finished(JobResult { job: Job::Sessions(1), generation: 0,
+            output: Some("S\t$1\t1\t1709301900\t0\t1709301900\t\tci\n".to_owned()) });
+        app.selected_session = app.sessions.iter().position(|s| s.label() == "build/ci").unwrap();
+        app.keeping_selection(|app| {
+            app.sort = SortOrder::Attached;
+            app.sessions_changed();
+        });
+        assert_eq!(app.sessions[app.selected_session].label(), "build/ci");
+        app.cycle_group_by();
+        assert_eq!(app.sessions[app.selected_session].label(), "build/ci");
+    }
+
+    #[test]
+    fn remote_hosts_group_by_host_unless_configured() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.add_remote_hosts(vec!["build".to_owned()]);
+        assert_eq!(app.group_by, GroupBy::Host);
+
+        let mut app = app_with(&mock);
+        app.configure(&Config { group_by: Some(GroupBy::None), ..Config::default() });
+        app.add_remote_hosts(vec!["build".to_owned()]);
+        assert_eq!(app.group_by, GroupBy::None);
+    }
+
+    #[test]
+    fn search_for_starts_the_search_with_the_term() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.search_for("op");
+        assert_eq!(app.state, AppState::SessionsSearch);
+        assert_eq!(app.search_matches(), [(1, vec![0, 1])]);
+        app.search_for("re:.");
+        assert_eq!(app.search_matches().len(), 2);
+    }
+
+    #[test]
+    fn only_session_is_the_single_one() {
+        let mock = Arc::new(MockClient::new());
+        assert!(app_with(&mock).only_session().is_none());
+        mock.reply("list-sessions", Ok("S\t$1\t2\t1709301900\t0\t1709301900\t\tdev\n"));
+        let mut app = App::with_client(Arc::clone(&mock) as Arc<dyn TmuxClient>);
+        assert_eq!(app.only_session().map(|session| session.name.as_str()), Some("dev"));
+        app.hosts.push(Some("build-box".to_owned()));
+        assert!(app.only_session().is_none());
+    }
+
+    #[test]
+    fn server_info_describes_the_server() {
+        let mock = Arc::new(MockClient::new());
+        mock.reply("display-message", Ok("4242\t/tmp/tmux-1000/work\t1709301900\t3.4\n"));
+        let info = App::server_info(mock.as_ref(), None, Duration::from_secs(1)).unwrap();
+        assert_eq!((info.pid, info.socket_path.as_str(), info.start_time, info.version.as_str()), (4242, "/tmp/tmux-1000/work", 1709301900, "3.4"));
+        assert_eq!(info.describe(TimeZone::Utc)[0], ("Host", "local".to_owned()));
+        mock.reply("display-message", Err("no server running on /tmp/tmux-1000/work"));
+        assert_eq!(App::server_info(mock.as_ref(), None, Duration::from_secs(1)).unwrap_err(), "no server running on /tmp/tmux-1000/work");
+    }
+
+    #[test]
+    fn control_client_is_not_counted_as_attached() {
+        let sessions = App::parse_sessions(None, LS, Some("$2"));
+        assert_eq!(sessions[1].attached, 0);
+        assert!(!sessions[1].desc(TimeZone::Utc).contains("(attached)"));
+    }
+
+    #[test]
+    fn delete_targets_session_id() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.selected_session = 1;
+        app.delete();
+        assert!(mock.calls().iter().any(|(host, args)| host.is_none() && args == &["kill-session", "-t", "$2"]));
+    }
+
+    #[test]
+    fn delete_confirmation_closes_when_the_session_vanishes() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.confirm_delete();
+        assert_eq!(app.state, AppState::Deleting);
+        // `dev` ends while the confirmation is open, and `ops:1` slides into its row, which must
+        // not be the one a late "yes" kills
+        mock.reply("list-sessions", Ok("S\t$2\t1\t1709301960\t1\t1709301990\t\tops:1\n"));
+        app.refresh();
+        assert_eq!(app.selected_session, 0);
+        assert!(!mock.calls().iter().any(|(_, args)| args[0] == "kill-session"));
+        assert_eq!(app.state, AppState::Sessions);
+        assert_eq!(app.status().map(|status| status.message.as_str()), Some("dev no longer exists, nothing was done"));
+    }
+
+    #[test]
+    fn graceful_delete_interrupts_panes_before_killing() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.selected_session = 1;
+        mock.reply("list-panes", Ok("%4\t1234\n"));
+        app.delete_gracefully();
+        let killed = |mock: &MockClient| mock.calls().iter().any(|(_, args)| args[0] == "kill-session");
+        assert!(mock.calls().iter().any(|(_, args)| args == &["send-keys", "-t", "%4", "C-c"]));
+        assert!(mock.calls().iter().any(|(_, args)| args == &["run-shell", "-b", "kill -TERM 1234"]));
+        assert!(!killed(&mock));
+        // Once the grace period is over
+        app.delayed_kills[0].0 = Instant::now();
+        app.apply_delayed_kills();
+        assert!(killed(&mock));
+        assert!(app.delayed_kills.is_empty());
+    }
+
+    #[test]
+    fn detaching_clients_asks_only_when_some_are_attached() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.confirm_detach_clients();
+        assert_eq!(app.state, AppState::Sessions);
+        assert_eq!(app.status().map(|status| status.message.as_str()), Some("No clients attached to dev"));
+        app.selected_session = 1;
+        app.confirm_detach_clients();
+        assert_eq!(app.state, AppState::DetachingClients);
+        app.detach_clients();
+        assert!(mock.calls().iter().any(|(_, args)| args == &["detach-client", "-s", "$2"]));
+    }
+
+    #[test]
+    fn disabled_actions_say_why() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        assert_eq!(app.disabled_reason(Action::DetachClients), Some("No clients attached to dev".to_owned()));
+        assert_eq!(app.disabled_reason(Action::Delete), None);
+        app.selected_session = 1;
+        assert_eq!(app.disabled_reason(Action::DetachClients), None);
+        // Sessions of a host that cannot be reached any more are left alone
+        app.add_remote_hosts(vec!["build".to_owned()]);
+        for output in [Some("S\t$1\t1\t1709301900\t0\t1709301900\t\tci\n"), None] {
+            app.job_finished(JobResult { job: Job::Sessions(1), generation: 0, output: output.map(str::to_owned) });
+        }
+        app.selected_session = app.sessions.iter().position(|s| s.label() == "build/ci").unwrap();
+        assert_eq!(app.disabled_reason(Action::Attach), Some("build is unreachable".to_owned()));
+    }
+
+    #[test]
+    fn read_only_mode_disables_changes() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.set_read_only();
+        assert_eq!(app.disabled_reason(Action::Delete), Some(READ_ONLY.to_owned()));
+        assert_eq!(app.disabled_reason(Action::Attach), None);
+        assert!(!app.hotkey_actions.values().any(|action| action.mutates()));
+        assert!(!app.hotkeys[&AppState::Clients].values().any(|description| *description == "Kill"));
+        app.new_session_from_template("", "web", None);
+        assert_eq!(app.status().map(|status| status.message.as_str()), Some(READ_ONLY));
+        // Nor does anything in the windows view change the server
+        mock.reply("list-windows", Ok("W\t$1\t0\t2\t1\t0\t80x24\tvim\tedit\n\
+            P\t$1\t0\t0\t1\t0\t80x12\tvim\t/src\nP\t$1\t0\t1\t0\t0\t80x11\tbash\t/src\n"));
+        app.show_windows();
+        let calls = mock.calls().len();
+        app.toggle_pane_mark();
+        app.move_selected_window(true);
+        app.toggle_pane_zoom();
+        app.confirm_split(false);
+        app.confirm_kill_pane();
+        assert_eq!(app.state, AppState::Windows);
+        assert_eq!(mock.calls().len(), calls);
+        let windows_hotkeys: Vec<&str> = app.hotkeys[&AppState::Windows].values().copied().collect();
+        assert!(!["Mark Pane", "Move Down", "Split Right", "Zoom", "Kill Pane"].iter().any(|hidden| windows_hotkeys.contains(hidden)));
+    }
+
+    #[test]
+    fn templates_run_their_commands_after_creating_the_session() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        mock.reply("new-session", Ok("$1:\t@1\t%1\n"));
+        mock.reply("send-keys", Err("no such window"));
+        app.templates = vec![Template {
+            after: vec![("editor".to_owned(), "direnv allow".to_owned()), ("logs".to_owned(), "tail -f log".to_owned())],
+            ..Template::new("dev")
+        }];
+        app.new_session_from_template("", "dev", None);
+        assert_eq!(app.state, AppState::BatchRunning);
+        while app.state == AppState::BatchRunning {
+            app.step_batch();
+        }
+        assert_eq!(app.state, AppState::BatchResults);
+        assert_eq!(app.batch.as_ref().unwrap().result_lines(), [
+            "fail run direnv allow in dev:editor: no such window",
+            "fail run tail -f log in dev:logs: no such window",
+        ]);
+        let sent: Vec<String> = mock.calls().into_iter().map(|(_, args)| args.join(" "))
+            .filter(|call| call.starts_with("send-keys")).collect();
+        assert_eq!(sent, ["send-keys -t $1:editor -l direnv allow", "send-keys -t $1:logs -l tail -f log"]);
+    }
+
+    #[test]
+    fn window_numbers_attach_their_window() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        mock.reply("list-windows", Ok("W\t$1\t1\t1\t1\t0\t80x24\tbash\tmain\nW\t$1\t3\t1\t0\t0\t80x24\tvim\tedit\n"));
+        app.show_windows();
+        app.attach_window_numbered(2);
+        assert!(app.running);
+        app.attach_window_numbered(3);
+        assert_eq!(app.selected_window, 1);
+        assert_eq!(app.on_exit, ExitAction::AttachWindow(None, "dev".to_owned(), 3, None, app.attach_size));
+    }
+
+    #[test]
+    fn splitting_a_pane_selects_the_new_pane() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        mock.reply("list-windows", Ok("W\t$1\t0\t1\t1\t0\t80x24\tvim\tedit\nP\t$1\t0\t0\t1\t0\t80x24\tvim\t/src\n"));
+        app.show_windows();
+        app.confirm_split(true);
+        assert_eq!(app.state, AppState::SplitPane);
+        mock.reply("list-windows", Ok("W\t$1\t0\t2\t1\t0\t80x24\tvim\tedit\n\
+            P\t$1\t0\t0\t1\t0\t80x12\tvim\t/src\nP\t$1\t0\t1\t0\t0\t80x11\tbash\t/src\n"));
+        mock.reply("split-window", Ok("1\n"));
+        app.split_pane();
+        let split = mock.calls().into_iter().find(|(_, args)| args[0] == "split-window").unwrap();
+        assert_eq!(split.1, ["split-window", "-d", "-v", "-P", "-F", "#{pane_index}", "-t", "=dev:0.0", "-c", "/src"]);
+        assert_eq!(app.state, AppState::Windows);
+        assert_eq!(app.tree_rows()[app.selected_window], TreeRow::Pane(0, 1));
+    }
+
+    #[test]
+    fn killing_a_pane_asks_first() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        mock.reply("list-windows", Ok("W\t$1\t0\t2\t1\t0\t80x24\tvim\tedit\n\
+            P\t$1\t0\t0\t1\t0\t80x12\tvim\t/src\nP\t$1\t0\t1\t0\t0\t80x11\tbash\t/src\n"));
+        app.show_windows();
+        app.expand_selected_window();
+        app.selected_window = 2;
+        app.confirm_kill_pane();
+        assert_eq!(app.state, AppState::KillingPane);
+        assert!(!mock.calls().iter().any(|(_, args)| args[0] == "kill-pane"));
+        mock.reply("list-windows", Ok("W\t$1\t0\t1\t1\t0\t80x24\tvim\tedit\nP\t$1\t0\t0\t1\t0\t80x24\tvim\t/src\n"));
+        app.kill_pane();
+        assert!(mock.calls().iter().any(|(_, args)| args == &["kill-pane", "-t", "=dev:0.1"]));
+        assert_eq!(app.state, AppState::Windows);
+        assert_eq!(app.selected_window, 1);
+        // The session ends with its last pane
+        app.confirm_kill_pane();
+        mock.reply("list-windows", Ok(""));
+        app.kill_pane();
+        assert_eq!(app.state, AppState::Sessions);
+    }
+
+    #[test]
+    fn capturing_a_pane_saves_its_output() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        mock.reply("list-windows", Ok("W\t$1\t0\t1\t1\t0\t80x24\tvim\tedit\nP\t$1\t0\t0\t1\t0\t80x24\tvim\t/src\n"));
+        app.show_windows();
+        app.confirm_capture_pane();
+        assert_eq!(app.state, AppState::CapturingPane);
+        assert_eq!(app.capture_ta.as_ref().unwrap().lines()[0], "~/dev-0.0.log");
+        mock.reply("capture-pane", Ok("$ make\nok\n"));
+        let path = env::temp_dir().join(format!("tmm-capture-{}.log", std::process::id()));
+        app.capture_pane(path.to_str().unwrap());
+        assert!(mock.calls().iter().any(|(_, args)| args == &["capture-pane", "-p", "-J", "-S", "-", "-t", "=dev:0.0"]));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "$ make\nok\n");
+        assert_eq!(app.state, AppState::Windows);
+        fs::remove_file(path).unwrap();
+    }
+
+    /// Run a deep search to the end, as the main loop does
+    fn finish_deep_search(app: &mut App) {
+        let (sender, receiver) = mpsc::channel();
+        app.tick(&sender);
+        while app.state == AppState::DeepSearchRunning {
+            if let Event::PaneSearch(generation, update) = receiver.recv_timeout(Duration::from_secs(5)).expect("the search finishes") {
+                app.pane_search_update(generation, update);
+            }
+        }
+    }
+
+    #[test]
+    fn deep_search_lists_the_panes_mentioning_the_term() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.start_deep_search();
+        assert_eq!(app.state, AppState::DeepSearching);
+        mock.reply("list-panes", Ok("%1\t0\t0\tbash\tdev\n%2\t1\t0\tpsql\tops:1\n"));
+        // Every pane gets the same reply, which the term decides
+        mock.reply("capture-pane", Ok("$ rake db:migrate\n== CreateUsers: migrated\n$ ls\n"));
+        app.deep_search("migrat");
+        assert_eq!(app.state, AppState::DeepSearchRunning);
+        finish_deep_search(&mut app);
+        assert_eq!(app.state, AppState::DeepResults);
+        assert_eq!(app.pane_matches.len(), 2);
+        assert_eq!((app.pane_matches[1].session.as_str(), app.pane_matches[1].window, app.pane_matches[1].count), ("ops:1", 1, 2));
+        assert_eq!(app.pane_matches[1].line, "== CreateUsers: migrated");
+        app.selected_pane_match = 1;
+        app.attach_pane_match();
+        assert_eq!(app.on_exit, ExitAction::AttachWindow(None, "ops:1".to_owned(), 1, Some(0), app.attach_size));
+        // Uppercase makes the case count
+        app.running = true;
+        app.deep_search("Migrat");
+        finish_deep_search(&mut app);
+        assert_eq!(app.state, AppState::Sessions);
+        assert_eq!(app.status().map(|status| status.message.as_str()), Some("No pane mentions Migrat"));
+    }
+
+    #[test]
+    fn cancelled_deep_searches_stop_and_report_nothing() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        mock.reply("list-panes", Ok("%1\t0\t0\tbash\tdev\n%2\t1\t0\tpsql\tops:1\n"));
+        mock.reply("capture-pane", Ok("migrated\n"));
+        let cancel = AtomicBool::new(true);
+        assert!(search_panes(&*mock, &[None], "migrat", Duration::from_secs(1), &cancel, |_, _| {}).is_empty());
+        let mut progress = vec![];
+        cancel.store(false, Ordering::Relaxed);
+        let matches = search_panes(&*mock, &[None], "migrat", Duration::from_secs(1), &cancel, |done, total| progress.push((done, total)));
+        assert_eq!((matches.len(), progress), (2, vec![(0, 2), (1, 2)]));
+        // What a cancelled search still sends is dropped
+        app.deep_search("migrat");
+        app.cancel_deep_search();
+        app.pane_search_update(app.pane_search_generation, PaneSearchUpdate::Finished(matches));
+        assert_eq!(app.state, AppState::Sessions);
+        assert!(app.pane_matches.is_empty());
+        assert_eq!(app.status().map(|status| status.message.as_str()), Some("Deep search cancelled"));
+    }
+
+    #[test]
+    fn copy_mode_selects_lines_of_the_list() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        mock.reply("list-windows", Ok("W\t$1\t0\t1\t1\t1\t80x24\tvim\tedit\nW\t$1\t1\t2\t0\t0\t80x24\tbash\tshell\n"));
+        app.show_windows();
+        app.selected_window = 1;
+        app.start_copy_mode();
+        assert_eq!(app.state, AppState::Copying);
+        assert_eq!(app.copy_lines, ["0: edit*Z (1 panes) [80x24] vim", "1: shell (2 panes) [80x24] bash"]);
+        assert_eq!(app.copy_selection(), 1..=1);
+        app.toggle_copy_selection();
+        app.move_copy_cursor(-5);
+        assert_eq!(app.copy_selection(), 0..=1);
+        app.leave_copy_mode();
+        assert_eq!(app.state, AppState::Windows);
+        app.dismiss_all();
+        app.start_copy_mode();
+        assert!(app.copy_lines[1].starts_with("ops:1: 1 windows"));
+        app.move_copy_cursor(5);
+        assert_eq!(app.copy_selection(), 1..=1);
+    }
+
+    #[test]
+    fn switching_servers_lists_the_sessions_of_the_other_server() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.servers = vec![PathBuf::from("/tmp/tmm-test/work")];
+        app.state = AppState::Servers;
+        mock.reply("list-sessions", Ok("S\t$1\t1\t1709301900\t0\t1709301900\t\twiki\n"));
+        app.prefetched_windows.insert((None, "$1".to_owned()), vec![]);
+        app.switch_server();
+        assert_eq!(mock.server().args(), ["-S", "/tmp/tmm-test/work"]);
+        assert!(app.prefetched_windows.is_empty());
+        assert_eq!(app.state, AppState::Sessions);
+        assert_eq!(app.server.as_deref(), Some("/tmp/tmm-test/work"));
+        let names: Vec<&str> = app.sessions.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["wiki"]);
+        assert_eq!(app.status().map(|status| status.message.as_str()), Some("Switched to server /tmp/tmm-test/work"));
+    }
+
+    #[test]
+    fn zooming_a_pane_flags_its_window() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        mock.reply("list-windows", Ok("W\t$1\t0\t1\t1\t0\t80x24\tvim\tedit\nP\t$1\t0\t0\t1\t0\t80x24\tvim\t/src\n"));
+        app.show_windows();
+        mock.reply("list-windows", Ok("W\t$1\t0\t1\t1\t1\t80x24\tvim\tedit\nP\t$1\t0\t0\t1\t0\t80x24\tvim\t/src\n"));
+        app.toggle_pane_zoom();
+        assert!(mock.calls().iter().any(|(_, args)| args == &["resize-pane", "-Z", "-t", "=dev:0.0"]));
+        assert!(app.windows[0].zoomed);
+    }
+
+    #[test]
+    fn attaching_the_current_session_does_as_configured() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.current_session = CurrentSession::Notice;
+        app.attach_current_session();
+        assert!(app.running);
+        assert_eq!(app.status().map(|status| status.message.as_str()), Some("Already in this session"));
+        app.current_session = CurrentSession::Windows;
+        app.attach_current_session();
+        assert_eq!(app.state, AppState::Windows);
+        app.current_session = CurrentSession::Quit;
+        app.attach_current_session();
+        assert!(!app.running);
+        assert_eq!(app.on_exit, ExitAction::None);
+    }
+
+    #[test]
+    fn session_numbers_attach_their_session() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        assert_eq!(app.numbered_sessions(), [0, 1]);
+        app.attach_session_numbered(3);
+        assert!(app.running);
+        assert_eq!(app.status().map(|status| status.message.as_str()), Some("No session 3"));
+        app.attach_session_numbered(2);
+        assert_eq!(app.selected_session, 1);
+        assert!(matches!(&app.on_exit, ExitAction::AttachSession(None, name, _) if name == "ops:1"));
+    }
+
+    #[test]
+    fn session_colors_are_set_in_their_option() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.select_next();
+        app.confirm_color();
+        assert_eq!(app.color_ta.as_ref().unwrap().lines(), ["red"]);
+        app.set_selected_color("reddish");
+        assert_eq!(app.state, AppState::Coloring);
+        app.set_selected_color("#ff8800");
+        app.confirm_color();
+        app.set_selected_color("");
+        let calls: Vec<String> = mock.calls().into_iter().map(|(_, args)| args.join(" ")).filter(|call| call.starts_with("set-option")).collect();
+        assert_eq!(calls, ["set-option -t $2: @tmm_color #ff8800", "set-option -u -t $2: @tmm_color"]);
+        assert_eq!(app.state, AppState::Sessions);
+    }
+
+    #[test]
+    fn archived_sessions_are_restored_or_purged() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        let dir = env::temp_dir().join(format!("tmm-archive-app-test-{}", std::process::id()));
+        app.archive_dir = Some(dir.clone());
+        mock.reply("list-panes", Ok("0\t/src\tnvim\teditor\n"));
+        app.archive_selected();
+        let calls: Vec<String> = mock.calls().into_iter().map(|(_, args)| args.join(" ")).collect();
+        assert!(calls.contains(&"kill-session -t $1".to_owned()));
+        app.show_archive();
+        assert_eq!(app.archived.len(), 1);
+        assert_eq!(app.archived[0].template.name, "dev");
+        // The listing still has the session, so it is not restored over it
+        app.restore_selected_archived();
+        assert_eq!(app.state, AppState::Error);
+        app.dismiss_error();
+        mock.reply("list-sessions", Ok(""));
+        app.refresh();
+        mock.reply("new-session", Ok("$3:\t@1\t%1\n"));
+        app.show_archive();
+        app.restore_selected_archived();
+        assert_eq!(app.state, AppState::Sessions);
+        assert!(mock.calls().iter().any(|(_, args)| args.join(" ").starts_with("new-session -d -s dev")));
+        app.show_archive();
+        assert!(app.archived.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn selection_wraps_around_when_configured() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.select_previous();
+        assert_eq!(app.selected_session, 0);
+        app.wrap_around = true;
+        app.select_previous();
+        assert_eq!(app.selected_session, 1);
+        app.select_next();
+        assert_eq!(app.selected_session, 0);
+    }
+
+    #[test]
+    fn windows_view_opens_from_prefetched_windows() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        let output = "W\t$1\t0\t2\t1\t0\t80x24\tvim\tedit\nW\t$2\t0\t1\t1\t0\t80x24\tbash\tops\n\
+            P\t$1\t0\t0\t1\t0\t40x24\tvim\t/src\nP\t$1\t0\t1\t0\t0\t40x24\tcargo\t/src\n";
+        app.job_finished(JobResult { job: Job::Windows(0), generation: 0, output: Some(output.to_owned()) });
+        let calls = mock.calls().len();
+        app.show_windows();
+        assert_eq!(mock.calls().len(), calls);
+        assert_eq!(app.windows.len(), 1);
+        assert_eq!(app.windows[0].pane_list.iter().map(|pane| pane.command.as_str()).collect::<Vec<_>>(), ["vim", "cargo"]);
+        // The view catches up with the next prefetch, but not with one from before a change
+        let output = "W\t$1\t0\t1\t1\t0\t80x24\tvim\tedit\nW\t$1\t1\t1\t0\t0\t80x24\tbash\tlogs\n";
+        app.job_finished(JobResult { job: Job::Windows(0), generation: 0, output: Some(output.to_owned()) });
+        assert_eq!(app.windows.len(), 2);
+        app.windows_generation += 1;
+        let output = "W\t$1\t0\t1\t1\t0\t80x24\tvim\tedit\n";
+        app.job_finished(JobResult { job: Job::Windows(0), generation: 0, output: Some(output.to_owned()) });
+        assert_eq!(app.windows.len(), 2);
+    }
+
+    #[test]
+    fn killing_the_server_asks_first() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.confirm_kill_server();
+        assert_eq!(app.state, AppState::KillingServer);
+        assert!(!mock.calls().iter().any(|(_, args)| args[0] == "kill-server"));
+        app.kill_server();
+        assert_eq!(app.state, AppState::Sessions);
+        assert!(mock.calls().iter().any(|(host, args)| host.is_none() && args == &["kill-server"]));
+    }
+
+    #[test]
+    fn edited_configuration_is_reloaded() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        let path = env::temp_dir().join(format!("tmm-edit-{}", std::process::id())).join("config.toml");
+        app.config_path = Some(path.clone());
+        app.edit_config();
+        assert_eq!(app.foreground, Some(Foreground::EditConfig(path.clone())));
+        fs::write(&path, "[layout]\npreview_width = 30\n").unwrap();
+        app.foreground_finished(app.foreground.clone().unwrap(), Ok(ExitStatus::from_raw(0)));
+        assert_eq!(app.preview_width, 30);
+        fs::write(&path, "[layout]\npreview_width = 95\n").unwrap();
+        app.foreground_finished(Foreground::EditConfig(path.clone()), Ok(ExitStatus::from_raw(0)));
+        assert_eq!(app.preview_width, 30);
+        assert_eq!(app.state, AppState::Error);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn attach_and_run_types_the_command_before_attaching() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.attach_command = Some("git pull".to_owned());
+        app.confirm_attach_run();
+        assert_eq!(app.state, AppState::AttachCommand);
+        app.attach_selected_and_run(" ");
+        let calls: Vec<String> = mock.calls().into_iter().map(|(_, args)| args.join(" ")).collect();
+        assert!(calls.ends_with(&["send-keys -t =dev: -l git pull".to_owned(), "send-keys -t =dev: Enter".to_owned()]));
+        assert!(!app.running);
+        assert!(matches!(&app.on_exit, ExitAction::AttachSession(None, name, _) if name == "dev"));
+    }
+
+    #[test]
+    fn directory_field_completes_against_the_filesystem() {
+        let root = env::temp_dir().join(format!("tmm-complete-{}", std::process::id()));
+        for dir in ["project-one", "project-two", ".hidden"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let mut form = NewSessionForm::new("", "", &[]);
+        form.directory.insert_str(format!("{}/p", root.display()));
+        assert!(form.complete_directory());
+        assert_eq!(form.directory.lines()[0], format!("{}/project-", root.display()));
+        assert!(form.complete_directory());
+        assert_eq!(form.candidates, ["project-one/", "project-two/"]);
+        form.directory.insert_str("t");
+        assert!(form.complete_directory());
+        assert_eq!(form.directory(), Some(format!("{}/project-two/", root.display())));
+        // Nothing left to complete
+        assert!(!form.complete_directory());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn clients_view_leaves_out_the_control_client() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        mock.reply("list-clients", Ok("0\t/dev/pts/3\t80x24\t1709301990\txterm\t/dev/pts/3\n1\t\t80x24\t1709301990\t\tclient-42\n"));
+        app.selected_session = 1;
+        app.show_clients();
+        assert_eq!(app.state, AppState::Clients);
+        assert_eq!(app.clients.len(), 1);
+        assert_eq!(app.clients[0].tty, "/dev/pts/3");
+        app.confirm_kill_client();
+        assert_eq!(app.state, AppState::KillingClient);
+        assert!(!mock.calls().iter().any(|(_, args)| args[0] == "detach-client"));
+        app.cancel_kill_client();
+        assert_eq!(app.state, AppState::Clients);
+        app.confirm_kill_client();
+        app.kill_client();
+        assert_eq!(app.state, AppState::Clients);
+        assert!(mock.calls().iter().any(|(_, args)| args == &["detach-client", "-P", "-t", "/dev/pts/3"]));
+    }
+
+    #[test]
+    fn remote_hosts_get_a_section_with_their_status() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.add_remote_hosts(vec!["build".to_owned()]);
+        let listed = |app: &mut App, output: Option<&str>| {
+            app.job_finished(JobResult { job: Job::Sessions(1), generation: 0, output: output.map(str::to_owned) });
+        };
+        assert_eq!(app.host_status(1), HostStatus::Connecting);
+        listed(&mut app, None);
+        assert_eq!(app.host_status(1), HostStatus::Disconnected);
+        // An unreachable host still has its section
+        assert!(app.list_rows().contains(&ListRow::Header("build".to_owned(), 0, false)));
+        listed(&mut app, Some(""));
+        assert_eq!(app.host_status(1), HostStatus::Connected);
+        listed(&mut app, None);
+        assert!(matches!(app.host_status(1), HostStatus::Stale(_)));
+        assert_eq!(app.section_host("build"), Some(1));
+    }
+
+    #[test]
+    fn remote_hosts_are_listed_concurrently_as_each_answers() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.add_remote_hosts(vec!["build".to_owned(), "web".to_owned()]);
+        app.scheduler.min_gap = Duration::ZERO;
+        // Both hosts are asked at once, neither waiting for the other
+        let now = Instant::now();
+        let mut launched = vec![];
+        while let Some(job) = app.scheduler.due(now) {
+            launched.push(job);
+        }
+        assert!(launched.contains(&Job::Sessions(1)) && launched.contains(&Job::Sessions(2)));
+        let listed = |app: &mut App, idx: usize, output: Option<&str>| {
+            app.job_finished(JobResult { job: Job::Sessions(idx), generation: 0, output: output.map(str::to_owned) });
+        };
+        // The second host answering first is shown while the first is still being asked
+        listed(&mut app, 2, Some("S\t$1\t1\t1709301900\t0\t1709301900\t\tsite\n"));
+        assert!(app.scheduler.in_flight(Job::Sessions(1)));
+        assert_eq!(app.host_status(1), HostStatus::Connecting);
+        assert!(app.sessions.iter().any(|session| session.label() == "web/site"));
+        // The first timing out leaves the second's sessions alone
+        listed(&mut app, 1, None);
+        assert_eq!(app.host_status(1), HostStatus::Disconnected);
+        assert_eq!(app.host_status(2), HostStatus::Connected);
+        assert!(app.sessions.iter().any(|session| session.label() == "web/site"));
+    }
+
+    #[test]
+    fn rename_prompt_starts_with_the_current_name() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.confirm_rename();
+        let textarea = app.rename_session_ta.as_mut().unwrap();
+        assert_eq!(textarea.lines(), ["dev"]);
+        assert_eq!(textarea.cursor(), (0, 3));
+        // Typing replaces the selected name
+        textarea.insert_char('x');
+        assert_eq!(textarea.lines(), ["x"]);
+    }
+
+    #[test]
+    fn session_names_are_checked_as_tmux_would() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        assert_eq!(app.name_feedback("", false), Some("Name required".to_owned()));
+        assert_eq!(app.name_feedback("v8.1:beta", false), Some("tmux will name it v8_1_beta".to_owned()));
+        assert_eq!(app.name_feedback("web-2", false), None);
+        assert_eq!(app.name_feedback("dev", false), Some("dev already exists".to_owned()));
+        // The session being renamed may keep its name
+        assert_eq!(app.name_feedback("dev", true), None);
+        app.selected_session = 1;
+        assert_eq!(app.name_feedback("dev", true), Some("dev already exists: Enter to suffix or merge".to_owned()));
+    }
+
+    #[test]
+    fn failed_rename_is_reported() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        mock.reply("rename-session", Err("duplicate session: ops"));
+        app.rename("ops");
+        assert_eq!(app.state, AppState::Error);
+        assert_eq!(app.errors.front().map(String::as_str), Some("Failed to rename session: duplicate session: ops"));
+        app.dismiss_error();
+        assert_eq!(app.state, AppState::Sessions);
+    }
+
+    #[test]
+    fn renaming_says_so_in_the_status_line() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.rename("web");
+        assert_eq!(app.state, AppState::Sessions);
+        assert_eq!(app.status().map(|status| (status.message.as_str(), status.failure)), Some(("Renamed dev to web", false)));
+        // A background refresh failing is pointed out once, without the error popup
+        app.job_finished(JobResult { job: Job::Sessions(0), generation: 0, output: None });
+        assert_eq!(app.status().map(|status| (status.message.as_str(), status.failure)), Some(("Refresh failed: tmux did not reply", true)));
+        app.status = None;
+        app.job_finished(JobResult { job: Job::Sessions(0), generation: 0, output: None });
+        assert_eq!(app.status(), None);
+        assert_eq!(app.state, AppState::Sessions);
+    }
+
+    #[test]
+    fn actions_wait_for_the_refresh_in_flight() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.scheduler.min_gap = Duration::ZERO;
+        let in_flight = |app: &mut App| {
+            app.scheduler.trigger(Job::Sessions(0));
+            while app.scheduler.due(Instant::now()).is_some_and(|job| job != Job::Sessions(0)) {}
+            assert!(app.scheduler.in_flight(Job::Sessions(0)));
+        };
+        let renames = |mock: &MockClient| {
+            mock.calls().into_iter().map(|(_, args)| args.join(" ")).filter(|call| call.starts_with("rename-session")).collect::<Vec<_>>()
+        };
+        in_flight(&mut app);
+        app.rename("web");
+        assert_eq!(app.pending_actions.len(), 1);
+        assert!(renames(&mock).is_empty());
+        // Applied to the session by id once the refresh lands, even though it moved
+        let generation = app.sessions_generations[0];
+        let moved = "S\t$2\t1\t1709301960\t1\t1709301990\t\tops:1\nS\t$1\t2\t1709301900\t0\t1709301900\t\tdev\n";
+        app.job_finished(JobResult { job: Job::Sessions(0), generation, output: Some(moved.to_owned()) });
+        assert!(app.pending_actions.is_empty());
+        assert_eq!(renames(&mock), ["rename-session -t $1 web"]);
+        // Dropped when the session went away meanwhile
+        in_flight(&mut app);
+        app.selected_session = app.sessions.iter().position(|session| session.id == "$2").unwrap();
+        app.rename("logs");
+        let generation = app.sessions_generations[0];
+        app.job_finished(JobResult { job: Job::Sessions(0), generation, output: Some("S\t$1\t2\t1709301900\t0\t1709301900\t\tdev\n".to_owned()) });
+        assert!(app.pending_actions.is_empty());
+        assert_eq!(renames(&mock), ["rename-session -t $1 web"]);
+    }
+
+    #[test]
+    fn rename_collision_offers_suffix_or_merge() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.selected_session = 1;
+        app.rename("dev");
+        assert_eq!(app.state, AppState::RenameConflict);
+        assert_eq!(app.suffixed_rename().as_deref(), Some("dev-2"));
+        mock.reply("list-windows", Ok("@3\tmain\n@4\tlogs\n"));
+        app.merge_into_conflict();
+        assert_eq!(app.state, AppState::BatchRunning);
+        while app.state == AppState::BatchRunning {
+            app.step_batch();
+        }
+        // Only failures are worth a results popup after a merge
+        assert_eq!(app.state, AppState::Sessions);
+        let moves: Vec<Vec<String>> = mock.calls().into_iter()
+            .filter(|(_, args)| args[0] == "move-window")
+            .map(|(_, args)| args)
+            .collect();
+        assert_eq!(moves, [
+            ["move-window", "-s", "@3", "-t", "$1:"],
+            ["move-window", "-s", "@4", "-t", "$1:"],
+        ]);
+    }
+
+    #[test]
+    fn batch_rename_runs_for_each_marked_session() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.toggle_mark();
+        app.toggle_mark();
+        app.batch_rename("old-{}");
+        app.run_batch();
+        while app.state == AppState::BatchRunning {
+            app.step_batch();
+        }
+        let renames: Vec<Vec<String>> = mock.calls().into_iter()
+            .filter(|(_, args)| args[0] == "rename-session")
+            .map(|(_, args)| args)
+            .collect();
+        assert_eq!(renames, [
+            ["rename-session", "-t", "$1", "old-dev"],
+            ["rename-session", "-t", "$2", "old-ops:1"],
+        ]);
+        assert_eq!(app.batch.map(|batch| batch.failures()), Some(0));
+    }
+
+    #[test]
+    fn aborted_batch_leaves_the_rest_undone() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.toggle_mark();
+        app.toggle_mark();
+        app.confirm_delete();
+        app.run_batch();
+        app.step_batch();
+        app.abort_batch();
+        let kills = mock.calls().into_iter().filter(|(_, args)| args[0] == "kill-session").count();
+        assert_eq!(kills, 1);
+        assert_eq!(app.state, AppState::BatchResults);
+        assert!(app.batch.is_some_and(|batch| batch.aborted && batch.done() == 1));
+    }
+
+    #[test]
+    fn directory_entry_opens_a_session_there() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.sources = vec![Source::Sessions, Source::Directories];
+        app.entries = vec![SourceEntry { source: Source::Directories, label: "~/src/my.app".to_owned(), path: "/home/me/src/my.app".to_owned() }];
+        assert_eq!(app.list_rows().last(), Some(&ListRow::Entry(0)));
+        app.select_last();
+        assert!(app.selected_session().is_none());
+        app.open_selected_entry();
+        assert_eq!(mock.calls().last().unwrap().1, ["new-session", "-d", "-s", "my_app", "-c", "/home/me/src/my.app"]);
+        assert_eq!(app.on_exit, ExitAction::AttachSession(None, "my_app".to_owned(), AttachSize::Detach));
+    }
+
+    #[test]
+    fn sources_listed_in_the_background_keep_their_place() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.templates = vec![Template::new("web")];
+        app.set_sources(vec![Source::Sessions, Source::Directories, Source::Templates]);
+        // Templates are at hand, directories wait for zoxide
+        assert_eq!(app.entries.iter().map(|entry| entry.source).collect::<Vec<_>>(), [Source::Templates]);
+        assert_eq!(app.scheduler.due(Instant::now()), Some(Job::Entries(Source::Directories)));
+        let dir = SourceEntry { source: Source::Directories, label: "~/src".to_owned(), path: "/home/me/src".to_owned() };
+        app.entries_loaded(Source::Directories, Ok(vec![dir.clone()]));
+        assert_eq!(app.entries.iter().map(|entry| entry.source).collect::<Vec<_>>(), [Source::Directories, Source::Templates]);
+        // A failure is reported once, not at every refresh
+        app.entries_loaded(Source::Directories, Err("zoxide failed".to_owned()));
+        app.entries_loaded(Source::Directories, Err("zoxide failed".to_owned()));
+        assert_eq!(app.errors, ["zoxide failed"]);
+        assert_eq!(app.entries.len(), 1);
+        // Nor are entries of a source hidden in the meantime applied
+        app.set_sources(vec![Source::Sessions]);
+        app.entries_loaded(Source::Directories, Ok(vec![dir]));
+        assert!(app.entries.is_empty());
+        assert_eq!(app.selected_entry, None);
+    }
+
+    #[test]
+    fn template_entry_creates_a_session() {
+        let mock = Arc::new(MockClient::new());
+        let mut app = app_with(&mock);
+        app.templates = vec![Template::new("web")];
+        app.set_sources(vec![Source::Templates]);
+        assert_eq!(app.selected_entry, Some(0));
+        app.open_selected_entry();
+        assert!(mock.calls().iter().any(|(_, args)| args[..4] == ["new-session", "-d", "-s", "web"]));
+    }
+}
\ No newline at end of file
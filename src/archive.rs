@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::history;
+use crate::template::{Template, TemplateWindow};
+use crate::tmux::TmuxClient;
+
+/// A session that was archived: killed after its windows and panes were saved as a template,
+/// which restores it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Archived {
+    /// Layout of the session, named after it
+    pub template: Template,
+    /// File the template is saved in
+    pub path: PathBuf,
+    /// When it was archived, in seconds since the unix epoch
+    pub timestamp: u64,
+}
+
+/// Where archived sessions are kept: `archive` in tmm's state directory
+pub fn archive_dir() -> Option<PathBuf> {
+    Some(history::state_dir()?.join("archive"))
+}
+
+/// Format of the pane lines [`snapshot`] reads. The window name is last since it is the only field
+/// that may itself contain tabs.
+const PANE_FORMAT: &str = "#{window_index}\t#{session_path}\t#{pane_start_command}\t#{window_name}";
+
+/// The windows and panes of the local session `target` as a template named `name`: the session's
+/// directory, and each window's name and the commands its panes were started with
+pub fn snapshot(tmux: &dyn TmuxClient, target: &str, name: &str) -> Result<Template, String> {
+    let output = tmux.output(None, &["list-panes", "-s", "-t", target, "-F", PANE_FORMAT]).map_err(|err| err.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_owned());
+    }
+    let mut template = Template::new(name);
+    let mut window_index = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut fields = line.splitn(4, '\t');
+        let (Some(index), Some(dir), Some(command), Some(window)) = (fields.next(), fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        template.dir.get_or_insert_with(|| dir.to_owned());
+        // Panes of a window are listed together, after the window's first
+        if window_index != Some(index.to_owned()) {
+            window_index = Some(index.to_owned());
+            template.windows.push(TemplateWindow { name: window.to_owned(), layout: None, panes: vec![] });
+        }
+        if let Some(current) = template.windows.last_mut() {
+            current.panes.push(unquote(command));
+        }
+    }
+    if template.windows.is_empty() {
+        return Err(format!("no panes in {}", target));
+    }
+    Ok(template)
+}
+
+/// A pane's start command as typed into a shell. tmux quotes a command given as a single argument
+/// that has spaces, such as `new-window 'npm run dev'`, which a shell would take for one word.
+fn unquote(command: &str) -> String {
+    let Some(inner) = command.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) else { return command.to_owned() };
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            // Several arguments, each quoted
+            '"' => return command.to_owned(),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// Save a snapshot in `dir`, as a template file named after when it was archived and the session
+pub fn save(dir: &Path, template: &Template) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    let safe: String = template.name.chars().map(|c| if c.is_alphanumeric() || "-_.".contains(c) { c } else { '_' }).collect();
+    let path = dir.join(format!("{}-{}.toml", history::now(), safe));
+    fs::write(&path, template.to_file()).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(path)
+}
+
+/// The sessions archived in `dir`, most recent first. Files that are not archives are skipped.
+pub fn load(dir: &Path) -> Vec<Archived> {
+    let Ok(files) = fs::read_dir(dir) else { return vec![] };
+    let mut archived: Vec<Archived> = files.filter_map(|file| {
+        let path = file.ok()?.path();
+        let stem = path.file_stem()?.to_str()?.to_owned();
+        let (timestamp, name) = stem.split_once('-')?;
+        let template = config::template_file(&fs::read_to_string(&path).ok()?, name).ok()?;
+        Some(Archived { template, timestamp: timestamp.parse().ok()?, path })
+    }).collect();
+    archived.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| a.path.cmp(&b.path)));
+    archived
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmux::MockClient;
+
+    #[test]
+    fn snapshots_are_saved_and_restored() {
+        let mock = MockClient::new();
+        mock.reply("list-panes", Ok("0\t/src/web\t\teditor\n1\t/src/web\t\"npm run dev\"\tserver\t2\n1\t/src/web\t\tserver\t2\n"));
+        let template = snapshot(&mock, "$1", "web \"main\"").unwrap();
+        assert_eq!(template.dir.as_deref(), Some("/src/web"));
+        assert_eq!(template.windows.iter().map(|window| window.name.as_str()).collect::<Vec<_>>(), ["editor", "server\t2"]);
+        assert_eq!(template.windows[1].panes, ["npm run dev", ""]);
+        let dir = std::env::temp_dir().join(format!("tmm-archive-test-{}", std::process::id()));
+        let path = save(&dir, &template).unwrap();
+        let archived = load(&dir);
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].path, path);
+        assert_eq!(archived[0].template, template);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,253 @@
+use crate::app::Session;
+use crate::tmux::TmuxClient;
+
+/// The change applied to a single session as part of a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchAction {
+    /// Kill the session
+    Kill,
+    /// Rename the session to the contained name
+    Rename(String),
+    /// Move a window (id, name) of the session into another session, at its next free index
+    MoveWindow(String, String, Session),
+    /// Type a command into a window (name or index) of the session, and Enter
+    SendKeys(String, String),
+}
+
+/// One target of a batch operation and, once run, its outcome
+#[derive(Debug, Clone)]
+pub struct BatchItem {
+    /// The targeted session
+    pub session: Session,
+    /// What will be done to the session
+    pub action: BatchAction,
+    /// `None` until the batch has run, then the tmux outcome (error holds stderr)
+    pub result: Option<Result<(), String>>,
+}
+
+impl BatchItem {
+    /// Human readable description of the exact change, e.g. `kill dev` or `rename dev -> dev-old`
+    pub fn describe(&self) -> String {
+        match &self.action {
+            BatchAction::Kill => format!("kill {}", self.session.label()),
+            BatchAction::Rename(new_name) => format!("rename {} -> {}", self.session.label(), new_name),
+            BatchAction::MoveWindow(_, name, into) => format!("move {}:{} -> {}", self.session.label(), name, into.label()),
+            BatchAction::SendKeys(window, command) => format!("run {} in {}:{}", command, self.session.label(), window),
+        }
+    }
+
+    /// Run the tmux command for this item and record the outcome
+    fn run(&mut self, client: &dyn TmuxClient) {
+        let target = self.session.target();
+        if let BatchAction::SendKeys(window, command) = &self.action {
+            // Typed literally, so that words such as `Enter` in the command are not taken for keys
+            let pane = format!("{}:{}", target, window);
+            self.result = Some(self.send(client, &["send-keys", "-t", &pane, "-l", command])
+                .and_then(|()| self.send(client, &["send-keys", "-t", &pane, "Enter"])));
+            return;
+        }
+        let destination;
+        let args: Vec<&str> = match &self.action {
+            BatchAction::Kill => vec!["kill-session", "-t", target],
+            BatchAction::Rename(new_name) => vec!["rename-session", "-t", target, new_name],
+            BatchAction::MoveWindow(window, _, into) => {
+                // `session:` moves to the next free index. tmux ends the session with its last window.
+                destination = format!("{}:", into.target());
+                vec!["move-window", "-s", window, "-t", &destination]
+            }
+            BatchAction::SendKeys(..) => unreachable!("sent above"),
+        };
+        self.result = Some(self.send(client, &args));
+    }
+
+    /// Run a tmux command on the session's host, with its stderr as the error
+    fn send(&self, client: &dyn TmuxClient, args: &[&str]) -> Result<(), String> {
+        match client.output(self.session.host.as_deref(), args) {
+            Ok(proc) if proc.status.success() => Ok(()),
+            Ok(proc) => Err(String::from_utf8_lossy(&proc.stderr).trim().to_owned()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
+
+/// A batch operation over the marked sessions, shown as a summary before it runs, as it
+/// progresses, and as a list of per-item results afterwards
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    /// Every targeted session in list order
+    pub items: Vec<BatchItem>,
+    /// First visible line of the summary/results popup
+    pub scroll: usize,
+    /// Whether the results are shown even when every item succeeded
+    pub report_success: bool,
+    /// Whether the batch was aborted, leaving the items without a result undone
+    pub aborted: bool,
+}
+
+impl Batch {
+    /// Kill every given session
+    pub fn kill(sessions: &[Session]) -> Self {
+        Self {
+            items: sessions.iter().map(|session| BatchItem {
+                session: session.clone(),
+                action: BatchAction::Kill,
+                result: None,
+            }).collect(),
+            report_success: true,
+            ..Self::default()
+        }
+    }
+
+    /// Rename every given session using `pattern`, where each `{}` is replaced by the current
+    /// name. A pattern without `{}` is used as a prefix.
+    pub fn rename(sessions: &[Session], pattern: &str) -> Self {
+        Self {
+            items: sessions.iter().map(|session| {
+                let new_name = if pattern.contains("{}") {
+                    pattern.replace("{}", &session.name)
+                } else {
+                    format!("{}{}", pattern, session.name)
+                };
+                BatchItem {
+                    session: session.clone(),
+                    action: BatchAction::Rename(new_name),
+                    result: None,
+                }
+            }).collect(),
+            report_success: true,
+            ..Self::default()
+        }
+    }
+
+    /// Move the given windows (id, name) of `session` into `into`. Only failures are reported.
+    pub fn merge(session: &Session, windows: &[(String, String)], into: &Session) -> Self {
+        Self {
+            items: windows.iter().map(|(id, name)| BatchItem {
+                session: session.clone(),
+                action: BatchAction::MoveWindow(id.clone(), name.clone(), into.clone()),
+                result: None,
+            }).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Type each (window, command) into `session`, as a template asks once it has created it.
+    /// Only failures are reported.
+    pub fn send_keys(session: &Session, commands: &[(String, String)]) -> Self {
+        Self {
+            items: commands.iter().map(|(window, command)| BatchItem {
+                session: session.clone(),
+                action: BatchAction::SendKeys(window.clone(), command.clone()),
+                result: None,
+            }).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Run the next item, if any is left, and return whether more are left after it. Failures do
+    /// not stop the remaining items.
+    pub fn run_next(&mut self, client: &dyn TmuxClient) -> bool {
+        if let Some(item) = self.items.iter_mut().find(|item| item.result.is_none()) {
+            item.run(client);
+        }
+        self.done() < self.items.len()
+    }
+
+    /// Number of items that have run
+    pub fn done(&self) -> usize {
+        self.items.iter().filter(|item| item.result.is_some()).count()
+    }
+
+    /// Number of items that failed
+    pub fn failures(&self) -> usize {
+        self.items.iter().filter(|item| matches!(item.result, Some(Err(_)))).count()
+    }
+
+    /// Title of the results: how many items failed, or how far an aborted batch got
+    pub fn summary(&self) -> String {
+        if self.aborted {
+            format!("Aborted: {} of {} Done, {} Failed", self.done(), self.items.len(), self.failures())
+        } else {
+            format!("Results: {} of {} Failed", self.failures(), self.items.len())
+        }
+    }
+
+    /// The result of each item as plain text, e.g. `ok   kill dev`
+    pub fn result_lines(&self) -> Vec<String> {
+        self.items.iter().map(|item| match &item.result {
+            None if self.aborted => format!("skip {}", item.describe()),
+            None => item.describe(),
+            Some(Ok(())) => format!("ok   {}", item.describe()),
+            Some(Err(err)) => format!("fail {}: {}", item.describe(), err),
+        }).collect()
+    }
+
+    /// Scroll the popup up one line
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    /// Scroll the popup down one line
+    pub fn scroll_down(&mut self) {
+        self.scroll = (self.scroll + 1).min(self.items.len().saturating_sub(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmux::MockClient;
+
+    fn session(host: Option<&str>, name: &str, id: &str) -> Session {
+        Session {
+            host: host.map(str::to_owned), name: name.to_owned(), id: id.to_owned(), windows: 1, created: 1709301900,
+            attached: 0, activity: 1709301900, dead_panes: 0, group: None, last_attach: None, color: None,
+        }
+    }
+
+    #[test]
+    fn items_run_one_step_at_a_time_past_failures() {
+        let client = MockClient::new();
+        client.reply("kill-session -t $2", Err("can't find session: $2"));
+        let sessions = [session(None, "dev", "$1"), session(None, "ops", "$2"), session(Some("build"), "ci", "$1")];
+        let mut batch = Batch::kill(&sessions);
+        assert_eq!(batch.result_lines(), ["kill dev", "kill ops", "kill build/ci"]);
+        assert!(batch.run_next(&client));
+        assert_eq!(batch.done(), 1);
+        assert_eq!(client.calls().len(), 1);
+        assert!(batch.run_next(&client));
+        assert!(!batch.run_next(&client));
+        // Nothing is left to run
+        assert!(!batch.run_next(&client));
+        assert_eq!(client.calls(), [
+            (None, vec!["kill-session".to_owned(), "-t".to_owned(), "$1".to_owned()]),
+            (None, vec!["kill-session".to_owned(), "-t".to_owned(), "$2".to_owned()]),
+            (Some("build".to_owned()), vec!["kill-session".to_owned(), "-t".to_owned(), "$1".to_owned()]),
+        ]);
+        assert_eq!(batch.failures(), 1);
+        assert_eq!(batch.summary(), "Results: 1 of 3 Failed");
+        assert_eq!(batch.result_lines(), ["ok   kill dev", "fail kill ops: can't find session: $2", "ok   kill build/ci"]);
+    }
+
+    #[test]
+    fn aborted_batches_skip_the_rest() {
+        let client = MockClient::new();
+        let mut batch = Batch::rename(&[session(None, "dev", "$1"), session(None, "ops", "")], "old-");
+        batch.run_next(&client);
+        batch.aborted = true;
+        assert_eq!(batch.summary(), "Aborted: 1 of 2 Done, 0 Failed");
+        assert_eq!(batch.result_lines(), ["ok   rename dev -> old-dev", "skip rename ops -> old-ops"]);
+        assert_eq!(Batch::rename(&[session(None, "dev", "$1")], "{}-{}").items[0].action, BatchAction::Rename("dev-dev".to_owned()));
+    }
+
+    #[test]
+    fn commands_are_typed_literally_then_entered() {
+        let client = MockClient::new();
+        let mut batch = Batch::send_keys(&session(None, "web", "$3"), &[("server".to_owned(), "echo Enter".to_owned())]);
+        assert_eq!(batch.result_lines(), ["run echo Enter in web:server"]);
+        batch.run_next(&client);
+        let commands: Vec<Vec<String>> = client.calls().into_iter().map(|(_, args)| args).collect();
+        assert_eq!(commands, [["send-keys", "-t", "$3:server", "-l", "echo Enter"].as_slice(), &["send-keys", "-t", "$3:server", "Enter"]]);
+        assert_eq!(batch.result_lines(), ["ok   run echo Enter in web:server"]);
+    }
+}
@@ -0,0 +1,392 @@
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::app::{self, App, Session};
+use crate::config::Config;
+use crate::{config, import, lock, log, source};
+use crate::pattern::Pattern;
+use crate::history::TimeZone;
+use crate::tmux::TmuxClient;
+
+/// Exit code of a session attached or created, or a subcommand that did its job. Attaching hands
+/// the process over to tmux, so the exit code is then tmux's.
+pub const EXIT_OK: i32 = 0;
+/// Exit code of a subcommand that failed, e.g. a kill refused by tmux
+pub const EXIT_FAILED: i32 = 1;
+/// Exit code of invalid arguments, as for the usage errors of the argument parser
+pub const EXIT_USAGE: i32 = 2;
+/// Exit code of quitting the TUI without attaching anything, or declining a confirmation
+pub const EXIT_CANCELLED: i32 = 3;
+/// Exit code when there is no session to act on: the one named on the command line does not
+/// exist, or none matches `tmm kill --match`
+pub const EXIT_NO_SESSIONS: i32 = 4;
+/// Exit code when tmux is not installed
+pub const EXIT_NO_TMUX: i32 = 5;
+/// Exit code of a configuration file that cannot be parsed, outside the TUI (which reports it and
+/// goes on with the defaults)
+pub const EXIT_CONFIG: i32 = 6;
+
+/// Options of `tmm kill`
+#[derive(Debug, Clone)]
+pub struct KillOptions {
+    /// Sessions whose name matches are killed
+    pub pattern: Pattern,
+    /// Leave the sessions some client is attached to alone
+    pub detached_only: bool,
+    /// Only print the sessions that would be killed
+    pub dry_run: bool,
+    /// Kill without asking for confirmation
+    pub yes: bool,
+}
+
+/// Kill the local sessions matching a pattern, after printing them and asking for confirmation.
+/// Returns the exit code: [`EXIT_OK`] when every matching session was killed (or would be, for a
+/// dry run), [`EXIT_NO_SESSIONS`] when none matched, [`EXIT_CANCELLED`] when confirmation was
+/// refused and [`EXIT_FAILED`] when listing or a kill failed.
+pub fn kill(client: &dyn TmuxClient, options: &KillOptions) -> i32 {
+    let sessions = match App::list_sessions(client, None) {
+        Ok(sessions) => sessions,
+        Err(err) => {
+            eprintln!("tmm: could not list sessions: {}", err);
+            return EXIT_FAILED;
+        }
+    };
+    let matching: Vec<_> = sessions.iter()
+        .filter(|session| options.pattern.matches(&session.name))
+        .filter(|session| !options.detached_only || session.attached == 0)
+        .collect();
+    if matching.is_empty() {
+        eprintln!("tmm: no sessions match");
+        return EXIT_NO_SESSIONS;
+    }
+    for session in &matching {
+        println!("{}{}", session.name, if session.attached > 0 { " (attached)" } else { "" });
+    }
+    if options.dry_run {
+        return EXIT_OK;
+    }
+    if !options.yes && !confirm(&format!("Kill {} session{}? [y/N] ", matching.len(), if matching.len() == 1 { "" } else { "s" })) {
+        return EXIT_CANCELLED;
+    }
+    let mut code = EXIT_OK;
+    for session in matching {
+        match client.output(None, &["kill-session", "-t", session.target()]) {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => {
+                eprintln!("tmm: could not kill {}: {}", session.name, String::from_utf8_lossy(&output.stderr).trim());
+                code = EXIT_FAILED;
+            }
+            Err(err) => {
+                eprintln!("tmm: could not kill {}: {}", session.name, err);
+                code = EXIT_FAILED;
+            }
+        }
+    }
+    code
+}
+
+/// How `tmm ls` prints the sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ListFormat {
+    /// Like `tmux ls`, e.g. `dev: 2 windows (created 2024-03-01 14:05) (attached)`
+    #[default]
+    Text,
+    /// An array of objects with the name, id, windows, attached clients, creation and last
+    /// activity times (in seconds since the epoch) and group (or null) of each session
+    Json,
+    /// A line per session of the same fields as JSON, without the id, separated by tabs, with
+    /// an empty group for none
+    Tsv,
+}
+
+/// Print the local sessions in `format`. Returns the exit code: [`EXIT_OK`] once listed, even if
+/// there are none, and [`EXIT_FAILED`] when they could not be listed.
+pub fn ls(client: &dyn TmuxClient, format: ListFormat) -> i32 {
+    match App::list_sessions(client, None) {
+        Ok(sessions) => {
+            print!("{}", format_sessions(&sessions, format));
+            EXIT_OK
+        }
+        // Without a server there are no sessions, which is no failure
+        Err(err) if err.starts_with("no server running") || err.starts_with("error connecting") => {
+            print!("{}", format_sessions(&[], format));
+            EXIT_OK
+        }
+        Err(err) => {
+            eprintln!("tmm: could not list sessions: {}", err);
+            EXIT_FAILED
+        }
+    }
+}
+
+/// The sessions as `tmm ls` prints them in `format`
+fn format_sessions(sessions: &[Session], format: ListFormat) -> String {
+    match format {
+        ListFormat::Text => sessions.iter().map(|session| format!("{}: {}\n", session.name, session.desc(TimeZone::Local))).collect(),
+        ListFormat::Tsv => sessions.iter().map(|session| {
+            format!("{}\t{}\t{}\t{}\t{}\t{}\n", session.name, session.windows, session.attached, session.created,
+                session.activity, session.group.as_deref().unwrap_or(""))
+        }).collect(),
+        ListFormat::Json => {
+            let objects: Vec<serde_json::Value> = sessions.iter().map(|session| serde_json::json!({
+                "name": session.name,
+                "id": session.id,
+                "windows": session.windows,
+                "attached": session.attached,
+                "created": session.created,
+                "activity": session.activity,
+                "group": session.group,
+            })).collect();
+            format!("{}\n", serde_json::to_string(&objects).expect("JSON values always serialize"))
+        }
+    }
+}
+
+/// Create a detached local session, starting in `directory` (`~` expanded) or else the current
+/// one. Returns the exit code: [`EXIT_OK`] once created, [`EXIT_USAGE`] for an empty name and
+/// [`EXIT_FAILED`] when the name is taken or tmux refused.
+pub fn new_session(client: &dyn TmuxClient, name: &str, directory: Option<&str>) -> i32 {
+    // Named the way tmux would name it, so that the name printed is the one to use afterwards
+    let Some(name) = app::tmux_session_name(name) else {
+        eprintln!("tmm: a session name is required");
+        return EXIT_USAGE;
+    };
+    if session_exists(client, &name) {
+        eprintln!("tmm: a session named {} already exists", name);
+        return EXIT_FAILED;
+    }
+    let directory = directory.map(source::expand_home);
+    let mut args = vec!["new-session", "-d", "-s", &name];
+    if let Some(directory) = &directory {
+        args.extend(["-c", directory]);
+    }
+    if !run(client, &args, &format!("could not create {}", name)) {
+        return EXIT_FAILED;
+    }
+    println!("{}", name);
+    EXIT_OK
+}
+
+/// Kill the local session named `name`, without asking. Returns the exit code: [`EXIT_OK`] once
+/// killed, [`EXIT_NO_SESSIONS`] when there is no such session and [`EXIT_FAILED`] when tmux refused.
+pub fn kill_session(client: &dyn TmuxClient, name: &str) -> i32 {
+    if !session_exists(client, name) {
+        eprintln!("tmm: no session named {}", name);
+        return EXIT_NO_SESSIONS;
+    }
+    if !run(client, &["kill-session", "-t", &format!("={}", name)], &format!("could not kill {}", name)) {
+        return EXIT_FAILED;
+    }
+    EXIT_OK
+}
+
+/// Rename the local session named `old` to `new`. Returns the exit code: [`EXIT_OK`] once renamed,
+/// [`EXIT_USAGE`] for an empty new name, [`EXIT_NO_SESSIONS`] when there is no session named `old`
+/// and [`EXIT_FAILED`] when the new name is taken or tmux refused.
+pub fn rename_session(client: &dyn TmuxClient, old: &str, new: &str) -> i32 {
+    let Some(new) = app::tmux_session_name(new) else {
+        eprintln!("tmm: a session name is required");
+        return EXIT_USAGE;
+    };
+    if !session_exists(client, old) {
+        eprintln!("tmm: no session named {}", old);
+        return EXIT_NO_SESSIONS;
+    }
+    if new != old && session_exists(client, &new) {
+        eprintln!("tmm: a session named {} already exists", new);
+        return EXIT_FAILED;
+    }
+    if !run(client, &["rename-session", "-t", &format!("={}", old), &new], &format!("could not rename {}", old)) {
+        return EXIT_FAILED;
+    }
+    println!("{}", new);
+    EXIT_OK
+}
+
+/// Whether there is a local session named exactly `name`
+fn session_exists(client: &dyn TmuxClient, name: &str) -> bool {
+    // `=` makes tmux match the name exactly instead of as a prefix
+    client.output(None, &["has-session", "-t", &format!("={}", name)]).is_ok_and(|output| output.status.success())
+}
+
+/// Run a local tmux command, printing what `failure` says and tmux's error if it fails. Returns
+/// whether it succeeded.
+fn run(client: &dyn TmuxClient, args: &[&str], failure: &str) -> bool {
+    match client.output(None, args) {
+        Ok(output) if output.status.success() => true,
+        Ok(output) => {
+            eprintln!("tmm: {}: {}", failure, String::from_utf8_lossy(&output.stderr).trim());
+            false
+        }
+        Err(err) => {
+            eprintln!("tmm: {}: {}", failure, err);
+            false
+        }
+    }
+}
+
+/// Install a template file from a URL or a path into the templates directory. Returns the exit
+/// code: [`EXIT_OK`] once installed, [`EXIT_FAILED`] when it could not be fetched, is not a valid
+/// template or is installed already (without `force`).
+pub fn import_template(source: &str, force: bool) -> i32 {
+    let Some(dir) = config::templates_dir() else {
+        eprintln!("tmm: no templates directory: neither $XDG_CONFIG_HOME nor $HOME is set");
+        return EXIT_FAILED;
+    };
+    match import::install(source, &dir, force) {
+        Ok((template, path)) => {
+            println!("Installed template {} ({}) as {}", template.name, template.summary(), path.display());
+            EXIT_OK
+        }
+        Err(err) => {
+            eprintln!("tmm: {}", err);
+            EXIT_FAILED
+        }
+    }
+}
+
+/// Print what tmm talks to, to debug which tmux server it is: the tmux version, the server tmm
+/// runs in, the configuration file, and the pid, socket and start time of the local server and
+/// those of the configured hosts. Returns the exit code: [`EXIT_OK`] when everything checks out,
+/// and [`EXIT_FAILED`] when the configuration file is broken or a server could not be described.
+pub fn doctor(client: &dyn TmuxClient, config_path: Option<&Path>) -> i32 {
+    let mut code = EXIT_OK;
+    let version = Command::new("tmux").arg("-V").output()
+        .map_or_else(|err| format!("cannot run tmux: {}", err), |output| String::from_utf8_lossy(&output.stdout).trim().to_owned());
+    println!("tmux     {}", version);
+    match lock::tmux_socket() {
+        Some(socket) => println!("$TMUX    {} (tmm runs in a session of this server)", socket),
+        None => println!("$TMUX    not set (tmm runs outside tmux)"),
+    }
+    let config = match config_path {
+        Some(path) if !path.exists() => {
+            println!("config   {} (missing, the defaults are used)", path.display());
+            Config::default()
+        }
+        Some(path) => match Config::load(path) {
+            (config, None) => {
+                println!("config   {}", path.display());
+                config
+            }
+            (config, Some(err)) => {
+                println!("config   {} ({})", path.display(), err);
+                code = EXIT_FAILED;
+                config
+            }
+        },
+        None => {
+            println!("config   none: neither $XDG_CONFIG_HOME nor $HOME is set");
+            Config::default()
+        }
+    };
+    match log::path() {
+        Some(path) => println!("log      {} (written with --debug or {}=1)", path.display(), log::ENV_VAR),
+        None => println!("log      none: neither $XDG_STATE_HOME nor $HOME is set"),
+    }
+    let timeout = Duration::from_secs_f64(config.host_timeout);
+    for host in [None].into_iter().chain(config.hosts.iter().map(|host| Some(host.as_str()))) {
+        println!();
+        match App::server_info(client, host, timeout) {
+            Ok(info) => {
+                for (label, value) in info.describe(TimeZone::Local) {
+                    println!("{:8} {}", label, value);
+                }
+            }
+            Err(err) => {
+                println!("Host     {}", host.unwrap_or("local"));
+                println!("Error    {}", err);
+                code = EXIT_FAILED;
+            }
+        }
+    }
+    code
+}
+
+/// Ask a yes or no question on the terminal, where anything but `y` or `yes` is no
+fn confirm(question: &str) -> bool {
+    eprint!("{}", question);
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmux::MockClient;
+
+    #[test]
+    fn sessions_are_listed_in_each_format() {
+        let session = |name: &str, id: &str, group: Option<&str>| Session {
+            host: None, name: name.to_owned(), id: id.to_owned(), windows: 2, created: 1709301900, attached: 1,
+            activity: 1709302000, dead_panes: 0, group: group.map(str::to_owned), last_attach: None, color: None,
+        };
+        let sessions = [session("dev", "$1", None), session("say \"hi\"", "$2", Some("work"))];
+        assert_eq!(format_sessions(&sessions, ListFormat::Json),
+            "[{\"name\":\"dev\",\"id\":\"$1\",\"windows\":2,\"attached\":1,\"created\":1709301900,\"activity\":1709302000,\"group\":null},\
+            {\"name\":\"say \\\"hi\\\"\",\"id\":\"$2\",\"windows\":2,\"attached\":1,\"created\":1709301900,\"activity\":1709302000,\"group\":\"work\"}]\n");
+        assert_eq!(format_sessions(&sessions, ListFormat::Tsv),
+            "dev\t2\t1\t1709301900\t1709302000\t\nsay \"hi\"\t2\t1\t1709301900\t1709302000\twork\n");
+        assert_eq!(format_sessions(&[], ListFormat::Json), "[]\n");
+    }
+
+    /// The tmux commands `client` was asked to run, but for `has-session`
+    fn commands(client: &MockClient) -> Vec<Vec<String>> {
+        client.calls().into_iter().map(|(_, args)| args).filter(|args| args[0] != "has-session").collect()
+    }
+
+    #[test]
+    fn new_sessions_get_a_free_name() {
+        let client = MockClient::new();
+        // Every session exists unless tmux says otherwise
+        assert_eq!(new_session(&client, "dev", None), EXIT_FAILED);
+        assert_eq!(new_session(&client, "", None), EXIT_USAGE);
+        assert!(commands(&client).is_empty());
+        client.reply("has-session", Err("can't find session: web_1"));
+        assert_eq!(new_session(&client, "web.1", Some("/src/web")), EXIT_OK);
+        assert_eq!(commands(&client), [["new-session", "-d", "-s", "web_1", "-c", "/src/web"]]);
+        client.reply("new-session", Err("bad directory"));
+        assert_eq!(new_session(&client, "web.1", Some("/src/web")), EXIT_FAILED);
+    }
+
+    #[test]
+    fn sessions_are_killed_and_renamed_by_name_or_alias() {
+        let client = MockClient::new();
+        let tables = config::parse("[aliases]\nw = \"work-main\"\n").unwrap();
+        let config = Config::from_tables(&tables).unwrap();
+        assert_eq!(kill_session(&client, config.resolve_alias("w")), EXIT_OK);
+        assert_eq!(rename_session(&client, config.resolve_alias("dev"), ""), EXIT_USAGE);
+        // The new name is taken
+        assert_eq!(rename_session(&client, "dev", "ops"), EXIT_FAILED);
+        client.reply("has-session -t =ops", Err("can't find session: ops"));
+        assert_eq!(rename_session(&client, "dev", "ops"), EXIT_OK);
+        assert_eq!(commands(&client), [
+            vec!["kill-session", "-t", "=work-main"],
+            vec!["rename-session", "-t", "=dev", "ops"],
+        ]);
+        assert_eq!(rename_session(&client, "ops", "dev"), EXIT_NO_SESSIONS);
+        client.reply("has-session", Err("can't find session: gone"));
+        assert_eq!(kill_session(&client, "gone"), EXIT_NO_SESSIONS);
+    }
+
+    #[test]
+    fn sessions_matching_a_pattern_are_killed() {
+        let client = MockClient::new();
+        client.reply("list-sessions", Ok("S\t$1\t1\t1709301900\t0\t1709301900\t\tbuild-1\n\
+            S\t$2\t1\t1709301900\t1\t1709301900\t\tbuild-2\n\
+            S\t$3\t1\t1709301900\t0\t1709301900\t\tdev\n"));
+        let options = |pattern, detached_only, dry_run| KillOptions { pattern: Pattern::glob(pattern), detached_only, dry_run, yes: true };
+        assert_eq!(kill(&client, &options("web-*", false, false)), EXIT_NO_SESSIONS);
+        assert_eq!(kill(&client, &options("build-*", false, true)), EXIT_OK);
+        assert_eq!(kill(&client, &options("build-*", true, false)), EXIT_OK);
+        client.reply("kill-session", Err("server exited"));
+        assert_eq!(kill(&client, &options("dev", false, false)), EXIT_FAILED);
+        let kills: Vec<Vec<String>> = commands(&client).into_iter().filter(|args| args[0] == "kill-session").collect();
+        assert_eq!(kills, [["kill-session", "-t", "$1"], ["kill-session", "-t", "$3"]]);
+    }
+}
@@ -0,0 +1,61 @@
+use std::io::{self, Write};
+
+use crate::tmux::TmuxClient;
+
+/// Copy `text` to the clipboard. Inside tmux it goes into a tmux buffer, which tmux also hands to
+/// the terminal's clipboard as its `set-clipboard` option allows. Elsewhere it goes straight to the
+/// terminal as an OSC 52 escape sequence, which most terminals take, over ssh too.
+pub fn copy(tmux: &dyn TmuxClient, nested: bool, text: &str) -> Result<(), String> {
+    if nested {
+        return match tmux.output(None, &["set-buffer", "-w", "--", text]) {
+            Ok(output) if output.status.success() => Ok(()),
+            Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_owned()),
+            Err(err) => Err(err.to_string()),
+        };
+    }
+    let mut stdout = io::stdout();
+    stdout.write_all(osc52(text).as_bytes())
+        .and_then(|()| stdout.flush())
+        .map_err(|err| err.to_string())
+}
+
+/// The OSC 52 escape sequence setting the clipboard to `text`
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+/// Standard base64 with padding, as OSC 52 takes it
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (idx, byte)| group | (*byte as u32) << (16 - 8 * idx));
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * idx) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmux::MockClient;
+
+    #[test]
+    fn copies_through_tmux_or_the_terminal() {
+        assert_eq!(base64(b"dev: 2 windows"), "ZGV2OiAyIHdpbmRvd3M=");
+        assert_eq!(base64(b"ops"), "b3Bz");
+        assert_eq!(base64(b"ok"), "b2s=");
+        assert_eq!(osc52("dev"), "\x1b]52;c;ZGV2\x07");
+        let mock = MockClient::new();
+        copy(&mock, true, "dev\nops").unwrap();
+        assert_eq!(mock.calls()[0].1, ["set-buffer", "-w", "--", "dev\nops"]);
+        mock.reply("set-buffer", Err("no clients"));
+        assert_eq!(copy(&mock, true, "dev"), Err("no clients".to_owned()));
+    }
+}
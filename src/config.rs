@@ -0,0 +1,785 @@
+use std::{env, fmt, fs, io, path::{Path, PathBuf}, time::Duration};
+use indexmap::IndexMap;
+use ratatui::style::Color;
+
+use crate::app::{AttachSize, AttachedFilter, CurrentSession, GroupBy, SortOrder, StartupView};
+use crate::keymap::{Action, KeyBinding, Keymap};
+use crate::source::Source;
+use crate::template::{Template, TemplateWindow};
+
+/// A value in the configuration file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(Value::to_string).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+/// Parsed configuration file: the keys of each table, by table name. Keys before the first table
+/// header are in the table named `""`.
+pub type Tables = IndexMap<String, IndexMap<String, Value>>;
+
+/// Built-in color themes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// For dark terminal backgrounds, using the terminal's own palette
+    #[default]
+    Dark,
+    /// For light terminal backgrounds, using the terminal's own palette
+    Light,
+    /// The Solarized palette, for either background
+    Solarized,
+}
+
+impl Theme {
+    /// Every theme, in the order they are cycled through
+    pub const ALL: [Theme; 3] = [Theme::Dark, Theme::Light, Theme::Solarized];
+
+    /// Name used in the configuration file
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+            Theme::Solarized => "solarized",
+        }
+    }
+
+    /// The theme after this one
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|theme| *theme == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+}
+
+/// Colors used by the interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    /// Selected row
+    pub highlight: Color,
+    /// Marker of sessions marked for a batch operation
+    pub marker: Color,
+    /// Window count badge
+    pub badge: Color,
+    /// Window count badge of sessions with dead panes
+    pub dead: Color,
+    /// Search matches
+    pub search_match: Color,
+    /// Badge of directory entries
+    pub directory: Color,
+    /// Background of popups
+    pub popup: Color,
+    /// Prompt in front of text inputs
+    pub prompt: Color,
+    /// Keys of the hotkey bar, shown reversed
+    pub hotkey: Color,
+    /// Secondary text, e.g. section counts or pane paths
+    pub dim: Color,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self::theme(Theme::Dark)
+    }
+}
+
+impl Colors {
+    /// The colors of a built-in theme
+    pub fn theme(theme: Theme) -> Self {
+        match theme {
+            Theme::Dark => Self {
+                highlight: Color::Cyan,
+                marker: Color::Yellow,
+                badge: Color::DarkGray,
+                dead: Color::Red,
+                search_match: Color::Magenta,
+                directory: Color::Blue,
+                popup: Color::DarkGray,
+                prompt: Color::Cyan,
+                hotkey: Color::DarkGray,
+                dim: Color::DarkGray,
+            },
+            Theme::Light => Self {
+                highlight: Color::Blue,
+                marker: Color::Magenta,
+                badge: Color::DarkGray,
+                dead: Color::Red,
+                search_match: Color::Red,
+                directory: Color::Blue,
+                popup: Color::Gray,
+                prompt: Color::Blue,
+                hotkey: Color::Gray,
+                dim: Color::DarkGray,
+            },
+            Theme::Solarized => Self {
+                highlight: Color::Rgb(0x2a, 0xa1, 0x98),
+                marker: Color::Rgb(0xb5, 0x89, 0x00),
+                badge: Color::Rgb(0x58, 0x6e, 0x75),
+                dead: Color::Rgb(0xdc, 0x32, 0x2f),
+                search_match: Color::Rgb(0xd3, 0x36, 0x82),
+                directory: Color::Rgb(0x26, 0x8b, 0xd2),
+                popup: Color::Rgb(0x07, 0x36, 0x42),
+                prompt: Color::Rgb(0x26, 0x8b, 0xd2),
+                hotkey: Color::Rgb(0x58, 0x6e, 0x75),
+                dim: Color::Rgb(0x58, 0x6e, 0x75),
+            },
+        }
+    }
+
+    /// Set one color by its name in the `[colors]` table
+    pub fn set(&mut self, name: &str, color: Color) -> Result<(), String> {
+        let field = match name {
+            "highlight" => &mut self.highlight,
+            "marker" => &mut self.marker,
+            "badge" => &mut self.badge,
+            "dead" => &mut self.dead,
+            "match" => &mut self.search_match,
+            "directory" => &mut self.directory,
+            "popup" => &mut self.popup,
+            "prompt" => &mut self.prompt,
+            "hotkey" => &mut self.hotkey,
+            "dim" => &mut self.dim,
+            _ => return Err("unknown color".to_owned()),
+        };
+        *field = color;
+        Ok(())
+    }
+
+    /// The colors of a theme with the user's overrides applied on top
+    pub fn themed(theme: Theme, overrides: &[(String, Color)]) -> Self {
+        let mut colors = Self::theme(theme);
+        for (name, color) in overrides {
+            // Names were checked when the configuration file was read
+            let _ = colors.set(name, *color);
+        }
+        colors
+    }
+}
+
+/// Settings from `~/.config/tmm/config.toml`. Every setting is optional; see [`Config::default`]
+/// for the values used when the file or a setting is missing.
+///
+/// ```toml
+/// refresh = 2.0           # seconds between background refreshes, 0 disables
+/// host_timeout = 5.0      # seconds to wait for a remote host
+/// hosts = ["build-box"]   # remote hosts listed in addition to --host
+/// sort = "name"           # none, name, created or activity
+/// view = "sessions"       # view to start in: sessions, projects, templates or history
+/// filter = "all"          # sessions listed at startup: all, detached or attached
+/// group_by = "host"       # sections of the list: none, host or session_group; by host with --host
+/// sources = ["sessions"]  # what the list shows: sessions, directories (from zoxide), projects, templates and/or snapshots
+/// projects = ["~/src"]    # roots searched for git repositories by the projects source
+/// theme = "dark"          # dark, light or solarized, which `T` cycles through
+/// attach_command = "git pull"  # what `!` runs in a session it attaches when nothing is typed
+/// new_command = "nvim"    # what new sessions start with instead of the server's default
+/// search_attach = false   # Enter in the search attaches the match instead of selecting it
+/// wrap_around = false     # moving past the last session selects the first, and back
+/// auto_attach = false     # attach the only session straight away, without the TUI
+/// current_session = "quit"  # attaching the session tmm runs in: notice, quit or windows
+///
+/// [intervals]             # seconds between background refreshes of each kind, 0 disables
+/// sessions = 2.0          # the session list with each session's activity, as `refresh`
+/// hosts = 2.0             # the session lists of remote hosts, as `sessions` unless set
+/// preview = 1.0           # the capture of the selected session in the preview pane
+/// windows = 0.0           # the windows and panes of every session, which are also listed
+///                         # whenever the selection moves to another session
+///
+/// [confirm]
+/// delete = true           # ask before killing a session
+/// batch = true            # show the summary before running a batch operation
+///
+/// [layout]
+/// preview = false         # start with the preview pane shown
+/// preview_width = 60      # percentage of the width taken by the preview pane
+/// hotkey_bar = true       # show the hotkey bar at the bottom
+/// tmux_popup = false      # inside tmux, show reports such as batch results in a tmux popup
+/// inline_rename = false   # rename in the session's row rather than in a popup
+///
+/// [colors]                # override colors of the theme: names such as "cyan" or
+/// highlight = "cyan"      # "light-blue", "#rrggbb" or 0-255
+/// marker = "yellow"
+/// badge = "dark-gray"
+/// dead = "red"
+/// match = "magenta"
+/// directory = "blue"
+/// popup = "dark-gray"     # popup background
+/// prompt = "cyan"
+/// hotkey = "dark-gray"    # keys of the hotkey bar
+/// dim = "dark-gray"       # secondary text
+///
+/// [keys]                  # a key or a list of keys per action, e.g. "a", "Enter", "C-n" or "M-x"
+/// detach = "Z"            # pressed twice, quit and detach the tmux client
+/// down = ["j", "Down", "C-n"]
+/// top = ["gg", "Home"]    # keys pressed one after the other, also e.g. "C-x s"
+/// leader = "Space"        # starts the sequences of less common actions, `\` by default
+/// sort = "<leader>s"
+///
+/// [aliases]               # short names for sessions, for `tmm <name>` and the search
+/// w = "work-main"
+///
+/// [templates.web]         # a session layout the new session form offers
+/// description = "Site with its dev server"
+/// dir = "~/src/web"       # where every pane starts
+/// layout = "tiled"        # of windows with several panes
+/// windows = [["editor", "nvim"], ["server", "npm run dev", "npm test"]]
+/// after = [["editor", "direnv allow"]]  # typed into a window once the session is built
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Seconds between background refreshes of the session list (0 disables)
+    pub refresh: f64,
+    /// Seconds between background refreshes of the session lists of remote hosts (0 disables), as
+    /// `refresh` if `None`
+    pub host_refresh: Option<f64>,
+    /// Seconds between captures of the preview pane (0 captures it only when the selection moves)
+    pub preview_refresh: f64,
+    /// Seconds between background listings of the windows of every session (0 lists them only when
+    /// the selection moves)
+    pub windows_refresh: f64,
+    /// Seconds to wait for a remote host to list its sessions
+    pub host_timeout: f64,
+    /// Remote hosts to list sessions of
+    pub hosts: Vec<String>,
+    /// Order of the sessions of each host
+    pub sort: SortOrder,
+    /// Sources shown in the list
+    pub sources: Vec<Source>,
+    /// View shown at startup
+    pub view: StartupView,
+    /// Which sessions are listed at startup
+    pub filter: AttachedFilter,
+    /// How the list is divided into sections, if set; otherwise by host when there are remote hosts
+    pub group_by: Option<GroupBy>,
+    /// Directories the projects source looks for git repositories in
+    pub projects: Vec<String>,
+    /// Ask for confirmation before killing a single session
+    pub confirm_delete: bool,
+    /// Ask for confirmation before running a batch operation
+    pub confirm_batch: bool,
+    /// Show the preview pane on startup
+    pub preview: bool,
+    /// Percentage of the width taken by the preview pane
+    pub preview_width: u16,
+    /// Show the hotkey bar
+    pub hotkey_bar: bool,
+    /// Inside tmux, show reports in a `display-popup` rather than an overlay
+    pub tmux_popup: bool,
+    /// Edit names being renamed in the session's row instead of a popup
+    pub inline_rename: bool,
+    /// How attached sessions are sized
+    pub attach_size: AttachSize,
+    /// Command the attach and run prompt runs when nothing is typed
+    pub attach_command: Option<String>,
+    /// Command new sessions start with instead of the server's default
+    pub new_command: Option<String>,
+    /// Enter in the search attaches the highlighted match, and Alt-Enter only selects it
+    pub search_attach: bool,
+    /// Moving the selection past either end of the list wraps around to the other
+    pub wrap_around: bool,
+    /// Starting tmm with a single session attaches it instead of showing the TUI
+    pub auto_attach: bool,
+    /// What attaching the session tmm is nested in does
+    pub current_session: CurrentSession,
+    /// Color theme
+    pub theme: Theme,
+    /// Colors changed from the theme's, by name
+    pub color_overrides: Vec<(String, Color)>,
+    /// Keys of the sessions view
+    pub keys: Keymap,
+    /// Session names by alias
+    pub aliases: IndexMap<String, String>,
+    /// Session templates of the new session form, in the order of the file
+    pub templates: Vec<Template>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            refresh: 2.0,
+            host_refresh: None,
+            preview_refresh: 1.0,
+            windows_refresh: 0.0,
+            host_timeout: 5.0,
+            hosts: vec![],
+            sort: SortOrder::None,
+            sources: vec![Source::Sessions],
+            view: StartupView::Sessions,
+            filter: AttachedFilter::All,
+            group_by: None,
+            projects: vec![],
+            confirm_delete: true,
+            confirm_batch: true,
+            preview: false,
+            preview_width: 60,
+            hotkey_bar: true,
+            tmux_popup: false,
+            inline_rename: false,
+            attach_size: AttachSize::Detach,
+            attach_command: None,
+            new_command: None,
+            search_attach: false,
+            wrap_around: false,
+            auto_attach: false,
+            current_session: CurrentSession::Quit,
+            theme: Theme::Dark,
+            color_overrides: vec![],
+            keys: Keymap::default(),
+            aliases: IndexMap::new(),
+            templates: vec![],
+        }
+    }
+}
+
+/// Default location of the configuration file: `$XDG_CONFIG_HOME/tmm/config.toml` or
+/// `~/.config/tmm/config.toml`
+pub fn config_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.toml"))
+}
+
+/// Where `tmm template import` installs templates: `templates` next to the default
+/// configuration file
+pub fn templates_dir() -> Option<PathBuf> {
+    Some(config_dir()?.join("templates"))
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("tmm"))
+}
+
+impl Config {
+    /// Load the configuration file. A missing file gives the defaults; an unreadable or invalid
+    /// one gives the defaults and the error.
+    pub fn load(path: &Path) -> (Self, Option<String>) {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return (Self::default(), None),
+            Err(err) => return (Self::default(), Some(format!("{}: {}", path.display(), err))),
+        };
+        match parse(&text).and_then(|tables| Self::from_tables(&tables)) {
+            Ok(config) => (config, None),
+            Err(err) => (Self::default(), Some(format!("{}: {}", path.display(), err))),
+        }
+    }
+
+    /// Add templates from outside the file, such as those [`crate::import::discover`] finds, unless
+    /// one of the same name comes first
+    pub fn add_templates(&mut self, templates: Vec<Template>) {
+        for template in templates {
+            if !self.templates.iter().any(|other| other.name == template.name) {
+                self.templates.push(template);
+            }
+        }
+    }
+
+    /// Build a configuration from parsed tables, starting from the defaults
+    pub fn from_tables(tables: &Tables) -> Result<Self, String> {
+        let mut config = Self::default();
+        for (table, entries) in tables {
+            // The leader comes first, for the keys bound after it wherever it is in the table
+            let mut entries: Vec<_> = entries.iter().collect();
+            if table == "keys" {
+                entries.sort_by_key(|(key, _)| *key != "leader");
+            }
+            for (key, value) in entries {
+                config.set(table, key, value).map_err(|err| {
+                    let name = if table.is_empty() { key.to_owned() } else { format!("{}.{}", table, key) };
+                    format!("{}: {}", name, err)
+                })?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// The session name an alias stands for, or the name itself when it is not an alias
+    pub fn resolve_alias<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map_or(name, String::as_str)
+    }
+
+    fn set(&mut self, table: &str, key: &str, value: &Value) -> Result<(), String> {
+        match (table, key) {
+            ("", "refresh") => self.refresh = number(value)?,
+            ("", "host_timeout") => self.host_timeout = number(value)?,
+            ("", "hosts") => self.hosts = strings(value)?,
+            ("", "projects") => self.projects = strings(value)?,
+            ("", "sort") => {
+                let name = string(value)?;
+                self.sort = SortOrder::from_name(name).ok_or_else(|| format!("unknown sort order {:?}", name))?;
+            }
+            ("", "attach_size") => {
+                let name = string(value)?;
+                self.attach_size = AttachSize::ALL.into_iter().find(|size| size.name() == name)
+                    .ok_or_else(|| format!("unknown attach size {:?}", name))?;
+            }
+            ("", "attach_command") => self.attach_command = Some(string(value)?.to_owned()).filter(|command| !command.is_empty()),
+            ("", "new_command") => self.new_command = Some(string(value)?.to_owned()).filter(|command| !command.is_empty()),
+            ("", "search_attach") => self.search_attach = boolean(value)?,
+            ("", "wrap_around") => self.wrap_around = boolean(value)?,
+            ("", "auto_attach") => self.auto_attach = boolean(value)?,
+            ("", "current_session") => {
+                let name = string(value)?;
+                self.current_session = CurrentSession::ALL.into_iter().find(|current| current.name() == name)
+                    .ok_or_else(|| format!("unknown current session behavior {:?}", name))?;
+            }
+            ("", "view") => {
+                let name = string(value)?;
+                self.view = StartupView::ALL.into_iter().find(|view| view.name() == name)
+                    .ok_or_else(|| format!("unknown view {:?}", name))?;
+            }
+            ("", "filter") => {
+                let name = string(value)?;
+                self.filter = AttachedFilter::ALL.into_iter().find(|filter| filter.name() == name)
+                    .ok_or_else(|| format!("unknown filter {:?}", name))?;
+            }
+            ("", "group_by") => {
+                let name = string(value)?;
+                self.group_by = Some(GroupBy::ALL.into_iter().find(|group_by| group_by.name() == name)
+                    .ok_or_else(|| format!("unknown grouping {:?}", name))?);
+            }
+            ("", "sources") => {
+                let sources = strings(value)?.iter().map(|name| {
+                    Source::from_name(name).ok_or_else(|| format!("unknown source {:?}", name))
+                }).collect::<Result<Vec<_>, _>>()?;
+                if sources.is_empty() {
+                    return Err("expected at least one source".to_owned());
+                }
+                self.sources = sources;
+            }
+            ("intervals", "sessions") => self.refresh = number(value)?,
+            ("intervals", "hosts") => self.host_refresh = Some(number(value)?),
+            ("intervals", "preview") => self.preview_refresh = number(value)?,
+            ("intervals", "windows") => self.windows_refresh = number(value)?,
+            ("confirm", "delete") => self.confirm_delete = boolean(value)?,
+            ("confirm", "batch") => self.confirm_batch = boolean(value)?,
+            ("layout", "preview") => self.preview = boolean(value)?,
+            ("layout", "preview_width") => self.preview_width = match value {
+                Value::Integer(width @ 10..=90) => *width as u16,
+                _ => return Err(format!("expected a percentage from 10 to 90, got {}", value)),
+            },
+            ("layout", "hotkey_bar") => self.hotkey_bar = boolean(value)?,
+            ("layout", "tmux_popup") => self.tmux_popup = boolean(value)?,
+            ("layout", "inline_rename") => self.inline_rename = boolean(value)?,
+            ("", "theme") => {
+                let name = string(value)?;
+                self.theme = Theme::ALL.into_iter().find(|theme| theme.name() == name)
+                    .ok_or_else(|| format!("unknown theme {:?}", name))?;
+            }
+            ("colors", name) => {
+                let color = color(value)?;
+                Colors::default().set(name, color)?;
+                self.color_overrides.push((name.to_owned(), color));
+            }
+            ("keys", "leader") => self.keys.set_leader(KeyBinding::parse(string(value)?)?),
+            ("keys", name) => {
+                let action = Action::from_name(name).ok_or_else(|| format!("unknown action {:?}", name))?;
+                // A single key or a list of keys, where an empty list unbinds the action
+                let keys = match value {
+                    Value::String(key) => vec![key.to_owned()],
+                    _ => strings(value)?,
+                };
+                let keys = keys.iter().map(|key| self.keys.parse(key)).collect::<Result<Vec<_>, _>>()?;
+                self.keys.bind(action, keys)?;
+            }
+            ("aliases", alias) => {
+                self.aliases.insert(alias.to_owned(), string(value)?.to_owned());
+            }
+            (table, key) if table.starts_with("templates.") => {
+                let name = &table["templates.".len()..];
+                let template = match self.templates.iter().position(|template| template.name == name) {
+                    Some(idx) => &mut self.templates[idx],
+                    None => {
+                        self.templates.push(Template::new(name));
+                        self.templates.last_mut().expect("template just added")
+                    }
+                };
+                set_template(template, key, value)?;
+            }
+            _ => return Err("unknown setting".to_owned()),
+        }
+        Ok(())
+    }
+}
+
+/// Set a key of a template: its description, directory, layout, windows or commands after
+fn set_template(template: &mut Template, key: &str, value: &Value) -> Result<(), String> {
+    match key {
+        "description" => template.description = Some(string(value)?.to_owned()),
+        "dir" => template.dir = Some(string(value)?.to_owned()),
+        "layout" => template.layout = Some(string(value)?.to_owned()),
+        // Each window is its name alone, or its name followed by a command per pane
+        "windows" => template.windows = match value {
+            Value::Array(windows) => windows.iter().map(|window| {
+                let mut names = match window {
+                    Value::String(name) => vec![name.to_owned()],
+                    _ => strings(window)?,
+                };
+                if names.is_empty() {
+                    return Err("expected a window name".to_owned());
+                }
+                let name = names.remove(0);
+                Ok(TemplateWindow { name, layout: None, panes: names })
+            }).collect::<Result<Vec<_>, String>>()?,
+            _ => return Err(format!("expected an array of windows, got {}", value)),
+        },
+        // Each entry is a window followed by the commands typed into it
+        "after" => template.after = match value {
+            Value::Array(entries) => entries.iter().map(|entry| {
+                let mut names = strings(entry)?;
+                if names.len() < 2 {
+                    return Err("expected a window followed by commands".to_owned());
+                }
+                let window = names.remove(0);
+                Ok(names.into_iter().map(|command| (window.clone(), command)).collect::<Vec<_>>())
+            }).collect::<Result<Vec<_>, String>>()?.concat(),
+            _ => return Err(format!("expected an array of windows and commands, got {}", value)),
+        },
+        _ => return Err("unknown setting".to_owned()),
+    }
+    Ok(())
+}
+
+/// Parse a template file: the keys of a `[templates.<name>]` table at the top level, and
+/// optionally its `name`, which is `default_name` otherwise
+pub fn template_file(text: &str, default_name: &str) -> Result<Template, String> {
+    let tables = parse(text)?;
+    let mut template = Template::new(default_name);
+    for (table, entries) in &tables {
+        if !table.is_empty() {
+            return Err(format!("unexpected table [{}]", table));
+        }
+        for (key, value) in entries {
+            match key.as_str() {
+                "name" => string(value).map(|name| template.name = name.to_owned()),
+                key => set_template(&mut template, key, value),
+            }.map_err(|err| format!("{}: {}", key, err))?;
+        }
+    }
+    Ok(template)
+}
+
+fn string(value: &Value) -> Result<&str, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        _ => Err(format!("expected a string, got {}", value)),
+    }
+}
+
+fn strings(value: &Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::Array(items) => items.iter().map(|item| string(item).map(str::to_owned)).collect(),
+        _ => Err(format!("expected an array of strings, got {}", value)),
+    }
+}
+
+/// An interval of a number of seconds from the configuration file, where 0 means none
+pub fn interval(seconds: f64) -> Option<Duration> {
+    (seconds > 0.0).then(|| Duration::from_secs_f64(seconds))
+}
+
+/// Parse a number of seconds given on the command line, as the configuration file takes them
+pub fn seconds(text: &str) -> Result<f64, String> {
+    let value = match text.parse() {
+        Ok(i) => Value::Integer(i),
+        Err(_) => Value::Float(text.parse().map_err(|_| format!("expected a number, got {:?}", text))?),
+    };
+    number(&value)
+}
+
+fn number(value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Integer(i) if *i >= 0 => Ok(*i as f64),
+        // Infinity would overflow the durations made of it
+        Value::Float(x) if *x >= 0.0 && x.is_finite() => Ok(*x),
+        _ => Err(format!("expected a positive number, got {}", value)),
+    }
+}
+
+fn boolean(value: &Value) -> Result<bool, String> {
+    match value {
+        Value::Bool(b) => Ok(*b),
+        _ => Err(format!("expected true or false, got {}", value)),
+    }
+}
+
+/// Parse a color name (as ratatui spells them, e.g. `light-blue`), `#rrggbb` or a 256 color index
+pub fn color(value: &Value) -> Result<Color, String> {
+    match value {
+        Value::Integer(index @ 0..=255) => Ok(Color::Indexed(*index as u8)),
+        Value::String(name) => name.parse().map_err(|_| format!("unknown color {:?}", name)),
+        _ => Err(format!("expected a color, got {}", value)),
+    }
+}
+
+/// Parse the configuration file: the keys of each table by its dotted name, such as
+/// `templates.web` for the keys of `[templates.web]`
+pub fn parse(text: &str) -> Result<Tables, String> {
+    let table: toml::Table = text.parse().map_err(|err: toml::de::Error| {
+        let line = err.span().map_or(1, |span| text[..span.start].matches('\n').count() + 1);
+        format!("line {}: {}", line, err.message().trim().replace('\n', ", "))
+    })?;
+    let mut tables = Tables::new();
+    tables.insert(String::new(), IndexMap::new());
+    flatten(&mut tables, "", table)?;
+    Ok(tables)
+}
+
+/// Add the keys of `table`, named `name`, to `tables`, and those of its subtables in turn
+fn flatten(tables: &mut Tables, name: &str, table: toml::Table) -> Result<(), String> {
+    for (key, value) in table {
+        let dotted = if name.is_empty() { key.clone() } else { format!("{}.{}", name, key) };
+        if let toml::Value::Table(subtable) = value {
+            tables.entry(dotted.clone()).or_default();
+            flatten(tables, &dotted, subtable)?;
+            continue;
+        }
+        let value = Value::try_from(value).map_err(|err| format!("{}: {}", dotted, err))?;
+        tables.entry(name.to_owned()).or_default().insert(key, value);
+    }
+    Ok(())
+}
+
+impl TryFrom<toml::Value> for Value {
+    type Error = String;
+
+    fn try_from(value: toml::Value) -> Result<Self, String> {
+        Ok(match value {
+            toml::Value::String(s) => Value::String(s),
+            toml::Value::Integer(i) => Value::Integer(i),
+            toml::Value::Float(x) => Value::Float(x),
+            toml::Value::Boolean(b) => Value::Bool(b),
+            toml::Value::Array(items) => Value::Array(items.into_iter().map(Value::try_from).collect::<Result<_, _>>()?),
+            toml::Value::Datetime(datetime) => return Err(format!("unexpected date {}", datetime)),
+            toml::Value::Table(_) => return Err("unexpected table in an array".to_owned()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_settings_over_defaults() {
+        let tables = parse(r##"
+            # Top level settings
+            refresh = 0.5
+            hosts = [
+                "build-box",  # comments inside arrays
+                'web#1',
+            ]
+            sort = "activity"
+            attach_size = "smallest"
+            view = "projects"
+            filter = "detached"
+            group_by = "none"
+            attach_command = "git pull"
+            new_command = "nvim"
+            search_attach = true
+            current_session = "windows"
+            wrap_around = true
+            auto_attach = true
+
+            [intervals]
+            hosts = 30
+            preview = 0
+            windows = 10
+
+            [confirm]
+            delete = false
+
+            [colors]
+            highlight = "#ff8800"
+            marker = 208
+
+            [keys]
+            detach = "Q"
+            sort = "<leader>S"
+            leader = "Space"
+
+            [aliases]
+            w = "work-main"
+
+            [templates.web]
+            dir = "~/src/web"
+            windows = ["logs", ["server", "npm run dev", ""]]
+            after = [["server", "nvm use", "direnv allow"]]
+        "##).unwrap();
+        let config = Config::from_tables(&tables).unwrap();
+        assert_eq!(config.refresh, 0.5);
+        assert_eq!(config.host_refresh, Some(30.0));
+        assert_eq!(interval(config.preview_refresh), None);
+        assert_eq!(interval(config.windows_refresh), Some(Duration::from_secs(10)));
+        assert_eq!(config.hosts, ["build-box", "web#1"]);
+        assert_eq!(config.sort, SortOrder::Activity);
+        assert_eq!(config.attach_size, AttachSize::Smallest);
+        assert_eq!(config.view, StartupView::Projects);
+        assert_eq!(config.filter, AttachedFilter::Detached);
+        assert_eq!(config.group_by, Some(GroupBy::None));
+        assert_eq!(config.attach_command.as_deref(), Some("git pull"));
+        assert_eq!(config.new_command.as_deref(), Some("nvim"));
+        assert!(config.search_attach);
+        assert_eq!(config.current_session, CurrentSession::Windows);
+        assert!(config.wrap_around);
+        assert!(config.auto_attach);
+        assert!(!config.confirm_delete);
+        assert!(config.confirm_batch);
+        let colors = Colors::themed(config.theme, &config.color_overrides);
+        assert_eq!(colors.highlight, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(colors.marker, Color::Indexed(208));
+        assert_eq!(colors.badge, Colors::default().badge);
+        assert_eq!(config.keys.label(Action::Detach), "QQ");
+        assert_eq!(config.keys.label(Action::Sort), "Space S");
+        assert_eq!(config.keys.label(Action::KillServer), "X");
+        assert_eq!(config.resolve_alias("w"), "work-main");
+        assert_eq!(config.resolve_alias("work"), "work");
+        assert_eq!(config.templates.len(), 1);
+        assert_eq!(template_file("name = \"api\"\nwindows = [\"logs\"]", "file").unwrap().name, "api");
+        assert_eq!(config.templates[0].name, "web");
+        assert_eq!(config.templates[0].dir.as_deref(), Some("~/src/web"));
+        assert_eq!(config.templates[0].windows, [
+            TemplateWindow { name: "logs".to_owned(), layout: None, panes: vec![] },
+            TemplateWindow { name: "server".to_owned(), layout: None, panes: vec!["npm run dev".to_owned(), String::new()] },
+        ]);
+        assert_eq!(config.templates[0].after, [
+            ("server".to_owned(), "nvm use".to_owned()), ("server".to_owned(), "direnv allow".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn reports_where_the_file_is_wrong() {
+        assert_eq!(parse("refresh = 1\n[layout\n").unwrap_err(), "line 2: invalid table header, expected `.`, `]`");
+        assert_eq!(parse("[colors]\nhighlight = 1979-05-27").unwrap_err(), "colors.highlight: unexpected date 1979-05-27");
+        let tables = parse("[layout]\npreview_width = 95").unwrap();
+        assert_eq!(Config::from_tables(&tables).unwrap_err(),
+            "layout.preview_width: expected a percentage from 10 to 90, got 95");
+        assert_eq!(seconds("0.5"), Ok(0.5));
+        assert_eq!(seconds("-1").unwrap_err(), "expected a positive number, got -1");
+        assert!(seconds("NaN").is_err());
+        assert!(seconds("soon").is_err());
+        let tables = parse("refresh = inf").unwrap();
+        assert_eq!(Config::from_tables(&tables).unwrap_err(), "refresh: expected a positive number, got inf");
+        let tables = parse("[keys]\nfly = \"f\"").unwrap();
+        assert_eq!(Config::from_tables(&tables).unwrap_err(), "keys.fly: unknown action \"fly\"");
+        assert_eq!(template_file("windows = \"editor\"", "web").unwrap_err(),
+            "windows: expected an array of windows, got \"editor\"");
+    }
+}
@@ -1,17 +1,31 @@
-use crate::app::AppResult;
+use crate::app::{AppResult, PaneSearchUpdate};
+use crate::scheduler::JobResult;
+use crate::source::{Source, SourceEntry};
 use crossterm::event::{self, Event as CrosstermEvent, KeyEvent, MouseEvent};
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
 /// Terminal events.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum Event {
+    /// Terminal tick.
+    Tick,
     /// Key press.
     Key(KeyEvent),
     /// Mouse click/scroll.
     Mouse(MouseEvent),
     /// Terminal resize.
     Resize(u16, u16),
+    /// A background job finished.
+    Job(JobResult),
+    /// A tmux control mode notification line, e.g. `%sessions-changed`.
+    Notification(String),
+    /// The entries of a source were listed in the background.
+    Entries(Source, Result<Vec<SourceEntry>, String>),
+    /// Progress of the deep search of the given generation.
+    PaneSearch(u64, PaneSearchUpdate),
 }
 
 /// Terminal event handler.
@@ -24,25 +38,59 @@ pub struct EventHandler {
     receiver: mpsc::Receiver<Event>,
     /// Event handler thread.
     handler: thread::JoinHandle<()>,
+    /// Whether the handler thread leaves the terminal alone, e.g. while an editor runs in it.
+    paused: Arc<AtomicBool>,
+    /// Held by the handler thread while it reads the terminal.
+    reading: Arc<Mutex<()>>,
+}
+
+impl Default for EventHandler {
+    fn default() -> Self {
+        Self::new(250)
+    }
 }
 
 impl EventHandler {
-    /// Constructs a new instance of [`EventHandler`].
-    pub fn new() -> Self {
+    /// Constructs a new instance of [`EventHandler`] sending a tick every `tick_rate` milliseconds.
+    pub fn new(tick_rate: u64) -> Self {
+        let tick_rate = Duration::from_millis(tick_rate);
         let (sender, receiver) = mpsc::channel();
+        let paused = Arc::new(AtomicBool::new(false));
+        let reading = Arc::new(Mutex::new(()));
         let handler = {
             let sender = sender.clone();
+            let paused = paused.clone();
+            let reading = reading.clone();
             thread::spawn(move || {
+                let mut last_tick = Instant::now();
                 loop {
-                    match event::read().expect("unable to read event") {
-                        CrosstermEvent::Key(e) => sender.send(Event::Key(e)),
-                        CrosstermEvent::Mouse(e) => sender.send(Event::Mouse(e)),
-                        CrosstermEvent::Resize(w, h) => sender.send(Event::Resize(w, h)),
-                        CrosstermEvent::FocusGained => Ok(()),
-                        CrosstermEvent::FocusLost => Ok(()),
-                        CrosstermEvent::Paste(_) => unimplemented!(),
+                    if paused.load(Ordering::SeqCst) {
+                        thread::sleep(Duration::from_millis(20));
+                        continue;
+                    }
+                    let _reading = reading.lock().expect("event reader poisoned");
+                    // Paused while waiting for the lock
+                    if paused.load(Ordering::SeqCst) {
+                        continue;
+                    }
+                    let timeout = tick_rate
+                        .checked_sub(last_tick.elapsed())
+                        .unwrap_or(tick_rate);
+                    if event::poll(timeout).expect("unable to poll for event") {
+                        match event::read().expect("unable to read event") {
+                            CrosstermEvent::Key(e) => sender.send(Event::Key(e)),
+                            CrosstermEvent::Mouse(e) => sender.send(Event::Mouse(e)),
+                            CrosstermEvent::Resize(w, h) => sender.send(Event::Resize(w, h)),
+                            CrosstermEvent::FocusGained => Ok(()),
+                            CrosstermEvent::FocusLost => Ok(()),
+                            CrosstermEvent::Paste(_) => unimplemented!(),
+                        }
+                        .expect("failed to send terminal event");
+                    }
+                    if last_tick.elapsed() >= tick_rate {
+                        sender.send(Event::Tick).expect("failed to send tick event");
+                        last_tick = Instant::now();
                     }
-                    .expect("failed to send terminal event");
                 }
             })
         };
@@ -50,9 +98,28 @@ impl EventHandler {
             sender,
             receiver,
             handler,
+            paused,
+            reading,
         }
     }
 
+    /// Stop reading terminal events (and sending ticks) until [`EventHandler::resume`], so that
+    /// another program can read the terminal. Returns once the handler thread is done reading.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        drop(self.reading.lock().expect("event reader poisoned"));
+    }
+
+    /// Read terminal events again after [`EventHandler::pause`].
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    /// Get a sender that background jobs can use to report back to the main loop.
+    pub fn sender(&self) -> mpsc::Sender<Event> {
+        self.sender.clone()
+    }
+
     /// Receive the next event from the handler thread.
     ///
     /// This function will always block the current thread if
@@ -1,103 +1,71 @@
-use crate::app::{App, AppResult, AppState};
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::app::{App, AppResult, AppState, AttachSize, FormField, ListRow};
+use crate::keymap::{Action, Chord, KeyBinding};
+use std::time::{Duration, Instant};
+use crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
 use tui_textarea::{Key, Input};
 
+/// Longest time between two clicks on a row that makes them a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// Longest wait for the next key of a key sequence such as `gg`, after which the keys typed so far
+/// run the action they are bound to on their own, if any
+const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+
 /// Handles the key events and updates the state of [`App`].
 pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
     // Check global hotkeys first and always immediately handle them regardless
     // of mode
 
     // TRUE globals - no matter what the mode always respond to these first
-    match key_event.into() {
-        // Exit application on `Ctrl-C`
-        Input { key: Key::Char('c') | Key::Char('C'), ctrl: true, ..} =>  {
+    // Exit application on `Ctrl-C`
+    if let Input { key: Key::Char('c') | Key::Char('C'), ctrl: true, ..} = key_event.into() {
+        app.quit();
+        return Ok(());
+    }
+    // Stop on `Ctrl-Z` like other programs, with the terminal reset while stopped
+    if let Input { key: Key::Char('z') | Key::Char('Z'), ctrl: true, ..} = key_event.into() {
+        app.stop();
+        return Ok(());
+    }
+
+    // As long as the state is not one of the prompting states, check for globals. Halfway through
+    // a key sequence the key belongs to the sequence instead.
+    if app.pending_keys.is_none() && !matches!(app.state, AppState::Renaming | AppState::SessionsSearch | AppState::NewSession | AppState::BatchRenaming | AppState::AttachCommand | AppState::Coloring | AppState::Hinting | AppState::SplitPane | AppState::CapturingPane | AppState::DeepSearching) {
+        // Exit application on `q`
+        if app.keymap.matches(Action::Quit, &key_event) {
             app.quit();
             return Ok(());
         }
-        _ => ()
-    }
-
-    // As long as the state is not one of the prompting states, check for globals
-    if !matches!(app.state, AppState::Renaming | AppState::SessionsSearch | AppState::NewSession) {
-        match key_event.code {
-            // Exit application on `ESC` or `q`
-            KeyCode::Char('q') => {
-                app.quit();
-                return Ok(());
+        // Detach-only exit on a double press of the detach key (`ZZ` by default). The first press
+        // is swallowed while waiting for the second.
+        if app.keymap.matches(Action::Detach, &key_event) {
+            if app.pending_detach {
+                app.detach();
+            } else {
+                app.pending_detach = true;
             }
-            _ => ()
+            return Ok(());
         }
     }
+    app.pending_detach = false;
     // Check hotkeys based on different app states that can handle different
     // keys
     match app.state {
         AppState::Sessions => {
-            match key_event.code {
-                // Move up the list
-                KeyCode::Char('k') | KeyCode::Up => {
-                    app.selected_session = app.selected_session.checked_sub(1).unwrap_or(0)
-                }
-                KeyCode::Char('p') => { // C-p
-                    if key_event.modifiers == KeyModifiers::CONTROL {
-                        app.selected_session = app.selected_session.checked_sub(1).unwrap_or(0)
-                    }
-                }
-                // Move down the list
-                KeyCode::Char('j') | KeyCode::Down => {
-                    app.selected_session = (app.selected_session + 1).min(app.sessions.len()-1)
-                }
-                KeyCode::Char('n') => { // C-n
-                    if key_event.modifiers == KeyModifiers::CONTROL {
-                        app.selected_session = (app.selected_session + 1).min(app.sessions.len()-1)
-                    } else {
-                        app.confirm_new_session();
-                    }
-                }
-                // Enter/select to attach
-                KeyCode::Enter | KeyCode::Char('a') => {
-                    let name = app.sessions[app.selected_session].0.clone();
-                    app.attach(name, true);
-                }
-                // Jump to top of list
-                KeyCode::Char('g') => {
-                    app.selected_session = 0;
-                }
-                KeyCode::Char(' ') => {
-                    app.refresh();
-                }
-                // Jump to top end of list
-                KeyCode::Char('G') => {
-                    app.selected_session = app.sessions.len() - 1;
-                }
-                KeyCode::Char('x') => {
-                    // Start the delete process for the currently selected
-                    // session
-                    app.confirm_delete();
-                }
-                KeyCode::Char('N') => {
-                    // Create and attach a new session. If the user is currently
-                    // in a tmux session so the attach would fail, instead of
-                    // attempting attach, just refresh the list
-                    app.new_session(None);
-                }
-                KeyCode::Char('r') => {
-                    app.confirm_rename();
-                }
-                KeyCode::Char('/') => {
-                    app.search();
-                }
-                // TODO: d -> detach all clients from the session
-                _ => {}
-            }
+            return handle_sessions_key(key_event, app);
         },
         AppState::SessionsSearch => {
             match key_event.into() {
+                // Attach the highlighted match right away, or with `search_attach` only select it.
+                // Most terminals only tell Ctrl-Enter apart from Enter by sending Ctrl-J, while
+                // Alt-Enter always works.
+                Input { key: Key::Enter, ctrl: true, .. }
+                | Input { key: Key::Enter, alt: true, .. }
+                | Input { key: Key::Char('j'), ctrl: true, .. } => {
+                    finish_search(app, !app.search_attach);
+                },
                 Input { key: Key::Enter, .. } => {
-                    // Update selected session with first search result
-                    if let Some(new_row) = app.search_session_selected {
-                        app.selected_session = new_row;
-                    }
-                    app.dismiss_all();
+                    finish_search(app, app.search_attach);
                 },
                 Input { key: Key::Esc, .. } => {
                     // Ensure no search session is selected
@@ -109,7 +77,7 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
                         if let Some(cur_idx) = app.matching_rows.iter().position(|row_idx| {
                             *row_idx == selected_row
                         }) {
-                            let new_idx = cur_idx.checked_sub(1).unwrap_or(0);
+                            let new_idx = cur_idx.saturating_sub(1);
                             app.search_session_selected = Some(app.matching_rows[new_idx]);
                         }
                     }
@@ -127,6 +95,8 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
                 input => {
                     if let Some(ref mut textarea) = app.search_session_ta {
                         textarea.input(input);
+                        // The best match of the new term is selected, which an alias's session is
+                        app.search_session_selected = None;
                     }
                 }
             }
@@ -137,6 +107,10 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
                     // Delete the highlighted session
                     app.delete();
                 },
+                KeyCode::Char('g') => {
+                    // Give its processes a chance to exit first
+                    app.delete_gracefully();
+                },
                 KeyCode::Char('n') | KeyCode::Esc => {
                     // Cancel - hide the popup
                     app.dismiss_all();
@@ -144,6 +118,34 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
                 _ => (),
             }
         },
+        AppState::DetachingClients => {
+            match key_event.code {
+                KeyCode::Char('y') => app.detach_clients(),
+                KeyCode::Char('n') | KeyCode::Esc => app.dismiss_all(),
+                _ => {}
+            }
+        }
+        AppState::KillingClient => {
+            match key_event.code {
+                KeyCode::Char('y') => app.kill_client(),
+                KeyCode::Char('n') | KeyCode::Esc => app.cancel_kill_client(),
+                _ => {}
+            }
+        }
+        AppState::KillingPane => {
+            match key_event.code {
+                KeyCode::Char('y') => app.kill_pane(),
+                KeyCode::Char('n') | KeyCode::Esc => app.cancel_kill_pane(),
+                _ => {}
+            }
+        }
+        AppState::KillingServer => {
+            match key_event.code {
+                KeyCode::Char('y') => app.kill_server(),
+                KeyCode::Char('n') | KeyCode::Esc => app.dismiss_all(),
+                _ => {}
+            }
+        }
         AppState::Renaming => {
             // If the renaming dialog is up, user can either escape out or hit enter to trigger
             // rename. Any valid symbols for a tmux session name should be pushed onto the rename
@@ -151,7 +153,8 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
             match key_event.into() {
                 Input { key: Key::Enter, .. } => {
                     // Read the textarea contents and use it to rename the session
-                    if let Some(textarea) = &app.rename_session_ta {
+                    // Not without a name, which the prompt points out
+                    if let Some(textarea) = app.rename_session_ta.as_ref().filter(|textarea| !textarea.lines()[0].is_empty()) {
                         let rename = &textarea.lines()[0].to_string();
                         app.rename(rename);
                     }
@@ -171,26 +174,729 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
             // Similar to Renaming interface except for final result
             match key_event.into() {
                 Input { key: Key::Enter, .. } => {
-                    // Read the textarea contents and use it to create a new session
-                    if let Some(textarea) = &app.new_session_ta {
-                        let name = &textarea.lines()[0].to_string();
-                        app.new_session(Some(name));
+                    // Read the form and use it to create a new session
+                    // Not without a name tmux can create, which the form points out
+                    if let Some(form) = app.new_session_form.as_ref().filter(|_| app.new_session_form_ready()) {
+                        let name = form.name.lines()[0].to_string();
+                        let dir = form.directory();
+                        let command = form.command().map(str::to_owned);
+                        match form.template().map(str::to_owned) {
+                            Some(template) => app.new_session_from_template(name.trim(), &template, dir.as_deref()),
+                            None => app.new_session(Some(&name), dir.as_deref(), command.as_deref()),
+                        }
+                    }
+                },
+                Input { key: Key::Esc, .. } => {
+                    app.dismiss_all();
+                },
+                Input { key: Key::Tab, .. } => {
+                    // Tab completes the directory, and moves on once there is nothing to complete
+                    if let Some(form) = app.new_session_form.as_mut() {
+                        if form.focus != FormField::Directory || !form.complete_directory() {
+                            form.next_field();
+                        }
+                    }
+                },
+                input => {
+                    if let Some(form) = app.new_session_form.as_mut() {
+                        form.candidates.clear();
+                        form.focused_mut().input(input);
+                    }
+                }
+            }
+        },
+        AppState::SplitPane => {
+            match key_event.into() {
+                Input { key: Key::Enter, .. } => app.split_pane(),
+                Input { key: Key::Esc, .. } => app.cancel_split(),
+                Input { key: Key::Tab, .. } => {
+                    if let Some(form) = app.split_form.as_mut() {
+                        form.next_field();
+                    }
+                },
+                input => {
+                    if let Some(form) = app.split_form.as_mut() {
+                        form.focused_mut().input(input);
+                    }
+                }
+            }
+        },
+        AppState::AttachCommand => {
+            match key_event.into() {
+                Input { key: Key::Enter, .. } => {
+                    if let Some(textarea) = &app.attach_command_ta {
+                        let command = textarea.lines()[0].to_string();
+                        app.attach_selected_and_run(&command);
                     }
                 },
                 Input { key: Key::Esc, .. } => {
                     app.dismiss_all();
                 },
                 input => {
-                    if let Some(ref mut textarea) = app.new_session_ta {
+                    if let Some(ref mut textarea) = app.attach_command_ta {
                         textarea.input(input);
                     }
                 }
             }
         },
-        AppState::WarnNested => {
+        AppState::CapturingPane => {
+            match key_event.into() {
+                Input { key: Key::Enter, .. } => {
+                    // Not without a path, which stays asked for
+                    if let Some(textarea) = app.capture_ta.as_ref().filter(|textarea| !textarea.lines()[0].trim().is_empty()) {
+                        let path = textarea.lines()[0].to_string();
+                        app.capture_pane(&path);
+                    }
+                },
+                Input { key: Key::Esc, .. } => app.cancel_capture_pane(),
+                input => {
+                    if let Some(ref mut textarea) = app.capture_ta {
+                        textarea.input(input);
+                    }
+                }
+            }
+        },
+        AppState::DeepSearching => {
+            match key_event.into() {
+                Input { key: Key::Enter, .. } => {
+                    // Not without a term, which everything would match
+                    if let Some(textarea) = app.deep_search_ta.as_ref().filter(|textarea| !textarea.lines()[0].is_empty()) {
+                        let term = textarea.lines()[0].to_string();
+                        app.deep_search(&term);
+                    }
+                },
+                Input { key: Key::Esc, .. } => app.dismiss_all(),
+                input => {
+                    if let Some(ref mut textarea) = app.deep_search_ta {
+                        textarea.input(input);
+                    }
+                }
+            }
+        },
+        AppState::DeepSearchRunning => {
+            if key_event.code == KeyCode::Esc {
+                app.cancel_deep_search();
+            }
+        },
+        AppState::DeepResults => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => {
+                    app.selected_pane_match = app.selected_pane_match.saturating_sub(1)
+                }
+                KeyCode::Char('j') | KeyCode::Down => {
+                    app.selected_pane_match = (app.selected_pane_match + 1).min(app.pane_matches.len().saturating_sub(1))
+                }
+                KeyCode::Enter | KeyCode::Char('a') => {
+                    app.attach_pane_match();
+                }
+                KeyCode::Esc | KeyCode::Char('h') => {
+                    app.dismiss_all();
+                }
+                _ => {}
+            }
+        },
+        AppState::Servers => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => {
+                    app.selected_server = app.selected_server.saturating_sub(1)
+                }
+                KeyCode::Char('j') | KeyCode::Down => {
+                    app.selected_server = (app.selected_server + 1).min(app.servers.len().saturating_sub(1))
+                }
+                KeyCode::Enter | KeyCode::Char('l') => {
+                    app.switch_server();
+                }
+                KeyCode::Esc | KeyCode::Char('h') => {
+                    app.dismiss_all();
+                }
+                _ => {}
+            }
+        },
+        AppState::Copying => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => app.move_copy_cursor(-1),
+                KeyCode::Char('j') | KeyCode::Down => app.move_copy_cursor(1),
+                KeyCode::PageUp => app.move_copy_cursor(-10),
+                KeyCode::PageDown => app.move_copy_cursor(10),
+                KeyCode::Char('g') | KeyCode::Home => app.copy_cursor = 0,
+                KeyCode::Char('G') | KeyCode::End => app.copy_cursor = app.copy_lines.len().saturating_sub(1),
+                KeyCode::Char('v') | KeyCode::Char('V') | KeyCode::Char(' ') => app.toggle_copy_selection(),
+                KeyCode::Char('y') | KeyCode::Enter => app.yank(),
+                // Like vim, Esc drops the selection before it leaves
+                KeyCode::Esc if app.copy_anchor.is_some() => app.copy_anchor = None,
+                KeyCode::Esc => app.leave_copy_mode(),
+                _ => {}
+            }
+        },
+        AppState::Hinting => {
+            match key_event.code {
+                KeyCode::Char(letter) if letter.is_ascii_alphabetic() => app.type_hint(letter),
+                _ => app.state = AppState::Sessions,
+            }
+        }
+        AppState::Coloring => {
+            match key_event.into() {
+                Input { key: Key::Enter, .. } => {
+                    if let Some(textarea) = &app.color_ta {
+                        let color = textarea.lines()[0].to_string();
+                        app.set_selected_color(&color);
+                    }
+                },
+                Input { key: Key::Esc, .. } => {
+                    app.dismiss_all();
+                },
+                input => {
+                    if let Some(ref mut textarea) = app.color_ta {
+                        textarea.input(input);
+                    }
+                }
+            }
+        },
+        AppState::BatchRenaming => {
+            match key_event.into() {
+                Input { key: Key::Enter, .. } => {
+                    // Read the pattern and summarize the resulting renames for confirmation
+                    if let Some(textarea) = &app.batch_rename_ta {
+                        let pattern = &textarea.lines()[0].to_string();
+                        app.batch_rename(pattern);
+                    }
+                },
+                Input { key: Key::Esc, .. } => {
+                    app.dismiss_all();
+                },
+                input => {
+                    if let Some(ref mut textarea) = app.batch_rename_ta {
+                        textarea.input(input);
+                    }
+                }
+            }
+        },
+        AppState::BatchConfirm => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => {
+                    if let Some(batch) = app.batch.as_mut() {
+                        batch.scroll_up();
+                    }
+                },
+                KeyCode::Char('j') | KeyCode::Down => {
+                    if let Some(batch) = app.batch.as_mut() {
+                        batch.scroll_down();
+                    }
+                },
+                KeyCode::Char('y') => {
+                    app.run_batch();
+                },
+                KeyCode::Char('n') | KeyCode::Esc => {
+                    app.dismiss_all();
+                },
+                _ => (),
+            }
+        },
+        AppState::BatchRunning => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => {
+                    if let Some(batch) = app.batch.as_mut() {
+                        batch.scroll_up();
+                    }
+                },
+                KeyCode::Char('j') | KeyCode::Down => {
+                    if let Some(batch) = app.batch.as_mut() {
+                        batch.scroll_down();
+                    }
+                },
+                KeyCode::Esc => {
+                    // Stop before the next step, keeping what has been done
+                    app.abort_batch();
+                },
+                _ => (),
+            }
+        },
+        AppState::BatchResults => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => {
+                    if let Some(batch) = app.batch.as_mut() {
+                        batch.scroll_up();
+                    }
+                },
+                KeyCode::Char('j') | KeyCode::Down => {
+                    if let Some(batch) = app.batch.as_mut() {
+                        batch.scroll_down();
+                    }
+                },
+                _ => app.dismiss_all(),
+            }
+        },
+        AppState::Windows => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => {
+                    app.selected_window = app.selected_window.saturating_sub(1)
+                }
+                KeyCode::Char('j') | KeyCode::Down => {
+                    app.selected_window = (app.selected_window + 1).min(app.tree_rows().len().saturating_sub(1))
+                }
+                KeyCode::Char('g') => {
+                    app.selected_window = 0;
+                }
+                KeyCode::Char('G') => {
+                    app.selected_window = app.tree_rows().len().saturating_sub(1);
+                }
+                KeyCode::Enter | KeyCode::Char('a') => {
+                    app.attach_selected_window();
+                }
+                KeyCode::Char(digit @ '0'..='9') => {
+                    app.attach_window_numbered(digit as usize - '0' as usize);
+                }
+                KeyCode::Char('m') => {
+                    app.toggle_pane_mark();
+                }
+                KeyCode::Char('\'') => {
+                    app.attach_marked_pane();
+                }
+                KeyCode::Char('<') => {
+                    app.move_selected_window(false);
+                }
+                KeyCode::Char('>') => {
+                    app.move_selected_window(true);
+                }
+                // Split the pane like tmux's prefix and `%` or `"`
+                KeyCode::Char('%') => {
+                    app.confirm_split(false);
+                }
+                KeyCode::Char('"') => {
+                    app.confirm_split(true);
+                }
+                KeyCode::Char('z') => {
+                    app.toggle_pane_zoom();
+                }
+                KeyCode::Char('x') => {
+                    app.confirm_kill_pane();
+                }
+                KeyCode::Char('s') => {
+                    app.confirm_capture_pane();
+                }
+                KeyCode::Char('y') => {
+                    app.start_copy_mode();
+                }
+                KeyCode::Char('l') | KeyCode::Right => {
+                    app.expand_selected_window();
+                }
+                KeyCode::Char('h') | KeyCode::Left => {
+                    // Collapse the current window, or go back up to the sessions if there is
+                    // nothing left to collapse
+                    let collapsed = app.collapse_selected_window();
+                    if !collapsed {
+                        app.dismiss_all();
+                    }
+                }
+                KeyCode::Esc => {
+                    app.dismiss_all();
+                }
+                _ => {}
+            }
+        },
+        AppState::Clients => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => {
+                    app.selected_client = app.selected_client.saturating_sub(1)
+                }
+                KeyCode::Char('j') | KeyCode::Down => {
+                    app.selected_client = (app.selected_client + 1).min(app.clients.len().saturating_sub(1))
+                }
+                KeyCode::Char('d') => {
+                    app.detach_selected_client(false);
+                }
+                KeyCode::Char('x') => {
+                    app.confirm_kill_client();
+                }
+                KeyCode::Esc | KeyCode::Char('h') => {
+                    app.dismiss_all();
+                }
+                _ => {}
+            }
+        },
+        AppState::Templates => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => {
+                    app.selected_template = app.selected_template.saturating_sub(1)
+                }
+                KeyCode::Char('j') | KeyCode::Down => {
+                    app.selected_template = (app.selected_template + 1).min(app.templates.len().saturating_sub(1))
+                }
+                KeyCode::Enter => {
+                    if let Some(template) = app.templates.get(app.selected_template) {
+                        let name = template.name.clone();
+                        app.new_session_from_template("", &name, None);
+                    }
+                }
+                KeyCode::Esc | KeyCode::Char('h') => {
+                    app.dismiss_all();
+                }
+                _ => {}
+            }
+        },
+        AppState::Archive => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => {
+                    app.selected_archived = app.selected_archived.saturating_sub(1)
+                }
+                KeyCode::Char('j') | KeyCode::Down => {
+                    app.selected_archived = (app.selected_archived + 1).min(app.archived.len().saturating_sub(1))
+                }
+                KeyCode::Enter => app.restore_selected_archived(),
+                KeyCode::Char('x') => app.purge_selected_archived(),
+                KeyCode::Esc | KeyCode::Char('h') => app.dismiss_all(),
+                _ => {}
+            }
+        },
+        AppState::History => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => {
+                    app.selected_history = app.selected_history.saturating_sub(1)
+                }
+                KeyCode::Char('j') | KeyCode::Down => {
+                    app.selected_history = (app.selected_history + 1).min(app.history.len().saturating_sub(1))
+                }
+                KeyCode::Char('g') => {
+                    app.selected_history = 0;
+                }
+                KeyCode::Char('G') => {
+                    app.selected_history = app.history.len().saturating_sub(1);
+                }
+                KeyCode::Enter | KeyCode::Char('a') => {
+                    app.attach_history_selected();
+                }
+                KeyCode::Esc | KeyCode::Char('h') => {
+                    app.dismiss_all();
+                }
+                _ => {}
+            }
+        },
+        AppState::Help => {
+            match key_event.code {
+                KeyCode::Char('k') | KeyCode::Up => app.scroll_help(-1),
+                KeyCode::Char('j') | KeyCode::Down => app.scroll_help(1),
+                KeyCode::PageUp => app.scroll_help(-10),
+                KeyCode::PageDown => app.scroll_help(10),
+                KeyCode::Esc | KeyCode::Char('?') | KeyCode::Char('h') => app.dismiss_all(),
+                _ => {}
+            }
+        }
+        AppState::WarnNested | AppState::ServerInfo => {
             // Any key should dismiss
             app.dismiss_all();
         }
+        AppState::RenameConflict => {
+            match key_event.code {
+                KeyCode::Char('s') => app.rename_with_suffix(),
+                KeyCode::Char('m') => app.merge_into_conflict(),
+                KeyCode::Esc => app.cancel_rename_conflict(),
+                _ => {}
+            }
+        }
+        AppState::AttachSizing => {
+            match key_event.code {
+                KeyCode::Char('d') => app.attach_selected(AttachSize::Detach),
+                KeyCode::Char('s') => app.attach_selected(AttachSize::Smallest),
+                KeyCode::Char('l') => app.attach_selected(AttachSize::Latest),
+                KeyCode::Esc => app.dismiss_all(),
+                _ => {}
+            }
+        }
+        AppState::Error => {
+            // Any key dismisses the oldest error
+            app.dismiss_error();
+        }
+    }
+    Ok(())
+}
+
+/// Looks up a key of the sessions view in [`App::keymap`]. A key starting a longer key sequence
+/// is held in [`App::pending_keys`] until the sequence is complete.
+fn handle_sessions_key(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    let key = KeyBinding::from_event(&key_event);
+    if let Some((mut keys, _)) = app.pending_keys.take() {
+        keys.push(key);
+        match app.keymap.resolve(&keys) {
+            Chord::Action(action) => run_sessions_action(action, app),
+            Chord::Prefix(_) => app.pending_keys = Some((keys, Instant::now())),
+            // Esc cancels the sequence. Any other key ends it, running what the keys before it
+            // are bound to, and then counts on its own, as if the sequence had timed out.
+            Chord::Unbound if key_event.code == KeyCode::Esc => {}
+            Chord::Unbound => {
+                keys.pop();
+                if let Chord::Prefix(Some(action)) = app.keymap.resolve(&keys) {
+                    run_sessions_action(action, app);
+                }
+                return handle_key_events(key_event, app);
+            }
+        }
+        return Ok(());
+    }
+    match app.keymap.resolve(&[key]) {
+        Chord::Action(action) => run_sessions_action(action, app),
+        Chord::Prefix(_) => app.pending_keys = Some((vec![key], Instant::now())),
+        // Esc lets go of the marked sessions, unless it is bound to an action
+        Chord::Unbound if key_event.code == KeyCode::Esc => app.clear_marks(),
+        Chord::Unbound => {
+            if let KeyCode::Char(digit @ '1'..='9') = key_event.code {
+                // Digits not bound to an action attach the session listed with that number
+                app.attach_session_numbered(digit as usize - '0' as usize);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handles the tick event for the keys: a key sequence left unfinished for [`SEQUENCE_TIMEOUT`]
+/// ends, running the action the keys typed so far are bound to, if any
+pub fn handle_key_timeout(app: &mut App) {
+    let Some((keys, at)) = app.pending_keys.take() else { return };
+    if at.elapsed() < SEQUENCE_TIMEOUT {
+        app.pending_keys = Some((keys, at));
+    } else if let Chord::Prefix(Some(action)) = app.keymap.resolve(&keys) {
+        run_sessions_action(action, app);
+    }
+}
+
+/// Carries out an action of the sessions view unless it is disabled, which the status tells why
+fn run_sessions_action(action: Action, app: &mut App) {
+    match app.disabled_reason(action) {
+        Some(reason) => app.show_status(&reason),
+        None => handle_sessions_action(action, app),
+    }
+}
+
+/// Carries out an action of the sessions view, bound to a key or triggered with the mouse
+fn handle_sessions_action(action: Action, app: &mut App) {
+    // Entries of other sources only have their source's default action
+    if app.selected_session().is_none() {
+        match action {
+            Action::Attach | Action::AttachSized | Action::Windows => {
+                app.open_selected_entry();
+                return;
+            }
+            _ => {}
+        }
+    }
+    // A collapsed section's header stands in for its hidden sessions. Attaching or opening
+    // it expands it, and the other session actions are disabled.
+    if app.selection_collapsed() && matches!(action, Action::Attach | Action::AttachSized | Action::Windows) {
+        app.toggle_group_collapsed();
+        return;
+    }
+    match action {
+        // Only bound to a key sequence, otherwise handled by handle_key_events in every view
+        Action::Quit => {
+            app.quit();
+        }
+        // Handled by handle_key_events in every view
+        Action::Detach => {}
+        // Move up the list
+        Action::Up => {
+            app.select_previous()
+        }
+        // Move down the list
+        Action::Down => {
+            app.select_next()
+        }
+        Action::New => {
+            app.confirm_new_session();
+        }
+        // Attach the selected session
+        Action::Attach => {
+            app.attach_selected(app.attach_size);
+        }
+        // Attach the selected session, asking how to size it first
+        Action::AttachSized => {
+            app.confirm_attach_size();
+        }
+        // Attach the selected session, running a command in it first
+        Action::AttachRun => {
+            app.confirm_attach_run();
+        }
+        // Enter/select to drill into the session's windows
+        Action::Windows => {
+            app.show_windows();
+        }
+        // Jump to top of list
+        Action::Top => {
+            app.select_first();
+        }
+        // Refresh on demand, e.g. with background refreshes disabled
+        Action::Refresh => {
+            app.refresh();
+            app.show_status("Refreshed");
+        }
+        // Jump to top end of list
+        Action::Bottom => {
+            app.select_last();
+        }
+        // Move through long lists a screenful at a time
+        Action::PageUp => {
+            app.select_page(-1, 1);
+        }
+        Action::PageDown => {
+            app.select_page(1, 1);
+        }
+        Action::HalfPageUp => {
+            app.select_page(-1, 2);
+        }
+        Action::HalfPageDown => {
+            app.select_page(1, 2);
+        }
+        Action::Delete => {
+            // Start the delete process for the currently selected
+            // session
+            app.confirm_delete();
+        }
+        Action::NewAttach => {
+            // Create and attach a new session. If the user is currently
+            // in a tmux session so the attach would fail, instead of
+            // attempting attach, just refresh the list
+            app.new_session(None, None, None);
+        }
+        Action::Rename => {
+            app.confirm_rename();
+        }
+        Action::RenameCurrent => {
+            // Rename the session tmm is running in, like tmux's own `prefix $`
+            app.confirm_rename_current();
+        }
+        Action::Mark => {
+            app.toggle_mark();
+        }
+        // Group the list into sections by host or tmux session group
+        Action::Group => {
+            app.cycle_group_by();
+        }
+        // Cycle the sort order, remembered for the next start
+        Action::Sort => {
+            app.cycle_sort();
+        }
+        // Show only detached, then only attached sessions
+        Action::Filter => {
+            app.cycle_attached_filter();
+        }
+        // Show directories and other sources alongside or instead of the sessions
+        Action::Sources => {
+            app.cycle_sources();
+        }
+        // Collapse or expand the selected session's section
+        Action::Fold => {
+            app.toggle_group_collapsed();
+        }
+        Action::History => {
+            app.show_history();
+        }
+        Action::Preview => {
+            app.toggle_preview();
+        }
+        Action::Search => {
+            app.search();
+        }
+        Action::Theme => {
+            app.cycle_theme();
+        }
+        Action::DetachClients => {
+            app.confirm_detach_clients();
+        }
+        Action::KillServer => {
+            app.confirm_kill_server();
+        }
+        Action::Clients => {
+            app.show_clients();
+        }
+        Action::RefreshHost => {
+            app.refresh_selected_host();
+        }
+        Action::EditConfig => {
+            app.edit_config();
+        }
+        Action::Templates => {
+            app.show_templates();
+        }
+        // Accent the selected session with a color, e.g. to make prod stand out
+        Action::Color => {
+            app.confirm_color();
+        }
+        // Jump to a row on screen by its hint
+        Action::Jump => {
+            app.start_hints();
+        }
+        // Kill the selected session, keeping its layout to restore it from the archive view
+        Action::Archive => {
+            app.archive_selected();
+        }
+        Action::Archived => {
+            app.show_archive();
+        }
+        // Tell which tmux server the selected session lives on, e.g. with several sockets around
+        Action::ServerInfo => {
+            app.show_server_info();
+        }
+        Action::Help => {
+            app.show_help();
+        }
+        // Find where something was run, whichever session it was in
+        Action::DeepSearch => {
+            app.start_deep_search();
+        }
+        // Mouse selection is out while tmm captures the mouse, so the keyboard has to do
+        Action::CopyMode => {
+            app.start_copy_mode();
+        }
+        // Several servers are run to keep sessions apart, e.g. with `tmux -L work`
+        Action::Servers => {
+            app.show_servers();
+        }
+    }
+}
+
+/// Select the highlighted match of the search and, if `attach`, attach it
+fn finish_search(app: &mut App, attach: bool) {
+    let Some(new_row) = app.search_session_selected else {
+        // Nothing matches, so there is nothing to attach
+        if !attach {
+            app.dismiss_all();
+        }
+        return;
+    };
+    app.selected_session = new_row;
+    app.dismiss_all();
+    if attach {
+        app.attach_selected(app.attach_size);
+    }
+}
+
+/// Handles the mouse events and updates the state of [`App`]. Clicking a row of the sessions list
+/// selects it and double clicking attaches it, while clicking a popup's button presses its key.
+pub fn handle_mouse_events(mouse_event: MouseEvent, app: &mut App) -> AppResult<()> {
+    let (column, row) = (mouse_event.column, mouse_event.row);
+    match mouse_event.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            let button = app.click_targets.iter().find(|(area, _)| {
+                (area.x..area.x + area.width).contains(&column) && (area.y..area.y + area.height).contains(&row)
+            });
+            if let Some((_, key)) = button {
+                return handle_key_events(KeyEvent::from(*key), app);
+            }
+            if app.state != AppState::Sessions {
+                return Ok(());
+            }
+            let Some(clicked) = app.list_row_at(column, row) else { return Ok(()) };
+            let double = app.last_click.is_some_and(|(at, last)| last == clicked && at.elapsed() < DOUBLE_CLICK);
+            app.last_click = (!double).then(|| (Instant::now(), clicked));
+            app.pending_detach = false;
+            app.pending_keys = None;
+            app.select_row(clicked);
+            // Headers fold on every click instead
+            if double && matches!(app.list_rows().get(clicked), Some(ListRow::Session(_) | ListRow::Entry(_))) {
+                run_sessions_action(Action::Attach, app);
+            }
+        }
+        MouseEventKind::ScrollUp if app.state == AppState::Sessions => app.select_previous(),
+        MouseEventKind::ScrollDown if app.state == AppState::Sessions => app.select_next(),
+        _ => {}
     }
     Ok(())
 }
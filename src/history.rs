@@ -0,0 +1,181 @@
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Local};
+
+/// Maximum number of attach events kept in the history file
+const MAX_ENTRIES: usize = 500;
+
+/// One recorded attach
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    /// Remote host of the session, `None` for the local server
+    pub host: Option<String>,
+    /// Session name
+    pub session: String,
+    /// Seconds until the next recorded attach, of any session. tmm hands the terminal over to tmux
+    /// when it attaches and does not see the detach, so this is not how long the session was used:
+    /// an attach left overnight counts until the next morning's. `None` for the most recent attach.
+    pub until_next_attach: Option<u64>,
+}
+
+/// Directory for tmm's persistent state: `$XDG_STATE_HOME/tmm` or `~/.local/state/tmm`
+pub fn state_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local").join("state"),
+    };
+    Some(base.join("tmm"))
+}
+
+fn history_file() -> Option<PathBuf> {
+    Some(state_dir()?.join("history"))
+}
+
+/// Current time in seconds since the epoch
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Record an attach event. History is a convenience, so failures to write it are ignored.
+pub fn record(host: Option<&str>, session: &str) {
+    let Some(path) = history_file() else { return };
+    record_in(&path, now(), host, session);
+}
+
+/// Record an attach event at `timestamp` in the history file at `path`
+fn record_in(path: &Path, timestamp: u64, host: Option<&str>, session: &str) {
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    // Tab separated: timestamp, host (empty for local), session name
+    let line = format!("{}\t{}\t{}\n", timestamp, host.unwrap_or(""), session);
+    let mut lines: Vec<String> = fs::read_to_string(path).unwrap_or_default()
+        .lines().map(|l| format!("{}\n", l)).collect();
+    if lines.len() >= MAX_ENTRIES {
+        // Rewrite the file without the oldest entries
+        lines.drain(..=lines.len() - MAX_ENTRIES);
+        lines.push(line);
+        let _ = fs::write(path, lines.concat());
+    } else if let Ok(mut file) = fs::OpenOptions::new().create(true).append(true).open(path) {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
+
+/// Load the recorded attach events, most recent first
+pub fn load() -> Vec<HistoryEntry> {
+    let Some(contents) = history_file().and_then(|path| fs::read_to_string(path).ok()) else {
+        return vec![];
+    };
+    parse(&contents)
+}
+
+/// Parse the lines of the history file, most recent first, skipping any that are not attaches
+fn parse(contents: &str) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = contents.lines().filter_map(|line| {
+        let mut parts = line.splitn(3, '\t');
+        let timestamp = parts.next()?.parse().ok()?;
+        let host = parts.next()?;
+        let session = parts.next()?;
+        Some(HistoryEntry {
+            timestamp,
+            host: (!host.is_empty()).then(|| host.to_owned()),
+            session: session.to_owned(),
+            until_next_attach: None,
+        })
+    }).collect();
+    for idx in 1..entries.len() {
+        entries[idx - 1].until_next_attach = Some(entries[idx].timestamp.saturating_sub(entries[idx - 1].timestamp));
+    }
+    entries.reverse();
+    entries
+}
+
+/// The time zone times are shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeZone {
+    /// The machine's, from `$TZ` or `/etc/localtime`
+    #[default]
+    Local,
+    /// UTC, e.g. for output that must not depend on the machine
+    Utc,
+}
+
+/// Format a timestamp in `zone`, e.g. `2024-03-01 14:05`
+pub fn format_timestamp(timestamp: u64, zone: TimeZone) -> String {
+    format_time(timestamp, 0, "%Y-%m-%d %H:%M", zone)
+}
+
+/// Format a time, in seconds and milliseconds since the epoch, in `zone` with a
+/// [`chrono` format](chrono::format::strftime). Times out of range are shown as the seconds.
+pub fn format_time(seconds: u64, millis: u32, format: &str, zone: TimeZone) -> String {
+    let Some(time) = i64::try_from(seconds).ok().and_then(|seconds| DateTime::from_timestamp(seconds, millis * 1_000_000)) else {
+        return seconds.to_string();
+    };
+    match zone {
+        TimeZone::Local => time.with_timezone(&Local).format(format).to_string(),
+        TimeZone::Utc => time.format(format).to_string(),
+    }
+}
+
+/// Format a duration compactly, e.g. `45s`, `12m`, `3h05m`, `2d04h`
+pub fn format_duration(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+        _ => format!("{}d{:02}h", seconds / 86400, seconds % 86400 / 3600),
+    }
+}
+
+/// Name of the local machine, used to label local entries
+pub fn local_hostname() -> String {
+    let hostname = gethostname::gethostname().to_string_lossy().into_owned();
+    if hostname.is_empty() { "localhost".to_owned() } else { hostname }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_are_formatted_in_their_zone() {
+        assert_eq!(format_timestamp(1709301900, TimeZone::Utc), "2024-03-01 14:05");
+        assert_eq!(format_time(1709301903, 120, "%Y-%m-%dT%H:%M:%S%.3f", TimeZone::Utc), "2024-03-01T14:05:03.120");
+        assert_eq!(format_timestamp(1709301900, TimeZone::Local).len(), "2024-03-01 14:05".len());
+        // Out of range
+        assert_eq!(format_timestamp(u64::MAX, TimeZone::Utc), u64::MAX.to_string());
+    }
+
+    #[test]
+    fn history_keeps_the_latest_entries() {
+        let path = env::temp_dir().join(format!("tmm-history-test-{}", std::process::id())).join("history");
+        for timestamp in 0..MAX_ENTRIES as u64 + 2 {
+            record_in(&path, timestamp, None, "dev");
+        }
+        record_in(&path, 1000, Some("build"), "ci");
+        let entries = parse(&fs::read_to_string(&path).unwrap());
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries[0], HistoryEntry { timestamp: 1000, host: Some("build".to_owned()), session: "ci".to_owned(), until_next_attach: None });
+        // The oldest three went to make room
+        assert_eq!(entries[MAX_ENTRIES - 1].timestamp, 3);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn entries_know_the_time_until_the_next_attach() {
+        let entries = parse("1709301900\t\tdev\nnot a timestamp\t\tops\n1709302000\tbuild\n\n1709309200\tbuild\tci\n");
+        assert_eq!(entries.iter().map(|entry| entry.session.as_str()).collect::<Vec<_>>(), ["ci", "dev"]);
+        assert_eq!(entries[0].host.as_deref(), Some("build"));
+        assert_eq!(entries[0].until_next_attach, None);
+        // Until the next attach of any session, not how long it was used
+        assert_eq!(entries[1].until_next_attach, Some(7300));
+        assert_eq!(entries[1].host, None);
+    }
+}
@@ -0,0 +1,399 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config;
+use crate::template::{Template, TemplateWindow};
+
+/// A YAML (or JSON) value, where every scalar is kept as a string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Yaml {
+    Null,
+    Scalar(String),
+    List(Vec<Yaml>),
+    /// Entries in the order of the file
+    Map(Vec<(String, Yaml)>),
+}
+
+impl Yaml {
+    /// The value of `key` in a mapping
+    pub fn get(&self, key: &str) -> Option<&Yaml> {
+        match self {
+            Yaml::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Yaml::Scalar(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// The tool a layout file comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Tmuxinator,
+    Tmuxp,
+}
+
+/// The templates installed by `tmm template import`, then those made from the layouts of
+/// tmuxinator and tmuxp in their usual directories
+pub fn discover() -> Vec<Template> {
+    let installed = config::templates_dir().map(|dir| installed(&dir)).unwrap_or_default();
+    installed.into_iter().chain(templates(&layout_dirs())).collect()
+}
+
+/// Templates made from the tmuxinator and tmuxp layout files in `dirs`, by file name within each
+/// directory. Files that cannot be read or understood (e.g. tmuxinator's ERB templates) are left
+/// out.
+pub fn templates(dirs: &[(Tool, PathBuf)]) -> Vec<Template> {
+    let mut templates = vec![];
+    for (tool, dir) in dirs {
+        let tool = *tool;
+        let Ok(entries) = fs::read_dir(dir) else { continue };
+        let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect();
+        paths.sort();
+        for path in paths {
+            let extensions: &[&str] = match tool {
+                Tool::Tmuxinator => &["yml", "yaml"],
+                Tool::Tmuxp => &["yml", "yaml", "json"],
+            };
+            if !path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| extensions.contains(&ext)) {
+                continue;
+            }
+            if let Some(template) = load(tool, &path) {
+                templates.push(template);
+            }
+        }
+    }
+    templates
+}
+
+/// Where tmuxinator and tmuxp look for layout files
+pub fn layout_dirs() -> Vec<(Tool, PathBuf)> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => home.as_ref().map(|home| home.join(".config")),
+    };
+    let mut dirs = vec![];
+    for (tool, variable, name, dotted) in [
+        (Tool::Tmuxinator, "TMUXINATOR_CONFIG", "tmuxinator", ".tmuxinator"),
+        (Tool::Tmuxp, "TMUXP_CONFIGDIR", "tmuxp", ".tmuxp"),
+    ] {
+        dirs.extend(env::var_os(variable).filter(|dir| !dir.is_empty()).map(|dir| (tool, PathBuf::from(dir))));
+        dirs.extend(config.as_ref().map(|config| (tool, config.join(name))));
+        dirs.extend(home.as_ref().map(|home| (tool, home.join(dotted))));
+    }
+    dirs
+}
+
+/// Read a layout file as a template named after its session, or else after the file
+pub fn load(tool: Tool, path: &Path) -> Option<Template> {
+    let text = fs::read_to_string(path).ok()?;
+    let yaml = parse_layout(&text, path.extension().is_some_and(|ext| ext == "json")).ok()?;
+    let stem = path.file_stem()?.to_string_lossy();
+    let mut template = match tool {
+        Tool::Tmuxinator => tmuxinator(&yaml, &stem),
+        Tool::Tmuxp => tmuxp(&yaml, &stem),
+    }?;
+    template.file = Some(path.to_owned());
+    Some(template)
+}
+
+/// The templates installed by `tmm template import` in `dir`, by file name: tmm's own (`.toml`)
+/// and tmuxp's (`.yaml`, `.yml` or `.json`). Files that cannot be read are left out.
+pub fn installed(dir: &Path) -> Vec<Template> {
+    let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect();
+    paths.sort();
+    paths.iter().filter_map(|path| match path.extension()?.to_str()? {
+        "toml" => {
+            let text = fs::read_to_string(path).ok()?;
+            let mut template = config::template_file(&text, &path.file_stem()?.to_string_lossy()).ok()?;
+            template.file = Some(path.to_owned());
+            Some(template)
+        }
+        "yaml" | "yml" | "json" => load(Tool::Tmuxp, path),
+        _ => None,
+    }).collect()
+}
+
+/// Fetch a template file from a URL (with `curl`) or a path, check that it makes a template with
+/// windows, and copy it into `dir` as is, named after the template. An installed template of the
+/// same name is only replaced with `force`. Returns the template and where it was installed.
+pub fn install(source: &str, dir: &Path, force: bool) -> Result<(Template, PathBuf), String> {
+    let remote = source.starts_with("http://") || source.starts_with("https://");
+    let text = if remote {
+        let output = Command::new("curl").args(["-fsSL", source]).output()
+            .map_err(|err| format!("could not run curl: {}", err))?;
+        if !output.status.success() {
+            return Err(format!("could not fetch {}: {}", source, String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        String::from_utf8(output.stdout).map_err(|_| format!("{} is not text", source))?
+    } else {
+        fs::read_to_string(source).map_err(|err| format!("could not read {}: {}", source, err))?
+    };
+
+    // The format goes by the extension, of the URL's path for a URL
+    let path = source.split(['?', '#']).next().unwrap_or_default();
+    let file_name = path.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+    let (stem, extension) = file_name.rsplit_once('.').unwrap_or((file_name, ""));
+    let template = match extension {
+        "toml" => config::template_file(&text, stem)?,
+        "yaml" | "yml" | "json" => {
+            let yaml = parse_layout(&text, extension == "json")?;
+            tmuxp(&yaml, stem).ok_or_else(|| "not a tmuxp session: expected a list of windows".to_owned())?
+        }
+        _ => return Err(format!("unknown template format {:?}, expected .toml, .yaml, .yml or .json", extension)),
+    };
+    if template.windows.is_empty() {
+        return Err("the template has no windows".to_owned());
+    }
+
+    let destination = dir.join(format!("{}.{}", template.name.replace('/', "_"), extension));
+    if destination.exists() && !force {
+        return Err(format!("{} exists already, use --force to replace it", destination.display()));
+    }
+    fs::create_dir_all(dir).map_err(|err| format!("could not create {}: {}", dir.display(), err))?;
+    fs::write(&destination, text).map_err(|err| format!("could not write {}: {}", destination.display(), err))?;
+    Ok((template, destination))
+}
+
+/// A template from a tmuxinator project, whose windows are each a mapping of the window name to
+/// its command, or to its layout and panes
+pub fn tmuxinator(yaml: &Yaml, default_name: &str) -> Option<Template> {
+    let mut template = Template::new(yaml.get("name").and_then(Yaml::as_str).unwrap_or(default_name));
+    template.dir = yaml.get("root").or(yaml.get("project_root")).and_then(Yaml::as_str).map(str::to_owned);
+    let Some(Yaml::List(windows)) = yaml.get("windows").or(yaml.get("tabs")) else { return None };
+    for window in windows {
+        let Yaml::Map(entries) = window else { return None };
+        for (name, value) in entries {
+            let (layout, panes) = match value {
+                Yaml::Map(_) => {
+                    let panes = match value.get("panes") {
+                        // Panes are commands, or mappings of a pane title to its commands
+                        Some(Yaml::List(panes)) => panes.iter().map(|pane| match pane {
+                            Yaml::Map(entries) => entries.first().map(|(_, commands)| run(commands)).unwrap_or_default(),
+                            pane => run(pane),
+                        }).collect(),
+                        _ => vec![],
+                    };
+                    (value.get("layout").and_then(Yaml::as_str).map(str::to_owned), panes)
+                }
+                Yaml::Null => (None, vec![]),
+                commands => (None, vec![run(commands)]),
+            };
+            template.windows.push(TemplateWindow { name: name.to_owned(), layout, panes });
+        }
+    }
+    Some(template)
+}
+
+/// A template from a tmuxp session, whose windows are mappings with a `window_name`, a `layout`
+/// and `panes`, and whose `shell_command_before` commands run first in every pane
+pub fn tmuxp(yaml: &Yaml, default_name: &str) -> Option<Template> {
+    let mut template = Template::new(yaml.get("session_name").and_then(Yaml::as_str).unwrap_or(default_name));
+    template.dir = yaml.get("start_directory").and_then(Yaml::as_str).map(str::to_owned);
+    let before = yaml.get("shell_command_before").map(run).unwrap_or_default();
+    let Some(Yaml::List(windows)) = yaml.get("windows") else { return None };
+    for window in windows {
+        let Yaml::Map(_) = window else { return None };
+        let window_before = window.get("shell_command_before").map(run).unwrap_or_default();
+        let panes = match window.get("panes") {
+            Some(Yaml::List(panes)) => panes.iter().map(|pane| {
+                let command = match pane {
+                    Yaml::Scalar(word) if word == "blank" || word == "pane" => String::new(),
+                    Yaml::Map(_) => pane.get("shell_command").map(run).unwrap_or_default(),
+                    pane => run(pane),
+                };
+                let commands = [before.as_str(), window_before.as_str(), command.as_str()];
+                commands.into_iter().filter(|command| !command.is_empty()).collect::<Vec<_>>().join("; ")
+            }).collect(),
+            _ => vec![],
+        };
+        template.windows.push(TemplateWindow {
+            name: window.get("window_name").and_then(Yaml::as_str).unwrap_or_default().to_owned(),
+            layout: window.get("layout").and_then(Yaml::as_str).map(str::to_owned),
+            panes,
+        });
+    }
+    Some(template)
+}
+
+/// A command, or a list of commands run one after the other, as a single command line
+fn run(commands: &Yaml) -> String {
+    match commands {
+        Yaml::Scalar(command) => command.to_owned(),
+        Yaml::List(commands) => {
+            commands.iter().map(run).filter(|command| !command.is_empty()).collect::<Vec<_>>().join("; ")
+        }
+        _ => String::new(),
+    }
+}
+
+/// Parse a layout file, as JSON if `json` and as YAML otherwise
+pub fn parse_layout(text: &str, json: bool) -> Result<Yaml, String> {
+    if json {
+        serde_json::from_str::<serde_json::Value>(text).map(Yaml::from).map_err(|err| err.to_string())
+    } else {
+        serde_yaml::from_str::<serde_yaml::Value>(text).map(Yaml::from).map_err(|err| err.to_string())
+    }
+}
+
+impl From<serde_yaml::Value> for Yaml {
+    fn from(value: serde_yaml::Value) -> Self {
+        match value {
+            serde_yaml::Value::Null => Yaml::Null,
+            serde_yaml::Value::Bool(b) => Yaml::Scalar(b.to_string()),
+            serde_yaml::Value::Number(n) => Yaml::Scalar(n.to_string()),
+            serde_yaml::Value::String(s) => Yaml::Scalar(s),
+            serde_yaml::Value::Sequence(items) => Yaml::List(items.into_iter().map(Yaml::from).collect()),
+            // Keys such as the `1:` of a window named 1 are scalars too
+            serde_yaml::Value::Mapping(entries) => Yaml::Map(entries.into_iter().map(|(key, value)| {
+                let key = match Yaml::from(key) {
+                    Yaml::Scalar(key) => key,
+                    _ => String::new(),
+                };
+                (key, Yaml::from(value))
+            }).collect()),
+            serde_yaml::Value::Tagged(tagged) => Yaml::from(tagged.value),
+        }
+    }
+}
+
+impl From<serde_json::Value> for Yaml {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Yaml::Null,
+            serde_json::Value::Bool(b) => Yaml::Scalar(b.to_string()),
+            serde_json::Value::Number(n) => Yaml::Scalar(n.to_string()),
+            serde_json::Value::String(s) => Yaml::Scalar(s),
+            serde_json::Value::Array(items) => Yaml::List(items.into_iter().map(Yaml::from).collect()),
+            serde_json::Value::Object(entries) => Yaml::Map(entries.into_iter().map(|(key, value)| (key, Yaml::from(value))).collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(name: &str, layout: Option<&str>, panes: &[&str]) -> TemplateWindow {
+        TemplateWindow {
+            name: name.to_owned(),
+            layout: layout.map(str::to_owned),
+            panes: panes.iter().map(|pane| pane.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn reads_tmuxinator_projects() {
+        let yaml = parse_layout(r#"
+# ~/.config/tmuxinator/web.yml
+name: web
+root: ~/src/web
+
+windows:
+  - editor:
+      layout: main-vertical
+      panes:
+        - vim
+        - - cd test
+          - guard
+        -
+  - server: "bundle exec rails s"  # a single command
+  - logs:
+"#, false).unwrap();
+        let template = tmuxinator(&yaml, "file").unwrap();
+        assert_eq!(template.name, "web");
+        assert_eq!(template.dir.as_deref(), Some("~/src/web"));
+        assert_eq!(template.windows, [
+            window("editor", Some("main-vertical"), &["vim", "cd test; guard", ""]),
+            window("server", None, &["bundle exec rails s"]),
+            window("logs", None, &[]),
+        ]);
+    }
+
+    #[test]
+    fn reads_tmuxp_sessions_in_yaml_and_json() {
+        let yaml = parse_layout("
+session_name: api
+shell_command_before: source .env
+windows:
+- window_name: dev
+  layout: tiled
+  panes:
+  - shell_command: [make run]
+  - blank
+- window_name: 'it''s'
+  panes:
+    - shell_command:
+        - cd docs
+        - make
+", false).unwrap();
+        let template = tmuxp(&yaml, "file").unwrap();
+        assert_eq!(template.name, "api");
+        assert_eq!(template.windows, [
+            window("dev", Some("tiled"), &["source .env; make run", "source .env"]),
+            window("it's", None, &["source .env; cd docs; make"]),
+        ]);
+        let json = parse_layout(r#"{
+            "start_directory": "/srv",
+            "windows": [{"window_name": "top", "panes": ["htop", null]}]
+        }"#, true).unwrap();
+        let template = tmuxp(&json, "monitor").unwrap();
+        assert_eq!(template.name, "monitor");
+        assert_eq!(template.dir.as_deref(), Some("/srv"));
+        assert_eq!(template.windows, [window("top", None, &["htop", ""])]);
+        assert!(parse_layout("a: 1\n   b: 2", false).is_err());
+        assert!(parse_layout("{\"windows\": [}", true).is_err());
+    }
+
+    #[test]
+    fn finds_layouts_in_the_given_directories() {
+        let dir = env::temp_dir().join(format!("tmm-layouts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("monitor.json"), r#"{"windows": [{"window_name": "top", "panes": ["htop"]}]}"#).unwrap();
+        fs::write(dir.join("notes.txt"), "windows: []").unwrap();
+        fs::write(dir.join("broken.yaml"), "windows: [").unwrap();
+        let templates = templates(&[(Tool::Tmuxp, dir.clone()), (Tool::Tmuxinator, dir.join("missing"))]);
+        let names: Vec<&str> = templates.iter().map(|template| template.name.as_str()).collect();
+        assert_eq!(names, ["monitor"]);
+        assert_eq!(templates[0].file, Some(dir.join("monitor.json")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn installs_valid_template_files_by_name() {
+        let dir = env::temp_dir().join(format!("tmm-install-{}", std::process::id()));
+        let (sources, installed_dir) = (dir.join("sources"), dir.join("templates"));
+        fs::create_dir_all(&sources).unwrap();
+        let file = |name: &str, text: &str| {
+            fs::write(sources.join(name), text).unwrap();
+            sources.join(name).to_string_lossy().into_owned()
+        };
+        let toml = file("web.toml", "name = \"web/app\"\nwindows = [\"editor\", \"server\"]\n");
+        let (template, path) = install(&toml, &installed_dir, false).unwrap();
+        assert_eq!(template.name, "web/app");
+        assert_eq!(path, installed_dir.join("web_app.toml"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), fs::read_to_string(&toml).unwrap());
+        // Replaced only when forced
+        assert!(install(&toml, &installed_dir, false).unwrap_err().contains("--force"));
+        assert!(install(&toml, &installed_dir, true).is_ok());
+        let yaml = file("api.yml", "session_name: api\nwindows:\n- window_name: dev\n  panes: [make run]\n");
+        assert_eq!(install(&yaml, &installed_dir, false).unwrap().1, installed_dir.join("api.yml"));
+        let names: Vec<String> = installed(&installed_dir).into_iter().map(|template| template.name).collect();
+        assert_eq!(names, ["api", "web/app"]);
+        // Nothing is installed from files that do not make a template with windows
+        for (name, text) in [("empty.toml", "name = \"empty\"\n"), ("list.yaml", "- a\n- b\n"), ("notes.txt", "windows")] {
+            assert!(install(&file(name, text), &installed_dir, false).is_err(), "{}", name);
+        }
+        assert!(install(&sources.join("missing.toml").to_string_lossy(), &installed_dir, false).is_err());
+        assert_eq!(fs::read_dir(&installed_dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,609 @@
+use std::fmt;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use indexmap::IndexMap;
+
+/// Something a key can be bound to in the sessions view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Quit without attaching (in every view but the prompts)
+    Quit,
+    /// Pressed twice, quit and detach the tmux client (in every view but the prompts)
+    Detach,
+    /// Select the previous row
+    Up,
+    /// Select the next row
+    Down,
+    /// Select the first row
+    Top,
+    /// Select the last row
+    Bottom,
+    /// Move the selection up a page
+    PageUp,
+    /// Move the selection down a page
+    PageDown,
+    /// Move the selection up half a page
+    HalfPageUp,
+    /// Move the selection down half a page
+    HalfPageDown,
+    /// Attach the selected session
+    Attach,
+    /// Choose how to size the selected session, then attach it
+    AttachSized,
+    /// Prompt for a command to run in the selected session, then attach it
+    AttachRun,
+    /// Browse the selected session's windows
+    Windows,
+    /// Rename the selected (or marked) sessions
+    Rename,
+    /// Rename the session tmm is running in
+    RenameCurrent,
+    /// Prompt for a new session
+    New,
+    /// Create and attach an unnamed session
+    NewAttach,
+    /// Kill the selected (or marked) sessions
+    Delete,
+    /// Detach every client from the selected session
+    DetachClients,
+    /// Kill the tmux server of the selected session
+    KillServer,
+    /// Show the clients attached to the selected session
+    Clients,
+    /// Mark or unmark the selected session
+    Mark,
+    /// Cycle how the list is grouped
+    Group,
+    /// Cycle how the sessions are sorted
+    Sort,
+    /// Cycle between all, detached and attached sessions
+    Filter,
+    /// Cycle the sources shown in the list
+    Sources,
+    /// Collapse or expand the selected section
+    Fold,
+    /// Show the attach history
+    History,
+    /// Show or hide the preview pane
+    Preview,
+    /// Search the sessions
+    Search,
+    /// Refresh the list
+    Refresh,
+    /// Refresh the sessions of the selected session's host only
+    RefreshHost,
+    /// Switch to the next color theme
+    Theme,
+    /// Edit the configuration file, and reload it
+    EditConfig,
+    /// Browse the session templates
+    Templates,
+    /// Set the selected session's accent color
+    Color,
+    /// Show hints on the rows on screen to jump to one by typing its hint
+    Jump,
+    /// Save the selected session's windows and panes, then kill it
+    Archive,
+    /// Show the archived sessions
+    Archived,
+    /// Show the pid, socket and start time of the selected session's tmux server
+    ServerInfo,
+    /// Show every key of every view
+    Help,
+    /// Search the scrollback of every pane for a string
+    DeepSearch,
+    /// Browse the text of the list by keyboard to copy rows of it
+    CopyMode,
+    /// List the local tmux servers to switch to another one
+    Servers,
+}
+
+impl Action {
+    /// Every action, in the order bindings are looked up
+    pub const ALL: [Action; 45] = [
+        Action::Quit, Action::Detach, Action::Up, Action::Down, Action::Top, Action::Bottom,
+        Action::PageUp, Action::PageDown, Action::HalfPageUp, Action::HalfPageDown,
+        Action::Attach, Action::AttachSized, Action::AttachRun, Action::Windows, Action::Rename, Action::RenameCurrent, Action::New,
+        Action::NewAttach, Action::Delete, Action::DetachClients, Action::KillServer, Action::Clients,
+        Action::Mark, Action::Group, Action::Sort, Action::Filter,
+        Action::Sources, Action::Fold,
+        Action::History, Action::Preview, Action::Search, Action::Refresh, Action::RefreshHost, Action::Theme,
+        Action::EditConfig, Action::Templates, Action::Color, Action::Jump,
+        Action::Archive, Action::Archived, Action::ServerInfo, Action::Help, Action::DeepSearch,
+        Action::CopyMode, Action::Servers,
+    ];
+
+    /// Whether the action changes sessions or the server, or hands the terminal to an editor,
+    /// which read-only mode rules out
+    pub fn mutates(self) -> bool {
+        matches!(self, Action::AttachSized | Action::AttachRun | Action::Rename | Action::RenameCurrent | Action::New
+            | Action::NewAttach | Action::Delete | Action::DetachClients | Action::KillServer | Action::EditConfig
+            | Action::Templates | Action::Color | Action::Archive)
+    }
+
+    /// Name used in the `[keys]` table of the configuration file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Detach => "detach",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::HalfPageDown => "half_page_down",
+            Action::Attach => "attach",
+            Action::AttachSized => "attach_sized",
+            Action::AttachRun => "attach_run",
+            Action::Windows => "windows",
+            Action::Rename => "rename",
+            Action::RenameCurrent => "rename_current",
+            Action::New => "new",
+            Action::NewAttach => "new_attach",
+            Action::Delete => "delete",
+            Action::DetachClients => "detach_clients",
+            Action::KillServer => "kill_server",
+            Action::Clients => "clients",
+            Action::Mark => "mark",
+            Action::Group => "group",
+            Action::Sort => "sort",
+            Action::Filter => "filter",
+            Action::Sources => "sources",
+            Action::Fold => "fold",
+            Action::History => "history",
+            Action::Preview => "preview",
+            Action::Search => "search",
+            Action::Refresh => "refresh",
+            Action::RefreshHost => "refresh_host",
+            Action::Theme => "theme",
+            Action::EditConfig => "edit_config",
+            Action::Templates => "templates",
+            Action::Color => "color",
+            Action::Jump => "jump",
+            Action::Archive => "archive",
+            Action::Archived => "archived",
+            Action::ServerInfo => "server_info",
+            Action::Help => "help",
+            Action::DeepSearch => "deep_search",
+            Action::CopyMode => "copy_mode",
+            Action::Servers => "servers",
+        }
+    }
+
+    /// What the action does, as the help lists it
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Detach => "Quit and detach the tmux client (pressed twice)",
+            Action::Up => "Select the previous row",
+            Action::Down => "Select the next row",
+            Action::Top => "Select the first row",
+            Action::Bottom => "Select the last row",
+            Action::PageUp => "Move up a page",
+            Action::PageDown => "Move down a page",
+            Action::HalfPageUp => "Move up half a page",
+            Action::HalfPageDown => "Move down half a page",
+            Action::Attach => "Attach the session",
+            Action::AttachSized => "Choose how to size the session, then attach it",
+            Action::AttachRun => "Run a command in the session, then attach it",
+            Action::Windows => "Browse the session's windows",
+            Action::Rename => "Rename the session (or the marked ones)",
+            Action::RenameCurrent => "Rename the session tmm runs in",
+            Action::New => "Create a session",
+            Action::NewAttach => "Create and attach an unnamed session",
+            Action::Delete => "Kill the session (or the marked ones)",
+            Action::DetachClients => "Detach every client from the session",
+            Action::KillServer => "Kill the session's tmux server",
+            Action::Clients => "List the session's clients",
+            Action::Mark => "Mark or unmark the session",
+            Action::Group => "Cycle how the list is grouped",
+            Action::Sort => "Cycle how the list is sorted",
+            Action::Filter => "Cycle between all, detached and attached sessions",
+            Action::Sources => "Cycle the sources listed",
+            Action::Fold => "Collapse or expand the section",
+            Action::History => "Show the attach history",
+            Action::Preview => "Show or hide the preview",
+            Action::Search => "Search the sessions",
+            Action::Refresh => "Refresh the list",
+            Action::RefreshHost => "Refresh the session's host only",
+            Action::Theme => "Switch to the next color theme",
+            Action::EditConfig => "Edit the configuration file",
+            Action::Templates => "Browse the session templates",
+            Action::Color => "Set the session's accent color",
+            Action::Jump => "Jump to a row by its hint",
+            Action::Archive => "Archive the session",
+            Action::Archived => "Show the archived sessions",
+            Action::ServerInfo => "Show the session's tmux server",
+            Action::Help => "Show this help",
+            Action::DeepSearch => "Search the scrollback of every pane",
+            Action::CopyMode => "Copy rows of the list",
+            Action::Servers => "Switch to another local tmux server",
+        }
+    }
+
+    /// Look up an action by [`Action::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// A key with its Ctrl and Alt modifiers. Shift is part of the character instead, e.g. `G`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Bind a key without modifiers
+    pub const fn plain(code: KeyCode) -> Self {
+        Self { code, modifiers: KeyModifiers::NONE }
+    }
+
+    /// Bind a character pressed with Ctrl
+    pub const fn ctrl(c: char) -> Self {
+        Self { code: KeyCode::Char(c), modifiers: KeyModifiers::CONTROL }
+    }
+
+    /// Parse a key as written in the configuration file and the hotkey bar: a character, a key
+    /// name such as `Enter`, `Tab`, `Up` or `Space`, optionally prefixed with `C-` and/or `M-`
+    pub fn parse(key: &str) -> Result<Self, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = key;
+        loop {
+            if let Some(r) = rest.strip_prefix("C-").filter(|r| !r.is_empty()) {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("M-").filter(|r| !r.is_empty()) {
+                modifiers |= KeyModifiers::ALT;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+        let code = match rest {
+            "Enter" => KeyCode::Enter,
+            "Esc" => KeyCode::Esc,
+            "Tab" => KeyCode::Tab,
+            "BackTab" => KeyCode::BackTab,
+            "Backspace" => KeyCode::Backspace,
+            "Delete" => KeyCode::Delete,
+            "Space" => KeyCode::Char(' '),
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(format!("unknown key {:?}", key)),
+                }
+            }
+        };
+        Ok(Self { code, modifiers })
+    }
+
+    /// The key of a key press
+    pub fn from_event(event: &KeyEvent) -> Self {
+        // Terminals report Shift inconsistently for characters, and it is implied by the character
+        Self { code: event.code, modifiers: event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT) }
+    }
+
+    /// Whether a key press is this key
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        Self::from_event(event) == *self
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "C-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "M-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Keys pressed one after the other, such as `gg` or `C-x C-s`, usually just one
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeySequence(pub Vec<KeyBinding>);
+
+impl KeySequence {
+    /// Parse a sequence as written in the configuration file: keys separated by spaces, where a
+    /// run of plain characters that is not a key name is one key per character, so `gg` is `g g`
+    pub fn parse(keys: &str) -> Result<Self, String> {
+        let mut sequence = vec![];
+        for word in keys.split_whitespace() {
+            match KeyBinding::parse(word) {
+                Ok(key) => sequence.push(key),
+                Err(_) if !word.starts_with("C-") && !word.starts_with("M-") => {
+                    sequence.extend(word.chars().map(|c| KeyBinding::plain(KeyCode::Char(c))));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        if sequence.is_empty() {
+            return Err("expected a key".to_owned());
+        }
+        Ok(Self(sequence))
+    }
+
+    /// The key, if the sequence is a single one
+    pub fn single(&self) -> Option<&KeyBinding> {
+        match self.0.as_slice() {
+            [key] => Some(key),
+            _ => None,
+        }
+    }
+}
+
+impl From<KeyBinding> for KeySequence {
+    fn from(key: KeyBinding) -> Self {
+        Self(vec![key])
+    }
+}
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Plain characters run together, like `gg`, anything else is spaced out
+        let plain = self.0.iter().all(|key| key.modifiers == KeyModifiers::NONE && matches!(key.code, KeyCode::Char(c) if c != ' '));
+        let keys: Vec<String> = self.0.iter().map(KeyBinding::to_string).collect();
+        write!(f, "{}", keys.join(if plain { "" } else { " " }))
+    }
+}
+
+/// What the keys pressed so far are bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chord {
+    /// An action, and no longer sequence starts with them
+    Action(Action),
+    /// The start of a longer sequence, so more keys are awaited. The keys may be bound to an
+    /// action themselves, which runs if no key follows in time.
+    Prefix(Option<Action>),
+    /// Nothing
+    Unbound,
+}
+
+/// The keys bound to each action. A key is bound to at most one action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: IndexMap<Action, Vec<KeySequence>>,
+    /// Key starting the sequences of the less common actions, `<leader>` in the configuration file
+    leader: KeyBinding,
+}
+
+/// Default [`Keymap`] leader, as in vim
+const LEADER: KeyBinding = KeyBinding::plain(KeyCode::Char('\\'));
+
+/// Less common actions bound after the leader by default, and their keys there
+const LEADER_BINDINGS: [(Action, char); 14] = [
+    (Action::KillServer, 'X'), (Action::DetachClients, 'd'), (Action::Clients, 'c'), (Action::RefreshHost, 'r'),
+    (Action::Group, 'o'), (Action::Sort, 's'), (Action::Sources, 'v'), (Action::Theme, 'T'),
+    (Action::EditConfig, 'e'), (Action::Templates, 't'), (Action::Archived, 'B'), (Action::ServerInfo, 'i'),
+    (Action::DeepSearch, '/'), (Action::Servers, 'S'),
+];
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use KeyCode::*;
+        let plain = KeyBinding::plain;
+        Self {
+            bindings: [
+                (Action::Quit, vec![plain(Char('q'))]),
+                (Action::Detach, vec![plain(Char('Z'))]),
+                (Action::Up, vec![plain(Char('k')), plain(Up), KeyBinding::ctrl('p')]),
+                (Action::Down, vec![plain(Char('j')), plain(Down), KeyBinding::ctrl('n')]),
+                (Action::Top, vec![plain(Char('g')), plain(Home)]),
+                (Action::Bottom, vec![plain(Char('G')), plain(End)]),
+                (Action::PageUp, vec![plain(PageUp)]),
+                (Action::PageDown, vec![plain(PageDown)]),
+                (Action::HalfPageUp, vec![KeyBinding::ctrl('u')]),
+                (Action::HalfPageDown, vec![KeyBinding::ctrl('d')]),
+                (Action::Attach, vec![plain(Char('a'))]),
+                (Action::AttachSized, vec![plain(Char('A'))]),
+                (Action::AttachRun, vec![plain(Char('!'))]),
+                (Action::Windows, vec![plain(Enter), plain(Char('l')), plain(Right)]),
+                (Action::Rename, vec![plain(Char('r'))]),
+                (Action::RenameCurrent, vec![plain(Char('$'))]),
+                (Action::New, vec![plain(Char('n'))]),
+                (Action::NewAttach, vec![plain(Char('N'))]),
+                (Action::Delete, vec![plain(Char('x'))]),
+                (Action::DetachClients, vec![plain(Char('d'))]),
+                (Action::KillServer, vec![plain(Char('X'))]),
+                (Action::Clients, vec![plain(Char('c'))]),
+                (Action::Mark, vec![plain(Char('m')), plain(Char(' '))]),
+                (Action::Group, vec![plain(Char('o'))]),
+                (Action::Sort, vec![plain(Char('s'))]),
+                (Action::Filter, vec![plain(Char('f'))]),
+                (Action::Sources, vec![plain(Char('v'))]),
+                (Action::Fold, vec![plain(Tab)]),
+                (Action::History, vec![plain(Char('h'))]),
+                (Action::Preview, vec![plain(Char('p'))]),
+                (Action::Search, vec![plain(Char('/'))]),
+                (Action::Refresh, vec![plain(Char('R'))]),
+                (Action::RefreshHost, vec![KeyBinding::ctrl('r')]),
+                (Action::Theme, vec![plain(Char('T'))]),
+                (Action::EditConfig, vec![plain(Char('e'))]),
+                (Action::Templates, vec![plain(Char('t'))]),
+                (Action::Color, vec![plain(Char('C'))]),
+                (Action::Jump, vec![plain(Char(';'))]),
+                (Action::Archive, vec![plain(Char('b'))]),
+                (Action::Archived, vec![plain(Char('B'))]),
+                (Action::ServerInfo, vec![plain(Char('i'))]),
+                (Action::Help, vec![plain(Char('?'))]),
+                // Slow with a lot of scrollback, so only after the leader
+                (Action::DeepSearch, vec![]),
+                (Action::CopyMode, vec![plain(Char('y'))]),
+                (Action::Servers, vec![]),
+            ].into_iter().map(|(action, keys)| {
+                let mut keys: Vec<KeySequence> = keys.into_iter().map(KeySequence::from).collect();
+                // Also after the leader, to keep them at hand when their own keys are rebound
+                if let Some((_, key)) = LEADER_BINDINGS.iter().find(|(leader_action, _)| *leader_action == action) {
+                    keys.push(KeySequence(vec![LEADER, plain(Char(*key))]));
+                }
+                (action, keys)
+            }).collect(),
+            leader: LEADER,
+        }
+    }
+}
+
+impl Keymap {
+    /// The action a single key press is bound to
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        match self.resolve(&[KeyBinding::from_event(event)]) {
+            Chord::Action(action) | Chord::Prefix(Some(action)) => Some(action),
+            _ => None,
+        }
+    }
+
+    /// What a sequence of key presses is bound to, see [`Chord`]
+    pub fn resolve(&self, keys: &[KeyBinding]) -> Chord {
+        let mut bound = None;
+        let mut longer = false;
+        for (action, sequences) in &self.bindings {
+            for sequence in sequences {
+                if sequence.0 == keys {
+                    bound = bound.or(Some(*action));
+                } else if sequence.0.starts_with(keys) {
+                    longer = true;
+                }
+            }
+        }
+        match (bound, longer) {
+            (bound, true) => Chord::Prefix(bound),
+            (Some(action), false) => Chord::Action(action),
+            (None, false) => Chord::Unbound,
+        }
+    }
+
+    /// The keys that can follow `keys` and the actions they complete, such as `d` for `delete` after
+    /// `d` with `dd` bound, in the order of the bindings
+    pub fn continuations(&self, keys: &[KeyBinding]) -> Vec<(KeySequence, Action)> {
+        self.bindings.iter()
+            .flat_map(|(action, sequences)| sequences.iter().map(move |sequence| (sequence, *action)))
+            .filter(|(sequence, _)| sequence.0.len() > keys.len() && sequence.0.starts_with(keys))
+            .map(|(sequence, action)| (KeySequence(sequence.0[keys.len()..].to_vec()), action))
+            .collect()
+    }
+
+    /// Whether a key press is bound to `action` on its own
+    pub fn matches(&self, action: Action, event: &KeyEvent) -> bool {
+        self.keys(action).iter().filter_map(KeySequence::single).any(|key| key.matches(event))
+    }
+
+    /// Keys bound to `action`, the one shown in the hotkey bar first
+    pub fn keys(&self, action: Action) -> &[KeySequence] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Hotkey bar label of `action`, e.g. `a`, or empty if it is unbound. The detach key is
+    /// shown twice, as it is pressed.
+    pub fn label(&self, action: Action) -> String {
+        match (action, self.keys(action).first()) {
+            (_, None) => String::new(),
+            (Action::Detach, Some(key)) => format!("{}{}", key, key),
+            (_, Some(key)) => key.to_string(),
+        }
+    }
+
+    /// Parse a key sequence as written in the configuration file, see [`KeySequence::parse`],
+    /// where `<leader>` stands for the leader key, e.g. `<leader>s`
+    pub fn parse(&self, keys: &str) -> Result<KeySequence, String> {
+        KeySequence::parse(&keys.replace("<leader>", &format!(" {} ", self.leader)))
+    }
+
+    /// Make `leader` the key starting the sequences that start with the leader so far. Whatever
+    /// it was bound to on its own loses it.
+    pub fn set_leader(&mut self, leader: KeyBinding) {
+        for sequences in self.bindings.values_mut() {
+            sequences.retain(|sequence| sequence.single() != Some(&leader));
+            for sequence in sequences.iter_mut().filter(|sequence| sequence.0.len() > 1 && sequence.0[0] == self.leader) {
+                sequence.0[0] = leader;
+            }
+        }
+        self.leader = leader;
+    }
+
+    /// Bind `keys` to `action` in place of its current keys, taking them away from any other
+    /// action they were bound to
+    pub fn bind(&mut self, action: Action, keys: Vec<KeySequence>) -> Result<(), String> {
+        let single_character = |keys: &KeySequence| keys.single().is_some_and(|key| key.modifiers == KeyModifiers::NONE && matches!(key.code, KeyCode::Char(_)));
+        if action == Action::Detach && !keys.iter().all(single_character) {
+            return Err("the detach key must be a single character".to_owned());
+        }
+        for bound in self.bindings.values_mut() {
+            bound.retain(|key| !keys.contains(key));
+        }
+        self.bindings.insert(action, keys);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_round_trip_through_their_labels() {
+        for key in ["a", "G", "$", "Enter", "Space", "C-n", "M-Enter", "C-M-x", "PageDown"] {
+            assert_eq!(KeyBinding::parse(key).unwrap().to_string(), key);
+        }
+        assert!(KeyBinding::parse("Hyper").is_err());
+    }
+
+    #[test]
+    fn binding_a_key_takes_it_from_other_actions() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Action::Attach, vec![KeyBinding::plain(KeyCode::Char('x')).into()]).unwrap();
+        let x = KeyEvent::from(KeyCode::Char('x'));
+        assert_eq!(keymap.action(&x), Some(Action::Attach));
+        assert!(keymap.keys(Action::Delete).is_empty());
+        assert_eq!(keymap.label(Action::Delete), "");
+        assert!(keymap.bind(Action::Detach, vec![KeyBinding::ctrl('z').into()]).is_err());
+    }
+
+    #[test]
+    fn sequences_wait_for_their_next_key() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Action::Delete, vec![KeySequence::parse("dd").unwrap()]).unwrap();
+        keymap.bind(Action::Sort, vec![KeySequence::parse("C-x s").unwrap()]).unwrap();
+        let key = |c| KeyBinding::plain(KeyCode::Char(c));
+        assert_eq!(keymap.resolve(&[key('d')]), Chord::Prefix(Some(Action::DetachClients)));
+        assert_eq!(keymap.resolve(&[key('d'), key('d')]), Chord::Action(Action::Delete));
+        assert_eq!(keymap.resolve(&[KeyBinding::ctrl('x')]), Chord::Prefix(None));
+        assert_eq!(keymap.resolve(&[KeyBinding::ctrl('x'), key('x')]), Chord::Unbound);
+        assert_eq!(keymap.label(Action::Delete), "dd");
+        assert_eq!(keymap.continuations(&[KeyBinding::ctrl('x')]), [(KeySequence::parse("s").unwrap(), Action::Sort)]);
+        assert_eq!(keymap.label(Action::Sort), "C-x s");
+        assert_eq!(KeySequence::parse("Enter").unwrap(), KeyBinding::plain(KeyCode::Enter).into());
+        assert!(KeySequence::parse("C-Hyper").is_err());
+    }
+
+    #[test]
+    fn changing_the_leader_moves_its_sequences() {
+        let mut keymap = Keymap::default();
+        let key = |c| KeyBinding::plain(KeyCode::Char(c));
+        assert_eq!(keymap.resolve(&[key('\\'), key('X')]), Chord::Action(Action::KillServer));
+        keymap.set_leader(key(' '));
+        assert_eq!(keymap.resolve(&[key(' '), key('X')]), Chord::Action(Action::KillServer));
+        assert_eq!(keymap.resolve(&[key('\\')]), Chord::Unbound);
+        assert_eq!(keymap.keys(Action::Mark), [key('m').into()]);
+        assert_eq!(keymap.parse("<leader>dd").unwrap(), KeySequence(vec![key(' '), key('d'), key('d')]));
+    }
+}
@@ -12,3 +12,51 @@ pub mod tui;
 
 /// Event handler.
 pub mod handler;
+
+/// Batch operations over marked sessions.
+pub mod batch;
+
+/// Background job scheduler.
+pub mod scheduler;
+
+/// Tmux command helpers.
+pub mod tmux;
+
+/// Attach history store.
+pub mod history;
+
+/// Session metadata in tmux user options.
+pub mod meta;
+
+/// Single instance lock.
+pub mod lock;
+
+/// Remappable keys.
+pub mod keymap;
+
+/// Main list sources.
+pub mod source;
+
+/// Configuration file.
+pub mod config;
+
+/// Session templates.
+pub mod template;
+
+/// Archived sessions.
+pub mod archive;
+
+/// Layouts of tmuxinator and tmuxp.
+pub mod import;
+
+/// Session name patterns.
+pub mod pattern;
+
+/// Command line subcommands.
+pub mod cli;
+
+/// Debug log.
+pub mod log;
+
+/// Clipboard.
+pub mod clipboard;
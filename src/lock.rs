@@ -0,0 +1,116 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, Write},
+    os::fd::AsRawFd,
+    path::Path,
+};
+
+use crate::history;
+
+/// Holds the instance lock until dropped. The lock is an `flock` on `tmm.lock` in the state
+/// directory, so it is released when tmm exits or hands the terminal to tmux with `exec`.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// The running instance that holds the lock, as recorded in the lock file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtherInstance {
+    /// Process id
+    pub pid: u32,
+    /// Socket of the tmux server the instance runs in, if any
+    pub socket: Option<String>,
+    /// tmux pane the instance runs in (`$TMUX_PANE`), if any
+    pub pane: Option<String>,
+}
+
+/// Outcome of trying to take the instance lock
+#[derive(Debug)]
+pub enum Lock {
+    /// This is the only instance
+    Acquired(InstanceLock),
+    /// Another instance is running
+    Held(OtherInstance),
+    /// The lock file could not be used, e.g. the state directory is not writable
+    Unavailable,
+}
+
+/// Take the instance lock, recording this process in the lock file, or find out which instance
+/// holds it
+pub fn acquire() -> Lock {
+    let Some(dir) = history::state_dir() else { return Lock::Unavailable };
+    acquire_in(&dir)
+}
+
+/// Take the instance lock in `dir`, see [`acquire`]
+fn acquire_in(dir: &Path) -> Lock {
+    let _ = fs::create_dir_all(dir);
+    let Ok(mut file) = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(dir.join("tmm.lock")) else {
+        return Lock::Unavailable;
+    };
+    // SAFETY: flock only operates on the open file descriptor
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let mut contents = String::new();
+        let _ = file.read_to_string(&mut contents);
+        return match parse(&contents) {
+            Some(other) => Lock::Held(other),
+            // Taken, but the owner has not written itself down yet
+            None => Lock::Held(OtherInstance { pid: 0, socket: None, pane: None }),
+        };
+    }
+    // Tab separated: pid, tmux socket and pane (both empty outside tmux)
+    let _ = file.set_len(0);
+    let _ = file.rewind();
+    let _ = writeln!(file, "{}\t{}\t{}", std::process::id(), tmux_socket().unwrap_or_default(),
+        env::var("TMUX_PANE").unwrap_or_default());
+    Lock::Acquired(InstanceLock { _file: file })
+}
+
+/// Socket of the tmux server this process runs in, from `$TMUX` (`socket,pid,session`)
+pub fn tmux_socket() -> Option<String> {
+    let tmux = env::var("TMUX").ok()?;
+    let socket = tmux.split(',').next()?;
+    (!socket.is_empty()).then(|| socket.to_owned())
+}
+
+fn parse(contents: &str) -> Option<OtherInstance> {
+    let mut parts = contents.trim_end_matches('\n').splitn(3, '\t');
+    let pid = parts.next()?.parse().ok()?;
+    let socket = parts.next()?;
+    let pane = parts.next()?;
+    Some(OtherInstance {
+        pid,
+        socket: (!socket.is_empty()).then(|| socket.to_owned()),
+        pane: (!pane.is_empty()).then(|| pane.to_owned()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_second_lock_finds_the_first() {
+        let dir = env::temp_dir().join(format!("tmm-lock-test-{}", std::process::id()));
+        let Lock::Acquired(first) = acquire_in(&dir) else { panic!("the lock is free") };
+        // flock locks belong to the open file, so a second open conflicts even in one process
+        match acquire_in(&dir) {
+            Lock::Held(other) => assert_eq!(other.pid, std::process::id()),
+            lock => panic!("the lock is taken, got {:?}", lock),
+        }
+        drop(first);
+        assert!(matches!(acquire_in(&dir), Lock::Acquired(_)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn lock_files_are_parsed() {
+        assert_eq!(parse("42\t/tmp/tmux-1000/default\t%3\n"), Some(OtherInstance {
+            pid: 42, socket: Some("/tmp/tmux-1000/default".to_owned()), pane: Some("%3".to_owned()),
+        }));
+        assert_eq!(parse("42\t\t\n"), Some(OtherInstance { pid: 42, socket: None, pane: None }));
+        assert_eq!(parse(""), None);
+    }
+}
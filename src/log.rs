@@ -0,0 +1,96 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::history::{self, TimeZone};
+
+/// Environment variable that turns the debug log on like `--debug`, unless empty or `0`
+pub const ENV_VAR: &str = "TMM_LOG";
+
+/// The log file being appended to, once logging has started
+static LOG: Mutex<Option<File>> = Mutex::new(None);
+
+/// The debug log: `tmm.log` in the state directory
+pub fn path() -> Option<PathBuf> {
+    Some(history::state_dir()?.join("tmm.log"))
+}
+
+/// Whether [`ENV_VAR`] asks for the debug log
+pub fn requested_by_env() -> bool {
+    env::var(ENV_VAR).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Start appending to the debug log, returning its path, or why it cannot be written
+pub fn start() -> Result<PathBuf, String> {
+    let path = path().ok_or("no state directory: neither $XDG_STATE_HOME nor $HOME is set")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("could not create {}: {}", dir.display(), err))?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(&path)
+        .map_err(|err| format!("could not open {}: {}", path.display(), err))?;
+    if let Ok(mut log) = LOG.lock() {
+        *log = Some(file);
+    }
+    record("start", &[("pid", &std::process::id().to_string()), ("version", env!("CARGO_PKG_VERSION"))]);
+    Ok(path)
+}
+
+/// Whether events are being logged, to skip preparing those that would not be
+pub fn enabled() -> bool {
+    LOG.lock().is_ok_and(|log| log.is_some())
+}
+
+/// How the time of each event is written, e.g. `2024-03-01T14:05:03.120`
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f";
+
+/// Log an event, if logging: a line with the time, the kind of event and its fields
+pub fn record(event: &str, fields: &[(&str, &str)]) {
+    let Ok(mut log) = LOG.lock() else { return };
+    let Some(file) = log.as_mut() else { return };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    // Failing to log is not worth disturbing the TUI for
+    let _ = writeln!(file, "{}", line(&history::format_time(now.as_secs(), now.subsec_millis(), TIME_FORMAT, TimeZone::Local), event, fields));
+}
+
+/// An event as a logfmt line, e.g. `time=2024-03-01T14:05:03.120 event=tmux args="ls -F ..."`,
+/// which is easy to read and to grep
+fn line(time: &str, event: &str, fields: &[(&str, &str)]) -> String {
+    [("time", time), ("event", event)].iter().chain(fields)
+        .map(|(key, value)| format!("{}={}", key, quote(value)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A value as it is written in a logfmt line: quoted, with `"`, `\` and newlines escaped, when it
+/// is empty or has anything that would break the line up
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.chars().any(|c| c.is_whitespace() || c == '"' || c == '=' || c == '\\') {
+        return value.to_owned();
+    }
+    let escaped: String = value.chars().map(|c| match c {
+        '"' => "\\\"".to_owned(),
+        '\\' => "\\\\".to_owned(),
+        '\n' => "\\n".to_owned(),
+        '\t' => "\\t".to_owned(),
+        c => c.to_string(),
+    }).collect();
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_logfmt_lines() {
+        let fields = [("args", "list-sessions -F #{session_name}"), ("status", "1"), ("stderr", "no server running on \"/tmp\"\n"), ("host", "")];
+        assert_eq!(line("2024-03-01T14:05:03.120", "tmux", &fields),
+            "time=2024-03-01T14:05:03.120 event=tmux args=\"list-sessions -F #{session_name}\" status=1 \
+            stderr=\"no server running on \\\"/tmp\\\"\\n\" host=\"\"");
+    }
+}
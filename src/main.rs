@@ -1,13 +1,21 @@
 use tmm::app::{App, AppResult};
 use tmm::event::{Event, EventHandler};
-use tmm::handler::handle_key_events;
-use tmm::tui::Tui;
-use std::io;
+use tmm::handler::{handle_key_events, handle_key_timeout, handle_mouse_events};
+use tmm::tui::{self, Tui};
+use std::{env, io};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
-use tmm::app::ExitAction;
+use tmm::app::{AttachSize, ExitAction, StartupView};
+use tmm::{cli, config, history, import, lock, log, meta, tmux};
+use tmm::tmux::{ProcessClient, Server, TmuxClient};
+use tmm::pattern::Pattern;
+use tmm::config::Config;
+use tmm::lock::Lock;
 
 /// A Textual User Interface (TUI) Tmux session manager
 #[derive(Parser)]
@@ -19,69 +27,466 @@ such as attachment, renaming, deletion, etc.
 
 If a session name is provided on the command line, the session will instead be immediately attached
 or switched.")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
-    /// Attach the named session immediately instead of starting the TUI
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Attach the named session (or alias) immediately instead of starting the TUI
     #[arg(value_name="session name")]
-    session_name: Option<String>
+    session_name: Option<String>,
+    /// Seconds between background refreshes of the session list (0 disables) [default: 2]
+    #[arg(long, value_name="seconds", value_parser=config::seconds)]
+    refresh: Option<f64>,
+    /// Also list sessions on a remote host, reached with `ssh <host> tmux ...` (repeatable)
+    #[arg(long="host", value_name="ssh host")]
+    hosts: Vec<String>,
+    /// Seconds to wait for a remote host to list its sessions [default: 5]
+    #[arg(long, value_name="seconds", value_parser=config::seconds)]
+    host_timeout: Option<f64>,
+    /// Configuration file to use instead of ~/.config/tmm/config.toml
+    #[arg(long, value_name="path")]
+    config: Option<PathBuf>,
+    /// Leave out the hotkey bar, status messages and startup warnings, e.g. in a popup picker
+    #[arg(long, short)]
+    quiet: bool,
+    /// Disable killing, renaming and creating sessions, sending keys and everything else that
+    /// changes tmux, e.g. on a shared monitoring screen. Attaching leaves the other clients and
+    /// window sizes alone.
+    #[arg(long)]
+    read_only: bool,
+    /// Attach the only session straight away instead of starting the TUI, as `auto_attach` in the
+    /// configuration file does
+    #[arg(long)]
+    auto_attach: bool,
+    /// Always start the TUI, even with `auto_attach` in the configuration file
+    #[arg(long, conflicts_with="auto_attach")]
+    tui: bool,
+    /// Log the tmux commands tmm runs with their outcome, and the views it goes through, to
+    /// tmm.log in the state directory (~/.local/state/tmm), as TMM_LOG=1 does
+    #[arg(long)]
+    debug: bool,
+    /// Manage the tmux server with this socket name instead of the default one, as tmux -L does
+    #[arg(short='L', long, value_name="socket name", global=true)]
+    socket_name: Option<String>,
+    /// Manage the tmux server at this socket path instead of the default one, as tmux -S does
+    #[arg(short='S', long, value_name="path", global=true, conflicts_with="socket_name")]
+    socket_path: Option<String>,
 }
 
-/// Attach or switch to a session name and exit
-fn attach(name: &str, detach_others: bool) -> ! {
+#[derive(Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Cli(CliCommand),
+    /// Attach (or switch to) a local session, as `tmm <session name>` does
+    Attach {
+        /// Name or alias of the session
+        #[arg(value_name="session name")]
+        name: String,
+    },
+    /// Start the TUI with the git repositories under the project roots of the configuration
+    /// file, to attach a session in one (created if needed)
+    Projects,
+    /// Attach the only local session matching a search term, as the search of the TUI matches
+    /// them, or else start the TUI searching for it
+    Find {
+        /// Fuzzily matched against the session names, or a regular expression after `re:`
+        #[arg(value_name="search term")]
+        query: String,
+    },
+}
+
+/// Subcommands that run without the TUI, then exit
+#[derive(Subcommand)]
+enum CliCommand {
+    /// List the local sessions, like tmux ls
+    Ls {
+        /// How to print them: text (like tmux ls), json or tsv
+        #[arg(long, value_enum, default_value_t)]
+        format: cli::ListFormat,
+    },
+    /// Create a detached local session, and print its name
+    New {
+        /// Name of the session, with the `:` and `.` tmux reserves replaced like tmux does
+        #[arg(value_name="session name")]
+        name: String,
+        /// Directory the session starts in instead of the current one
+        #[arg(short='c', long, value_name="dir")]
+        directory: Option<String>,
+    },
+    /// Rename a local session, and print its new name
+    Rename {
+        /// Name or alias of the session
+        #[arg(value_name="session name")]
+        old: String,
+        /// New name of the session
+        #[arg(value_name="new name")]
+        new: String,
+    },
+    /// Kill a local session by name, or the local sessions whose name matches a pattern after
+    /// listing them and asking for confirmation
+    Kill {
+        /// Name or alias of the session to kill, without asking
+        #[arg(value_name="session name", required_unless_present="pattern", conflicts_with="pattern")]
+        name: Option<String>,
+        /// Shell style glob matched against the whole session name, e.g. 'build-*'
+        #[arg(long="match", value_name="pattern")]
+        pattern: Option<String>,
+        /// Treat the pattern as a regular expression, matched anywhere in the name
+        #[arg(long, conflicts_with="name")]
+        regex: bool,
+        /// Leave sessions with an attached client alone
+        #[arg(long, conflicts_with="name")]
+        detached_only: bool,
+        /// Only list the sessions that would be killed
+        #[arg(long, conflicts_with="name")]
+        dry_run: bool,
+        /// Kill without asking for confirmation
+        #[arg(long, short, conflicts_with="name")]
+        yes: bool,
+    },
+    /// Manage session templates
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommand,
+    },
+    /// Print the tmux version, the configuration file, and the pid, socket and start time of the
+    /// local tmux server and those of the configured hosts, to tell which server tmm talks to
+    Doctor,
+}
+
+#[derive(Subcommand)]
+enum TemplateCommand {
+    /// Install a tmm (.toml) or tmuxp (.yaml, .yml or .json) template file from a URL or a path,
+    /// after checking that it is valid
+    Import {
+        /// URL (fetched with curl) or path of the template file
+        #[arg(value_name="url or path")]
+        source: String,
+        /// Replace an installed template of the same name
+        #[arg(long, short)]
+        force: bool,
+    },
+}
+
+/// Run a subcommand and exit with its exit code
+fn run_command(tmux: &dyn TmuxClient, command: CliCommand, config_path: Option<PathBuf>) -> ! {
+    let config_path = config_path.or_else(config::config_path);
+    // Sessions can be named by their alias, which a broken configuration file leaves out
+    let config = config_path.as_deref().map(|path| Config::load(path).0).unwrap_or_default();
+    let code = match command {
+        CliCommand::Ls { format } => cli::ls(tmux, format),
+        CliCommand::New { name, directory } => cli::new_session(tmux, &name, directory.as_deref()),
+        CliCommand::Rename { old, new } => cli::rename_session(tmux, config.resolve_alias(&old), &new),
+        CliCommand::Kill { name: Some(name), .. } => cli::kill_session(tmux, config.resolve_alias(&name)),
+        CliCommand::Kill { name: None, pattern, regex, detached_only, dry_run, yes } => {
+            let pattern = pattern.unwrap_or_default();
+            let pattern = if regex {
+                match Pattern::regex(&pattern) {
+                    Ok(pattern) => pattern,
+                    Err(err) => {
+                        eprintln!("tmm: invalid regular expression: {}", err);
+                        std::process::exit(cli::EXIT_USAGE);
+                    }
+                }
+            } else {
+                Pattern::glob(&pattern)
+            };
+            cli::kill(tmux, &cli::KillOptions { pattern, detached_only, dry_run, yes })
+        }
+        CliCommand::Template { command: TemplateCommand::Import { source, force } } => cli::import_template(&source, force),
+        CliCommand::Doctor => cli::doctor(tmux, config_path.as_deref()),
+    };
+    std::process::exit(code);
+}
+
+/// Attach or switch to a session name, optionally selecting one of its windows (and one of that
+/// window's panes), and exit. Without a size, as in read-only mode, the session is attached as it
+/// is, without recording the attach in it.
+fn attach(tmux: &dyn TmuxClient, host: Option<&str>, name: &str, window: Option<(usize, Option<usize>)>, size: Option<AttachSize>) -> ! {
+    history::record(host, name);
+    if size.is_some() {
+        meta::record_attach(tmux, host, name);
+    }
+    let target = match window {
+        // `=` makes tmux match the session name exactly instead of as a prefix
+        Some((window, Some(pane))) => format!("={}:{}.{}", name, window, pane),
+        Some((window, None)) => format!("={}:{}", name, window),
+        None => name.to_owned(),
+    };
+    // The windows keep the size chosen for them until an attach that detaches the other clients,
+    // which switching clients cannot
+    let detaches_others = host.is_some() || !tmux.server().is_nested();
+    match size {
+        Some(AttachSize::Detach) if detaches_others => set_window_size(tmux, host, name, None),
+        Some(AttachSize::Detach) | None => {}
+        Some(size) => set_window_size(tmux, host, name, Some(size)),
+    }
+    log::record("attach", &[("target", &target), ("host", host.unwrap_or("local"))]);
+    if let Some(host) = host {
+        // Remote sessions are attached through an interactive ssh session, regardless of whether
+        // tmm itself is nested in a local session
+        let mut cmd = exec::Command::new("ssh");
+        cmd.arg("-t").arg(host).arg("tmux").arg("attach-session");
+        if size == Some(AttachSize::Detach) {
+            cmd.arg("-d");
+        }
+        let err = cmd.arg("-t").arg(tmux::shell_quote(&target)).exec();
+        panic!("{}", err);
+    }
+    let server = tmux.server();
     let mut cmd = exec::Command::new("tmux");
-    if App::is_nested() {
-        // If currently nested, use switch-client instead of attach
+    cmd.args(server.args());
+    if server.is_nested() {
+        // If currently nested, use switch-client instead of attach. It cannot detach the other
+        // clients, so those keep sizing the session as before.
         cmd.arg("switch-client");
     } else {
-        cmd.arg("attach-session").arg("-d");
-        if detach_others {
+        leave_other_server();
+        cmd.arg("attach-session");
+        if size == Some(AttachSize::Detach) {
             cmd.arg("-d");
         }
     }
-    let err = cmd.arg("-t").arg(name).exec();
+    let err = cmd.arg("-t").arg(target).exec();
     panic!("{}", err);
 }
 
-fn main() -> AppResult<()> {
-    // Create an application.
-    let mut app = App::new();
+/// Forget `$TMUX` before attaching from outside the chosen server, since tmux refuses to start a
+/// client inside a pane while it is set
+fn leave_other_server() {
+    env::remove_var("TMUX");
+    env::remove_var("TMUX_PANE");
+}
+
+/// Set the `window-size` option of every window of a session, so that the clients it is attached
+/// to share it as `size` says, or unset it with `None`, so that the windows follow the global
+/// option again. Failing is not worth stopping the attach for.
+fn set_window_size(tmux: &dyn TmuxClient, host: Option<&str>, name: &str, size: Option<AttachSize>) {
+    let Ok(output) = tmux.output(host, &["list-windows", "-t", &format!("={}", name), "-F", "#{window_id}"]) else { return };
+    for window in String::from_utf8_lossy(&output.stdout).lines() {
+        let _ = match size {
+            Some(size) => tmux.output(host, &["set-option", "-w", "-t", window, "window-size", size.name()]),
+            None => tmux.output(host, &["set-option", "-w", "-u", "-t", window, "window-size"]),
+        };
+    }
+}
 
+fn main() -> AppResult<()> {
     let args = Args::parse();
-    if let Some(session_name) = args.session_name {
-        attach(&session_name, true);
+    // Started first, to log the subcommands too
+    let log_error = if args.debug || log::requested_by_env() { log::start().err() } else { None };
+    let tui_mode = args.session_name.is_none()
+        && matches!(args.command, None | Some(Command::Projects | Command::Find { .. }));
+    if let Some(err) = log_error.as_ref().filter(|_| !tui_mode) {
+        eprintln!("tmm: no debug log: {}", err);
+    }
+    // Installing templates and checking the setup are the only things tmm does without tmux
+    if !matches!(args.command, Some(Command::Cli(CliCommand::Template { .. } | CliCommand::Doctor))) && !tmux::installed() {
+        eprintln!("tmm: tmux is not installed");
+        std::process::exit(cli::EXIT_NO_TMUX);
+    }
+    // A `$TMUX` left over from a server that is gone would send every tmux command there, and
+    // have tmm switch a client that no longer exists instead of attaching, so tmm runs as if
+    // outside tmux
+    let stale_tmux = tmux::stale_env();
+    if stale_tmux {
+        log::record("stale_tmux", &[("tmux", &env::var("TMUX").unwrap_or_default())]);
+        env::remove_var("TMUX");
+        env::remove_var("TMUX_PANE");
+        if !tui_mode {
+            eprintln!("tmm: ignoring $TMUX, whose tmux server is gone");
+        }
+    }
+    let client = ProcessClient::new(Server::new(args.socket_name.as_deref(), args.socket_path.as_deref()));
+    let projects = matches!(args.command, Some(Command::Projects));
+    let find = match &args.command {
+        Some(Command::Find { query }) => Some(query.clone()),
+        _ => None,
+    };
+    let session_name = match &args.command {
+        Some(Command::Attach { name }) => Some(name.clone()),
+        _ => args.session_name,
+    };
+    if let Some(Command::Cli(command)) = args.command.filter(|_| session_name.is_none()) {
+        run_command(&client, command, args.config.clone());
+    }
+    // A broken configuration file is reported, and the defaults are used instead
+    let config_path = args.config.or_else(config::config_path);
+    let (mut config, config_error) = match &config_path {
+        Some(path) => Config::load(path),
+        None => (Config::default(), None),
+    };
+    if let Some(session_name) = session_name {
+        // Without the TUI to report them in, a broken configuration file or a missing session are
+        // exit codes. An alias can only be trusted once the file is read.
+        if let Some(err) = config_error {
+            eprintln!("tmm: {}", err);
+            std::process::exit(cli::EXIT_CONFIG);
+        }
+        let name = config.resolve_alias(&session_name);
+        if !client.output(None, &["has-session", "-t", name]).is_ok_and(|output| output.status.success()) {
+            eprintln!("tmm: no session named {}", name);
+            std::process::exit(cli::EXIT_NO_SESSIONS);
+        }
+        attach(&client, None, name, None, (!args.read_only).then_some(AttachSize::Detach));
+    }
+
+    // Only one instance at a time, so that they do not fight over the state files. When the other
+    // instance runs in a pane of this tmux server, focus it instead.
+    let mut warning = None;
+    let _lock = match lock::acquire() {
+        Lock::Acquired(lock) => Some(lock),
+        Lock::Held(other) => {
+            let same_server = other.socket.is_some() && other.socket == lock::tmux_socket();
+            if let (Some(pane), true) = (&other.pane, same_server) {
+                let err = exec::Command::new("tmux").arg("switch-client").arg("-t").arg(pane).exec();
+                panic!("{}", err);
+            }
+            warning = Some(format!("Another tmm is already running (pid {}). Attach history may not be recorded reliably.", other.pid));
+            None
+        }
+        Lock::Unavailable => None,
+    };
+
+
+    // Create an application.
+    let mut app = App::with_client(Arc::new(client));
+    app.config_path = config_path;
+    // The installed templates and the layouts of tmuxinator and tmuxp are only offered in the TUI
+    config.add_templates(import::discover());
+    app.configure(&config);
+    app.server = app.tmux.server().label();
+    app.restore_sort();
+    // Where to start, which `tmm projects` and `tmm find` decide over the configuration file
+    app.attached_filter = config.filter;
+    let view = match (projects, &find) {
+        (true, _) => StartupView::Projects,
+        (false, Some(_)) => StartupView::Sessions,
+        (false, None) => config.view,
+    };
+    app.show_startup_view(view);
+    if args.quiet {
+        app.set_quiet();
+    }
+    if args.read_only {
+        app.set_read_only();
+    }
+    if stale_tmux {
+        app.show_status("Ignoring $TMUX: its tmux server is gone");
+    }
+    // A search term matching a single session attaches it without showing the TUI at all, unless
+    // there is a broken configuration file to report
+    if let Some(query) = &find {
+        app.search_for(query);
+        if let (None, [(idx, _)]) = (&config_error, app.search_matches().as_slice()) {
+            let session = &app.sessions[*idx];
+            attach(app.tmux.as_ref(), session.host.as_deref(), &session.name, None, (!app.read_only).then_some(app.attach_size));
+        }
+    }
+    if let Some(warning) = warning.filter(|_| !args.quiet) {
+        app.report_error(warning);
+    }
+    if let Some(err) = config_error {
+        app.report_error(err);
+    }
+    if let Some(err) = log_error {
+        app.report_error(format!("No debug log: {}", err));
+    }
+    // Command line options take precedence over the configuration file, and hosts from both are
+    // listed
+    app.host_timeout = Duration::from_secs_f64(args.host_timeout.unwrap_or(config.host_timeout));
+    app.add_remote_hosts(config.hosts.into_iter().chain(args.hosts).collect());
+    // A single session is attached without the TUI, unless asked for something else or there is
+    // something to report
+    let auto_attach = (args.auto_attach || config.auto_attach) && !args.tui;
+    if auto_attach && view == StartupView::Sessions && find.is_none() && app.errors.is_empty() {
+        if let Some(session) = app.only_session() {
+            attach(app.tmux.as_ref(), None, &session.name, None, (!app.read_only).then_some(app.attach_size));
+        }
+    }
+    let refresh = args.refresh.unwrap_or(config.refresh);
+    if refresh > 0.0 {
+        app.set_refresh_interval(Some(Duration::from_secs_f64(refresh)));
+    }
+    if let Some(seconds) = config.host_refresh {
+        app.set_host_refresh_interval(config::interval(seconds));
     }
 
     // Initialize the terminal user interface.
     let backend = CrosstermBackend::new(io::stderr());
     let terminal = Terminal::new(backend)?;
-    let events = EventHandler::new();
+    let events = EventHandler::new(250);
     let mut tui = Tui::new(terminal, events);
     tui.init()?;
+    let sender = tui.events.sender();
+    tmux::start_control_mode(&app.tmux.server(), move |line| {
+        let _ = sender.send(Event::Notification(line.to_owned()));
+    });
 
     // Start the main loop.
     while app.running {
         // Render the user interface.
         tui.draw(&mut app)?;
+        let state = app.state.clone();
         // Handle events.
         match tui.events.next()? {
+            Event::Tick => {
+                app.tick(&tui.events.sender());
+                handle_key_timeout(&mut app);
+            }
+            Event::Job(result) => app.job_finished(result),
+            Event::Entries(source, entries) => app.entries_loaded(source, entries),
+            Event::PaneSearch(generation, update) => app.pane_search_update(generation, update),
+            Event::Notification(line) => {
+                app.notification(&line);
+                app.tick(&tui.events.sender());
+            }
             Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
-            Event::Mouse(_) => {}
+            Event::Mouse(mouse_event) => handle_mouse_events(mouse_event, &mut app)?,
             Event::Resize(_, _) => {}
         }
+        if app.state != state && log::enabled() {
+            log::record("state", &[("from", &format!("{:?}", state)), ("to", &format!("{:?}", app.state))]);
+        }
+        if tui::take_stop_request() {
+            app.stop();
+        }
+        // Hand the terminal over, e.g. to an editor, and take it back once it exits
+        if let Some(foreground) = app.foreground.take() {
+            tui.suspend()?;
+            let status = foreground.run();
+            tui.resume()?;
+            app.foreground_finished(foreground, status);
+        }
     }
 
     // Exit the user interface.
     tui.exit()?;
 
     match app.on_exit {
-        ExitAction::AttachSession(name, detach_others) => {
-            attach(&name, detach_others);
+        ExitAction::AttachSession(host, name, size) => {
+            attach(app.tmux.as_ref(), host.as_deref(), &name, None, (!app.read_only).then_some(size));
+        },
+        ExitAction::AttachWindow(host, name, window, pane, size) => {
+            attach(app.tmux.as_ref(), host.as_deref(), &name, Some((window, pane)), (!app.read_only).then_some(size));
         },
-        ExitAction::NewSession => {
+        ExitAction::NewSession(command) => {
+            leave_other_server();
             let err = exec::Command::new("tmux")
-                .arg("new-session").exec();
+                .args(app.tmux.server().args())
+                .arg("new-session").args(command.as_slice()).exec();
             panic!("{}", err);
         }
+        ExitAction::Detach => {
+            if app.is_nested() {
+                let err = exec::Command::new("tmux")
+                    .args(app.tmux.server().args())
+                    .arg("detach-client").exec();
+                panic!("{}", err);
+            }
+        }
         ExitAction::None => ()
     }
-    Ok(())
+    // Nothing was attached
+    std::process::exit(cli::EXIT_CANCELLED)
 }
@@ -0,0 +1,44 @@
+use std::env;
+
+use crate::history;
+use crate::tmux::TmuxClient;
+
+/// User option of a session holding when and by whom tmm last attached it, as
+/// `<seconds since the epoch> <user>@<machine>`. Unlike the history file, which only knows about
+/// this machine, it is seen by every tmm listing the same server.
+pub const ATTACHED: &str = "@tmm_attached";
+
+/// User option of a session holding the color its row and preview are accented with, as tmm's
+/// configuration file spells colors (e.g. `red` or `#ff8800`)
+pub const COLOR: &str = "@tmm_color";
+
+/// The last attach of a session through tmm, from [`ATTACHED`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attach {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    /// Who attached it, e.g. `alice@laptop`
+    pub by: String,
+}
+
+impl Attach {
+    /// Parse the value of [`ATTACHED`], `None` if it is unset or not tmm's
+    pub fn parse(value: &str) -> Option<Self> {
+        let (timestamp, by) = value.split_once(' ')?;
+        Some(Self { timestamp: timestamp.parse().ok()?, by: by.to_owned() })
+    }
+
+    /// How long ago and by whom, e.g. `5m ago by alice@laptop`
+    pub fn describe(&self, now: u64) -> String {
+        format!("{} ago by {}", history::format_duration(now.saturating_sub(self.timestamp)), self.by)
+    }
+}
+
+/// Record an attach of `session` in its [`ATTACHED`] option. Like the history file this is a
+/// convenience, so failures are ignored.
+pub fn record_attach(tmux: &dyn TmuxClient, host: Option<&str>, session: &str) {
+    let user = env::var("USER").unwrap_or_else(|_| "someone".to_owned());
+    let value = format!("{} {}@{}", history::now(), user, history::local_hostname());
+    // `set-option` takes a pane, so `=name:` for the session's current one
+    let _ = tmux.output(host, &["set-option", "-t", &format!("={}:", session), ATTACHED, &value]);
+}
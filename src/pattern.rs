@@ -0,0 +1,227 @@
+use std::ops::Range;
+
+use regex::Regex;
+
+/// A pattern session names are matched against: a shell style glob, which has to match the whole
+/// name, or a regular expression, which matches anywhere in the name unless anchored. Both are
+/// matched by the regex crate, in time linear in the name whatever the pattern.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// `*` matches any run of characters, `?` any single one and `[...]` (or `[!...]`) a set
+    Glob(Regex),
+    /// The regex crate's syntax
+    Regex(Regex),
+}
+
+impl Pattern {
+    /// Parse a glob. Every glob is valid: an unterminated `[` stands for itself.
+    pub fn glob(pattern: &str) -> Self {
+        let chars: Vec<char> = pattern.chars().collect();
+        // Dot matches newlines too, as `*` and `?` do in a shell
+        let mut regex = "(?s)^".to_owned();
+        let mut idx = 0;
+        while idx < chars.len() {
+            match chars[idx] {
+                '*' => regex.push_str(".*"),
+                '?' => regex.push('.'),
+                '[' => match glob_class(&chars, idx + 1) {
+                    Some((class, next)) => {
+                        regex.push_str(&class);
+                        idx = next;
+                        continue;
+                    }
+                    None => regex.push_str(&regex::escape("[")),
+                },
+                c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+            idx += 1;
+        }
+        regex.push('$');
+        Pattern::Glob(Regex::new(&regex).expect("globs translate to valid regular expressions"))
+    }
+
+    /// Parse a regular expression, failing with the reason on one line
+    pub fn regex(pattern: &str) -> Result<Self, String> {
+        Regex::new(pattern).map(Pattern::Regex).map_err(|err| {
+            // Syntax errors show the pattern with a caret before the reason
+            let message = err.to_string();
+            let reason = message.lines().last().unwrap_or_default();
+            reason.strip_prefix("error: ").unwrap_or(reason).to_owned()
+        })
+    }
+
+    /// Whether `text` matches
+    pub fn matches(&self, text: &str) -> bool {
+        self.compiled().is_match(text)
+    }
+
+    /// Byte ranges of the successive matches in `text`, each starting as early as possible, with
+    /// repetitions greedy. Empty matches are included, so that `text` matches exactly when there
+    /// are any.
+    pub fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+        self.compiled().find_iter(text).map(|found| found.range()).collect()
+    }
+
+    fn compiled(&self) -> &Regex {
+        match self {
+            Pattern::Glob(regex) | Pattern::Regex(regex) => regex,
+        }
+    }
+}
+
+/// Translate the set of a glob class whose `[` is just before `idx` into a regex class: ranges
+/// such as `a-z` and single characters, optionally negated by `!` or `^` first. A `]` right at
+/// the start is part of the set. Returns the class and the index after its `]`, or `None` if it is
+/// not terminated.
+fn glob_class(chars: &[char], mut idx: usize) -> Option<(String, usize)> {
+    let mut class = "[".to_owned();
+    if chars.get(idx).is_some_and(|c| *c == '!' || *c == '^') {
+        class.push('^');
+        idx += 1;
+    }
+    let start = idx;
+    let escape = |c: char| regex::escape(c.encode_utf8(&mut [0; 4]));
+    loop {
+        let c = *chars.get(idx)?;
+        if c == ']' && idx > start {
+            class.push(']');
+            return Some((class, idx + 1));
+        }
+        class.push_str(&escape(c));
+        match (chars.get(idx + 1), chars.get(idx + 2)) {
+            (Some('-'), Some(&end)) if end != ']' => {
+                class.push('-');
+                class.push_str(&escape(end));
+                idx += 3;
+            }
+            _ => idx += 1,
+        }
+    }
+}
+
+/// Score of each matched character of a fuzzy search
+const FUZZY_MATCH: i64 = 16;
+/// Extra score of a matched character starting a word, e.g. the `a` of `dev-api`
+const FUZZY_BOUNDARY: i64 = 8;
+/// Extra score of a matched uppercase character following a lowercase one, e.g. the `A` of `devApi`
+const FUZZY_CAMEL: i64 = 7;
+/// Least extra score of a matched character following the previous match. A run of them gets the
+/// extra score of its first one, if higher, so the `pi` of `dev-api` count as much as the `a`.
+const FUZZY_CONSECUTIVE: i64 = 4;
+/// Penalty of skipping characters between two matched ones, plus the next for each further one
+const FUZZY_GAP_START: i64 = 3;
+const FUZZY_GAP_EXTENSION: i64 = 1;
+
+/// Match `needle` fzf-style against `text`: its characters have to appear in order but not
+/// necessarily next to each other, so `dvapi` matches `dev-api-server`. Case is ignored unless the
+/// needle has an uppercase character. Returns the score of the best alignment, higher for
+/// consecutive characters and word starts, and the byte offsets of its matched characters.
+pub fn fuzzy(needle: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let needle: Vec<char> = needle.chars().collect();
+    if needle.is_empty() {
+        return None;
+    }
+    let ignore_case = !needle.iter().any(|c| c.is_uppercase());
+    let fold = |c: char| if ignore_case { c.to_lowercase().next().unwrap_or(c) } else { c };
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let bonus = |idx: usize| match idx.checked_sub(1).map(|prev| chars[prev].1) {
+        None => FUZZY_BOUNDARY,
+        Some(prev) if !prev.is_alphanumeric() => FUZZY_BOUNDARY,
+        Some(prev) if prev.is_lowercase() && chars[idx].1.is_uppercase() => FUZZY_CAMEL,
+        Some(_) => 0,
+    };
+    // best[i][j] is the best (score, bonus of the run, previous match) aligning the first i + 1
+    // characters of the needle with the last at character j of the text
+    let mut best: Vec<Vec<Option<(i64, i64, usize)>>> = vec![vec![None; chars.len()]; needle.len()];
+    for (i, c) in needle.iter().enumerate() {
+        for j in i..chars.len() {
+            if fold(chars[j].1) != fold(*c) {
+                continue;
+            }
+            if i == 0 {
+                best[0][j] = Some((FUZZY_MATCH + bonus(j), bonus(j), j));
+                continue;
+            }
+            best[i][j] = (i - 1..j).filter_map(|k| best[i - 1][k].map(|(score, run, _)| {
+                if k + 1 == j {
+                    let run = run.max(bonus(j));
+                    (score + FUZZY_MATCH + run.max(FUZZY_CONSECUTIVE), run, k)
+                } else {
+                    let gap = FUZZY_GAP_START + (j - k - 2) as i64 * FUZZY_GAP_EXTENSION;
+                    (score + FUZZY_MATCH + bonus(j) - gap, bonus(j), k)
+                }
+            })).max_by_key(|(score, _, k)| (*score, *k));
+        }
+    }
+    // The earliest of the best ends, then back through the previous matches
+    let last = needle.len() - 1;
+    let (mut j, score) = (0..chars.len())
+        .filter_map(|j| best[last][j].map(|(score, _, _)| (j, score)))
+        .min_by_key(|(j, score)| (-score, *j))?;
+    let mut positions = vec![0; needle.len()];
+    for i in (0..needle.len()).rev() {
+        positions[i] = chars[j].0;
+        j = best[i][j].expect("matched characters have a score").2;
+    }
+    Some((score, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_whole_names() {
+        let glob = Pattern::glob("web-[0-9]*");
+        assert!(glob.matches("web-1"));
+        assert!(glob.matches("web-12-old"));
+        assert!(!glob.matches("old-web-1"));
+        assert!(Pattern::glob("?ev").matches("dev"));
+        assert!(!Pattern::glob("[!d]ev").matches("dev"));
+        assert!(Pattern::glob("a[b").matches("a[b"));
+        assert!(Pattern::glob("[]-]x.").matches("-x."));
+        assert!(!Pattern::glob("x.").matches("xy"));
+    }
+
+    #[test]
+    fn regexes_match_anywhere_unless_anchored() {
+        let regex = |pattern| Pattern::regex(pattern).unwrap();
+        assert!(regex("web-\\d+").matches("old-web-12"));
+        assert!(!regex("^web").matches("old-web"));
+        assert!(regex("^(dev|ops)$").matches("ops"));
+        assert!(!regex("^(dev|ops)$").matches("devops"));
+        assert!(regex("^a{2,3}b?$").matches("aaab"));
+        assert!(!regex("^a{2,3}$").matches("aaaa"));
+        assert!(regex("x(y*)*z").matches("xz"));
+        assert_eq!(Pattern::regex("(web").unwrap_err(), "unclosed group");
+        assert!(Pattern::regex("*web").is_err());
+        assert!(Pattern::regex("a{3,2}").is_err());
+        // Patterns that backtracking takes exponential time over
+        assert!(!regex("^(a|a)*b$").matches(&"a".repeat(64)));
+        assert!(!Pattern::glob("*a*a*a*a*a*b").matches(&"a".repeat(64)));
+    }
+
+    #[test]
+    fn matches_are_found_in_turn() {
+        let regex = |pattern| Pattern::regex(pattern).unwrap();
+        assert_eq!(regex("web-\\d+").find_all("web-1 old-web-12"), [0..5, 10..16]);
+        assert_eq!(regex("^d").find_all("dev-d"), vec![Range { start: 0, end: 1 }]);
+        assert_eq!(regex("x*").find_all("ab").len(), 3);
+        assert!(regex("z").find_all("dev").is_empty());
+        assert_eq!(Pattern::glob("d*").find_all("dev"), vec![Range { start: 0, end: 3 }]);
+    }
+
+    #[test]
+    fn fuzzy_matches_favour_runs_and_word_starts() {
+        let (_, positions) = fuzzy("dvapi", "dev-api-server").unwrap();
+        assert_eq!(positions, [0, 2, 4, 5, 6]);
+        assert!(fuzzy("dvapi", "dev-server").is_none());
+        let score = |needle, text| fuzzy(needle, text).unwrap().0;
+        assert!(score("web", "web-1") > score("web", "w-e-b"));
+        assert!(score("api", "dev-api") > score("api", "rapid"));
+        assert!(score("da", "devApi") > score("da", "devapi"));
+        // Smart case
+        assert!(fuzzy("API", "dev-api").is_none());
+        assert_eq!(fuzzy("Api", "devApi").unwrap().1, [3, 4, 5]);
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    process, time::{Duration, Instant, SystemTime, UNIX_EPOCH}
+};
+use indexmap::IndexMap;
+
+use crate::source::Source;
+
+/// Kinds of background work that are polled on an interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Job {
+    /// Re-list the tmux sessions of one host, by index into [`App::hosts`]
+    ///
+    /// [`App::hosts`]: crate::app::App::hosts
+    Sessions(usize),
+    /// Capture the active pane of the selected session for the preview pane
+    Preview,
+    /// List the windows and panes of every session of one host, by index into [`App::hosts`], so
+    /// that the windows view opens without waiting for tmux
+    ///
+    /// [`App::hosts`]: crate::app::App::hosts
+    Windows(usize),
+    /// List the entries of a source that is [`Source::is_fetched`], which report back as an
+    /// [`Event::Entries`] instead of a [`JobResult`]
+    ///
+    /// [`Event::Entries`]: crate::event::Event::Entries
+    Entries(Source),
+}
+
+/// Output of a background job, delivered back to the main loop as an [`Event`]
+///
+/// [`Event`]: crate::event::Event
+#[derive(Debug, Clone)]
+pub struct JobResult {
+    /// Which job produced this result
+    pub job: Job,
+    /// The generation of the data the job was launched against. Results from an older generation
+    /// are stale (e.g. a synchronous refresh happened while the job was running) and are dropped.
+    pub generation: u64,
+    /// Stdout of the tmux command, or `None` if it could not be run
+    pub output: Option<String>,
+}
+
+#[derive(Debug)]
+struct JobState {
+    /// How often the job runs. `None` disables it.
+    interval: Option<Duration>,
+    /// When the job should next be launched. `None` if it is not scheduled.
+    next_due: Option<Instant>,
+    /// Whether the job has been launched and not yet reported back
+    in_flight: bool,
+}
+
+/// Coordinates all periodic background work so that tmux (possibly behind a slow SSH connection) is
+/// never hit with a burst of processes.
+///
+/// - Each job runs at most once at a time.
+/// - At most `max_in_flight` jobs run concurrently, and launches are spaced by at least `min_gap`.
+/// - Every interval is randomly stretched or shrunk by up to `jitter` so that jobs with similar
+///   intervals drift apart instead of always firing together.
+#[derive(Debug)]
+pub struct Scheduler {
+    /// Job states, in priority order (earlier jobs win when several are due)
+    jobs: IndexMap<Job, JobState>,
+    /// Minimum time between two launches of any job
+    pub min_gap: Duration,
+    /// Maximum number of jobs running at the same time
+    pub max_in_flight: usize,
+    /// Fraction of the interval used as random jitter, e.g. 0.1 for +/- 10%
+    pub jitter: f64,
+    /// When the last job was launched
+    last_launch: Option<Instant>,
+    /// xorshift state for jitter
+    rng: u64,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
+            ^ ((process::id() as u64) << 32);
+        Self {
+            jobs: IndexMap::new(),
+            min_gap: Duration::from_millis(100),
+            max_in_flight: 2,
+            jitter: 0.1,
+            last_launch: None,
+            // xorshift must not be seeded with 0
+            rng: seed.max(1),
+        }
+    }
+}
+
+impl Scheduler {
+    /// Constructs a new instance of [`Scheduler`] with no jobs enabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable a job with the given interval, or disable it with `None`. A pending one-off run (see
+    /// [`Scheduler::trigger`]) is kept if it is due sooner.
+    pub fn set_interval(&mut self, job: Job, interval: Option<Duration>) {
+        let next_due = interval.map(|i| Instant::now() + self.jittered(i));
+        let state = self.state(job);
+        state.interval = interval;
+        state.next_due = match (state.next_due, next_due) {
+            (Some(pending), Some(next)) => Some(pending.min(next)),
+            (pending, next) => pending.or(next),
+        };
+    }
+
+    /// Run a job as soon as the rate limits allow, whether or not it has an interval
+    pub fn trigger(&mut self, job: Job) {
+        self.state(job).next_due = Some(Instant::now());
+    }
+
+    /// Get the state of a job, adding a disabled one if it is not known yet
+    fn state(&mut self, job: Job) -> &mut JobState {
+        self.jobs.entry(job).or_insert(JobState { interval: None, next_due: None, in_flight: false })
+    }
+
+    /// Return the next job that should be launched now, if any, and mark it as in flight. Call
+    /// repeatedly until it returns `None`.
+    pub fn due(&mut self, now: Instant) -> Option<Job> {
+        if self.jobs.values().filter(|state| state.in_flight).count() >= self.max_in_flight {
+            return None;
+        }
+        if self.last_launch.is_some_and(|last| now.duration_since(last) < self.min_gap) {
+            return None;
+        }
+        let (job, state) = self.jobs.iter_mut().find(|(_, state)| {
+            !state.in_flight && state.next_due.is_some_and(|due| due <= now)
+        })?;
+        state.in_flight = true;
+        self.last_launch = Some(now);
+        Some(*job)
+    }
+
+    /// Whether a job has been launched and not yet reported back
+    pub fn in_flight(&self, job: Job) -> bool {
+        self.jobs.get(&job).is_some_and(|state| state.in_flight)
+    }
+
+    /// Record that a job reported back and schedule its next run
+    pub fn finished(&mut self, job: Job, now: Instant) {
+        let interval = self.jobs.get(&job).and_then(|state| state.interval);
+        let next_due = interval.map(|i| now + self.jittered(i));
+        let state = self.state(job);
+        state.in_flight = false;
+        state.next_due = next_due;
+    }
+
+    /// The job's data was just refreshed some other way (e.g. synchronously after an action), so
+    /// push its next run back by a full interval.
+    pub fn reset(&mut self, job: Job, now: Instant) {
+        let Some(interval) = self.jobs.get(&job).and_then(|state| state.interval) else { return };
+        let next_due = now + self.jittered(interval);
+        self.state(job).next_due = Some(next_due);
+    }
+
+    /// Apply random jitter to an interval
+    fn jittered(&mut self, interval: Duration) -> Duration {
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        // Map to [-1, 1)
+        let unit = (self.rng >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0;
+        interval.mul_f64((1.0 + unit * self.jitter).max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn unjittered() -> Scheduler {
+        Scheduler { min_gap: Duration::ZERO, jitter: 0.0, ..Scheduler::new() }
+    }
+
+    #[test]
+    fn jobs_run_again_an_interval_after_finishing() {
+        let mut scheduler = unjittered();
+        scheduler.set_interval(Job::Preview, Some(SECOND));
+        let start = Instant::now();
+        assert_eq!(scheduler.due(start), None);
+        assert_eq!(scheduler.due(start + SECOND), Some(Job::Preview));
+        // Not again while it is in flight
+        assert!(scheduler.in_flight(Job::Preview));
+        assert_eq!(scheduler.due(start + 3 * SECOND), None);
+        scheduler.finished(Job::Preview, start + 3 * SECOND);
+        assert!(!scheduler.in_flight(Job::Preview));
+        assert_eq!(scheduler.due(start + 3 * SECOND), None);
+        assert_eq!(scheduler.due(start + 4 * SECOND), Some(Job::Preview));
+        // Jobs without an interval only run when triggered, once
+        scheduler.trigger(Job::Windows(0));
+        let now = Instant::now();
+        assert_eq!(scheduler.due(now), Some(Job::Windows(0)));
+        scheduler.finished(Job::Windows(0), now);
+        assert_eq!(scheduler.due(now + 60 * SECOND), None);
+    }
+
+    #[test]
+    fn reset_pushes_the_next_run_back() {
+        let mut scheduler = unjittered();
+        let start = Instant::now();
+        scheduler.set_interval(Job::Sessions(0), Some(SECOND));
+        scheduler.reset(Job::Sessions(0), start + SECOND / 2);
+        assert_eq!(scheduler.due(start + SECOND), None);
+        assert_eq!(scheduler.due(start + 3 * SECOND / 2), Some(Job::Sessions(0)));
+    }
+
+    #[test]
+    fn jitter_stays_within_its_fraction() {
+        let mut scheduler = Scheduler { jitter: 0.1, ..Scheduler::new() };
+        let intervals: Vec<Duration> = (0..1000).map(|_| scheduler.jittered(10 * SECOND)).collect();
+        assert!(intervals.iter().all(|interval| (9 * SECOND..=11 * SECOND).contains(interval)));
+        // Stretched and shrunk alike, not always the same
+        assert!(intervals.iter().any(|interval| *interval < 10 * SECOND));
+        assert!(intervals.iter().any(|interval| *interval > 10 * SECOND));
+    }
+
+    #[test]
+    fn launches_are_limited_and_spaced() {
+        let mut scheduler = Scheduler { max_in_flight: 2, ..unjittered() };
+        for host in 0..3 {
+            scheduler.trigger(Job::Sessions(host));
+        }
+        let now = Instant::now();
+        // In priority order, no more than two at a time
+        assert_eq!(scheduler.due(now), Some(Job::Sessions(0)));
+        assert_eq!(scheduler.due(now), Some(Job::Sessions(1)));
+        assert_eq!(scheduler.due(now), None);
+        scheduler.finished(Job::Sessions(0), now);
+        assert_eq!(scheduler.due(now), Some(Job::Sessions(2)));
+
+        let mut scheduler = Scheduler { min_gap: SECOND, max_in_flight: 3, ..unjittered() };
+        scheduler.trigger(Job::Preview);
+        scheduler.trigger(Job::Windows(0));
+        let now = Instant::now();
+        assert_eq!(scheduler.due(now), Some(Job::Preview));
+        assert_eq!(scheduler.due(now + SECOND / 2), None);
+        assert_eq!(scheduler.due(now + SECOND), Some(Job::Windows(0)));
+    }
+}
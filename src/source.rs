@@ -0,0 +1,193 @@
+use std::{env, fs, path::{Path, PathBuf}, process::Command};
+
+/// A kind of entry the main list can show. Live sessions (of the local server and every remote
+/// host, which is why hosts are not a source of their own) are listed and refreshed by the app
+/// itself, as are templates and archived sessions, which it has at hand. Directories and
+/// projects are listed by [`fetch`], in the background, and every source other than sessions is
+/// shown in a section of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    /// Live tmux sessions
+    Sessions,
+    /// Frequently used directories, from `zoxide query --list`
+    Directories,
+    /// Git repositories found under the project roots of the configuration file
+    Projects,
+    /// Session templates of the configuration file and of other tools' layouts
+    Templates,
+    /// Archived sessions
+    Snapshots,
+}
+
+impl Source {
+    /// Every source, in the order their sections are shown
+    pub const ALL: [Source; 5] = [Source::Sessions, Source::Directories, Source::Projects, Source::Templates, Source::Snapshots];
+
+    /// Name used in the configuration file and as the title of the source's section
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::Sessions => "sessions",
+            Source::Directories => "directories",
+            Source::Projects => "projects",
+            Source::Templates => "templates",
+            Source::Snapshots => "snapshots",
+        }
+    }
+
+    /// Look up a source by [`Source::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|source| source.name() == name)
+    }
+
+    /// Badge shown in front of the source's entries, in place of a session's window count
+    pub fn badge(self) -> &'static str {
+        match self {
+            Source::Sessions => "",
+            Source::Directories => "[dir]",
+            Source::Projects => "[git]",
+            Source::Templates => "[tpl]",
+            Source::Snapshots => "[arc]",
+        }
+    }
+
+    /// Whether [`fetch`] lists the source, which can take a while
+    pub fn is_fetched(self) -> bool {
+        matches!(self, Source::Directories | Source::Projects)
+    }
+}
+
+/// An entry of a source other than [`Source::Sessions`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceEntry {
+    /// The source that listed the entry
+    pub source: Source,
+    /// Text shown in the list
+    pub label: String,
+    /// What the entry stands for: a directory, the name of a template or the file of an archived
+    /// session
+    pub path: String,
+}
+
+impl SourceEntry {
+    /// Name of the session opened for the entry: the directory's base name, with the characters
+    /// tmux does not allow in session names replaced like tmux itself does
+    pub fn session_name(&self) -> String {
+        let base = self.path.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+        let base = if base.is_empty() { "root" } else { base };
+        base.replace(['.', ':'], "_")
+    }
+}
+
+/// How many directories down from a project root repositories are looked for, e.g.
+/// `~/src/github.com/user/repo` with `~/src` as the root
+pub const PROJECT_DEPTH: usize = 3;
+
+/// List the entries of a source, where projects are looked for under `project_roots`. Only the
+/// sources that are [`Source::is_fetched`] are listed here, so there are none for the others.
+pub fn fetch(source: Source, project_roots: &[String]) -> Result<Vec<SourceEntry>, String> {
+    match source {
+        Source::Sessions | Source::Templates | Source::Snapshots => Ok(vec![]),
+        Source::Directories => {
+            let output = Command::new("zoxide").args(["query", "--list"]).output()
+                .map_err(|err| format!("Could not run zoxide: {}", err))?;
+            if !output.status.success() {
+                return Err(format!("zoxide failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+            }
+            // Most frecent first, as zoxide lists them
+            Ok(String::from_utf8_lossy(&output.stdout).lines().filter(|line| !line.is_empty()).map(|path| {
+                SourceEntry { source, label: home_label(path), path: path.to_owned() }
+            }).collect())
+        }
+        Source::Projects => {
+            if project_roots.is_empty() {
+                return Err("No project roots: set `projects` in the configuration file".to_owned());
+            }
+            let mut repos = vec![];
+            for root in project_roots {
+                find_repos(Path::new(&expand_home(root)), PROJECT_DEPTH, &mut repos);
+            }
+            Ok(repos.into_iter().map(|repo| {
+                let path = repo.to_string_lossy().into_owned();
+                SourceEntry { source, label: home_label(&path), path }
+            }).collect())
+        }
+    }
+}
+
+/// Add the git repositories at or below `dir`, at most `depth` directories down, by name. The
+/// directories inside a repository and hidden ones are not searched.
+fn find_repos(dir: &Path, depth: usize, repos: &mut Vec<PathBuf>) {
+    if dir.join(".git").exists() {
+        repos.push(dir.to_owned());
+        return;
+    }
+    let Some(Ok(entries)) = (depth > 0).then(|| fs::read_dir(dir)) else { return };
+    let mut dirs: Vec<PathBuf> = entries.filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| entry.path())
+        .collect();
+    dirs.sort();
+    for dir in dirs {
+        find_repos(&dir, depth - 1, repos);
+    }
+}
+
+/// A path as shown in the list: relative to `~` if it is under the home directory
+pub fn home_label(path: &str) -> String {
+    let home = env::var("HOME").unwrap_or_default();
+    match path.strip_prefix(&home) {
+        Some(rest) if !home.is_empty() && (rest.is_empty() || rest.starts_with('/')) => format!("~{}", rest),
+        _ => path.to_owned(),
+    }
+}
+
+/// Replace a leading `~` with the home directory
+pub fn expand_home(path: &str) -> String {
+    match (path.strip_prefix('~'), env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", home, rest),
+        _ => path.to_owned(),
+    }
+}
+
+/// The completions of a directory path being typed: the directories next to its last component
+/// whose name starts with it, as the whole path with a `/` at the end, by name. Hidden
+/// directories only complete a component that starts with `.`.
+pub fn complete_directory(path: &str) -> Vec<String> {
+    if path == "~" {
+        return vec!["~/".to_owned()];
+    }
+    let (dir, prefix) = match path.rfind('/') {
+        Some(idx) => (&path[..=idx], &path[idx + 1..]),
+        None => ("", path),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { ".".to_owned() } else { expand_home(dir) }) else { return vec![] };
+    let mut completions: Vec<String> = entries.filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with(prefix) && (prefix.starts_with('.') || !name.starts_with('.')))
+        .map(|name| format!("{}{}/", dir, name))
+        .collect();
+    completions.sort();
+    completions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repositories_are_found_under_the_roots() {
+        let root = env::temp_dir().join(format!("tmm-projects-test-{}", std::process::id()));
+        for dir in ["tools/.git", "web/app/.git", "web/app/vendor/lib/.git", "a/b/c/d/.git", ".hidden/.git", "empty"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let entries = fetch(Source::Projects, &[root.to_string_lossy().into_owned()]).unwrap();
+        // Not inside a repository, nor in hidden directories, nor too deep
+        let paths: Vec<&str> = entries.iter().map(|entry| entry.path.strip_prefix(root.to_str().unwrap()).unwrap()).collect();
+        assert_eq!(paths, ["/tools", "/web/app"]);
+        assert_eq!(entries[1].session_name(), "app");
+        assert!(fetch(Source::Projects, &[]).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
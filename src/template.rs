@@ -0,0 +1,188 @@
+use std::io;
+use std::path::PathBuf;
+use std::process::Output;
+
+use crate::source::expand_home;
+use crate::tmux::TmuxClient;
+
+/// A session layout declared in the configuration file, which the new session form creates in
+/// one go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pub name: String,
+    /// What the template is for, shown in the templates view
+    pub description: Option<String>,
+    /// File the template was read from, `None` for the configuration file itself
+    pub file: Option<PathBuf>,
+    /// Directory every pane starts in, where a leading `~` is the home directory
+    pub dir: Option<String>,
+    /// Layout of windows with several panes (as `select-layout` takes it), `tiled` by default
+    pub layout: Option<String>,
+    /// Windows in order. None creates a single window with the default command.
+    pub windows: Vec<TemplateWindow>,
+    /// Commands typed into a window (by name or index) once the whole session is built, in
+    /// order, such as `direnv allow`
+    pub after: Vec<(String, String)>,
+}
+
+/// A window of a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateWindow {
+    pub name: String,
+    /// Layout of this window, over the template's
+    pub layout: Option<String>,
+    /// Command typed into each pane, one pane per command, where an empty command leaves the
+    /// shell alone. None gives a single pane.
+    pub panes: Vec<String>,
+}
+
+impl Template {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_owned(), description: None, file: None, dir: None, layout: None, windows: vec![], after: vec![] }
+    }
+
+    /// How many windows and panes the template creates, e.g. `2 windows, 3 panes`
+    pub fn summary(&self) -> String {
+        let windows = self.windows.len().max(1);
+        let panes: usize = self.windows.iter().map(|window| window.panes.len().max(1)).sum::<usize>().max(1);
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        format!("{} window{}, {} pane{}", windows, plural(windows), panes, plural(panes))
+    }
+
+    /// The template in tmm's template file format, as `tmm template import` installs them
+    pub fn to_file(&self) -> String {
+        let mut text = format!("name = {}\n", quote(&self.name));
+        for (key, value) in [("description", &self.description), ("dir", &self.dir), ("layout", &self.layout)] {
+            if let Some(value) = value {
+                text.push_str(&format!("{} = {}\n", key, quote(value)));
+            }
+        }
+        let windows: Vec<String> = self.windows.iter().map(|window| {
+            let names: Vec<String> = [&window.name].into_iter().chain(&window.panes).map(|name| quote(name)).collect();
+            format!("[{}]", names.join(", "))
+        }).collect();
+        text.push_str(&format!("windows = [{}]\n", windows.join(", ")));
+        if !self.after.is_empty() {
+            let after: Vec<String> = self.after.iter()
+                .map(|(window, command)| format!("[{}, {}]", quote(window), quote(command)))
+                .collect();
+            text.push_str(&format!("after = [{}]\n", after.join(", ")));
+        }
+        text
+    }
+
+    /// Create a local session named `session` with the windows and panes of the template. Stops
+    /// at the first tmux command that fails, leaving whatever was created so far.
+    pub fn create(&self, tmux: &dyn TmuxClient, session: &str) -> Result<(), String> {
+        let dir = self.dir.as_deref().map(expand_home);
+        let single = [TemplateWindow { name: String::new(), layout: None, panes: vec![] }];
+        let windows = if self.windows.is_empty() { &single[..] } else { &self.windows[..] };
+        let mut session_id = String::new();
+        for (idx, window) in windows.iter().enumerate() {
+            // The first window comes with the session, the others are added after it without
+            // switching to them
+            let mut args = if idx == 0 {
+                vec!["new-session", "-d", "-s", session]
+            } else {
+                vec!["new-window", "-d", "-t", &session_id]
+            };
+            args.extend(["-P", "-F", "#{session_id}:\t#{window_id}\t#{pane_id}"]);
+            if let Some(dir) = &dir {
+                args.extend(["-c", dir]);
+            }
+            if !window.name.is_empty() {
+                args.extend(["-n", &window.name]);
+            }
+            let created = printed(tmux.output(None, &args))?;
+            let mut ids = created.split('\t').map(str::to_owned);
+            let (Some(new_session_id), Some(window_id), Some(pane_id)) = (ids.next(), ids.next(), ids.next()) else {
+                return Err(format!("unexpected reply from tmux: {}", created));
+            };
+            session_id = new_session_id;
+
+            // Split the window into the remaining panes, then lay them out
+            let mut panes = vec![pane_id];
+            for _ in 1..window.panes.len() {
+                let mut args = vec!["split-window", "-d", "-t", &window_id, "-P", "-F", "#{pane_id}"];
+                if let Some(dir) = &dir {
+                    args.extend(["-c", dir]);
+                }
+                panes.push(printed(tmux.output(None, &args))?);
+            }
+            if panes.len() > 1 {
+                let layout = window.layout.as_deref().or(self.layout.as_deref()).unwrap_or("tiled");
+                printed(tmux.output(None, &["select-layout", "-t", &window_id, layout]))?;
+            }
+            for (pane, command) in panes.iter().zip(&window.panes).filter(|(_, command)| !command.is_empty()) {
+                // Typed literally, so that words such as `Enter` in the command are not taken
+                // for keys
+                printed(tmux.output(None, &["send-keys", "-t", pane, "-l", command]))?;
+                printed(tmux.output(None, &["send-keys", "-t", pane, "Enter"]))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A TOML basic string holding `value`
+fn quote(value: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// What a tmux command printed, or its error
+fn printed(output: io::Result<Output>) -> Result<String, String> {
+    match output {
+        Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned()),
+        Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_owned()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmux::MockClient;
+
+    #[test]
+    fn creates_every_window_and_pane() {
+        let mock = MockClient::new();
+        mock.reply("new-session", Ok("$1:\t@1\t%1\n"));
+        mock.reply("new-window", Ok("$1:\t@2\t%2\n"));
+        mock.reply("split-window", Ok("%3\n"));
+        let template = Template {
+            dir: Some("/src/web".to_owned()),
+            windows: vec![
+                TemplateWindow { name: "editor".to_owned(), layout: None, panes: vec!["nvim".to_owned()] },
+                TemplateWindow { name: "server".to_owned(), layout: None, panes: vec![String::new(), "npm test".to_owned()] },
+            ],
+            ..Template::new("web")
+        };
+        assert_eq!(template.summary(), "2 windows, 3 panes");
+        template.create(&mock, "site").unwrap();
+        let calls: Vec<String> = mock.calls().into_iter().map(|(_, args)| args.join(" ")).collect();
+        let format = "-P -F #{session_id}:\t#{window_id}\t#{pane_id}";
+        assert_eq!(calls, [
+            format!("new-session -d -s site {} -c /src/web -n editor", format),
+            "send-keys -t %1 -l nvim".to_owned(),
+            "send-keys -t %1 Enter".to_owned(),
+            format!("new-window -d -t $1: {} -c /src/web -n server", format),
+            "split-window -d -t @2 -P -F #{pane_id} -c /src/web".to_owned(),
+            "select-layout -t @2 tiled".to_owned(),
+            "send-keys -t %3 -l npm test".to_owned(),
+            "send-keys -t %3 Enter".to_owned(),
+        ]);
+    }
+}
@@ -0,0 +1,711 @@
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::{fs::FileTypeExt, net::UnixStream, process::ExitStatusExt},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, ExitStatus, Output, Stdio},
+    sync::{mpsc::{self, Receiver, Sender, TryRecvError}, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{lock, log};
+
+/// The local tmux server commands go to: the default one, or the one `tmux -L <socket name>` or
+/// `tmux -S <socket path>` would talk to. Remote hosts keep theirs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Server {
+    /// `-L <socket name>` or `-S <socket path>`, empty for the default server
+    args: Vec<String>,
+}
+
+impl Server {
+    /// The server with the socket named `name` (as `tmux -L` takes it) or at `path` (as `tmux -S`
+    /// does), or the default server with neither
+    pub fn new(name: Option<&str>, path: Option<&str>) -> Self {
+        let args = match (name, path) {
+            (_, Some(path)) => vec!["-S".to_owned(), path.to_owned()],
+            (Some(name), None) => vec!["-L".to_owned(), name.to_owned()],
+            (None, None) => vec![],
+        };
+        Self { args }
+    }
+
+    /// The server at `socket`
+    pub fn at(socket: &Path) -> Self {
+        Self::new(None, Some(&socket.to_string_lossy()))
+    }
+
+    /// The arguments choosing the server, to pass right after `tmux`
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Socket of the server, `None` for the default server. A socket name lives in tmux's
+    /// directory for the user, under `$TMUX_TMPDIR` or else `/tmp`.
+    pub fn socket(&self) -> Option<PathBuf> {
+        match self.args.as_slice() {
+            [flag, path] if flag == "-S" => Some(PathBuf::from(path)),
+            [_, name] => Some(socket_dir().join(name)),
+            _ => None,
+        }
+    }
+
+    /// Socket tmux commands go to: the server's, else the one tmm runs in (as tmux itself goes by
+    /// `$TMUX`), else the default one
+    pub fn current_socket(&self) -> PathBuf {
+        self.socket()
+            .or_else(|| lock::tmux_socket().map(PathBuf::from))
+            .unwrap_or_else(|| socket_dir().join("default"))
+    }
+
+    /// Name of the server for the interface to show, `None` for the default server
+    pub fn label(&self) -> Option<String> {
+        let socket = self.current_socket();
+        (socket != socket_dir().join("default")).then(|| server_name(&socket))
+    }
+
+    /// Whether tmm runs inside this server, from `$TMUX`, which tmm drops at startup when its
+    /// server is gone (see [`stale_env`]). Inside another server tmm is as good as outside tmux,
+    /// since a client can only be switched within its own server.
+    pub fn is_nested(&self) -> bool {
+        let Some(socket) = lock::tmux_socket() else { return false };
+        self.socket().is_none_or(|server| server.as_os_str() == socket.as_str())
+    }
+}
+
+/// tmux's directory for the user's sockets, under `$TMUX_TMPDIR` or else `/tmp`
+fn socket_dir() -> PathBuf {
+    let dir = env::var("TMUX_TMPDIR").ok().filter(|dir| !dir.is_empty()).unwrap_or_else(|| "/tmp".to_owned());
+    // SAFETY: getuid cannot fail
+    let uid = unsafe { libc::getuid() };
+    PathBuf::from(dir).join(format!("tmux-{}", uid))
+}
+
+/// The sockets of the running local tmux servers, found in tmux's directory for the user, and
+/// `current` wherever it is. Sockets left behind by servers that are gone are left out.
+pub fn servers(current: &Path) -> Vec<PathBuf> {
+    let mut servers: Vec<PathBuf> = fs::read_dir(socket_dir()).into_iter().flatten().flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_socket()))
+        .map(|entry| entry.path())
+        .filter(|path| UnixStream::connect(path).is_ok())
+        .collect();
+    if !servers.iter().any(|server| server == current) {
+        servers.push(current.to_owned());
+    }
+    servers.sort();
+    servers
+}
+
+/// How a server is called: its socket name (as `tmux -L` takes it) if its socket is in tmux's
+/// directory for the user, else its socket path
+pub fn server_name(socket: &Path) -> String {
+    match (socket.parent(), socket.file_name()) {
+        (Some(dir), Some(name)) if dir == socket_dir() => name.to_string_lossy().into_owned(),
+        _ => socket.display().to_string(),
+    }
+}
+
+/// Build a tmux command with the given arguments, for `server` unless they are for a remote host,
+/// which are run over ssh.
+///
+/// tmux is always run with `-u`. Without it, a client outside tmux in a non-UTF-8 locale (common
+/// for non-interactive ssh) gets control characters such as the tabs separating `-F` fields
+/// replaced with `_`.
+pub fn command<I, S>(server: &Server, host: Option<&str>, args: I) -> Command
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    match host {
+        None => {
+            let mut cmd = Command::new("tmux");
+            cmd.arg("-u").args(server.args()).args(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+            cmd
+        }
+        Some(host) => {
+            // ssh hands the command to the remote shell as a single string, so every argument has
+            // to be quoted to survive word splitting
+            let remote = ["tmux".to_owned(), "-u".to_owned()].into_iter()
+                .chain(args.into_iter().map(|arg| shell_quote(arg.as_ref())))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let mut cmd = Command::new("ssh");
+            cmd.args(["-o", "BatchMode=yes", host, &remote]);
+            cmd
+        }
+    }
+}
+
+/// Whether there is a tmux to run locally
+pub fn installed() -> bool {
+    !matches!(Command::new("tmux").arg("-V").output(), Err(err) if err.kind() == io::ErrorKind::NotFound)
+}
+
+/// Whether `$TMUX` is set but names a tmux server that is gone, as a variable left over in an
+/// environment that outlived its server does. tmux itself would then talk to that server instead
+/// of the default one, and fail.
+pub fn stale_env() -> bool {
+    if !matches!(env::var("TMUX"), Ok(value) if !value.is_empty()) {
+        return false;
+    }
+    // As the calling client, which asks the server $TMUX names
+    !command(&Server::default(), None, ["display-message", "-p", "#{pid}"]).output().is_ok_and(|output| output.status.success())
+}
+
+/// Run a tmux command and collect its output. Local commands go to `server`, over the control mode
+/// connection when there is one (see [`start_control_mode`]).
+///
+/// Commands whose effect depends on the calling client, such as `display-message` or
+/// `switch-client`, must be built with [`command`] instead, since over control mode the calling
+/// client is the control client.
+pub fn output<I, S>(server: &Server, host: Option<&str>, args: I) -> io::Result<Output>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let args: Vec<String> = args.into_iter().map(|arg| arg.as_ref().to_owned()).collect();
+    let started = Instant::now();
+    if host.is_none() {
+        if let Some(output) = control_output(server, &args, CONTROL_TIMEOUT) {
+            log_output(host, &args, "control", started, Ok(&output));
+            return Ok(output);
+        }
+    }
+    let output = command(server, host, &args).output();
+    log_output(host, &args, "process", started, output.as_ref().map_err(ToString::to_string));
+    output
+}
+
+/// Like [`output`], but give up if the command does not finish within `timeout`
+pub fn output_with_timeout<I, S>(server: &Server, host: Option<&str>, args: I, timeout: Duration) -> Option<Output>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let args: Vec<String> = args.into_iter().map(|arg| arg.as_ref().to_owned()).collect();
+    let started = Instant::now();
+    if host.is_none() {
+        if let Some(output) = control_output(server, &args, timeout) {
+            log_output(host, &args, "control", started, Ok(&output));
+            return Some(output);
+        }
+    }
+    let output = wait_with_timeout(command(server, host, &args), timeout);
+    log_output(host, &args, "process", started, output.as_ref().ok_or_else(|| "timed out".to_owned()));
+    output
+}
+
+/// Log a tmux command run `via` control mode or a process, with how long it took and its exit
+/// code and stderr, or why it could not be run
+fn log_output(host: Option<&str>, args: &[String], via: &str, started: Instant, output: Result<&Output, String>) {
+    if !log::enabled() {
+        return;
+    }
+    let elapsed = format!("{}ms", started.elapsed().as_millis());
+    let args = args.join(" ");
+    let mut fields = vec![("args", args.as_str()), ("via", via), ("took", elapsed.as_str())];
+    if let Some(host) = host {
+        fields.push(("host", host));
+    }
+    let (status, stderr);
+    match output {
+        Ok(output) => {
+            status = output.status.code().map_or_else(|| "signal".to_owned(), |code| code.to_string());
+            stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+            fields.push(("status", &status));
+            if !stderr.is_empty() {
+                fields.push(("stderr", &stderr));
+            }
+        }
+        Err(err) => {
+            stderr = err;
+            fields.push(("error", &stderr));
+        }
+    }
+    log::record("tmux", &fields);
+}
+
+/// Run a command over the control mode connection to `server`, connecting first if needed.
+/// Returns `None` if control mode is unavailable, in which case the caller spawns tmux instead.
+fn control_output(server: &Server, args: &[String], timeout: Duration) -> Option<Output> {
+    let mut state = CONTROL.lock().ok()?;
+    if state.client.as_ref().is_some_and(|client| client.server != *server) {
+        // Connected to another server, so start over with this one
+        state.client = None;
+        state.last_failure = None;
+    }
+    state.connect(server);
+    let output = state.client.as_mut()?.run(args, timeout);
+    if output.is_none() {
+        // Broken or out of sync with its replies, so start over
+        state.client = None;
+    }
+    output
+}
+
+/// Run a command to completion, killing it if it does not finish within `timeout`
+fn wait_with_timeout(mut cmd: Command, timeout: Duration) -> Option<Output> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    // Drained while waiting, since a child writing more than a pipe holds blocks until it is read
+    let stdout = drain(child.stdout.take()?);
+    let stderr = drain(child.stderr.take()?);
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(Output { status, stdout: stdout.join().ok()?, stderr: stderr.join().ok()? }),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+}
+
+/// Read a pipe to its end on a thread of its own
+fn drain(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut contents = vec![];
+        let _ = pipe.read_to_end(&mut contents);
+        contents
+    })
+}
+
+/// Quote a string for tmux's own command parser
+fn tmux_quote(arg: &str) -> String {
+    if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=@%+,:".contains(c)) {
+        arg.to_owned()
+    } else {
+        // Double quotes, in which tmux expands `$` variables and `\` escapes
+        let mut quoted = String::from('"');
+        for c in arg.chars() {
+            if matches!(c, '"' | '\\' | '$') {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted
+    }
+}
+
+/// Quote a string for a POSIX shell
+pub fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=@%+,".contains(c)) {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Runs tmux commands on behalf of the app. [`ProcessClient`] talks to real tmux servers, while
+/// [`MockClient`] answers from canned replies so that app logic can be tested without one.
+pub trait TmuxClient: fmt::Debug + Send + Sync {
+    /// Run a command and collect its output, see [`output`]
+    fn output(&self, host: Option<&str>, args: &[&str]) -> io::Result<Output>;
+
+    /// Run a command, giving up if it does not finish within `timeout`, see
+    /// [`output_with_timeout`]
+    fn output_with_timeout(&self, host: Option<&str>, args: &[&str], timeout: Duration) -> Option<Output>;
+
+    /// Run a local command as tmm's own client, for commands whose effect depends on the calling
+    /// client such as `display-message`
+    fn client_output(&self, args: &[&str]) -> io::Result<Output>;
+
+    /// Id of the local session the control mode client is attached to, see
+    /// [`control_session_id`]
+    fn control_session_id(&self) -> Option<String> {
+        None
+    }
+
+    /// The local server commands go to
+    fn server(&self) -> Server;
+
+    /// Send local commands to `server` from now on
+    fn use_server(&self, server: Server);
+}
+
+/// [`TmuxClient`] running tmux itself, over control mode where possible
+#[derive(Debug, Default)]
+pub struct ProcessClient {
+    server: Mutex<Server>,
+}
+
+impl ProcessClient {
+    /// Constructs a new instance of [`ProcessClient`] sending local commands to `server`.
+    pub fn new(server: Server) -> Self {
+        Self { server: Mutex::new(server) }
+    }
+}
+
+impl TmuxClient for ProcessClient {
+    fn output(&self, host: Option<&str>, args: &[&str]) -> io::Result<Output> {
+        output(&self.server(), host, args)
+    }
+
+    fn output_with_timeout(&self, host: Option<&str>, args: &[&str], timeout: Duration) -> Option<Output> {
+        output_with_timeout(&self.server(), host, args, timeout)
+    }
+
+    fn client_output(&self, args: &[&str]) -> io::Result<Output> {
+        let started = Instant::now();
+        let output = command(&self.server(), None, args).output();
+        let logged: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        log_output(None, &logged, "client", started, output.as_ref().map_err(ToString::to_string));
+        output
+    }
+
+    fn control_session_id(&self) -> Option<String> {
+        control_session_id()
+    }
+
+    fn server(&self) -> Server {
+        self.server.lock().map(|server| server.clone()).unwrap_or_default()
+    }
+
+    fn use_server(&self, server: Server) {
+        if let Ok(mut current) = self.server.lock() {
+            *current = server;
+        }
+    }
+}
+
+/// [`TmuxClient`] that records every command and answers with canned replies, per whole command
+/// line or else per command name (the first argument). Commands without a reply succeed with no
+/// output.
+#[derive(Debug)]
+pub struct MockClient {
+    /// Stdout of a successful command, or stderr of a failed one, by command line or name
+    replies: Mutex<HashMap<String, Result<String, String>>>,
+    /// Every command run so far as (host, arguments)
+    calls: Mutex<Vec<(Option<String>, Vec<String>)>>,
+    /// The server chosen with [`TmuxClient::use_server`]
+    server: Mutex<Server>,
+}
+
+impl Default for MockClient {
+    /// A mock of a server of its own, so that tmm never looks nested in it whatever `$TMUX` says
+    fn default() -> Self {
+        Self {
+            replies: Mutex::default(),
+            calls: Mutex::default(),
+            server: Mutex::new(Server::new(Some("tmm-mock"), None)),
+        }
+    }
+}
+
+impl MockClient {
+    /// Constructs a new instance of [`MockClient`] without any replies.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer every later run of `command` with `reply`, where `command` is a command name, or a
+    /// whole command line (its arguments separated by spaces) to answer only that one
+    pub fn reply(&self, command: &str, reply: Result<&str, &str>) {
+        let reply = reply.map(str::to_owned).map_err(str::to_owned);
+        self.replies.lock().expect("mock replies poisoned").insert(command.to_owned(), reply);
+    }
+
+    /// Every command run so far as (host, arguments)
+    pub fn calls(&self) -> Vec<(Option<String>, Vec<String>)> {
+        self.calls.lock().expect("mock calls poisoned").clone()
+    }
+}
+
+impl TmuxClient for MockClient {
+    fn output(&self, host: Option<&str>, args: &[&str]) -> io::Result<Output> {
+        self.calls.lock().expect("mock calls poisoned")
+            .push((host.map(str::to_owned), args.iter().map(|arg| arg.to_string()).collect()));
+        let replies = self.replies.lock().expect("mock replies poisoned");
+        let reply = replies.get(&args.join(" "))
+            .or_else(|| args.first().and_then(|command| replies.get(*command)))
+            .cloned();
+        drop(replies);
+        Ok(match reply.unwrap_or(Ok(String::new())) {
+            Ok(stdout) => Output { status: ExitStatus::from_raw(0), stdout: stdout.into_bytes(), stderr: vec![] },
+            Err(stderr) => Output { status: ExitStatus::from_raw(1 << 8), stdout: vec![], stderr: stderr.into_bytes() },
+        })
+    }
+
+    fn output_with_timeout(&self, host: Option<&str>, args: &[&str], _timeout: Duration) -> Option<Output> {
+        self.output(host, args).ok()
+    }
+
+    fn client_output(&self, args: &[&str]) -> io::Result<Output> {
+        self.output(None, args)
+    }
+
+    fn server(&self) -> Server {
+        self.server.lock().expect("mock server poisoned").clone()
+    }
+
+    fn use_server(&self, server: Server) {
+        *self.server.lock().expect("mock server poisoned") = server;
+    }
+}
+
+/// How long to wait before retrying after the control mode connection could not be established
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long to wait for a reply on the control mode connection before giving up on it
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Control mode format subscription covering what the session list shows that tmux has no
+/// notification for: which sessions are attached and which panes are dead. tmux checks it about
+/// once a second and sends `%subscription-changed` when its value changes.
+const SUBSCRIPTION: &str = "tmm-sessions::#{S:#{session_id}:#{session_attached}:#{W:#{P:#{pane_dead}}} }";
+
+/// Callback for control mode notifications
+type NotificationHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// A `tmux -C` client attached to the local server. Commands are written to its stdin as command
+/// lines, and tmux answers each one with a `%begin` ... `%end` (or `%error`) block on stdout,
+/// interleaved with `%` notifications about changes made by anyone.
+struct ControlClient {
+    /// The server it is attached to
+    server: Server,
+    child: Child,
+    stdin: ChildStdin,
+    /// Replies to our commands in order: the block contents, or the error for `%error` blocks
+    replies: Receiver<Result<String, String>>,
+}
+
+impl Drop for ControlClient {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+struct ControlState {
+    client: Option<ControlClient>,
+    /// Set by [`start_control_mode`]. Control mode is not used until then.
+    handler: Option<NotificationHandler>,
+    /// When connecting last failed, to rate limit retries
+    last_failure: Option<Instant>,
+}
+
+/// The local control mode connection, shared by every thread that runs tmux commands. It is locked
+/// for a whole command round trip, which keeps the replies in order.
+static CONTROL: Mutex<ControlState> = Mutex::new(ControlState { client: None, handler: None, last_failure: None });
+
+/// Id of the session the control client is attached to. Kept apart from [`CONTROL`] since it is
+/// updated by the reader thread while a command holds that lock.
+static CONTROL_SESSION: Mutex<Option<String>> = Mutex::new(None);
+
+/// Run local tmux commands over a persistent control mode connection, connecting to `server`
+/// first, instead of spawning a process for each, and pass every notification line (e.g. `%sessions-changed`) to `handler`. A final
+/// `%exit` is passed when the connection goes away.
+///
+/// The connection needs a session to attach to, so with no sessions (or when it is lost, e.g.
+/// because its session was killed) commands fall back to spawning tmux and reconnecting is retried
+/// every few seconds.
+pub fn start_control_mode(server: &Server, handler: impl Fn(&str) + Send + Sync + 'static) {
+    let Ok(mut state) = CONTROL.lock() else { return };
+    state.handler = Some(Arc::new(handler));
+    state.last_failure = None;
+    state.connect(server);
+}
+
+/// Id of the session the control mode client is attached to, if connected. tmux counts the
+/// control client like any other, so that session always looks attached.
+pub fn control_session_id() -> Option<String> {
+    CONTROL_SESSION.lock().ok()?.clone()
+}
+
+impl ControlState {
+    /// Connect to `server` if control mode is enabled, not connected and did not fail to connect
+    /// too recently
+    fn connect(&mut self, server: &Server) {
+        let Some(handler) = self.handler.clone() else { return };
+        // Nothing is waiting for a reply between commands, so a closed reply channel means the
+        // reader thread saw the client exit
+        if self.client.as_ref().is_some_and(|client| matches!(client.replies.try_recv(), Err(TryRecvError::Disconnected))) {
+            self.client = None;
+        }
+        if self.client.is_some() || self.last_failure.is_some_and(|last| last.elapsed() < RECONNECT_INTERVAL) {
+            return;
+        }
+        self.client = ControlClient::connect(server, handler);
+        self.last_failure = self.client.is_none().then(Instant::now);
+    }
+}
+
+impl ControlClient {
+    fn connect(server: &Server, handler: NotificationHandler) -> Option<Self> {
+        // Attach to the most recent session without affecting its size or receiving pane output
+        let mut child = Command::new("tmux")
+            .arg("-u")
+            .args(server.args())
+            .args(["-C", "attach-session", "-f", "no-output,ignore-size"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let stdin = child.stdin.take()?;
+        let stdout = BufReader::new(child.stdout.take()?);
+        let (reply_sender, replies) = mpsc::channel();
+        let (ready_sender, ready) = mpsc::channel();
+        thread::spawn(move || read_control(stdout, reply_sender, ready_sender, handler));
+        let mut client = Self { server: server.clone(), child, stdin, replies };
+        // tmux announces the session once attached, or exits if there is none to attach to
+        ready.recv_timeout(CONTROL_TIMEOUT).ok()?;
+        // Subscriptions need tmux 3.2. Without them changes only show up at the next refresh.
+        let _ = client.run(&["refresh-client".to_owned(), "-B".to_owned(), SUBSCRIPTION.to_owned()], CONTROL_TIMEOUT);
+        Some(client)
+    }
+
+    /// Run a command, where `;` arguments separate several commands as on the command line.
+    /// Returns `None` if the connection is broken.
+    fn run(&mut self, args: &[String], timeout: Duration) -> Option<Output> {
+        let commands: Vec<String> = args.split(|arg| arg == ";")
+            .map(|command| command.iter().map(|arg| tmux_quote(arg)).collect::<Vec<String>>().join(" "))
+            .collect();
+        let mut lines = commands.join("\n");
+        lines.push('\n');
+        self.stdin.write_all(lines.as_bytes()).ok()?;
+        self.stdin.flush().ok()?;
+        // Each command line gets its own reply block
+        let deadline = Instant::now() + timeout;
+        let mut output = Output { status: ExitStatus::from_raw(0), stdout: vec![], stderr: vec![] };
+        for _ in &commands {
+            match self.replies.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok()? {
+                Ok(stdout) => output.stdout.extend(stdout.into_bytes()),
+                Err(stderr) => {
+                    output.stderr.extend(stderr.into_bytes());
+                    // Exit code 1, like a failed tmux process
+                    output.status = ExitStatus::from_raw(1 << 8);
+                }
+            }
+        }
+        Some(output)
+    }
+}
+
+/// Read the control client's stdout until it exits, routing reply blocks and notifications
+fn read_control(stdout: impl BufRead, replies: Sender<Result<String, String>>, ready: Sender<()>,
+    handler: NotificationHandler)
+{
+    // Command number, whether it is a reply to one of our commands (rather than e.g. the initial
+    // attach) and the lines so far of the block being read
+    let mut block: Option<(String, bool, String)> = None;
+    for line in stdout.lines() {
+        let Ok(line) = line else { break };
+        let mut fields = line.split(' ');
+        let (tag, number, flags) = (fields.next().unwrap_or(""), fields.nth(1), fields.next());
+        if let Some((block_number, _, contents)) = block.as_mut() {
+            // Output lines may start with `%` too, so only the matching guard ends the block
+            if !(matches!(tag, "%end" | "%error") && number == Some(block_number.as_str())) {
+                contents.push_str(&line);
+                contents.push('\n');
+                continue;
+            }
+            let failed = tag == "%error";
+            if let Some((_, true, contents)) = block.take() {
+                let _ = replies.send(if failed { Err(contents) } else { Ok(contents) });
+            }
+            continue;
+        }
+        match tag {
+            "%begin" => block = Some((number.unwrap_or("").to_owned(), flags == Some("1"), String::new())),
+            "%exit" => break,
+            "%session-changed" => {
+                // `%session-changed <id> <name>`
+                if let Ok(mut session) = CONTROL_SESSION.lock() {
+                    *session = line.split(' ').nth(1).map(str::to_owned);
+                }
+                let _ = ready.send(());
+            }
+            _ => handler(&line),
+        }
+    }
+    if let Ok(mut session) = CONTROL_SESSION.lock() {
+        *session = None;
+    }
+    // Close the reply channel before announcing the exit, so that the next command falls back
+    drop(replies);
+    handler("%exit");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments_are_quoted_for_tmux() {
+        assert_eq!(tmux_quote("dev-1"), "dev-1");
+        assert_eq!(tmux_quote("=dev:0.1"), "=dev:0.1");
+        assert_eq!(tmux_quote(""), "\"\"");
+        assert_eq!(tmux_quote("my session"), "\"my session\"");
+        assert_eq!(tmux_quote("#{session_name};"), "\"#{session_name};\"");
+        assert_eq!(tmux_quote(r#"say "hi" to $USER\"#), r#""say \"hi\" to \$USER\\""#);
+    }
+
+    #[test]
+    fn remote_commands_are_quoted_for_the_remote_shell() {
+        let cmd = command(&Server::new(Some("work"), None), Some("build-box"), ["list-sessions", "-F", "#{session_name}\t#{session_id}"]);
+        assert_eq!(cmd.get_program(), "ssh");
+        let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
+        // The local server is not the remote host's
+        assert_eq!(args, ["-o", "BatchMode=yes", "build-box", "tmux -u list-sessions -F '#{session_name}\t#{session_id}'"]);
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("dev-1"), "dev-1");
+        assert_eq!(shell_quote(""), "''");
+        let cmd = command(&Server::new(Some("work"), None), None, ["list-sessions"]);
+        assert_eq!(cmd.get_program(), "tmux");
+        assert_eq!(cmd.get_args().collect::<Vec<_>>(), ["-u", "-L", "work", "list-sessions"]);
+    }
+
+    #[test]
+    fn long_output_does_not_time_out() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "head -c 200000 /dev/zero; echo failed >&2"]);
+        let output = wait_with_timeout(cmd, Duration::from_secs(5)).unwrap();
+        assert_eq!(output.stdout.len(), 200000);
+        assert_eq!(output.stderr, b"failed\n");
+        let mut cmd = Command::new("sleep");
+        cmd.arg("5");
+        assert!(wait_with_timeout(cmd, Duration::from_millis(100)).is_none());
+    }
+
+    #[test]
+    fn control_output_is_split_into_replies_and_notifications() {
+        let stdout = "%begin 1709301900 100 0\n\
+            %end 1709301900 100 0\n\
+            %session-changed $1 dev\n\
+            %begin 1709301901 101 1\n\
+            dev: 1 windows\n\
+            %end 1709301901 99 1\n\
+            %end 1709301901 101 1\n\
+            %window-add @3\n\
+            %begin 1709301902 102 1\n\
+            unknown command: foo\n\
+            %error 1709301902 102 1\n\
+            %sessions-changed\n";
+        let (reply_sender, replies) = mpsc::channel();
+        let (ready_sender, ready) = mpsc::channel();
+        let notifications = Arc::new(Mutex::new(vec![]));
+        let handler = {
+            let notifications = notifications.clone();
+            Arc::new(move |line: &str| notifications.lock().unwrap().push(line.to_owned()))
+        };
+        read_control(stdout.as_bytes(), reply_sender, ready_sender, handler);
+        assert!(ready.try_recv().is_ok());
+        // The initial attach is not a reply, and only the guard with the block's number ends it
+        assert_eq!(replies.try_recv(), Ok(Ok("dev: 1 windows\n%end 1709301901 99 1\n".to_owned())));
+        assert_eq!(replies.try_recv(), Ok(Err("unknown command: foo\n".to_owned())));
+        assert_eq!(replies.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(*notifications.lock().unwrap(), ["%window-add @3", "%sessions-changed", "%exit"]);
+        assert_eq!(control_session_id(), None);
+    }
+}
@@ -7,6 +7,32 @@ use ratatui::backend::Backend;
 use ratatui::Terminal;
 use std::io;
 use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set when tmm receives `SIGTSTP`, e.g. from `kill -TSTP`. Ctrl-z itself is a key press in raw
+/// mode.
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_stop(_signal: libc::c_int) {
+    STOP_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Whether `SIGTSTP` was received since the last call. The main loop suspends the interface and
+/// [`stop_process`] then.
+pub fn take_stop_request() -> bool {
+    STOP_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Stop the process like job control does for Ctrl-z, returning once it is continued (e.g. by
+/// `fg`). The terminal has to be reset first. Without job control there is nobody to continue the
+/// process, and the kernel ignores the stop instead.
+pub fn stop_process() {
+    unsafe {
+        libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+        libc::raise(libc::SIGTSTP);
+        libc::signal(libc::SIGTSTP, request_stop as *const () as libc::sighandler_t);
+    }
+}
 
 /// Representation of a terminal user interface.
 ///
@@ -41,6 +67,9 @@ impl<B: Backend> Tui<B> {
             panic_hook(panic);
         }));
 
+        // Stop requests are handled by the main loop, once the terminal can be reset
+        unsafe { libc::signal(libc::SIGTSTP, request_stop as *const () as libc::sighandler_t) };
+
         self.terminal.hide_cursor()?;
         self.terminal.clear()?;
         Ok(())
@@ -65,6 +94,26 @@ impl<B: Backend> Tui<B> {
         Ok(())
     }
 
+    /// Suspends the terminal interface, handing the terminal over to another program.
+    ///
+    /// Events stop being read, and the terminal properties are reverted as if exiting.
+    pub fn suspend(&mut self) -> AppResult<()> {
+        self.events.pause();
+        self.exit()
+    }
+
+    /// Resumes the terminal interface after [`Tui::suspend`].
+    ///
+    /// It sets the terminal properties again and redraws everything on the next draw.
+    pub fn resume(&mut self) -> AppResult<()> {
+        terminal::enable_raw_mode()?;
+        crossterm::execute!(io::stderr(), EnterAlternateScreen, EnableMouseCapture)?;
+        self.terminal.hide_cursor()?;
+        self.terminal.clear()?;
+        self.events.resume();
+        Ok(())
+    }
+
     /// Exits the terminal interface.
     ///
     /// It disables the raw mode and reverts back the terminal properties.
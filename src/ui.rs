@@ -1,20 +1,28 @@
 use ratatui::{
-    layout::{Layout, Direction, Constraint, Rect},
+    layout::{Alignment, Layout, Direction, Constraint, Margin, Rect},
     style::{Color, Style, Stylize},
     widgets::*,
     text::*,
     Frame,
 };
+use crossterm::event::KeyCode;
 use tui_textarea::TextArea;
 
-use crate::app::{App, AppState};
+use std::collections::HashMap;
+
+use crate::app::{App, AppState, AttachedFilter, FormField, HostStatus, ListRow, SortOrder, TreeRow};
+use crate::batch::Batch;
+use crate::config::Colors;
+use crate::{history, tmux};
+use crate::keymap::{Chord, KeyBinding, KeySequence};
+use crate::source::Source;
 
 /// Display a popup
 ///   x, y - top left coordinate
-fn display_popup_centered(frame: &mut Frame, rect: &Rect, title: &str, message: &str, prompt: &str) {
+fn display_popup_centered(frame: &mut Frame, colors: &Colors, buttons: &mut Vec<(Rect, KeyCode)>, rect: &Rect, title: &str, message: &str, prompt: &str) {
     // TODO: accept proper trait for spans, text, etc so it can be styled
     // Compute proper size of popup. Add 4 to account for border and padding.
-    let width: u16 = (title.len().max(message.len() + prompt.len()) + 4) as u16;
+    let width: u16 = ((title.len().max(message.len() + prompt.len()) + 4) as u16).min(rect.width);
     let height: u16 = 3;
     // Find the center of the provided rect
     let x = (2 * rect.x + rect.width - width)/2;
@@ -27,28 +35,72 @@ fn display_popup_centered(frame: &mut Frame, rect: &Rect, title: &str, message:
         .title(format!(" {} ", title))
         .borders(Borders::ALL)
         .padding(Padding::horizontal(1))
-        .style(Style::default().bg(Color::DarkGray));
+        .style(Style::default().bg(colors.popup));
     // Create the message inside the popup_block
     let msg = Paragraph::new(format!("{}{}", message, prompt))
             .block(popup_block);
     // Render
     frame.render_widget(msg, area);
+    // The prompt follows the message inside the border and padding
+    buttons.extend(prompt_buttons(prompt, area.x + 2 + message.len() as u16, area.y + 1));
+}
+
+/// Clickable keys of a popup prompt such as `[Y]es / [N]o / Esc` drawn at column `x` of row `y`:
+/// each bracketed letter stands for its key, and `Esc` for itself
+fn prompt_buttons(prompt: &str, x: u16, y: u16) -> Vec<(Rect, KeyCode)> {
+    let mut buttons = vec![];
+    let mut start = None;
+    for (idx, c) in prompt.char_indices().chain([(prompt.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(idx),
+            (true, Some(from)) => {
+                let word = &prompt[from..idx];
+                let key = match word.as_bytes() {
+                    [b'[', key, b']', ..] => Some(KeyCode::Char(key.to_ascii_lowercase() as char)),
+                    b"Esc" => Some(KeyCode::Esc),
+                    _ => None,
+                };
+                if let Some(key) = key {
+                    buttons.push((Rect::new(x + from as u16, y, word.len() as u16, 1), key));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    buttons
 }
 
-fn display_prompt_centered(frame: &mut Frame, rect: &Rect, textarea: &TextArea, title: &str) {
+/// Clickable keys of a prompt shown right aligned in the bottom border of a popup at `area`, as
+/// ` {prompt} `
+fn bottom_prompt_buttons(prompt: &str, area: Rect) -> Vec<(Rect, KeyCode)> {
+    let x = (area.x + area.width).saturating_sub(2 + prompt.len() as u16);
+    prompt_buttons(prompt, x, area.y + area.height.saturating_sub(1))
+}
+
+fn display_prompt_centered(frame: &mut Frame, colors: &Colors, rect: &Rect, textarea: &TextArea, title: &str) {
+    display_prompt_with_feedback(frame, colors, rect, textarea, title, None)
+}
+
+/// Display a single line prompt, with feedback on what is typed in its bottom border
+fn display_prompt_with_feedback(frame: &mut Frame, colors: &Colors, rect: &Rect, textarea: &TextArea, title: &str, feedback: Option<&str>) {
     // TODO: accept proper trait for spans, text, etc so it can be styled
     // Compute proper size of popup. Add 4 to account for border and padding.
     let prompt = " > ";
     let plen = prompt.len() as u16;
 
-    let width: u16 = (textarea.lines()[0].len()+4).max(18).max((rect.width/2) as usize) as u16;
+    let feedback_width = feedback.map_or(0, |feedback| feedback.chars().count() + 4);
+    let width: u16 = ((textarea.lines()[0].len()+4).max(18).max((rect.width/2) as usize).max(feedback_width) as u16).min(rect.width);
     let height: u16 = 3;
     // Find the center of the provided rect
     let x = (2 * rect.x + rect.width - width)/2;
     let y = (2 * rect.y + rect.height - height)/2;
     let area = Rect::new(x, y, width, height);
 
-    let block = Block::bordered().title(format!(" {} ", title)).style(Style::default().bg(Color::DarkGray));
+    let mut block = Block::bordered().title(format!(" {} ", title)).style(Style::default().bg(colors.popup));
+    if let Some(feedback) = feedback {
+        block = block.title_bottom(Line::styled(format!(" {} ", feedback), Style::default().fg(colors.marker)).right_aligned());
+    }
     frame.render_widget(Clear, area);
     // Get the inner area of the block that will be shared by the prompt and the textarea
     let inner_area = block.inner(area);
@@ -56,10 +108,444 @@ fn display_prompt_centered(frame: &mut Frame, rect: &Rect, textarea: &TextArea,
     let ta_area = Rect{x: inner_area.x + plen, width: inner_area.width - plen, ..inner_area};
     // Render the block, prompt, and the textarea
     frame.render_widget(block, area);
-    frame.render_widget(Span::styled(prompt, Style::default().fg(Color::Cyan)), prompt_area);
+    frame.render_widget(Span::styled(prompt, Style::default().fg(colors.prompt)), prompt_area);
     frame.render_widget(textarea.widget(), ta_area);
 }
 
+/// Where the selected session's name is edited with `inline_rename`: its row as last rendered, after
+/// the marker and window count badge. `None` for the popup instead, also when the row is not on
+/// screen.
+fn inline_rename_area(app: &App) -> Option<Rect> {
+    let (area, offset) = app.list_hit_area.filter(|_| app.inline_rename)?;
+    let row = app.selected_row(&app.list_rows())?.checked_sub(offset).filter(|row| *row < area.height as usize)?;
+    // The highlight symbol, marker and badge come first
+    let indent = 3 + 2 + app.session_rows.first().map_or(0, |row| row.badge.len()) as u16;
+    (area.width > indent).then(|| Rect::new(area.x + indent, area.y + row as u16, area.width - indent, 1))
+}
+
+/// Display a prompt over a single row of `rect`, such as a session's name in the list, with the
+/// feedback in the bottom border of `rect`
+fn display_prompt_inline(frame: &mut Frame, colors: &Colors, rect: &Rect, row: Rect, textarea: &TextArea, feedback: Option<&str>) {
+    frame.render_widget(Clear, row);
+    frame.render_widget(Block::default().style(Style::default().bg(colors.popup)), row);
+    frame.render_widget(textarea.widget(), row);
+    if let Some(feedback) = feedback {
+        let text = format!(" {} ", feedback);
+        let width = (text.chars().count() as u16).min(rect.width.saturating_sub(4));
+        let area = Rect::new(rect.right().saturating_sub(width + 2), rect.bottom().saturating_sub(1), width, 1);
+        frame.render_widget(Span::styled(text, Style::default().fg(colors.marker)), area);
+    }
+}
+
+/// Display a form of labelled text fields, one per line
+fn display_form_centered(frame: &mut Frame, colors: &Colors, rect: &Rect, title: &str, fields: &[(&str, &TextArea)], hint: Line) {
+    let label_width = fields.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let prompt = " > ";
+    let width: u16 = ((label_width + prompt.len() + 18).max((rect.width/2) as usize) as u16).min(rect.width);
+    let height: u16 = ((fields.len() + 2) as u16).min(rect.height);
+    let x = (2 * rect.x + rect.width - width)/2;
+    let y = (2 * rect.y + rect.height - height)/2;
+    let area = Rect::new(x, y, width, height);
+
+    let block = Block::bordered()
+        .title(format!(" {} ", title))
+        .title_bottom(hint.right_aligned())
+        .style(Style::default().bg(colors.popup));
+    frame.render_widget(Clear, area);
+    let inner_area = block.inner(area);
+    frame.render_widget(block, area);
+    let label_area_width = (label_width + 1 + prompt.len()) as u16;
+    for (row, (label, textarea)) in fields.iter().enumerate().take(inner_area.height as usize) {
+        let y = inner_area.y + row as u16;
+        let label_area = Rect { y, height: 1, width: label_area_width.min(inner_area.width), ..inner_area };
+        let ta_area = Rect { x: inner_area.x + label_area.width, y, height: 1, width: inner_area.width - label_area.width };
+        frame.render_widget(Line::from(vec![
+            Span::raw(format!(" {:width$}", label, width = label_width)),
+            Span::styled(prompt, Style::default().fg(colors.prompt)),
+        ]), label_area);
+        frame.render_widget(textarea.widget(), ta_area);
+    }
+}
+
+/// Display a scrollable popup listing every item of a batch operation. Before the batch has run this
+/// is the confirmation summary; afterwards each line reports the item's result.
+fn display_batch_centered(frame: &mut Frame, colors: &Colors, buttons: &mut Vec<(Rect, KeyCode)>, rect: &Rect, batch: &Batch, title: &str, prompt: &str) {
+    let lines: Vec<Line> = batch.items.iter().map(|item| {
+        match &item.result {
+            // Left undone by an abort
+            None if batch.aborted => Line::from(vec![
+                Span::styled("skip ", Style::default().fg(colors.dim)),
+                Span::raw(item.describe()),
+            ]),
+            None => Line::raw(item.describe()),
+            Some(Ok(())) => Line::from(vec![
+                Span::styled("ok   ", Style::default().fg(Color::Green)),
+                Span::raw(item.describe()),
+            ]),
+            Some(Err(err)) => Line::from(vec![
+                Span::styled("fail ", Style::default().fg(Color::Red)),
+                Span::raw(format!("{}: {}", item.describe(), err)),
+            ]),
+        }
+    }).collect();
+    // Size the popup to its contents, leaving room for the border (which also carries the prompt), but
+    // never beyond the provided rect. Anything that does not fit is reachable by scrolling.
+    let content_width = lines.iter().map(|line| line.width()).max().unwrap_or(0).max(prompt.len());
+    let width: u16 = ((title.len().max(content_width) + 4) as u16).min(rect.width);
+    let height: u16 = ((lines.len() + 2) as u16).min(rect.height);
+    let x = (2 * rect.x + rect.width - width)/2;
+    let y = (2 * rect.y + rect.height - height)/2;
+    let area = Rect::new(x, y, width, height);
+    frame.render_widget(Clear, area);
+    let block = Block::default()
+        .title(format!(" {} ", title))
+        .title_bottom(Line::from(format!(" {} ", prompt)).right_aligned())
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1))
+        .style(Style::default().bg(colors.popup));
+    let msg = Paragraph::new(lines)
+        .scroll((batch.scroll as u16, 0))
+        .block(block);
+    frame.render_widget(msg, area);
+    buttons.extend(bottom_prompt_buttons(prompt, area));
+}
+
+/// Display a message in a popup, wrapped to fit the provided rect
+fn display_message_centered(frame: &mut Frame, colors: &Colors, buttons: &mut Vec<(Rect, KeyCode)>, rect: &Rect, title: &str, message: &str, prompt: &str) {
+    let width: u16 = ((title.len().max(message.len()).max(prompt.len()) + 4) as u16).min(rect.width);
+    // Every line but the last is filled up to the inner width, so this is close for wrapped text
+    let inner_width = width.saturating_sub(4).max(1) as usize;
+    let height: u16 = ((message.len().div_ceil(inner_width).max(1) + 2) as u16).min(rect.height);
+    let x = (2 * rect.x + rect.width - width)/2;
+    let y = (2 * rect.y + rect.height - height)/2;
+    let area = Rect::new(x, y, width, height);
+    frame.render_widget(Clear, area);
+    let block = Block::default()
+        .title(format!(" {} ", title))
+        .title_bottom(Line::from(format!(" {} ", prompt)).right_aligned())
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1))
+        .style(Style::default().bg(colors.popup));
+    let msg = Paragraph::new(message.to_owned())
+        .wrap(Wrap { trim: true })
+        .block(block);
+    frame.render_widget(msg, area);
+    buttons.extend(bottom_prompt_buttons(prompt, area));
+}
+
+/// Display labelled lines in a popup sized to fit them, with the labels aligned
+fn display_lines_centered(frame: &mut Frame, colors: &Colors, buttons: &mut Vec<(Rect, KeyCode)>, rect: &Rect, title: &str, lines: &[(&str, String)], prompt: &str) {
+    let label_width = lines.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let lines: Vec<Line> = lines.iter().map(|(label, value)| Line::from(vec![
+        Span::styled(format!("{:width$}  ", label, width = label_width), Style::default().fg(colors.dim)),
+        Span::raw(value.to_owned()),
+    ])).collect();
+    let content_width = lines.iter().map(|line| line.width()).max().unwrap_or(0).max(prompt.len());
+    let width: u16 = ((title.len().max(content_width) + 4) as u16).min(rect.width);
+    let height: u16 = ((lines.len() + 2) as u16).min(rect.height);
+    let x = (2 * rect.x + rect.width - width)/2;
+    let y = (2 * rect.y + rect.height - height)/2;
+    let area = Rect::new(x, y, width, height);
+    frame.render_widget(Clear, area);
+    let block = Block::default()
+        .title(format!(" {} ", title))
+        .title_bottom(Line::from(format!(" {} ", prompt)).right_aligned())
+        .borders(Borders::ALL)
+        .padding(Padding::horizontal(1))
+        .style(Style::default().bg(colors.popup));
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+    buttons.extend(bottom_prompt_buttons(prompt, area));
+}
+
+/// Render the status message, if there is one, in the bottom right of the border of `area`
+fn render_status(app: &App, frame: &mut Frame, area: Rect) {
+    let Some(status) = app.status() else { return };
+    if area.width < 4 || area.height < 2 {
+        return;
+    }
+    // Clear of the corners, cut short if it is too long
+    let text: String = format!(" {} ", status.message).chars().take(area.width as usize - 2).collect();
+    let width = text.chars().count() as u16;
+    let color = if status.failure { app.colors.dead } else { app.colors.dim };
+    let line = Rect { x: area.right() - 1 - width, y: area.bottom() - 1, width, height: 1 };
+    frame.render_widget(Paragraph::new(Span::styled(text, Style::new().fg(color))), line);
+}
+
+/// Render the keys that can follow the keys typed so far of a key sequence, and the actions they
+/// complete, which-key style in the bottom right corner inside the border of `area`
+fn render_continuations(app: &App, frame: &mut Frame, area: Rect, keys: &[KeyBinding]) {
+    let area = area.inner(&Margin { vertical: 1, horizontal: 1 });
+    let continuations = app.keymap.continuations(keys);
+    let key_width = continuations.iter().map(|(rest, _)| rest.to_string().len()).max().unwrap_or(0);
+    let mut lines: Vec<Line> = continuations.iter().map(|(rest, action)| Line::from(vec![
+        Span::styled(format!("{:width$}  ", rest.to_string(), width = key_width), Style::new().fg(app.colors.highlight)),
+        Span::raw(action.description()),
+    ])).collect();
+    // What the keys do on their own once no other key comes
+    if let Chord::Prefix(Some(action)) = app.keymap.resolve(keys) {
+        lines.push(Line::styled(format!("{:width$}  {}", "…", action.description(), width = key_width), Style::new().fg(app.colors.dim)));
+    }
+    let title = format!(" {} ", KeySequence(keys.to_vec()));
+    let width = (lines.iter().map(Line::width).max().unwrap_or(0).max(title.len()) + 4) as u16;
+    let width = width.min(area.width);
+    let height = ((lines.len() + 2) as u16).min(area.height);
+    let popup = Rect::new(area.x + area.width - width, area.y + area.height - height, width, height);
+    frame.render_widget(Clear, popup);
+    let block = Block::bordered()
+        .title(title)
+        .padding(Padding::horizontal(1))
+        .style(Style::default().bg(app.colors.popup));
+    frame.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+/// Render the keys of every view in a popup over the whole screen, scrolled by
+/// [`App::help_scroll`]
+fn render_help(app: &App, frame: &mut Frame, area: Rect) {
+    let sections = app.help_sections();
+    let key_width = sections.iter().flat_map(|(_, keys)| keys.iter().map(|(key, _)| key.len())).max().unwrap_or(0);
+    let mut lines = vec![];
+    for (heading, keys) in sections {
+        if !lines.is_empty() {
+            lines.push(Line::raw(""));
+        }
+        lines.push(Line::styled(heading, Style::new().fg(app.colors.prompt).bold()));
+        for (key, description) in keys {
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {:width$}  ", key, width = key_width), Style::new().fg(app.colors.highlight)),
+                Span::raw(description),
+            ]));
+        }
+    }
+    frame.render_widget(Clear, area);
+    let block = Block::bordered()
+        .title(" Help ")
+        .title_bottom(Line::from(" j/k Scroll / Esc ").right_aligned())
+        .padding(Padding::horizontal(1))
+        .style(Style::default().bg(app.colors.popup));
+    frame.render_widget(Paragraph::new(lines).scroll((app.help_scroll as u16, 0)).block(block), area);
+}
+
+/// Render a list in place of the sessions list, bordered with `title`, with the `selected` item
+/// in `highlight` after a `>>`
+fn render_view_list<'a>(frame: &mut Frame, area: Rect, title: impl Into<Line<'a>>, items: Vec<ListItem<'a>>, selected: usize, highlight: Style) {
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(
+                Block::bordered()
+                    .title(title.into())
+                    .padding(Padding::uniform(1))
+            )
+            .highlight_style(highlight)
+            .highlight_symbol(">> ")
+            .highlight_spacing(HighlightSpacing::Always)
+            .repeat_highlight_symbol(false)
+            .direction(ListDirection::TopToBottom),
+        area, &mut state
+    );
+}
+
+/// Render the attach history list in place of the sessions list
+fn render_history(app: &App, frame: &mut Frame, area: Rect) {
+    // Columns: timestamp, host, session, time until the next attach. Sessions that are known to be gone are dimmed
+    // since they cannot be re-attached.
+    let host_width = app.history.iter().map(|entry| {
+        entry.host.as_ref().unwrap_or(&app.local_hostname).len()
+    }).max().unwrap_or(0);
+    let session_width = app.history.iter().map(|entry| entry.session.len()).max().unwrap_or(0);
+    let items: Vec<ListItem> = app.history.iter().map(|entry| {
+        let row = format!("{}  {:<4$}  {:<5$}  {}",
+            history::format_timestamp(entry.timestamp, app.time_zone),
+            entry.host.as_ref().unwrap_or(&app.local_hostname),
+            entry.session,
+            entry.until_next_attach.map_or("-".to_owned(), |seconds| format!("next attach {} later", history::format_duration(seconds))),
+            host_width, session_width,
+        );
+        match app.history_entry_exists(entry) {
+            Some(false) => ListItem::new(row).style(Style::default().fg(app.colors.dim)),
+            _ => ListItem::new(row),
+        }
+    }).collect();
+    render_view_list(frame, area, " Attach History ", items, app.selected_history, Style::default().fg(app.colors.highlight).reversed());
+}
+
+/// Render the clients of the selected session in place of the sessions list
+fn render_clients(app: &App, frame: &mut Frame, area: Rect) {
+    // Columns: tty, size, terminal type and how long ago the client was last used
+    let tty_width = app.clients.iter().map(|client| client.tty.len()).max().unwrap_or(0);
+    let termname_width = app.clients.iter().map(|client| client.termname.len()).max().unwrap_or(0);
+    let now = history::now();
+    let items: Vec<ListItem> = app.clients.iter().map(|client| {
+        ListItem::new(Line::from(vec![
+            Span::raw(format!("{:<3$}  {:>9}  {:<4$}  ", client.tty, client.size, client.termname, tty_width, termname_width)),
+            Span::styled(format!("active {} ago", history::format_duration(now.saturating_sub(client.activity))),
+                Style::default().fg(app.colors.dim)),
+        ]))
+    }).collect();
+    let title = match &app.clients_session {
+        Some(session) => format!(" Clients: {} ", session.label()),
+        None => " Clients ".to_owned(),
+    };
+    render_view_list(frame, area, title, items, app.selected_client, Style::default().fg(app.colors.highlight).reversed());
+}
+
+/// Render the session templates in place of the sessions list: each with what it creates and,
+/// below, its description or else the file it comes from
+fn render_templates(app: &App, frame: &mut Frame, area: Rect) {
+    let name_width = app.templates.iter().map(|template| template.name.len()).max().unwrap_or(0);
+    let items: Vec<ListItem> = app.templates.iter().map(|template| {
+        let about = match (&template.description, &template.file) {
+            (Some(description), _) => description.to_owned(),
+            (None, Some(file)) => file.display().to_string(),
+            (None, None) => "config.toml".to_owned(),
+        };
+        ListItem::new(vec![
+            Line::from(vec![
+                Span::styled(format!("{:<1$}  ", template.name, name_width), Style::default().bold()),
+                Span::styled(template.summary(), Style::default().fg(app.colors.dim)),
+            ]),
+            Line::styled(format!("  {}", about), Style::default().fg(app.colors.dim)),
+        ])
+    }).collect();
+    let title = if app.templates.is_empty() { " Templates: none configured " } else { " Templates " };
+    render_view_list(frame, area, title, items, app.selected_template, Style::default().fg(app.colors.highlight).reversed());
+}
+
+/// Render the panes found by a deep search in place of the sessions list: each with its target,
+/// command and how many lines mention the term and, below, the most recent of them
+fn render_pane_matches(app: &App, frame: &mut Frame, area: Rect) {
+    let targets: Vec<String> = app.pane_matches.iter().map(|found| match &found.host {
+        Some(host) => format!("{}: {}:{}.{}", host, found.session, found.window, found.pane),
+        None => format!("{}:{}.{}", found.session, found.window, found.pane),
+    }).collect();
+    let target_width = targets.iter().map(String::len).max().unwrap_or(0);
+    let items: Vec<ListItem> = app.pane_matches.iter().zip(&targets).map(|(found, target)| {
+        let lines = if found.count == 1 { "1 line".to_owned() } else { format!("{} lines", found.count) };
+        ListItem::new(vec![
+            Line::from(vec![
+                Span::styled(format!("{:<1$}  ", target, target_width), Style::default().bold()),
+                Span::styled(format!("{}, {}", found.command, lines), Style::default().fg(app.colors.dim)),
+            ]),
+            Line::styled(format!("  {}", found.line), Style::default().fg(app.colors.dim)),
+        ])
+    }).collect();
+    render_view_list(frame, area, format!(" Panes mentioning {} ", app.deep_search_term), items, app.selected_pane_match, Style::default().fg(app.colors.highlight).reversed());
+}
+
+/// Render the local tmux servers in place of the sessions list: the name of each (its socket path
+/// when it is not in tmux's directory), with the current one flagged `*`
+fn render_servers(app: &App, frame: &mut Frame, area: Rect) {
+    let current = app.tmux.server().current_socket();
+    let items: Vec<ListItem> = app.servers.iter().map(|socket| {
+        let name = tmux::server_name(socket);
+        if *socket == current {
+            ListItem::new(Line::from(vec![Span::raw(name), Span::styled("*", Style::default().bold())]))
+        } else {
+            ListItem::new(name)
+        }
+    }).collect();
+    render_view_list(frame, area, " Tmux Servers ", items, app.selected_server, Style::default().fg(app.colors.highlight).reversed());
+}
+
+/// Render the text of the list in copy mode in place of the list it stands for: the cursor's line
+/// highlighted and the visual selection, if any, reversed
+fn render_copy_mode(app: &App, frame: &mut Frame, area: Rect) {
+    let selection = app.copy_anchor.map(|_| app.copy_selection());
+    let items: Vec<ListItem> = app.copy_lines.iter().enumerate().map(|(idx, line)| {
+        match &selection {
+            Some(selection) if selection.contains(&idx) => ListItem::new(line.as_str()).style(Style::default().reversed()),
+            _ => ListItem::new(line.as_str()),
+        }
+    }).collect();
+    let title = match &selection {
+        Some(selection) => format!(" Copy Mode: {} Selected ", selection.end() - selection.start() + 1),
+        None => " Copy Mode ".to_owned(),
+    };
+    render_view_list(frame, area, title, items, app.copy_cursor, Style::default().fg(app.colors.highlight).bold());
+}
+
+/// Render the archived sessions in place of the sessions list, like the templates they are saved as
+fn render_archive(app: &App, frame: &mut Frame, area: Rect) {
+    let name_width = app.archived.iter().map(|archived| archived.template.name.len()).max().unwrap_or(0);
+    let items: Vec<ListItem> = app.archived.iter().map(|archived| {
+        ListItem::new(vec![
+            Line::from(vec![
+                Span::styled(format!("{:<1$}  ", archived.template.name, name_width), Style::default().bold()),
+                Span::styled(archived.template.summary(), Style::default().fg(app.colors.dim)),
+            ]),
+            Line::styled(format!("  archived {} in {}", history::format_timestamp(archived.timestamp, app.time_zone),
+                archived.template.dir.as_deref().unwrap_or("~")), Style::default().fg(app.colors.dim)),
+        ])
+    }).collect();
+    let title = if app.archived.is_empty() { " Archive: empty " } else { " Archive " };
+    render_view_list(frame, area, title, items, app.selected_archived, Style::default().fg(app.colors.highlight).reversed());
+}
+
+/// Render the windows of the drilled-into session in place of the sessions list
+fn render_windows(app: &App, frame: &mut Frame, area: Rect) {
+    // Window columns: expansion marker, index, name (with tmux's `*` for the current window, `Z`
+    // for a zoomed one and `M` for the window of the marked pane), pane count, size, command. Panes of expanded windows are
+    // indented below their window with their index (`*` for the active pane, `M` for the marked
+    // one), size, command and current directory.
+    let names: Vec<String> = app.windows.iter().map(|window| {
+        let marked = window.pane_list.iter().any(|pane| pane.marked);
+        format!("{}{}{}{}", window.name, if window.active { "*" } else { "" }, if window.zoomed { "Z" } else { "" },
+            if marked { "M" } else { "" })
+    }).collect();
+    let name_width = app.windows.iter().zip(&names).map(|(window, name)| name.len().max(window.name.len() + 1)).max().unwrap_or(0);
+    let items: Vec<ListItem> = app.tree_rows().iter().map(|row| match *row {
+        TreeRow::Window(widx) => {
+            let window = &app.windows[widx];
+            let name = &names[widx];
+            let expander = if app.expanded_windows.contains(&window.index) { "-" } else { "+" };
+            ListItem::new(format!("{} {:>2}: {:<6$}  {:>2} panes  {:>9}  {}",
+                expander, window.index, name, window.panes, window.size, window.command, name_width))
+        }
+        TreeRow::Pane(widx, pidx) => {
+            let pane = &app.windows[widx].pane_list[pidx];
+            let index = format!("{}{}{}", pane.index, if pane.active { "*" } else { "" }, if pane.marked { "M" } else { "" });
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("     {:<3} {:>9}  {:<10} ", index, pane.size, pane.command)),
+                Span::styled(pane.path.to_owned(), Style::default().fg(app.colors.dim)),
+            ]))
+        }
+    }).collect();
+    let title = match &app.windows_session {
+        Some(session) => format!(" Windows: {} ", session.label()),
+        None => " Windows ".to_owned(),
+    };
+    render_view_list(frame, area, title, items, app.selected_window, Style::default().fg(app.colors.highlight).reversed());
+}
+
+/// Render the captured contents of the selected session's active pane
+fn render_preview(app: &App, frame: &mut Frame, area: Rect) {
+    let title = match &app.preview_label {
+        Some(label) => format!(" Preview: {} ", label),
+        None => " Preview ".to_owned(),
+    };
+    let mut block = Block::bordered().title(title);
+    // When tmm last attached the session, wherever it ran
+    let previewed = app.sessions.iter().find(|session| app.preview_label.as_ref() == Some(&session.label()));
+    if let Some(color) = previewed.and_then(|session| session.color) {
+        block = block.border_style(Style::default().fg(color));
+    }
+    if let Some(attach) = previewed.and_then(|session| session.last_attach.as_ref()) {
+        block = block.title_bottom(Line::styled(format!(" attached {} ", attach.describe(history::now())), Style::default().fg(app.colors.dim)));
+    }
+    // Show the bottom of the pane, where the most recent output is, ignoring trailing blank lines
+    let inner_height = block.inner(area).height as usize;
+    let text = match &app.preview {
+        Some(capture) => {
+            let lines: Vec<&str> = capture.trim_end().lines().collect();
+            let start = lines.len().saturating_sub(inner_height);
+            Text::from(lines[start..].iter().map(|line| Line::raw(*line)).collect::<Vec<Line>>())
+        }
+        None => Text::styled("Loading...", Style::default().fg(app.colors.dim)),
+    };
+    frame.render_widget(Paragraph::new(text).block(block), area);
+}
+
 /// Renders the user interface widgets.
 pub fn render(app: &mut App, frame: &mut Frame) {
     // This is where you add new widgets.
@@ -71,46 +557,85 @@ pub fn render(app: &mut App, frame: &mut Frame) {
     // we will use a stateful list where the list is 1 item per tmux session.
     // Highlight the selected session.
     
-    // app.sessions is a vector of (name, desc). We want to join name and desc.
-    let width = app.max_session_name_width();
+    // Set up the list state including selected row. When grouped, the list has header rows between
+    // the sessions, so list rows and session indexes differ.
+    let list_rows = app.list_rows();
+    let search_matches = app.search_matches();
+    let mut state = ListState::default().with_offset(app.list_offset.min(list_rows.len().saturating_sub(1)));
+    state.select(app.selected_row(&list_rows));
 
-    // Set up the list state including selected row
-    let mut state = ListState::default();
-    state.select(Some(app.selected_session));
+    // The strings that will be displayed (one per session) are formatted when the sessions change.
+    // They are borrowed by the list items while the search below updates the app, so they are
+    // moved out for the frame and put back once the list is rendered.
+    let session_rows = std::mem::take(&mut app.session_rows);
+    let item_strings: Vec<&str> = session_rows.iter().map(|row| row.text.as_str()).collect();
+
+    // Marked sessions get a leading marker so the batch targets are visible. Otherwise the first
+    // nine sessions listed show the digit attaching them, except while typing a search.
+    let numbers: HashMap<usize, usize> = match app.state {
+        AppState::SessionsSearch => HashMap::new(),
+        _ => app.numbered_sessions().into_iter().enumerate().map(|(number, idx)| (idx, number + 1)).collect(),
+    };
+    // While jumping, the rows on screen show their hints there instead
+    let hints: HashMap<ListRow, String> = match app.state {
+        AppState::Hinting => app.hints().into_iter().map(|(row, hint)| (list_rows[row].clone(), hint)).collect(),
+        _ => HashMap::new(),
+    };
+    let hint_span = |hint: &str| Span::styled(format!("{:<2}", hint), Style::default().fg(app.colors.highlight).bold());
+    let markers: Vec<Span> = session_rows.iter().enumerate().map(|(idx, row)| {
+        if let Some(hint) = hints.get(&ListRow::Session(idx)) {
+            hint_span(hint)
+        } else if app.marked.contains(&row.label) {
+            Span::styled("* ", Style::default().fg(app.colors.marker))
+        } else if let Some(number) = numbers.get(&idx) {
+            Span::styled(format!("{} ", number), Style::default().fg(app.colors.dim))
+        } else {
+            Span::raw("  ")
+        }
+    }).collect();
+
+    // Window count badge per session, e.g. `[4w]`, in red when any of its panes is dead
+    let badge_width = session_rows.first().map_or(5, |row| row.badge.len() - 1);
+    let badges: Vec<Span> = app.sessions.iter().zip(&session_rows).map(|(session, row)| {
+        if session.dead_panes > 0 {
+            Span::styled(row.badge.as_str(), Style::default().fg(app.colors.dead))
+        } else {
+            Span::styled(row.badge.as_str(), Style::default().fg(app.colors.badge))
+        }
+    }).collect();
 
-    // Compute the strings that will be displayed (one per row)
-    let item_strings: Vec<String> = app.sessions.iter().map(|(name, desc)| {
-        format!("{:>2$}: {}", name, desc, width)
+    // Sessions given an accent color show their row in it
+    let accents: Vec<Style> = app.sessions.iter().map(|session| match session.color {
+        Some(color) => Style::default().fg(color),
+        None => Style::default(),
     }).collect();
 
-    let items: Vec<ListItem> = match app.state {
+    // One item per session, in session order
+    let mut session_items: Vec<Option<ListItem>> = match app.state {
         AppState::SessionsSearch => {
-            // If searching, filter/modify the items based on the current search string
-            let search_needle = &app.search_session_ta.as_ref().expect("Could not get search term").lines()[0];
-            let mut row_idx = 0;
-            app.matching_rows.clear();
-            let mapped_strings = item_strings.iter().map(|row| {
-                // For each string, find any/all matches and convert result into a vec of spans
-                let mut spans: Vec<Span> = vec![];
-                let mut idx = 0;
-                let mut matched = false;
-                if !search_needle.is_empty() {
-                    for (jdx, _) in row.match_indices(search_needle) {
-                        spans.push(Span::raw(row[idx..jdx].to_owned()));
-                        spans.push(Span::styled(search_needle.to_owned(), Style::default().fg(Color::Magenta)));
-                        idx = jdx + search_needle.len();
-                        matched = true;
+            // If searching, only the matches are listed, best first, with the matched characters
+            // highlighted
+            let positions: HashMap<usize, &Vec<usize>> = search_matches.iter().map(|(idx, positions)| (*idx, positions)).collect();
+            let mapped_strings = item_strings.iter().enumerate().map(|(row_idx, row)| {
+                let mut spans: Vec<Span> = vec![markers[row_idx].clone(), badges[row_idx].clone()];
+                let matched = positions.get(&row_idx).map_or(&[][..], |positions| positions.as_slice());
+                // Runs of matched and unmatched characters
+                let run = |from: usize, to: usize, matched: bool| match matched {
+                    true => Span::styled(&row[from..to], Style::default().fg(app.colors.search_match)),
+                    false => Span::styled(&row[from..to], accents[row_idx]),
+                };
+                let (mut start, mut run_matched) = (0, false);
+                for (idx, _) in row.char_indices() {
+                    if idx > start && matched.contains(&idx) != run_matched {
+                        spans.push(run(start, idx, run_matched));
+                        start = idx;
                     }
+                    run_matched = matched.contains(&idx);
                 }
-                if matched {
-                    app.matching_rows.push(row_idx);
-                }
-                if idx < row.len() {
-                    spans.push(Span::raw(row[idx..].to_owned()));
-                }
-                row_idx += 1;
-                ListItem::new(Line::from(spans))
+                spans.push(run(start, row.len(), run_matched));
+                Some(ListItem::new(Line::from(spans)))
             }).collect();
+            app.matching_rows = search_matches.into_iter().map(|(idx, _)| idx).collect();
             // If there is already a desired selection among matches
             if let Some(selected_match) = app.search_session_selected {
                 // There is already a requested selected match. Only keep it if that row is in the
@@ -125,87 +650,381 @@ pub fn render(app: &mut App, frame: &mut Frame) {
                     app.search_session_selected = Some(app.matching_rows[0])
                 }
             }
-            else if let Some(first_match) = app.matching_rows.get(0) {
+            else if let Some(first_match) = app.matching_rows.first() {
                 // There is no current desired selection among matches. Use the first match or none
                 // if there are no matches
                 app.search_session_selected = Some(*first_match);
             } else {
                 app.search_session_selected = None;
             }
-            state.select(app.search_session_selected);
+            state.select(app.search_session_selected.and_then(|idx| app.row_of(&list_rows, idx)));
             mapped_strings
         }
         _ => {
-            item_strings.iter().map(|s| {
-                ListItem::new(s.to_owned())
+            item_strings.iter().zip(markers).zip(badges).zip(&accents).map(|(((s, marker), badge), accent)| {
+                Some(ListItem::new(Line::from(vec![marker, badge, Span::styled(*s, *accent)])))
             }).collect()
         }
     };
 
+    // Section headers, e.g. `- web (3)`, with `+` for collapsed sections
+    let items: Vec<ListItem> = list_rows.iter().map(|row| match row {
+        ListRow::Header(title, count, collapsed) => {
+            let mut spans = vec![
+                Span::styled(format!("{} {}", if *collapsed { "+" } else { "-" }, title), Style::default().bold()),
+                Span::styled(format!(" ({})", count), Style::default().fg(app.colors.dim)),
+            ];
+            // Sections of remote hosts say whether their sessions are current
+            if let Some(host_idx) = app.section_host(title).filter(|idx| *idx > 0) {
+                spans.push(match app.host_status(host_idx) {
+                    HostStatus::Connecting => Span::styled(" connecting", Style::default().fg(app.colors.dim)),
+                    HostStatus::Connected => Span::styled(" connected", Style::default().fg(app.colors.dim)),
+                    HostStatus::Stale(age) => Span::styled(format!(" stale, {} old", history::format_duration(age.as_secs())),
+                        Style::default().fg(app.colors.marker)),
+                    HostStatus::Disconnected => Span::styled(" disconnected", Style::default().fg(app.colors.dead)),
+                });
+            }
+            ListItem::new(Line::from(spans))
+        }
+        ListRow::Session(idx) => session_items[*idx].take().expect("each session is listed once"),
+        // Entries of other sources are badged with their source in place of a window count
+        ListRow::Entry(idx) => {
+            let entry = &app.entries[*idx];
+            let color = match entry.source {
+                Source::Sessions => app.colors.badge,
+                Source::Directories | Source::Projects => app.colors.directory,
+                Source::Templates => app.colors.prompt,
+                Source::Snapshots => app.colors.dim,
+            };
+            ListItem::new(Line::from(vec![
+                hints.get(row).map_or(Span::raw("  "), |hint| hint_span(hint)),
+                Span::styled(format!("{:<1$} ", entry.source.badge(), badge_width), Style::default().fg(color)),
+                Span::raw(entry.label.to_owned()),
+            ]))
+        }
+    }).collect();
+
     /**********/
     /* LAYOUT */
     /**********/
 
     // Split the screen to create layout sections/chunks
+    let rows = match app.state {
+        AppState::History => app.history.len(),
+        AppState::Windows | AppState::SplitPane | AppState::KillingPane | AppState::CapturingPane => app.tree_rows().len(),
+        AppState::Clients | AppState::KillingClient => app.clients.len(),
+        // Two lines each
+        AppState::Templates => 2 * app.templates.len(),
+        AppState::DeepResults => 2 * app.pane_matches.len(),
+        AppState::Copying => app.copy_lines.len(),
+        AppState::Servers => app.servers.len(),
+        AppState::Archive => 2 * app.archived.len(),
+        _ => list_rows.len(),
+    };
+    // The preview needs room to be useful, so when it is shown the list takes the full height
+    let preview_visible = app.preview_visible && !matches!(app.state, AppState::History | AppState::Windows | AppState::SplitPane | AppState::KillingPane | AppState::CapturingPane | AppState::Clients | AppState::KillingClient | AppState::Templates | AppState::Archive | AppState::DeepResults | AppState::Copying | AppState::Servers);
+    // Longer lists take the whole height and scroll
+    let hotkey_bar_height = if app.hotkey_bar { 1 } else { 0 };
+    let list_height = (4 + rows).min(frame.size().height.saturating_sub(hotkey_bar_height) as usize) as u16;
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Fill(1),
-            Constraint::Max(4 + app.sessions.len() as u16),
-            Constraint::Length(1),
+            if preview_visible { Constraint::Length(0) } else { Constraint::Fill(1) },
+            if preview_visible { Constraint::Fill(1) } else { Constraint::Length(list_height) },
+            Constraint::Length(hotkey_bar_height),
         ])
         .split(frame.size());
+    let (list_area, preview_area) = if preview_visible {
+        let halves = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(100 - app.preview_width), Constraint::Percentage(app.preview_width)])
+            .split(chunks[1]);
+        (halves[0], Some(halves[1]))
+    } else {
+        (chunks[1], None)
+    };
 
     /*****************/
     /* SESSIONS LIST */
     /*****************/
 
-    frame.render_stateful_widget(
-        List::new(items)
-            .block(
-                Block::bordered()
-                    .title(" Tmux Session Manager ")
-                    .padding(Padding::uniform(1))
-            )
-            .highlight_style(Style::default().fg(Color::Cyan).reversed())
-            .highlight_symbol(">> ")
-            .highlight_spacing(HighlightSpacing::Always)
-            .repeat_highlight_symbol(false)
-            .direction(ListDirection::TopToBottom),
-        chunks[1], &mut state
-    );
+    app.list_hit_area = None;
+    if app.state == AppState::History {
+        render_history(app, frame, list_area);
+    } else if matches!(app.state, AppState::Windows | AppState::SplitPane | AppState::KillingPane | AppState::CapturingPane) {
+        render_windows(app, frame, list_area);
+    } else if matches!(app.state, AppState::Clients | AppState::KillingClient) {
+        render_clients(app, frame, list_area);
+    } else if app.state == AppState::Templates {
+        render_templates(app, frame, list_area);
+    } else if app.state == AppState::Archive {
+        render_archive(app, frame, list_area);
+    } else if app.state == AppState::DeepResults {
+        render_pane_matches(app, frame, list_area);
+    } else if app.state == AppState::Copying {
+        render_copy_mode(app, frame, list_area);
+    } else if app.state == AppState::Servers {
+        render_servers(app, frame, list_area);
+    } else {
+        // Whose sessions these are, when they are not the default server's
+        let title = match &app.server {
+            Some(server) => format!(" Tmux Session Manager: {} ", server),
+            None => " Tmux Session Manager ".to_owned(),
+        };
+        let mut block = Block::bordered()
+            .title(title)
+            .padding(Padding::uniform(1));
+        // What is marked and how the list is filtered and sorted, when it is
+        let mut view = vec![];
+        if !app.marked.is_empty() {
+            view.push(format!("{} marked", app.marked.len()));
+        }
+        match app.attached_filter {
+            AttachedFilter::All => {}
+            AttachedFilter::Detached => view.push("detached only".to_owned()),
+            AttachedFilter::Attached => view.push("attached only".to_owned()),
+        }
+        if app.sort != SortOrder::None {
+            view.push(format!("sorted by {}", app.sort.name()));
+        }
+        if !view.is_empty() {
+            block = block.title(block::Title::from(format!(" {} ", view.join(", "))).alignment(Alignment::Right));
+        }
+        if let Some(err) = app.search_error() {
+            block = block.title(block::Title::from(Span::styled(format!(" Invalid regex: {} ", err), Style::new().fg(app.colors.dead)))
+                .position(block::Position::Bottom)
+                .alignment(Alignment::Right));
+        }
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(block)
+                .highlight_style(Style::default().fg(app.colors.highlight).reversed())
+                .highlight_symbol(">> ")
+                .highlight_spacing(HighlightSpacing::Always)
+                .repeat_highlight_symbol(false)
+                .direction(ListDirection::TopToBottom),
+            list_area, &mut state
+        );
+        // Each item is one line, starting inside the border and padding
+        let inner = Block::bordered().padding(Padding::uniform(1)).inner(list_area);
+        app.list_hit_area = Some((inner, state.offset()));
+        app.list_offset = state.offset();
+        // A scrollbar in the right border when the rows do not all fit
+        let viewport = inner.height as usize;
+        if list_rows.len() > viewport {
+            let mut scrollbar = ScrollbarState::new(list_rows.len() - viewport + 1).position(state.offset()).viewport_content_length(viewport);
+            frame.render_stateful_widget(
+                Scrollbar::new(ScrollbarOrientation::VerticalRight).begin_symbol(None).end_symbol(None)
+                    .track_style(Style::new().fg(app.colors.dim)).thumb_style(Style::new().fg(app.colors.highlight)),
+                list_area.inner(&Margin { vertical: 1, horizontal: 0 }),
+                &mut scrollbar,
+            );
+        }
+    }
+    app.session_rows = session_rows;
     
+    /***********/
+    /* PREVIEW */
+    /***********/
+
+    if let Some(preview_area) = preview_area {
+        render_preview(app, frame, preview_area);
+    }
+
+    /**********/
+    /* STATUS */
+    /**********/
+
+    // Whatever the view, unless an invalid search regex is pointed out there
+    if app.search_error().is_none() {
+        render_status(app, frame, list_area);
+    }
+
     /**********/
     /* POPUPS */
     /**********/
 
-    // Possibly render popups depending on app state
+    // Possibly render popups depending on app state, collecting the buttons that can be clicked
+    let mut buttons = vec![];
+    // Halfway through a key sequence, which keys can follow
+    if let Some((keys, _)) = app.pending_keys.as_ref().filter(|_| app.state == AppState::Sessions) {
+        render_continuations(app, frame, list_area, keys);
+    }
     match app.state {
         AppState::Deleting => {
-            // Get the name of the session
-            let (name, _) = &app.sessions[app.selected_session];
-            // Center the popup in the sessions rect
-            display_popup_centered(frame, &chunks[1], "Confirm Delete",
-                format!("Are you sure you want to delete {}?", name).as_str(),
-                " [Y]es / [N]o"
+            // The session being confirmed, as it was named when asked
+            if let Some((_, _, name)) = &app.confirming {
+                // Center the popup in the sessions rect
+                display_popup_centered(frame, &app.colors, &mut buttons, &list_area, "Confirm Delete",
+                    format!("Are you sure you want to delete {}?", name).as_str(),
+                    " [Y]es / [G]racefully / [N]o"
+                )
+            }
+        }
+        AppState::DetachingClients => {
+            if let Some(session) = app.pinned_session() {
+                let clients = if session.attached == 1 { "1 client".to_owned() } else { format!("{} clients", session.attached) };
+                display_popup_centered(frame, &app.colors, &mut buttons, &list_area, "Confirm Detach",
+                    format!("Detach {} from {}?", clients, session.label()).as_str(),
+                    " [Y]es / [N]o"
+                )
+            }
+        }
+        AppState::KillingClient => {
+            if let Some(client) = app.clients.get(app.selected_client) {
+                display_popup_centered(frame, &app.colors, &mut buttons, &list_area, "Confirm Kill",
+                    format!("Detach {} and end the shell it was started from?", client.tty).as_str(),
+                    " [Y]es / [N]o"
+                )
+            }
+        }
+        AppState::KillingPane => {
+            if let Some(target) = &app.killing_pane {
+                display_popup_centered(frame, &app.colors, &mut buttons, &list_area, "Confirm Kill",
+                    format!("Are you sure you want to kill pane {}?", target.trim_start_matches('=')).as_str(),
+                    " [Y]es / [N]o"
+                )
+            }
+        }
+        AppState::KillingServer => {
+            let host = app.server_host();
+            let sessions = app.sessions.iter().filter(|session| session.host == host).count();
+            let server = match &host {
+                Some(host) => format!("the tmux server on {}", host),
+                None => "the local tmux server".to_owned(),
+            };
+            display_message_centered(frame, &app.colors, &mut buttons, &list_area, "Kill Server",
+                &format!("This kills {}, ending ALL {} of its sessions and every program running in them. There is no undo. Kill it?", server, sessions),
+                "[Y]es / [N]o"
             )
         }
+        AppState::Help => {
+            let height = frame.size().height.saturating_sub(hotkey_bar_height);
+            render_help(app, frame, Rect { height, ..frame.size() }.inner(&Margin { vertical: 1, horizontal: 2 }));
+        }
+        AppState::ServerInfo => {
+            if let Some(info) = &app.server_info {
+                display_lines_centered(frame, &app.colors, &mut buttons, &list_area, "tmux Server", &info.describe(app.time_zone), "[D]ismiss")
+            }
+        }
         AppState::WarnNested => {
-            display_popup_centered(frame, &chunks[1], "Error",
+            display_popup_centered(frame, &app.colors, &mut buttons, &list_area, "Error",
                 "Cannot create nested session.",
                 " [D]ismiss"
             )
         }
+        AppState::RenameConflict => {
+            if let (Some(name), Some(suffixed)) = (&app.rename_conflict, app.suffixed_rename()) {
+                display_message_centered(frame, &app.colors, &mut buttons, &list_area, "Name Taken",
+                    &format!("A session named {} already exists. Rename to {} instead, or merge the windows into {}?",
+                        name, suffixed, name),
+                    "[S]uffix / [M]erge / Esc"
+                )
+            }
+        }
+        AppState::AttachSizing => {
+            if let Some((_, _, name)) = &app.confirming {
+                display_message_centered(frame, &app.colors, &mut buttons, &list_area, "Attach Size",
+                    &format!("Detach the other clients of {} to size it to this terminal, or keep them and size it to the smallest or the latest used client?", name),
+                    "[D]etach / [S]mallest / [L]atest / Esc"
+                )
+            }
+        }
+        AppState::Error => {
+            if let Some(error) = app.errors.front() {
+                let title = match app.errors.len() {
+                    1 => "Error".to_owned(),
+                    queued => format!("Error (1 of {})", queued),
+                };
+                display_message_centered(frame, &app.colors, &mut buttons, &list_area, &title, error, "[D]ismiss")
+            }
+        }
         AppState::Renaming => {
-            // Render text input dialog to get the desired new name
+            // Render text input dialog to get the desired new name, or edit it in the row
             if let Some(textarea) = &app.rename_session_ta {
-                display_prompt_centered(frame, &chunks[1], textarea, "New Session Name")
+                let feedback = app.name_feedback(&textarea.lines()[0], true);
+                match inline_rename_area(app) {
+                    Some(row) => display_prompt_inline(frame, &app.colors, &list_area, row, textarea, feedback.as_deref()),
+                    None => display_prompt_with_feedback(frame, &app.colors, &list_area, textarea, "New Session Name", feedback.as_deref()),
+                }
+            }
+        }
+        AppState::AttachCommand => {
+            if let Some(textarea) = &app.attach_command_ta {
+                display_prompt_centered(frame, &app.colors, &list_area, textarea, "Command to Run After Attaching")
+            }
+        }
+        AppState::DeepSearching => {
+            if let Some(textarea) = &app.deep_search_ta {
+                display_prompt_centered(frame, &app.colors, &list_area, textarea, "Search Every Pane's Scrollback")
+            }
+        }
+        AppState::DeepSearchRunning => {
+            if let Some(search) = &app.pane_search {
+                let progress = match search.total {
+                    Some(total) => format!("Searched {} of {} panes for {}", search.done, total, app.deep_search_term),
+                    None => format!("Listing the panes to search for {}", app.deep_search_term),
+                };
+                display_message_centered(frame, &app.colors, &mut buttons, &list_area, "Deep Search", &progress, "Esc to cancel")
+            }
+        }
+        AppState::CapturingPane => {
+            if let Some(textarea) = &app.capture_ta {
+                display_prompt_centered(frame, &app.colors, &list_area, textarea, "Save Pane Output To")
+            }
+        }
+        AppState::Coloring => {
+            if let Some(textarea) = &app.color_ta {
+                display_prompt_centered(frame, &app.colors, &list_area, textarea, "Session Color")
             }
         }
         AppState::NewSession => {
-            // Render text input dialog to get the desired new name
-            if let Some(textarea) = &app.new_session_ta {
-                display_prompt_centered(frame, &chunks[1], textarea, "New Session Name")
+            if let Some(form) = &app.new_session_form {
+                let mut fields = vec![("Name", &form.name), ("Directory", &form.directory), ("Command", &form.command)];
+                fields.extend(form.template.as_ref().map(|template| ("Template", template)));
+                let feedback = app.name_feedback(app.new_session_name(form), false);
+                let hint = match (form.focus, feedback) {
+                    _ if !form.candidates.is_empty() => Line::raw(format!(" {} ", form.candidates.join(" "))),
+                    (FormField::Directory, _) => Line::raw(" Tab complete "),
+                    (_, Some(feedback)) => Line::styled(format!(" {} ", feedback), Style::default().fg(app.colors.marker)),
+                    _ => Line::raw(" Tab next field "),
+                };
+                display_form_centered(frame, &app.colors, &list_area, "New Session", &fields, hint)
+            }
+        }
+        AppState::SplitPane => {
+            if let Some(form) = &app.split_form {
+                let title = if form.below { "Split Below" } else { "Split Right" };
+                display_form_centered(frame, &app.colors, &list_area, title,
+                    &[("Directory", &form.directory), ("Command", &form.command)], Line::raw(" Tab next field "))
+            }
+        }
+        AppState::BatchRenaming => {
+            // Render text input dialog to get the batch rename pattern
+            if let Some(textarea) = &app.batch_rename_ta {
+                display_prompt_centered(frame, &app.colors, &list_area, textarea,
+                    &format!("Rename {} Sessions ({{}} = current name)", app.marked.len()))
+            }
+        }
+        AppState::BatchConfirm => {
+            if let Some(batch) = &app.batch {
+                display_batch_centered(frame, &app.colors, &mut buttons, &list_area, batch,
+                    &format!("Confirm {} Changes", batch.items.len()),
+                    "[Y]es / [N]o"
+                )
+            }
+        }
+        AppState::BatchRunning => {
+            if let Some(batch) = &app.batch {
+                display_batch_centered(frame, &app.colors, &mut buttons, &list_area, batch,
+                    &format!("Running: {} of {} Done", batch.done(), batch.items.len()),
+                    "Esc to abort"
+                )
+            }
+        }
+        AppState::BatchResults => {
+            if let Some(batch) = &app.batch {
+                display_batch_centered(frame, &app.colors, &mut buttons, &list_area, batch, &batch.summary(), "[D]ismiss")
             }
         }
         AppState::SessionsSearch => {
@@ -213,16 +1032,17 @@ pub fn render(app: &mut App, frame: &mut Frame) {
             if let Some(textarea) = &app.search_session_ta {
                 // Need to render the search prompt immediately after the sessions list
                 // Compute the rect
-                let Rect{x, y, width, height} = chunks[1];
+                let Rect{x, y, width, height} = list_area;
                 let prompt_rect = Rect::new(x+2, y+height-2, width-2, 1);
                 let search_rect = Rect::new(x+4, y+height-2, width-4, 1);
                 frame.render_widget(Clear, search_rect);
                 frame.render_widget(textarea.widget(), search_rect);
-                frame.render_widget(Span::styled("> ", Style::new().fg(Color::Cyan)), prompt_rect);
+                frame.render_widget(Span::styled("> ", Style::new().fg(app.colors.prompt)), prompt_rect);
             }
         }
         _ => ()
     }
+    app.click_targets = buttons;
 
     /***********/
     /* HOTKEYS */
@@ -235,15 +1055,37 @@ pub fn render(app: &mut App, frame: &mut Frame) {
         // Use the Sessions state as a default if the current state does not have custom hotkeys
         // defined
         _ => app.hotkeys.get(&AppState::Sessions).expect("Could not get sessions hotkeys")
-    }.iter().map(|(k, v)| {
+    }.iter().flat_map(|(k, v)| {
             // Each hotkey will have the key highlighted in dark gray and description in normal
-            // text with some spaces padding
+            // text with some spaces padding. Actions of the sessions view that cannot run on the
+            // selection are dimmed.
+            let sessions_bar = app.state == AppState::Sessions || !app.hotkeys.contains_key(&app.state);
+            let disabled = sessions_bar && app.hotkey_actions.get(k)
+                .is_some_and(|action| app.disabled_reason(*action).is_some());
+            let (key, description) = match disabled {
+                true => (Style::new().fg(app.colors.dim), Style::new().fg(app.colors.dim)),
+                false => (Style::new().fg(app.colors.hotkey).reversed(), Style::new()),
+            };
             vec![
                 Span::raw("  "),
-                Span::styled(k.to_string(), Style::new().fg(Color::DarkGray).reversed()),
-                Span::raw(format!(" {}", v)),
+                Span::styled(k.to_string(), key),
+                Span::styled(format!(" {}", v), description),
             ]
-        }).flatten().collect();
+        }).collect();
     // render it
-    frame.render_widget(Line::from(hotkey_spans), chunks[2]);
+    if app.hotkey_bar {
+        frame.render_widget(Line::from(hotkey_spans), chunks[2]);
+    }
+}
+
+/// Renders the user interface into an in-memory buffer of the given size instead of the terminal,
+/// e.g. to compare it against a snapshot. Only `app` is drawn from, so an app with a
+/// [`MockClient`](crate::tmux::MockClient) and injected sessions renders the same every time.
+/// Only built with the `test-support` feature, which the tests and benchmarks turn on.
+#[cfg(feature = "test-support")]
+pub fn render_to_buffer(app: &mut App, width: u16, height: u16) -> ratatui::buffer::Buffer {
+    use ratatui::{backend::TestBackend, Terminal};
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).expect("the test backend cannot fail");
+    terminal.draw(|frame| render(app, frame)).expect("the test backend cannot fail");
+    terminal.backend().buffer().clone()
 }
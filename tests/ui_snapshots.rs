@@ -0,0 +1,284 @@
+//! Renders the interface with canned sessions and compares it against the text snapshots in
+//! `tests/snapshots`. After an intended change of the interface, update them with
+//! `UPDATE_SNAPSHOTS=1 cargo test --test ui_snapshots` and review the diff.
+
+use std::{env, fs, path::PathBuf, sync::Arc};
+use ratatui::buffer::Buffer;
+use crossterm::event::{KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use crossterm::event::KeyEvent;
+use tmm::{app::{App, AppState, GroupBy, ListRow}, handler::{handle_key_events, handle_mouse_events}, history::TimeZone, tmux::{MockClient, TmuxClient}, ui};
+use tmm::keymap::{Action, KeySequence};
+use tmm::scheduler::{Job, JobResult};
+
+/// `list-sessions` output: a plain session, an attached one with a dead pane, two sessions of a
+/// session group and one with a long name
+const LS: &str = "S\t$1\t2\t1709301900\t0\t1709301900\t\tdev\n\
+    S\t$2\t1\t1709301960\t1\t1709301990\t\tops\n\
+    S\t$3\t4\t1709302020\t0\t1709302020\tweb\tweb-1\n\
+    S\t$4\t4\t1709302080\t0\t1709302080\tweb\tweb-2\n\
+    S\t$5\t1\t1709302140\t0\t1709302140\t\ta-rather-long-session-name\n\
+    P\t$2\t1\n";
+
+/// An app listing [`LS`], rendered the same regardless of where the tests run
+fn app() -> App<'static> {
+    app_listing(LS)
+}
+
+/// An app listing the sessions of `list-sessions` output
+fn app_listing(ls: &str) -> App<'static> {
+    // The mock is a server of its own, so the hotkeys do not depend on running inside tmux
+    let mock = Arc::new(MockClient::new());
+    mock.reply("list-sessions", Ok(ls));
+    let mut app = App::with_client(mock as Arc<dyn TmuxClient>);
+    // Creation times are shown in local time otherwise
+    app.set_time_zone(TimeZone::Utc);
+    app
+}
+
+/// The text of a buffer, one line per row with trailing whitespace removed
+fn text(buffer: &Buffer) -> String {
+    let area = buffer.area;
+    (0..area.height).map(|y| {
+        let line: String = (0..area.width).map(|x| buffer.get(x, y).symbol()).collect();
+        line.trim_end().to_owned() + "\n"
+    }).collect()
+}
+
+/// Compare the app rendered at 80x20 with `tests/snapshots/<name>.txt`, or write the snapshot
+/// instead if `UPDATE_SNAPSHOTS` is set
+fn assert_snapshot(name: &str, app: &mut App) {
+    let actual = text(&ui::render_to_buffer(app, 80, 20));
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{}.txt", name));
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, actual).expect("could not write snapshot");
+        return;
+    }
+    match fs::read_to_string(&path) {
+        Ok(expected) => {
+            assert!(actual == expected, "{} changed, expected:\n{}\nactual:\n{}", path.display(), expected, actual);
+        }
+        Err(err) => panic!("{}: {}, write it with UPDATE_SNAPSHOTS=1", path.display(), err),
+    }
+}
+
+#[test]
+fn sessions_list() {
+    let mut app = app();
+    app.select_next();
+    app.toggle_mark();
+    assert_snapshot("sessions_list", &mut app);
+}
+
+#[test]
+fn long_lists_scroll() {
+    let ls: String = (1..=30).map(|idx| format!("S\t${}\t1\t1709301900\t0\t1709301900\t\tsession-{:02}\n", idx, idx)).collect();
+    let mut app = app_listing(&ls);
+    app.select_last();
+    ui::render_to_buffer(&mut app, 80, 20);
+    // Moving up from the bottom keeps the list where it is
+    for _ in 0..3 {
+        app.select_previous();
+    }
+    assert_snapshot("long_lists_scroll", &mut app);
+    assert_eq!(app.list_offset, 15);
+}
+
+#[test]
+fn pages_move_the_selection_and_the_list() {
+    let ls: String = (1..=30).map(|idx| format!("S\t${}\t1\t1709301900\t0\t1709301900\t\tsession-{:02}\n", idx, idx)).collect();
+    let mut app = app_listing(&ls);
+    ui::render_to_buffer(&mut app, 80, 20);
+    let height = app.list_hit_area.unwrap().0.height as usize;
+    app.select_page(1, 2);
+    assert_eq!((app.selected_session, app.list_offset), (height / 2, height / 2));
+    app.select_page(1, 1);
+    ui::render_to_buffer(&mut app, 80, 20);
+    assert_eq!(app.selected_session, height / 2 + height);
+    // Stops at the last page
+    app.select_page(1, 1);
+    assert_eq!((app.selected_session, app.list_offset), (29, 30 - height));
+    app.select_page(-1, 1);
+    assert_eq!(app.selected_session, 29 - height);
+}
+
+#[test]
+fn jump_hints() {
+    let mut app = app();
+    ui::render_to_buffer(&mut app, 80, 20);
+    app.start_hints();
+    assert_snapshot("jump_hints", &mut app);
+    app.type_hint('d');
+    assert_eq!((app.state.clone(), app.selected_session), (AppState::Sessions, 2));
+    assert!(app.running);
+    app.start_hints();
+    app.type_hint('S');
+    assert_eq!(app.selected_session, 1);
+    assert!(!app.running);
+}
+
+#[test]
+fn grouped_by_session_group() {
+    let mut app = app();
+    app.group_by = GroupBy::SessionGroup;
+    app.select_last();
+    app.toggle_group_collapsed();
+    assert_snapshot("grouped_by_session_group", &mut app);
+}
+
+#[test]
+fn confirm_delete_popup() {
+    let mut app = app();
+    app.confirm_delete();
+    assert_eq!(app.state, AppState::Deleting);
+    assert_snapshot("confirm_delete_popup", &mut app);
+}
+
+#[test]
+fn help_lists_every_key() {
+    let mut app = app();
+    app.show_help();
+    app.scroll_help(2);
+    assert_snapshot("help", &mut app);
+}
+
+#[test]
+fn pending_keys_list_their_continuations() {
+    let mut app = app();
+    let sequences = ["dd", "dD"].map(|keys| KeySequence::parse(keys).unwrap());
+    app.keymap.bind(Action::Delete, sequences.to_vec()).unwrap();
+    handle_key_events(KeyEvent::from(KeyCode::Char('d')), &mut app).unwrap();
+    assert!(app.pending_keys.is_some());
+    assert_snapshot("pending_keys", &mut app);
+}
+
+#[test]
+fn inline_rename() {
+    let mut app = app();
+    app.inline_rename = true;
+    app.select_next();
+    ui::render_to_buffer(&mut app, 80, 20);
+    app.confirm_rename();
+    assert_eq!(app.state, AppState::Renaming);
+    if let Some(textarea) = app.rename_session_ta.as_mut() {
+        textarea.insert_str("dev");
+    }
+    assert_snapshot("inline_rename", &mut app);
+}
+
+#[test]
+fn wrapped_error_popup() {
+    let mut app = app();
+    app.report_error("can't find session: a-rather-long-session-name (it may have been killed by another client in the meantime)".to_owned());
+    app.report_error("second failure".to_owned());
+    assert_snapshot("wrapped_error_popup", &mut app);
+}
+
+#[test]
+fn failure_in_the_status_line() {
+    let mut app = app();
+    app.show_failure("Refresh failed: tmux did not reply");
+    assert_snapshot("failure_status", &mut app);
+}
+
+#[test]
+fn search_highlights_matches() {
+    let mut app = app();
+    app.search();
+    if let Some(textarea) = app.search_session_ta.as_mut() {
+        textarea.insert_str("wb");
+    }
+    assert_snapshot("search_highlights_matches", &mut app);
+    assert_eq!(app.matching_rows, [2, 3]);
+    // Best match first: the `s` starting a word of `a-rather-long-session-name` beats the one of `ops`
+    if let Some(textarea) = app.search_session_ta.as_mut() {
+        textarea.delete_line_by_head();
+        textarea.insert_str("s");
+    }
+    ui::render_to_buffer(&mut app, 80, 20);
+    assert_eq!(app.matching_rows, [4, 1]);
+    // Regular expressions match the whole row, e.g. the window counts
+    if let Some(textarea) = app.search_session_ta.as_mut() {
+        textarea.delete_line_by_head();
+        textarea.insert_str("re: [12] windows");
+    }
+    ui::render_to_buffer(&mut app, 80, 20);
+    assert_eq!(app.matching_rows, [0, 1, 4]);
+    assert_eq!(app.search_error(), None);
+    if let Some(textarea) = app.search_session_ta.as_mut() {
+        textarea.insert_str("(");
+    }
+    assert_eq!(app.search_error(), Some("unclosed group".to_owned()));
+}
+
+fn click(app: &mut App, column: u16, row: u16) {
+    let event = MouseEvent { kind: MouseEventKind::Down(MouseButton::Left), column, row, modifiers: KeyModifiers::NONE };
+    handle_mouse_events(event, app).expect("mouse events do not fail");
+    ui::render_to_buffer(app, 80, 20);
+}
+
+#[test]
+fn mouse_selects_rows_and_presses_popup_buttons() {
+    let mut app = app();
+    ui::render_to_buffer(&mut app, 80, 20);
+    // The first session is on the line below the border and padding, see `sessions_list.txt`
+    click(&mut app, 30, 13);
+    assert_eq!(app.selected_session, 1);
+    app.confirm_delete();
+    let buffer = ui::render_to_buffer(&mut app, 80, 20);
+    let (no, _) = *app.click_targets.iter().find(|(_, key)| *key == KeyCode::Char('n')).expect("a [N]o button");
+    let label: String = (no.x..no.x + no.width).map(|x| buffer.get(x, no.y).symbol()).collect();
+    assert_eq!(label, "[N]o");
+    click(&mut app, no.x, no.y);
+    assert_eq!(app.state, AppState::Sessions);
+    // Buttons in the bottom border too
+    app.report_error("failed".to_owned());
+    let buffer = ui::render_to_buffer(&mut app, 80, 20);
+    let (dismiss, _) = app.click_targets[0];
+    let label: String = (dismiss.x..dismiss.x + dismiss.width).map(|x| buffer.get(x, dismiss.y).symbol()).collect();
+    assert_eq!(label, "[D]ismiss");
+    click(&mut app, dismiss.x, dismiss.y);
+    assert_eq!(app.state, AppState::Sessions);
+    // Double clicking attaches
+    click(&mut app, 30, 14);
+    click(&mut app, 30, 14);
+    assert!(!app.running);
+}
+
+#[test]
+fn double_clicking_a_session_that_cannot_be_attached_says_why() {
+    let mut app = app();
+    app.add_remote_hosts(vec!["build".to_owned()]);
+    for output in [Some("S\t$1\t1\t1709301900\t0\t1709301900\t\tci\n"), None] {
+        app.job_finished(JobResult { job: Job::Sessions(1), generation: 0, output: output.map(str::to_owned) });
+    }
+    ui::render_to_buffer(&mut app, 80, 20);
+    let ci = app.sessions.iter().position(|session| session.label() == "build/ci").unwrap();
+    let row = app.list_rows().iter().position(|row| *row == ListRow::Session(ci)).unwrap();
+    let y = (0..20).find(|y| app.list_row_at(30, *y) == Some(row)).expect("build/ci is shown");
+    click(&mut app, 30, y);
+    click(&mut app, 30, y);
+    assert!(app.running);
+    assert_eq!(app.status().map(|status| status.message.as_str()), Some("build is unreachable"));
+}
+
+#[test]
+fn confirmations_close_when_a_refresh_removes_their_session() {
+    let mock = Arc::new(MockClient::new());
+    mock.reply("list-sessions", Ok(LS));
+    let mut app = App::with_client(Arc::clone(&mock) as Arc<dyn TmuxClient>);
+    for confirm in [App::confirm_delete, App::confirm_detach_clients, App::confirm_attach_size] {
+        mock.reply("list-sessions", Ok(LS));
+        app.refresh();
+        // ops, which has a client attached
+        app.selected_session = 1;
+        confirm(&mut app);
+        assert_ne!(app.state, AppState::Sessions);
+        ui::render_to_buffer(&mut app, 80, 20);
+        // Every session is gone by the next refresh, which must not take the popup down with it
+        mock.reply("list-sessions", Ok(""));
+        app.refresh();
+        ui::render_to_buffer(&mut app, 80, 20);
+        assert_eq!(app.state, AppState::Sessions);
+        assert_eq!(app.status().map(|status| status.message.as_str()), Some("ops no longer exists, nothing was done"));
+    }
+}